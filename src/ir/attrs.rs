@@ -49,3 +49,31 @@ impl Default for ComplexityHint
         ComplexityHint::None
     }
 }
+
+/// Specifies the visibility of an item outside of its module.
+#[derive(Copy,Clone,Debug,PartialEq,Eq)]
+pub enum Linkage
+{
+    /// The item is visible to other modules.
+    External,
+    /// The item is only visible within its own module.
+    Internal,
+}
+
+impl Default for Linkage
+{
+    fn default() -> Self {
+        Linkage::External
+    }
+}
+
+/// Specifies the interrupt which a function handles.
+///
+/// Interrupt handlers are entered by the hardware rather than
+/// by a call, and so return in a special way.
+#[derive(Copy,Clone,Debug,PartialEq,Eq)]
+pub struct Interrupt
+{
+    /// The index of the interrupt in the vector table.
+    pub vector: u32,
+}
@@ -0,0 +1,24 @@
+/// A comment written in the source of a module.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct Comment
+{
+    /// The text following the semicolon.
+    pub text: String,
+    /// Whether the comment came after other code on its line.
+    pub inline: bool,
+}
+
+impl Comment
+{
+    /// Creates a comment which sits on its own line.
+    pub fn new<S>(text: S) -> Self
+        where S: Into<String> {
+        Comment { text: text.into(), inline: false }
+    }
+
+    /// Creates a comment which follows code on the same line.
+    pub fn inline<S>(text: S) -> Self
+        where S: Into<String> {
+        Comment { text: text.into(), inline: true }
+    }
+}
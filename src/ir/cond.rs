@@ -1,7 +1,7 @@
 use Value;
 
 /// A branching condition.
-#[derive(Clone,Debug,PartialEq,Eq)]
+#[derive(Clone,Debug,PartialEq,Eq,Hash)]
 pub enum Condition
 {
     /// Always.
@@ -25,6 +25,23 @@ impl Condition
         if value { Condition::True } else { Condition::False }
     }
 
+    /// Creates a comparison from its textual abbreviation.
+    pub fn from_abbreviation(abbreviation: &str,
+                             lhs: Value,
+                             rhs: Value) -> Option<Self> {
+        let (lhs, rhs) = (Box::new(lhs), Box::new(rhs));
+
+        match abbreviation {
+            "eq" => Some(Condition::Equal(lhs, rhs)),
+            "neq" => Some(Condition::NotEqual(lhs, rhs)),
+            "gt" => Some(Condition::GreaterThan(lhs, rhs)),
+            "gte" => Some(Condition::GreaterThanOrEq(lhs, rhs)),
+            "lt" => Some(Condition::LessThan(lhs, rhs)),
+            "lte" => Some(Condition::LessThanOrEq(lhs, rhs)),
+            _ => None,
+        }
+    }
+
     /// Checks if the condition is `true` or `false`.
     pub fn is_trivial(&self) -> bool {
         match *self {
@@ -34,6 +51,38 @@ impl Condition
         }
     }
 
+    /// Gets the values that the condition compares.
+    pub fn values(&self) -> Vec<&Value> {
+        match *self {
+            Condition::True |
+            Condition::False => Vec::new(),
+
+            Condition::Equal(ref lhs, ref rhs) |
+            Condition::NotEqual(ref lhs, ref rhs) |
+            Condition::GreaterThan(ref lhs, ref rhs) |
+            Condition::GreaterThanOrEq(ref lhs, ref rhs) |
+            Condition::LessThan(ref lhs, ref rhs) |
+            Condition::LessThanOrEq(ref lhs, ref rhs) => vec![lhs, rhs],
+        }
+    }
+
+    /// Performs a mapping over the values that the condition compares.
+    pub fn map_values<F>(self, mut f: F) -> Self
+        where F: FnMut(Value) -> Value {
+        let mut map = |v: Box<Value>| Box::new(f(*v));
+
+        match self {
+            Condition::True => Condition::True,
+            Condition::False => Condition::False,
+            Condition::Equal(lhs, rhs) => Condition::Equal(map(lhs), map(rhs)),
+            Condition::NotEqual(lhs, rhs) => Condition::NotEqual(map(lhs), map(rhs)),
+            Condition::GreaterThan(lhs, rhs) => Condition::GreaterThan(map(lhs), map(rhs)),
+            Condition::GreaterThanOrEq(lhs, rhs) => Condition::GreaterThanOrEq(map(lhs), map(rhs)),
+            Condition::LessThan(lhs, rhs) => Condition::LessThan(map(lhs), map(rhs)),
+            Condition::LessThanOrEq(lhs, rhs) => Condition::LessThanOrEq(map(lhs), map(rhs)),
+        }
+    }
+
     /// Gets the textual abbreviation of the code.
     pub fn abbreviation(&self) -> &'static str {
         match *self {
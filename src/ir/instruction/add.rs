@@ -1,6 +1,6 @@
 use {Instruction,Value,Expression,Type};
 
-#[derive(Clone,Debug,PartialEq,Eq)]
+#[derive(Clone,Debug,PartialEq,Eq,Hash)]
 pub struct Add
 {
     lhs: Box<Value>,
@@ -0,0 +1,26 @@
+use {Instruction,Value,Expression,Type};
+
+/// A bitwise and of the two's complement representations.
+#[derive(Clone,Debug,PartialEq,Eq,Hash)]
+pub struct And
+{
+    lhs: Box<Value>,
+    rhs: Box<Value>,
+}
+
+impl And
+{
+    pub fn new(lhs: Value, rhs: Value) -> Self {
+        assert!(lhs.node.ty() == rhs.node.ty());
+
+        And {
+            lhs: Box::new(lhs),
+            rhs: Box::new(rhs),
+        }
+    }
+
+    pub fn ty(&self) -> Type { self.lhs.node.ty() }
+}
+
+impl_instruction!(And: lhs, rhs);
+impl_instruction_binary!(And: lhs, rhs);
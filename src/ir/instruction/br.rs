@@ -1,6 +1,6 @@
 use {Value,Condition,Instruction,Expression,Type};
 
-#[derive(Clone,Debug,PartialEq,Eq)]
+#[derive(Clone,Debug,PartialEq,Eq,Hash)]
 pub struct Break
 {
     cond: Condition,
@@ -31,8 +31,38 @@ impl Break
         &self.target
     }
 
+    pub fn subvalues(&self) -> Vec<&Value> {
+        let mut values = self.cond.values();
+        values.push(&self.target);
+        values
+    }
+
+    pub fn map_subvalues<F>(self, mut f: F) -> Self
+        where F: FnMut(Value) -> Value {
+        Break {
+            cond: self.cond.map_values(|v| f(v)),
+            target: Box::new(f(*self.target)),
+        }
+    }
+
     pub fn ty(&self) -> Type { Type::void() }
 }
 
-impl_instruction!(Break: target);
+impl ::InstructionTrait for Break { }
+
+impl ::ExpressionTrait for Break { }
+
+impl Into<Instruction> for Break
+{
+    fn into(self) -> Instruction {
+        Instruction::Break(self)
+    }
+}
+
+impl Into<Expression> for Break
+{
+    fn into(self) -> Expression {
+        Expression::Instruction(self.into())
+    }
+}
 
@@ -1,25 +1,66 @@
 use {Instruction,Value,Expression,Type};
 
-// TODO: allow passing values as arguments.
-
-#[derive(Clone,Debug,PartialEq,Eq)]
+#[derive(Clone,Debug,PartialEq,Eq,Hash)]
 pub struct Call
 {
     target: Box<Value>,
+    arguments: Vec<Value>,
+    tail: bool,
 }
 
 impl Call
 {
+    /// Creates a call with no arguments.
     pub fn new(target: Value) -> Self {
+        Call::with_arguments(target, Vec::new())
+    }
+
+    /// Creates a call which passes a list of arguments.
+    pub fn with_arguments(target: Value, arguments: Vec<Value>) -> Self {
         Call {
             target: Box::new(target),
+            arguments: arguments,
+            tail: false,
         }
     }
 
+    /// Marks the call as being in tail position.
+    ///
+    /// A tail call may be lowered to a jump, reusing the stack
+    /// frame of the caller.
+    pub fn tail(mut self) -> Self {
+        self.tail = true;
+        self
+    }
+
+    /// Checks if the call is marked as being in tail position.
+    pub fn is_tail(&self) -> bool { self.tail }
+
     pub fn target(&self) -> &Value {
         &self.target
     }
 
+    /// Gets the arguments passed to the callee.
+    pub fn arguments(&self) -> ::std::slice::Iter<Value> {
+        self.arguments.iter()
+    }
+
+    /// Removes the arguments whose positions match a predicate.
+    pub fn remove_arguments<F>(mut self, mut f: F) -> Self
+        where F: FnMut(usize) -> bool {
+        self.arguments = self.arguments.into_iter().enumerate()
+                                       .filter(|&(i, _)| !f(i))
+                                       .map(|(_, a)| a)
+                                       .collect();
+        self
+    }
+
+    /// Sets the value being called.
+    pub fn with_target(mut self, target: Value) -> Self {
+        self.target = Box::new(target);
+        self
+    }
+
     pub fn target_id(&self) -> ::util::Id {
         if let Expression::FunctionRef(ref r) = self.target().node {
             r.function_id()
@@ -28,10 +69,54 @@ impl Call
         }
     }
 
+    pub fn subvalues(&self) -> Vec<&Value> {
+        let mut values = vec![&*self.target];
+        values.extend(self.arguments.iter());
+        values
+    }
+
+    pub fn map_subvalues<F>(mut self, mut f: F) -> Self
+        where F: FnMut(Value) -> Value {
+
+        self.target = Box::new(f(*self.target));
+        self.arguments = self.arguments.into_iter().map(|a| f(a)).collect();
+        self
+    }
+
+    /// Gets the type of the value the call evaluates to.
     pub fn ty(&self) -> Type {
-        self.target.node.ty()
+        match self.target.node {
+            Expression::FunctionRef(ref f) => f.signature().return_type(),
+            ref target => target.ty(),
+        }
     }
 }
 
-impl_instruction!(Call: target);
-impl_instruction_unary!(Call: target);
+impl ::instruction::Unary for Call
+{
+    fn with_operand(target: Value) -> Self {
+        Call::new(target)
+    }
+
+    fn operand(&self) -> &Value {
+        &self.target
+    }
+}
+
+impl ::InstructionTrait for Call { }
+
+impl ::ExpressionTrait for Call { }
+
+impl Into<Instruction> for Call
+{
+    fn into(self) -> Instruction {
+        Instruction::Call(self)
+    }
+}
+
+impl Into<Expression> for Call
+{
+    fn into(self) -> Expression {
+        Expression::Instruction(self.into())
+    }
+}
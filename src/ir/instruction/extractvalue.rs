@@ -0,0 +1,34 @@
+use {Instruction,Value,Expression,Type};
+
+/// Reads an element out of a struct, array or vector.
+#[derive(Clone,Debug,PartialEq,Eq,Hash)]
+pub struct ExtractValue
+{
+    aggregate: Box<Value>,
+    index: u64,
+}
+
+impl ExtractValue
+{
+    pub fn new(aggregate: Value, index: u64) -> Self {
+        ExtractValue {
+            aggregate: Box::new(aggregate),
+            index: index,
+        }
+    }
+
+    /// Gets the aggregate being read from.
+    pub fn aggregate(&self) -> &Value { &self.aggregate }
+
+    /// Gets the index of the element being read.
+    pub fn index(&self) -> u64 { self.index }
+
+    /// Gets the type of the element.
+    ///
+    /// This is void if the index is out of range.
+    pub fn ty(&self) -> Type {
+        self.aggregate.node.ty().element(self.index).cloned().unwrap_or_else(Type::void)
+    }
+}
+
+impl_instruction!(ExtractValue: aggregate);
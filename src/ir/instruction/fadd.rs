@@ -0,0 +1,28 @@
+use {Instruction,Value,Expression,Type};
+
+/// Floating point addition.
+#[derive(Clone,Debug,PartialEq,Eq,Hash)]
+pub struct FAdd
+{
+    lhs: Box<Value>,
+    rhs: Box<Value>,
+}
+
+impl FAdd
+{
+    pub fn new(lhs: Value, rhs: Value) -> Self {
+        assert_eq!(lhs.node.ty(), rhs.node.ty());
+
+        FAdd {
+            lhs: Box::new(lhs),
+            rhs: Box::new(rhs),
+        }
+    }
+
+    pub fn ty(&self) -> Type {
+        self.lhs.node.ty()
+    }
+}
+
+impl_instruction!(FAdd: lhs, rhs);
+impl_instruction_binary!(FAdd: lhs, rhs);
@@ -0,0 +1,56 @@
+use {Value,Condition,Instruction,Expression,Type};
+
+/// Compares two floating point values, giving `u1 1` if the
+/// condition holds and `u1 0` otherwise.
+///
+/// Comparisons with NaN never hold, except for `neq` which
+/// always does.
+#[derive(Clone,Debug,PartialEq,Eq,Hash)]
+pub struct FCmp
+{
+    cond: Condition,
+}
+
+impl FCmp
+{
+    pub fn new(cond: Condition) -> Self {
+        FCmp {
+            cond: cond,
+        }
+    }
+
+    pub fn condition(&self) -> &Condition {
+        &self.cond
+    }
+
+    pub fn subvalues(&self) -> Vec<&Value> {
+        self.cond.values()
+    }
+
+    pub fn map_subvalues<F>(self, f: F) -> Self
+        where F: FnMut(Value) -> Value {
+        FCmp {
+            cond: self.cond.map_values(f),
+        }
+    }
+
+    pub fn ty(&self) -> Type { Type::u(1) }
+}
+
+impl ::InstructionTrait for FCmp { }
+
+impl ::ExpressionTrait for FCmp { }
+
+impl Into<Instruction> for FCmp
+{
+    fn into(self) -> Instruction {
+        Instruction::FCmp(self)
+    }
+}
+
+impl Into<Expression> for FCmp
+{
+    fn into(self) -> Expression {
+        Expression::Instruction(self.into())
+    }
+}
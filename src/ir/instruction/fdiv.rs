@@ -0,0 +1,28 @@
+use {Instruction,Value,Expression,Type};
+
+/// Floating point division.
+#[derive(Clone,Debug,PartialEq,Eq,Hash)]
+pub struct FDiv
+{
+    lhs: Box<Value>,
+    rhs: Box<Value>,
+}
+
+impl FDiv
+{
+    pub fn new(lhs: Value, rhs: Value) -> Self {
+        assert_eq!(lhs.node.ty(), rhs.node.ty());
+
+        FDiv {
+            lhs: Box::new(lhs),
+            rhs: Box::new(rhs),
+        }
+    }
+
+    pub fn ty(&self) -> Type {
+        self.lhs.node.ty()
+    }
+}
+
+impl_instruction!(FDiv: lhs, rhs);
+impl_instruction_binary!(FDiv: lhs, rhs);
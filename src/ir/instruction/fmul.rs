@@ -0,0 +1,28 @@
+use {Instruction,Value,Expression,Type};
+
+/// Floating point multiplication.
+#[derive(Clone,Debug,PartialEq,Eq,Hash)]
+pub struct FMul
+{
+    lhs: Box<Value>,
+    rhs: Box<Value>,
+}
+
+impl FMul
+{
+    pub fn new(lhs: Value, rhs: Value) -> Self {
+        assert_eq!(lhs.node.ty(), rhs.node.ty());
+
+        FMul {
+            lhs: Box::new(lhs),
+            rhs: Box::new(rhs),
+        }
+    }
+
+    pub fn ty(&self) -> Type {
+        self.lhs.node.ty()
+    }
+}
+
+impl_instruction!(FMul: lhs, rhs);
+impl_instruction_binary!(FMul: lhs, rhs);
@@ -0,0 +1,28 @@
+use {Instruction,Value,Expression,Type};
+
+/// Floating point subtraction.
+#[derive(Clone,Debug,PartialEq,Eq,Hash)]
+pub struct FSub
+{
+    lhs: Box<Value>,
+    rhs: Box<Value>,
+}
+
+impl FSub
+{
+    pub fn new(lhs: Value, rhs: Value) -> Self {
+        assert_eq!(lhs.node.ty(), rhs.node.ty());
+
+        FSub {
+            lhs: Box::new(lhs),
+            rhs: Box::new(rhs),
+        }
+    }
+
+    pub fn ty(&self) -> Type {
+        self.lhs.node.ty()
+    }
+}
+
+impl_instruction!(FSub: lhs, rhs);
+impl_instruction_binary!(FSub: lhs, rhs);
@@ -0,0 +1,34 @@
+use {Instruction,Value,Expression,Type};
+use types;
+
+/// Converts a floating point value to an integer, rounding
+/// towards zero.
+///
+/// The result is undefined if the value is NaN or does not fit
+/// in the integer type.
+#[derive(Clone,Debug,PartialEq,Eq,Hash)]
+pub struct FloatToInt
+{
+    value: Box<Value>,
+    ty: types::Integer,
+}
+
+impl FloatToInt
+{
+    pub fn new(value: Value, ty: types::Integer) -> Self {
+        FloatToInt {
+            value: Box::new(value),
+            ty: ty,
+        }
+    }
+
+    /// Gets the value being converted.
+    pub fn value(&self) -> &Value { &self.value }
+
+    /// Gets the type being converted to.
+    pub fn integer_ty(&self) -> types::Integer { self.ty }
+
+    pub fn ty(&self) -> Type { self.ty.into() }
+}
+
+impl_instruction!(FloatToInt: value);
@@ -0,0 +1,103 @@
+use {Instruction,Value,Expression,Type};
+use num::traits::ToPrimitive;
+
+/// Computes the address of an element inside the memory a pointer
+/// points to.
+///
+/// The first index steps over whole objects of the pointee type, and
+/// each index after that steps into an element of an aggregate.
+/// Indices into structs must be integer literals.
+#[derive(Clone,Debug,PartialEq,Eq,Hash)]
+pub struct GetElementPtr
+{
+    pointer: Box<Value>,
+    indices: Vec<Value>,
+}
+
+impl GetElementPtr
+{
+    pub fn new(pointer: Value, indices: Vec<Value>) -> Self {
+        GetElementPtr {
+            pointer: Box::new(pointer),
+            indices: indices,
+        }
+    }
+
+    /// Gets the pointer the address is computed from.
+    pub fn pointer(&self) -> &Value { &self.pointer }
+
+    /// Gets the indices.
+    pub fn indices(&self) -> ::std::slice::Iter<Value> {
+        self.indices.iter()
+    }
+
+    /// Gets the type of the element being addressed, or `None` if
+    /// the indices do not select an element.
+    pub fn element_ty(&self) -> Option<Type> {
+        let mut ty = match self.pointer.node.ty().pointee() {
+            Some(ty) => ty.clone(),
+            None => return None,
+        };
+
+        for index in self.indices.iter().skip(1) {
+            ty = match ty {
+                Type::Struct(..) => match Self::constant(index) {
+                    Some(i) => match ty.element(i) {
+                        Some(field) => field.clone(),
+                        None => return None,
+                    },
+                    None => return None,
+                },
+                Type::Array(ref a) => a.element().clone(),
+                Type::Vector(ref v) => v.element().clone(),
+                _ => return None,
+            };
+        }
+
+        Some(ty)
+    }
+
+    /// Gets the value of an index if it is an integer literal.
+    pub fn constant(index: &Value) -> Option<u64> {
+        index.node.as_literal().and_then(|l| l.as_integer())
+                               .and_then(|i| i.value().to_u64())
+    }
+
+    pub fn subvalues(&self) -> Vec<&Value> {
+        let mut values = vec![&*self.pointer];
+        values.extend(self.indices.iter());
+        values
+    }
+
+    pub fn map_subvalues<F>(mut self, mut f: F) -> Self
+        where F: FnMut(Value) -> Value {
+        self.pointer = Box::new(f(*self.pointer));
+        self.indices = self.indices.into_iter().map(|i| f(i)).collect();
+        self
+    }
+
+    /// Gets the type of the computed pointer.
+    ///
+    /// This is void if the indices do not select an element.
+    pub fn ty(&self) -> Type {
+        self.element_ty().map(Type::pointer).unwrap_or_else(Type::void)
+    }
+}
+
+impl ::InstructionTrait for GetElementPtr { }
+
+impl ::ExpressionTrait for GetElementPtr { }
+
+impl Into<Instruction> for GetElementPtr
+{
+    fn into(self) -> Instruction {
+        Instruction::GetElementPtr(self)
+    }
+}
+
+impl Into<Expression> for GetElementPtr
+{
+    fn into(self) -> Expression {
+        Expression::Instruction(self.into())
+    }
+}
@@ -0,0 +1,53 @@
+use {Value,Condition,Instruction,Expression,Type};
+
+/// Compares two integers, giving `u1 1` if the condition holds
+/// and `u1 0` otherwise.
+#[derive(Clone,Debug,PartialEq,Eq,Hash)]
+pub struct ICmp
+{
+    cond: Condition,
+}
+
+impl ICmp
+{
+    pub fn new(cond: Condition) -> Self {
+        ICmp {
+            cond: cond,
+        }
+    }
+
+    pub fn condition(&self) -> &Condition {
+        &self.cond
+    }
+
+    pub fn subvalues(&self) -> Vec<&Value> {
+        self.cond.values()
+    }
+
+    pub fn map_subvalues<F>(self, f: F) -> Self
+        where F: FnMut(Value) -> Value {
+        ICmp {
+            cond: self.cond.map_values(f),
+        }
+    }
+
+    pub fn ty(&self) -> Type { Type::u(1) }
+}
+
+impl ::InstructionTrait for ICmp { }
+
+impl ::ExpressionTrait for ICmp { }
+
+impl Into<Instruction> for ICmp
+{
+    fn into(self) -> Instruction {
+        Instruction::ICmp(self)
+    }
+}
+
+impl Into<Expression> for ICmp
+{
+    fn into(self) -> Expression {
+        Expression::Instruction(self.into())
+    }
+}
@@ -0,0 +1,36 @@
+use {Instruction,Value,Expression,Type};
+
+/// Gives a copy of a struct, array or vector with one element replaced.
+#[derive(Clone,Debug,PartialEq,Eq,Hash)]
+pub struct InsertValue
+{
+    aggregate: Box<Value>,
+    value: Box<Value>,
+    index: u64,
+}
+
+impl InsertValue
+{
+    pub fn new(aggregate: Value, value: Value, index: u64) -> Self {
+        InsertValue {
+            aggregate: Box::new(aggregate),
+            value: Box::new(value),
+            index: index,
+        }
+    }
+
+    /// Gets the aggregate being copied.
+    pub fn aggregate(&self) -> &Value { &self.aggregate }
+
+    /// Gets the value being inserted.
+    pub fn value(&self) -> &Value { &self.value }
+
+    /// Gets the index of the element being replaced.
+    pub fn index(&self) -> u64 { self.index }
+
+    pub fn ty(&self) -> Type {
+        self.aggregate.node.ty()
+    }
+}
+
+impl_instruction!(InsertValue: aggregate, value);
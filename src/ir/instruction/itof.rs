@@ -0,0 +1,32 @@
+use {Instruction,Value,Expression,Type};
+use types;
+
+/// Converts an integer to the nearest floating point value.
+///
+/// The integer is treated as signed if its type is.
+#[derive(Clone,Debug,PartialEq,Eq,Hash)]
+pub struct IntToFloat
+{
+    value: Box<Value>,
+    ty: types::Decimal,
+}
+
+impl IntToFloat
+{
+    pub fn new(value: Value, ty: types::Decimal) -> Self {
+        IntToFloat {
+            value: Box::new(value),
+            ty: ty,
+        }
+    }
+
+    /// Gets the integer being converted.
+    pub fn value(&self) -> &Value { &self.value }
+
+    /// Gets the type being converted to.
+    pub fn decimal_ty(&self) -> types::Decimal { self.ty }
+
+    pub fn ty(&self) -> Type { self.ty.into() }
+}
+
+impl_instruction!(IntToFloat: value);
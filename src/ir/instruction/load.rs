@@ -0,0 +1,29 @@
+use {Instruction,Value,Expression,Type};
+
+/// Reads the value that a pointer points to.
+#[derive(Clone,Debug,PartialEq,Eq,Hash)]
+pub struct Load
+{
+    ptr: Box<Value>,
+}
+
+impl Load
+{
+    pub fn new(ptr: Value) -> Self {
+        assert!(ptr.node.ty().pointee().is_some(), "can only load from a pointer");
+
+        Load {
+            ptr: Box::new(ptr),
+        }
+    }
+
+    /// Gets the pointer being loaded from.
+    pub fn pointer(&self) -> &Value { &self.ptr }
+
+    pub fn ty(&self) -> Type {
+        self.ptr.node.ty().pointee().cloned().unwrap()
+    }
+}
+
+impl_instruction!(Load: ptr);
+impl_instruction_unary!(Load: ptr);
@@ -4,19 +4,36 @@ pub use self::add::Add;
 pub use self::sub::Sub;
 pub use self::mul::Mul;
 pub use self::div::Div;
+pub use self::rem::Rem;
+pub use self::mulh::MulHigh;
+pub use self::and::And;
 pub use self::shl::Shl;
 pub use self::shr::Shr;
 pub use self::call::Call;
 pub use self::ret::Return;
 pub use self::br::Break;
 pub use self::copy::Copy;
+pub use self::load::Load;
+pub use self::store::Store;
+pub use self::phi::Phi;
+pub use self::extractvalue::ExtractValue;
+pub use self::insertvalue::InsertValue;
+pub use self::getelementptr::GetElementPtr;
+pub use self::fadd::FAdd;
+pub use self::fsub::FSub;
+pub use self::fmul::FMul;
+pub use self::fdiv::FDiv;
+pub use self::icmp::ICmp;
+pub use self::fcmp::FCmp;
+pub use self::itof::IntToFloat;
+pub use self::ftoi::FloatToInt;
 
 
 #[macro_use]
 pub mod instruction
 {
     use std::fmt;
-    use {instruction,Value,Expression,Type,ExpressionTrait,value,Block,
+    use {instruction,types,Value,Expression,Type,ExpressionTrait,value,Block,
          Condition};
 
     pub trait InstructionTrait : fmt::Debug +
@@ -52,13 +69,16 @@ pub mod instruction
         }
     }
 
-    #[derive(Clone,Debug,PartialEq,Eq)]
+    #[derive(Clone,Debug,PartialEq,Eq,Hash)]
     pub enum Instruction
     {
         Add(instruction::Add),
         Sub(instruction::Sub),
         Mul(instruction::Mul),
         Div(instruction::Div),
+        Rem(instruction::Rem),
+        MulHigh(instruction::MulHigh),
+        And(instruction::And),
         Shl(instruction::Shl),
         Shr(instruction::Shr),
 
@@ -67,6 +87,22 @@ pub mod instruction
         Return(instruction::Return),
 
         Copy(instruction::Copy),
+        Load(instruction::Load),
+        Store(instruction::Store),
+        Phi(instruction::Phi),
+
+        ExtractValue(instruction::ExtractValue),
+        InsertValue(instruction::InsertValue),
+        GetElementPtr(instruction::GetElementPtr),
+
+        FAdd(instruction::FAdd),
+        FSub(instruction::FSub),
+        FMul(instruction::FMul),
+        FDiv(instruction::FDiv),
+        ICmp(instruction::ICmp),
+        FCmp(instruction::FCmp),
+        IntToFloat(instruction::IntToFloat),
+        FloatToInt(instruction::FloatToInt),
     }
 
     impl Instruction
@@ -91,6 +127,21 @@ pub mod instruction
             instruction::Div::new(lhs.into(), rhs.into()).into()
         }
 
+        pub fn rem<V1,V2>(lhs: V1, rhs: V2) -> Instruction
+            where V1: Into<Value>, V2: Into<Value> {
+            instruction::Rem::new(lhs.into(), rhs.into()).into()
+        }
+
+        pub fn mulh<V1,V2>(lhs: V1, rhs: V2) -> Instruction
+            where V1: Into<Value>, V2: Into<Value> {
+            instruction::MulHigh::new(lhs.into(), rhs.into()).into()
+        }
+
+        pub fn and<V1,V2>(lhs: V1, rhs: V2) -> Instruction
+            where V1: Into<Value>, V2: Into<Value> {
+            instruction::And::new(lhs.into(), rhs.into()).into()
+        }
+
         pub fn shl<V1,V2>(val: V1, amount: V2) -> Instruction
             where V1: Into<Value>, V2: Into<Value> {
             instruction::Shl::new(val.into(), amount.into()).into()
@@ -120,6 +171,81 @@ pub mod instruction
             instruction::Call::new(target.into()).into()
         }
 
+        pub fn call_with_arguments<V,I>(target: V, arguments: I) -> Self
+            where V: Into<Value>, I: IntoIterator<Item=Value> {
+            instruction::Call::with_arguments(target.into(),
+                                              arguments.into_iter().collect()).into()
+        }
+
+        pub fn load<V>(ptr: V) -> Self
+            where V: Into<Value> {
+            instruction::Load::new(ptr.into()).into()
+        }
+
+        pub fn store<V1,V2>(ptr: V1, value: V2) -> Self
+            where V1: Into<Value>, V2: Into<Value> {
+            instruction::Store::new(ptr.into(), value.into()).into()
+        }
+
+        pub fn phi<I>(ty: Type, incoming: I) -> Self
+            where I: IntoIterator<Item=(Value,Value)> {
+            instruction::Phi::new(ty, incoming.into_iter().collect()).into()
+        }
+
+        pub fn extractvalue<V>(aggregate: V, index: u64) -> Self
+            where V: Into<Value> {
+            instruction::ExtractValue::new(aggregate.into(), index).into()
+        }
+
+        pub fn insertvalue<V1,V2>(aggregate: V1, value: V2, index: u64) -> Self
+            where V1: Into<Value>, V2: Into<Value> {
+            instruction::InsertValue::new(aggregate.into(), value.into(), index).into()
+        }
+
+        pub fn getelementptr<V,I>(pointer: V, indices: I) -> Self
+            where V: Into<Value>, I: IntoIterator<Item=Value> {
+            instruction::GetElementPtr::new(pointer.into(),
+                                            indices.into_iter().collect()).into()
+        }
+
+        pub fn fadd<V1,V2>(lhs: V1, rhs: V2) -> Instruction
+            where V1: Into<Value>, V2: Into<Value> {
+            instruction::FAdd::new(lhs.into(), rhs.into()).into()
+        }
+
+        pub fn fsub<V1,V2>(lhs: V1, rhs: V2) -> Instruction
+            where V1: Into<Value>, V2: Into<Value> {
+            instruction::FSub::new(lhs.into(), rhs.into()).into()
+        }
+
+        pub fn fmul<V1,V2>(lhs: V1, rhs: V2) -> Instruction
+            where V1: Into<Value>, V2: Into<Value> {
+            instruction::FMul::new(lhs.into(), rhs.into()).into()
+        }
+
+        pub fn fdiv<V1,V2>(lhs: V1, rhs: V2) -> Instruction
+            where V1: Into<Value>, V2: Into<Value> {
+            instruction::FDiv::new(lhs.into(), rhs.into()).into()
+        }
+
+        pub fn icmp(condition: Condition) -> Self {
+            instruction::ICmp::new(condition).into()
+        }
+
+        pub fn fcmp(condition: Condition) -> Self {
+            instruction::FCmp::new(condition).into()
+        }
+
+        pub fn itof<V>(value: V, ty: types::Decimal) -> Self
+            where V: Into<Value> {
+            instruction::IntToFloat::new(value.into(), ty).into()
+        }
+
+        pub fn ftoi<V>(value: V, ty: types::Integer) -> Self
+            where V: Into<Value> {
+            instruction::FloatToInt::new(value.into(), ty).into()
+        }
+
         /// Flattens the instruction.
         ///
         /// Subvalues are placed into registers in the block.
@@ -174,12 +300,29 @@ pub mod instruction
                Instruction::Sub(ref instr) => instr.subvalues(),
                Instruction::Mul(ref instr) => instr.subvalues(),
                Instruction::Div(ref instr) => instr.subvalues(),
+               Instruction::Rem(ref instr) => instr.subvalues(),
+               Instruction::MulHigh(ref instr) => instr.subvalues(),
+               Instruction::And(ref instr) => instr.subvalues(),
                Instruction::Shl(ref instr) => instr.subvalues(),
                Instruction::Shr(ref instr) => instr.subvalues(),
                Instruction::Call(ref instr) => instr.subvalues(),
                Instruction::Break(ref instr) => instr.subvalues(),
                Instruction::Return(ref instr) => instr.subvalues(),
                Instruction::Copy(ref instr) => instr.subvalues(),
+               Instruction::Load(ref instr) => instr.subvalues(),
+               Instruction::Store(ref instr) => instr.subvalues(),
+               Instruction::Phi(ref instr) => instr.subvalues(),
+               Instruction::ExtractValue(ref instr) => instr.subvalues(),
+               Instruction::InsertValue(ref instr) => instr.subvalues(),
+               Instruction::GetElementPtr(ref instr) => instr.subvalues(),
+               Instruction::FAdd(ref instr) => instr.subvalues(),
+               Instruction::FSub(ref instr) => instr.subvalues(),
+               Instruction::FMul(ref instr) => instr.subvalues(),
+               Instruction::FDiv(ref instr) => instr.subvalues(),
+               Instruction::ICmp(ref instr) => instr.subvalues(),
+               Instruction::FCmp(ref instr) => instr.subvalues(),
+               Instruction::IntToFloat(ref instr) => instr.subvalues(),
+               Instruction::FloatToInt(ref instr) => instr.subvalues(),
             }
         }
 
@@ -191,12 +334,29 @@ pub mod instruction
                Instruction::Sub(instr) => instr.map_subvalues(f).into(),
                Instruction::Mul(instr) => instr.map_subvalues(f).into(),
                Instruction::Div(instr) => instr.map_subvalues(f).into(),
+               Instruction::Rem(instr) => instr.map_subvalues(f).into(),
+               Instruction::MulHigh(instr) => instr.map_subvalues(f).into(),
+               Instruction::And(instr) => instr.map_subvalues(f).into(),
                Instruction::Shl(instr) => instr.map_subvalues(f).into(),
                Instruction::Shr(instr) => instr.map_subvalues(f).into(),
                Instruction::Call(instr) => instr.map_subvalues(f).into(),
                Instruction::Break(instr) => instr.map_subvalues(f).into(),
                Instruction::Return(instr) => instr.map_subvalues(f).into(),
                Instruction::Copy(instr) => instr.map_subvalues(f).into(),
+               Instruction::Load(instr) => instr.map_subvalues(f).into(),
+               Instruction::Store(instr) => instr.map_subvalues(f).into(),
+               Instruction::Phi(instr) => instr.map_subvalues(f).into(),
+               Instruction::ExtractValue(instr) => instr.map_subvalues(f).into(),
+               Instruction::InsertValue(instr) => instr.map_subvalues(f).into(),
+               Instruction::GetElementPtr(instr) => instr.map_subvalues(f).into(),
+               Instruction::FAdd(instr) => instr.map_subvalues(f).into(),
+               Instruction::FSub(instr) => instr.map_subvalues(f).into(),
+               Instruction::FMul(instr) => instr.map_subvalues(f).into(),
+               Instruction::FDiv(instr) => instr.map_subvalues(f).into(),
+               Instruction::ICmp(instr) => instr.map_subvalues(f).into(),
+               Instruction::FCmp(instr) => instr.map_subvalues(f).into(),
+               Instruction::IntToFloat(instr) => instr.map_subvalues(f).into(),
+               Instruction::FloatToInt(instr) => instr.map_subvalues(f).into(),
             }
         }
 
@@ -206,12 +366,62 @@ pub mod instruction
                 Instruction::Sub(..) => false,
                 Instruction::Mul(..) => false,
                 Instruction::Div(..) => false,
+                Instruction::Rem(..) => false,
+                Instruction::MulHigh(..) => false,
+                Instruction::And(..) => false,
                 Instruction::Shl(..) => false,
                 Instruction::Shr(..) => false,
                 Instruction::Call(..) => true,
                 Instruction::Break(..) => true,
                 Instruction::Return(..) => true,
                 Instruction::Copy(..) => false,
+                Instruction::Load(..) => false,
+                Instruction::Store(..) => true,
+                Instruction::Phi(..) => false,
+                Instruction::ExtractValue(..) => false,
+                Instruction::InsertValue(..) => false,
+                Instruction::GetElementPtr(..) => false,
+                Instruction::FAdd(..) => false,
+                Instruction::FSub(..) => false,
+                Instruction::FMul(..) => false,
+                Instruction::FDiv(..) => false,
+                Instruction::ICmp(..) => false,
+                Instruction::FCmp(..) => false,
+                Instruction::IntToFloat(..) => false,
+                Instruction::FloatToInt(..) => false,
+            }
+        }
+
+        /// Gets the mnemonic the instruction is written with.
+        pub fn mnemonic(&self) -> &'static str {
+            match *self {
+                Instruction::Add(..) => "add",
+                Instruction::Sub(..) => "sub",
+                Instruction::Mul(..) => "mul",
+                Instruction::Div(..) => "div",
+                Instruction::Rem(..) => "rem",
+                Instruction::MulHigh(..) => "mulh",
+                Instruction::And(..) => "and",
+                Instruction::Shl(..) => "shl",
+                Instruction::Shr(..) => "shr",
+                Instruction::Call(..) => "call",
+                Instruction::Break(..) => "br",
+                Instruction::Return(..) => "ret",
+                Instruction::Copy(..) => "copy",
+                Instruction::Load(..) => "load",
+                Instruction::Store(..) => "store",
+                Instruction::Phi(..) => "phi",
+                Instruction::ExtractValue(..) => "extractvalue",
+                Instruction::InsertValue(..) => "insertvalue",
+                Instruction::GetElementPtr(..) => "getelementptr",
+                Instruction::FAdd(..) => "fadd",
+                Instruction::FSub(..) => "fsub",
+                Instruction::FMul(..) => "fmul",
+                Instruction::FDiv(..) => "fdiv",
+                Instruction::ICmp(..) => "icmp",
+                Instruction::FCmp(..) => "fcmp",
+                Instruction::IntToFloat(..) => "itof",
+                Instruction::FloatToInt(..) => "ftoi",
             }
         }
 
@@ -229,12 +439,29 @@ pub mod instruction
                 Instruction::Sub(ref instr) => instr.ty(),
                 Instruction::Mul(ref instr) => instr.ty(),
                 Instruction::Div(ref instr) => instr.ty(),
+                Instruction::Rem(ref instr) => instr.ty(),
+                Instruction::MulHigh(ref instr) => instr.ty(),
+                Instruction::And(ref instr) => instr.ty(),
                 Instruction::Shl(ref instr) => instr.ty(),
                 Instruction::Shr(ref instr) => instr.ty(),
                 Instruction::Call(ref instr) => instr.ty(),
                 Instruction::Break(ref instr) => instr.ty(),
                 Instruction::Return(ref instr) => instr.ty(),
                 Instruction::Copy(ref instr) => instr.ty(),
+                Instruction::Load(ref instr) => instr.ty(),
+                Instruction::Store(ref instr) => instr.ty(),
+                Instruction::Phi(ref instr) => instr.ty(),
+                Instruction::ExtractValue(ref instr) => instr.ty(),
+                Instruction::InsertValue(ref instr) => instr.ty(),
+                Instruction::GetElementPtr(ref instr) => instr.ty(),
+                Instruction::FAdd(ref instr) => instr.ty(),
+                Instruction::FSub(ref instr) => instr.ty(),
+                Instruction::FMul(ref instr) => instr.ty(),
+                Instruction::FDiv(ref instr) => instr.ty(),
+                Instruction::ICmp(ref instr) => instr.ty(),
+                Instruction::FCmp(ref instr) => instr.ty(),
+                Instruction::IntToFloat(ref instr) => instr.ty(),
+                Instruction::FloatToInt(ref instr) => instr.ty(),
              }
         }
 
@@ -325,10 +552,27 @@ pub mod add;
 pub mod sub;
 pub mod mul;
 pub mod div;
+pub mod rem;
+pub mod mulh;
+pub mod and;
 pub mod shl;
 pub mod shr;
 pub mod call;
 pub mod ret;
 pub mod br;
 pub mod copy;
+pub mod load;
+pub mod store;
+pub mod phi;
+pub mod extractvalue;
+pub mod insertvalue;
+pub mod getelementptr;
+pub mod fadd;
+pub mod fsub;
+pub mod fmul;
+pub mod fdiv;
+pub mod icmp;
+pub mod fcmp;
+pub mod itof;
+pub mod ftoi;
 
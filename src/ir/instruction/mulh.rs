@@ -0,0 +1,29 @@
+use {Instruction,Value,Expression,Type};
+
+/// The upper half of the double-width product of two integers.
+///
+/// The operands are treated as signed or unsigned depending
+/// on their type.
+#[derive(Clone,Debug,PartialEq,Eq,Hash)]
+pub struct MulHigh
+{
+    lhs: Box<Value>,
+    rhs: Box<Value>,
+}
+
+impl MulHigh
+{
+    pub fn new(lhs: Value, rhs: Value) -> Self {
+        assert!(lhs.node.ty() == rhs.node.ty());
+
+        MulHigh {
+            lhs: Box::new(lhs),
+            rhs: Box::new(rhs),
+        }
+    }
+
+    pub fn ty(&self) -> Type { self.lhs.node.ty() }
+}
+
+impl_instruction!(MulHigh: lhs, rhs);
+impl_instruction_binary!(MulHigh: lhs, rhs);
@@ -0,0 +1,98 @@
+use {Instruction,Value,Expression,Type};
+use util;
+
+/// Selects a value based on which block control came from.
+///
+/// Each incoming value is paired with a reference to the
+/// predecessor block it flows in from.
+#[derive(Clone,Debug,PartialEq,Eq,Hash)]
+pub struct Phi
+{
+    ty: Type,
+    incoming: Vec<(Value,Value)>,
+}
+
+impl Phi
+{
+    /// Creates a new phi.
+    pub fn new(ty: Type, incoming: Vec<(Value,Value)>) -> Self {
+        Phi {
+            ty: ty,
+            incoming: incoming,
+        }
+    }
+
+    /// Gets the incoming values, along with the blocks they come from.
+    pub fn incoming(&self) -> ::std::slice::Iter<(Value,Value)> {
+        self.incoming.iter()
+    }
+
+    /// Gets the value which flows in from a block.
+    pub fn incoming_from(&self, block: util::Id) -> Option<&Value> {
+        self.incoming.iter().find(|&&(_, ref b)| Self::is_block(b, block))
+                            .map(|&(ref v, _)| v)
+    }
+
+    /// Removes the values which flow in from blocks matching a predicate.
+    pub fn remove_incoming<F>(mut self, mut f: F) -> Self
+        where F: FnMut(util::Id) -> bool {
+        self.incoming.retain(|&(_, ref b)| match b.node {
+            Expression::BlockRef(ref r) => !f(r.block_id()),
+            _ => true,
+        });
+        self
+    }
+
+    /// Makes the values which flowed in from one block flow in
+    /// from another instead.
+    pub fn rename_incoming(mut self, from: util::Id, to: util::Id) -> Self {
+        for &mut (_, ref mut b) in self.incoming.iter_mut() {
+            if Self::is_block(b, from) {
+                *b = Value::new(::value::BlockRef::new(to).into());
+            }
+        }
+        self
+    }
+
+    pub fn subvalues(&self) -> Vec<&Value> {
+        self.incoming.iter().flat_map(|&(ref v, ref b)| vec![v, b]).collect()
+    }
+
+    pub fn map_subvalues<F>(mut self, mut f: F) -> Self
+        where F: FnMut(Value) -> Value {
+        self.incoming = self.incoming.into_iter().map(|(v, b)| {
+            let v = f(v);
+            (v, f(b))
+        }).collect();
+        self
+    }
+
+    pub fn ty(&self) -> Type {
+        self.ty.clone()
+    }
+
+    fn is_block(value: &Value, block: util::Id) -> bool {
+        match value.node {
+            Expression::BlockRef(ref r) => r.block_id() == block,
+            _ => false,
+        }
+    }
+}
+
+impl ::InstructionTrait for Phi { }
+
+impl ::ExpressionTrait for Phi { }
+
+impl Into<Instruction> for Phi
+{
+    fn into(self) -> Instruction {
+        Instruction::Phi(self)
+    }
+}
+
+impl Into<Expression> for Phi
+{
+    fn into(self) -> Expression {
+        Expression::Instruction(self.into())
+    }
+}
@@ -0,0 +1,26 @@
+use {Instruction,Value,Expression,Type};
+
+/// The remainder of a division, which has the sign of the dividend.
+#[derive(Clone,Debug,PartialEq,Eq,Hash)]
+pub struct Rem
+{
+    lhs: Box<Value>,
+    rhs: Box<Value>,
+}
+
+impl Rem
+{
+    pub fn new(lhs: Value, rhs: Value) -> Self {
+        assert!(lhs.node.ty() == rhs.node.ty());
+
+        Rem {
+            lhs: Box::new(lhs),
+            rhs: Box::new(rhs),
+        }
+    }
+
+    pub fn ty(&self) -> Type { self.lhs.node.ty() }
+}
+
+impl_instruction!(Rem: lhs, rhs);
+impl_instruction_binary!(Rem: lhs, rhs);
@@ -1,7 +1,7 @@
 
 use {Instruction,Value,Expression,Type};
 
-#[derive(Clone,Debug,PartialEq,Eq)]
+#[derive(Clone,Debug,PartialEq,Eq,Hash)]
 pub struct Return
 {
     value: Option<Box<Value>>,
@@ -0,0 +1,30 @@
+use {Instruction,Value,Expression,Type};
+
+/// Writes a value to the memory a pointer points to.
+#[derive(Clone,Debug,PartialEq,Eq,Hash)]
+pub struct Store
+{
+    ptr: Box<Value>,
+    value: Box<Value>,
+}
+
+impl Store
+{
+    pub fn new(ptr: Value, value: Value) -> Self {
+        Store {
+            ptr: Box::new(ptr),
+            value: Box::new(value),
+        }
+    }
+
+    /// Gets the pointer being stored to.
+    pub fn pointer(&self) -> &Value { &self.ptr }
+
+    /// Gets the value being stored.
+    pub fn value(&self) -> &Value { &self.value }
+
+    pub fn ty(&self) -> Type { Type::void() }
+}
+
+impl_instruction!(Store: ptr, value);
+impl_instruction_binary!(Store: ptr, value);
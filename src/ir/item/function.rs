@@ -1,5 +1,5 @@
-use {Item,ItemTrait,Value,Type,Block};
-use {InlineHint,ComplexityHint,CallingConvention};
+use {Item,ItemTrait,Value,Type,Block,Comment};
+use {InlineHint,ComplexityHint,CallingConvention,Linkage,Interrupt};
 
 use util;
 use std;
@@ -59,6 +59,14 @@ impl std::cmp::PartialEq for Parameter
 
 impl std::cmp::Eq for Parameter { }
 
+impl std::hash::Hash for Parameter
+{
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.ty.hash(state);
+        self.name.hash(state);
+    }
+}
+
 /// A function signature.
 /// 
 /// Holds the return and parameter types.
@@ -107,6 +115,17 @@ impl Signature
         self.return_types.iter()
     }
 
+    /// Gets the type of the value returned from a call to the function.
+    ///
+    /// Functions with several return values return a structure.
+    pub fn return_type(&self) -> Type {
+        match self.return_types.len() {
+            0 => Type::void(),
+            1 => self.return_types[0].clone(),
+            _ => ::types::Struct::new(self.return_types.iter().cloned()).into(),
+        }
+    }
+
     /// Checks if the function has return values.
     pub fn has_returns(&self) -> bool {
         !self.return_types.is_empty()
@@ -117,6 +136,13 @@ impl Signature
         self.params.iter()
     }
 
+    /// Removes the parameters which match a predicate.
+    pub fn remove_parameters<F>(mut self, mut f: F) -> Self
+        where F: FnMut(&Parameter) -> bool {
+        self.params = self.params.into_iter().filter(|p| !f(p)).collect();
+        self
+    }
+
     /// Checks if the function has any parameters.
     pub fn has_parameters(&self) -> bool {
         !self.params.is_empty()
@@ -145,6 +171,13 @@ impl std::cmp::PartialEq for Signature
 
 impl std::cmp::Eq for Signature { }
 
+impl std::hash::Hash for Signature
+{
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.return_types.hash(state);
+    }
+}
+
 /// A function.
 #[derive(Clone,Debug)]
 pub struct Function
@@ -155,10 +188,24 @@ pub struct Function
     pub signature: Signature,
     pub blocks: Vec<Block>,
 
-    pub cc: CallingConvention,
+    /// The calling convention, if one was given.
+    pub cc: Option<CallingConvention>,
+    pub linkage: Linkage,
 
     pub inline_hint: InlineHint,
     pub complexity_hint: ComplexityHint,
+
+    /// Whether the function never returns to its caller.
+    pub noreturn: bool,
+    /// Whether the function is emitted without a prologue or epilogue.
+    pub naked: bool,
+    /// The interrupt the function handles, if it is a handler.
+    pub interrupt: Option<Interrupt>,
+    /// The section of the output the function is placed in.
+    pub section: Option<String>,
+
+    /// The comments written around the function, if they were kept.
+    pub comments: Vec<Comment>,
 }
 
 impl Function
@@ -176,9 +223,15 @@ impl Function
             signature: signature,
             blocks: blocks,
 
-            cc: CallingConvention::default(),
+            cc: None,
+            linkage: Linkage::default(),
             inline_hint: InlineHint::default(),
             complexity_hint: ComplexityHint::default(),
+            noreturn: false,
+            naked: false,
+            interrupt: None,
+            section: None,
+            comments: Vec::new(),
         }
     }
 
@@ -214,6 +267,19 @@ impl Function
         self.blocks.iter()
     }
 
+    /// Gets the entry block of the function, if it has one.
+    ///
+    /// The entry block is always the first block.
+    pub fn entry_block(&self) -> Option<&Block> {
+        self.blocks.first()
+    }
+
+    /// Finds a block in the function by its ID.
+    pub fn find_block(&self, id: util::Id) -> Option<&Block> {
+        use util::Identifiable;
+        self.blocks.iter().find(|b| b.get_id() == id)
+    }
+
     /// Gets a mutable iterator to the contained blocks.
     pub fn blocks_mut(&mut self) -> std::slice::IterMut<Block> {
         self.blocks.iter_mut()
@@ -254,7 +320,17 @@ impl Function
 
     /// Gets the calling convention.
     pub fn calling_convention(&self) -> CallingConvention {
-        self.cc
+        self.cc.unwrap_or_default()
+    }
+
+    /// Gets the linkage.
+    pub fn linkage(&self) -> Linkage {
+        self.linkage
+    }
+
+    /// Checks if the function is visible outside of the module.
+    pub fn is_exported(&self) -> bool {
+        self.linkage == Linkage::External
     }
 
     /// Gets the inline hint.
@@ -266,6 +342,26 @@ impl Function
     pub fn complexity_hint(&self) -> ComplexityHint {
         self.complexity_hint
     }
+
+    /// Checks if the function never returns to its caller.
+    pub fn is_noreturn(&self) -> bool {
+        self.noreturn
+    }
+
+    /// Checks if the function is emitted without a prologue or epilogue.
+    pub fn is_naked(&self) -> bool {
+        self.naked
+    }
+
+    /// Gets the interrupt the function handles, if it is a handler.
+    pub fn interrupt(&self) -> Option<Interrupt> {
+        self.interrupt
+    }
+
+    /// Gets the section of the output the function is placed in.
+    pub fn section(&self) -> Option<&str> {
+        self.section.as_ref().map(|s| &s[..])
+    }
 }
 
 impl util::Identifiable for Function
@@ -1,4 +1,4 @@
-use {Item,ItemTrait,Value,Type};
+use {Item,ItemTrait,Value,Type,Linkage,Comment};
 use util;
 
 use std;
@@ -12,6 +12,12 @@ pub struct Global
     pub name: String,
     /// The variable value.
     pub value: Value,
+    /// The linkage of the global.
+    pub linkage: Linkage,
+    /// Whether the value of the global can never change.
+    pub constant: bool,
+    /// The comments written around the global, if they were kept.
+    pub comments: Vec<Comment>,
 }
 
 impl Global
@@ -23,9 +29,24 @@ impl Global
 
             name: name,
             value: value,
+            linkage: Linkage::default(),
+            constant: false,
+            comments: Vec::new(),
         }
     }
 
+    /// Sets the linkage of the global.
+    pub fn with_linkage(mut self, linkage: Linkage) -> Self {
+        self.linkage = linkage;
+        self
+    }
+
+    /// Marks the global as constant.
+    pub fn constant(mut self) -> Self {
+        self.constant = true;
+        self
+    }
+
     /// Gets the name of the global.
     pub fn name(&self) -> &str { &self.name }
     /// Gets the value of the global.
@@ -34,6 +55,12 @@ impl Global
     pub fn ty(&self) -> Type { self.value.node.ty() }
     /// Gets the ID of the global.
     pub fn id(&self) -> util::Id { self.id }
+    /// Gets the linkage of the global.
+    pub fn linkage(&self) -> Linkage { self.linkage }
+    /// Checks if the global is visible outside of the module.
+    pub fn is_exported(&self) -> bool { self.linkage == Linkage::External }
+    /// Checks if the value of the global can never change.
+    pub fn is_constant(&self) -> bool { self.constant }
 
     pub fn map_value<F>(mut self, mut f: F) -> Self
         where F: FnMut(Value) -> Value {
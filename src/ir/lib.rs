@@ -1,13 +1,14 @@
 pub use self::types::{Type,TypeTrait};
 pub use self::value::{Value,Expression,ExpressionTrait,Register};
 pub use self::instruction::{Instruction,InstructionTrait,Unary,Binary};
-pub use self::print::printable;
+pub use self::print::{printable,printable_exact};
 pub use self::users::Users;
 pub use self::cond::Condition;
 
 pub use self::module::Module;
 
-pub use self::span::Spanned;
+pub use self::span::{Spanned,Span,Location};
+pub use self::comment::Comment;
 pub use self::item::Global;
 pub use self::item::{Item,ItemTrait,Function,Signature,Parameter};
 
@@ -20,6 +21,8 @@ pub use self::attrs::*;
 pub mod item;
 
 pub mod span;
+/// Comments kept from the source of a module.
+pub mod comment;
 
 /// Where types are implemented.
 pub mod types;
@@ -33,6 +36,8 @@ pub mod verifier;
 pub mod print;
 /// IR reading routines.
 pub mod read;
+/// IR writing routines.
+pub mod write;
 /// Expression user information.
 pub mod users;
 /// Condition codes.
@@ -1,4 +1,4 @@
-use {Value, Function, Global, Block, Users, ItemTrait};
+use {Value, Function, Global, Block, Users, ItemTrait, Comment};
 use util::Identifiable;
 use util;
 
@@ -10,6 +10,11 @@ pub struct Module
 {
     pub functions: util::List<Function>,
     pub globals: util::List<Global>,
+
+    /// Comments written at the top of the module, apart from any item.
+    pub leading_comments: Vec<Comment>,
+    /// Comments written after the last item.
+    pub trailing_comments: Vec<Comment>,
 }
 
 impl Module
@@ -19,6 +24,8 @@ impl Module
         Module {
             functions: util::List::empty(),
             globals: util::List::empty(),
+            leading_comments: Vec::new(),
+            trailing_comments: Vec::new(),
         }
     }
 
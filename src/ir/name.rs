@@ -63,3 +63,12 @@ impl std::cmp::PartialEq for Name
 }
 
 impl std::cmp::Eq for Name { }
+
+impl std::hash::Hash for Name
+{
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        if let Name::Named(ref name) = *self {
+            name.hash(state);
+        }
+    }
+}
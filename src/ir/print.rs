@@ -1,4 +1,5 @@
-use {Module,Global,Expression,Function,Value,value,Condition,Block,Register};
+use {Module,Global,Expression,Function,Value,value,Condition,Block,Register,Comment};
+use {Linkage,InlineHint,ComplexityHint,CallingConvention};
 use util;
 use util::Identifiable;
 use std::fmt;
@@ -6,21 +7,41 @@ use std::collections::HashMap;
 
 pub const TAB_STRING: &'static str = "  ";
 
+/// How a module is printed.
+#[derive(Copy,Clone,Debug,PartialEq,Eq)]
+pub enum Mode
+{
+    /// A readable outline of the module, with nested values
+    /// parenthesised and unnamed registers numbered.
+    Readable,
+    /// The syntax the parser reads, keeping names, block order,
+    /// attributes and comments so that the module can be read back.
+    Exact,
+}
+
 /// A hack to be able to implement `Display` on a module.
 ///
 /// This is to work around Rust's orphan rules.
-pub struct Printable<'a>(&'a Module);
+pub struct Printable<'a>(&'a Module, Mode);
 
 impl<'a> fmt::Display for Printable<'a>
 {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        self::module(self.0, fmt)
+        self::module_in(self.0, self.1, fmt)
     }
 }
 
 /// Creates a printable module.
 pub fn printable(module: &Module) -> Printable {
-    Printable(module)
+    Printable(module, Mode::Readable)
+}
+
+/// Creates a module which prints as the parser reads it.
+///
+/// Reading a module with comments preserved and printing it this way
+/// gives back the original text if it was written in the canonical style.
+pub fn printable_exact(module: &Module) -> Printable {
+    Printable(module, Mode::Exact)
 }
 
 /// Holds IR printing state.
@@ -29,6 +50,9 @@ pub struct Printer<'a>
     /// The module that is being printed.
     module: &'a Module,
 
+    /// How the module is being printed.
+    mode: Mode,
+
     /// The function that is currently being enumerated.
     current_function: Option<&'a Function>,
 
@@ -89,18 +113,35 @@ impl<'a> Printer<'a>
         self.register_accumulator = 0;
         self.register_map.clear();
     }
+
+    /// Checks if the module is being printed as the parser reads it.
+    fn is_exact(&self) -> bool {
+        self.mode == Mode::Exact
+    }
 }
 
 /// Prints an IR module.
 pub fn module<'a>(module: &'a Module, fmt: &mut fmt::Formatter) -> fmt::Result {
+    self::module_in(module, Mode::Readable, fmt)
+}
+
+/// Prints an IR module in the given mode.
+pub fn module_in<'a>(module: &'a Module,
+                     mode: Mode,
+                     fmt: &mut fmt::Formatter) -> fmt::Result {
 
     let mut printer = Printer {
         register_map: HashMap::new(),
         module: module,
+        mode: mode,
         current_function: None,
         register_accumulator: 0,
     };
 
+    if mode == Mode::Exact {
+        return self::exact_module(module, &mut printer, fmt);
+    }
+
     for global in module.globals() {
         try!(self::global(global, &mut printer, fmt));
     }
@@ -115,14 +156,108 @@ pub fn module<'a>(module: &'a Module, fmt: &mut fmt::Formatter) -> fmt::Result {
     Ok(())
 }
 
+/// Prints a module with items split by blank lines, and any
+/// header and footer comments set apart.
+fn exact_module<'a>(module: &'a Module,
+                    printer: &mut Printer<'a>,
+                    fmt: &mut fmt::Formatter) -> fmt::Result {
+    let mut separate = false;
+
+    if !module.leading_comments.is_empty() {
+        try!(self::comments_before(&module.leading_comments, "", fmt));
+        separate = true;
+    }
+
+    for (i, global) in module.globals().enumerate() {
+        if separate && i == 0 {
+            try!(write!(fmt, "\n"));
+        }
+
+        try!(self::global(global, printer, fmt));
+        separate = true;
+    }
+
+    for func in module.functions() {
+        if separate {
+            try!(write!(fmt, "\n"));
+        }
+
+        try!(self::function(func, printer, fmt));
+        separate = true;
+    }
+
+    if !module.trailing_comments.is_empty() {
+        if separate {
+            try!(write!(fmt, "\n"));
+        }
+
+        try!(self::comments_before(&module.trailing_comments, "", fmt));
+    }
+
+    Ok(())
+}
+
+/// Prints the comments which sit on their own lines.
+pub fn comments_before(comments: &[Comment],
+                       indent: &str,
+                       fmt: &mut fmt::Formatter) -> fmt::Result {
+    for comment in comments.iter().filter(|c| !c.inline) {
+        try!(write!(fmt, "{};{}\n", indent, comment.text));
+    }
+
+    Ok(())
+}
+
+/// Prints the comments which follow code on the same line.
+pub fn comments_after(comments: &[Comment],
+                      fmt: &mut fmt::Formatter) -> fmt::Result {
+    for comment in comments.iter().filter(|c| c.inline) {
+        try!(write!(fmt, " ;{}", comment.text));
+    }
+
+    Ok(())
+}
+
 pub fn global(global: &Global,
               printer: &mut Printer,
               fmt: &mut fmt::Formatter) -> fmt::Result {
+    if printer.is_exact() {
+        return self::exact_global(global, printer, fmt);
+    }
+
+    if !global.is_exported() {
+        try!(write!(fmt, "internal "));
+    }
+
+    if global.is_constant() {
+        try!(write!(fmt, "constant "));
+    }
+
     try!(write!(fmt, "%{} = ", global.name()));
     try!(plain_value(global.value(), printer, fmt));
     write!(fmt, "\n")
 }
 
+fn exact_global(global: &Global,
+                printer: &mut Printer,
+                fmt: &mut fmt::Formatter) -> fmt::Result {
+    try!(self::comments_before(&global.comments, "", fmt));
+    try!(write!(fmt, "global "));
+
+    if global.linkage() == Linkage::Internal {
+        try!(write!(fmt, "internal "));
+    }
+
+    if global.is_constant() {
+        try!(write!(fmt, "constant "));
+    }
+
+    try!(write!(fmt, "@{} = ", global.name()));
+    try!(plain_value(global.value(), printer, fmt));
+    try!(self::comments_after(&global.comments, fmt));
+    write!(fmt, "\n")
+}
+
 pub fn function<'a>(func: &'a Function,
                     printer: &mut Printer<'a>,
                     fmt: &mut fmt::Formatter) -> fmt::Result {
@@ -130,13 +265,35 @@ pub fn function<'a>(func: &'a Function,
     printer.clear_registers();
     printer.current_function = Some(func);
 
+    if printer.is_exact() {
+        try!(self::comments_before(&func.comments, "", fmt));
+    }
+
     try!(write!(fmt, "fn @{}", func.name()));
     try!(function_parameters(&func, fmt));
     try!(function_tail(func, fmt));
+    try!(function_attributes(func, printer, fmt));
+
+    if func.blocks().next().is_none() {
+        return self::declaration(func, printer, fmt);
+    }
 
-    try!(write!(fmt, " {{\n"));
+    try!(write!(fmt, " {{"));
+
+    if printer.is_exact() {
+        try!(self::comments_after(&func.comments, fmt));
+    }
+
+    try!(write!(fmt, "\n"));
+
+    for (i, block) in func.blocks().enumerate() {
+        // The parser gives the first block this label when it has none.
+        let implied = i == 0 && block.name() == ::read::parse::ENTRY_LABEL_NAME;
+
+        if printer.is_exact() && !implied {
+            try!(write!(fmt, ":{}\n", block.name()));
+        }
 
-    for block in func.blocks() {
         try!(self::block(block, printer, fmt));
     }
 
@@ -146,6 +303,18 @@ pub fn function<'a>(func: &'a Function,
     Ok(())
 }
 
+/// Finishes printing a function which has no body.
+fn declaration(func: &Function,
+               printer: &mut Printer,
+               fmt: &mut fmt::Formatter) -> fmt::Result {
+    if printer.is_exact() {
+        try!(self::comments_after(&func.comments, fmt));
+    }
+
+    printer.current_function = None;
+    write!(fmt, "\n")
+}
+
 pub fn function_parameters(func: &Function,
                            fmt: &mut fmt::Formatter) -> fmt::Result {
     let params = func.signature().parameters().map(|p|
@@ -166,11 +335,60 @@ pub fn function_tail(func: &Function,
     Ok(())
 }
 
+/// Prints the attributes of a function which differ from the defaults.
+pub fn function_attributes(func: &Function,
+                           printer: &Printer,
+                           fmt: &mut fmt::Formatter) -> fmt::Result {
+    if func.linkage() == Linkage::Internal {
+        try!(write!(fmt, " internal"));
+    }
+
+    match func.inline_hint() {
+        InlineHint::None => (),
+        InlineHint::Inline => try!(write!(fmt, " inline")),
+        InlineHint::MustInline => try!(write!(fmt, " must_inline")),
+    }
+
+    if func.complexity_hint() == ComplexityHint::ConstantTime {
+        try!(write!(fmt, " constant_time"));
+    }
+
+    // There is only one calling convention, so it is only worth
+    // printing when the source gave it.
+    if let (Some(cc), true) = (func.cc, printer.is_exact()) {
+        match cc {
+            CallingConvention::C => try!(write!(fmt, " cc(c)")),
+        }
+    }
+
+    if func.is_noreturn() {
+        try!(write!(fmt, " noreturn"));
+    }
+
+    if func.is_naked() {
+        try!(write!(fmt, " naked"));
+    }
+
+    if let Some(interrupt) = func.interrupt() {
+        try!(write!(fmt, " interrupt({})", interrupt.vector));
+    }
+
+    if let Some(section) = func.section() {
+        try!(write!(fmt, " section(\"{}\")", section));
+    }
+
+    Ok(())
+}
+
 pub fn block(block: &Block,
              printer: &mut Printer,
              fmt: &mut fmt::Formatter) -> fmt::Result {
 
-    try!(write!(fmt, "{}:\n", block.name()));
+    // Labels are printed by the function in exact mode, as the
+    // label of the first block may be implied.
+    if !printer.is_exact() {
+        try!(write!(fmt, "{}:\n", block.name()));
+    }
 
     for value in block.values() {
         try!(self::root_value(value, printer, fmt));
@@ -206,7 +424,15 @@ pub fn condition(cond: &Condition,
 pub fn root_value(value: &Value,
                   printer: &mut Printer,
                   fmt: &mut fmt::Formatter) -> fmt::Result {
-    root_expression(&value.node, printer, fmt)
+    if !printer.is_exact() {
+        return root_expression(&value.node, printer, fmt);
+    }
+
+    try!(self::comments_before(&value.comments, TAB_STRING, fmt));
+    try!(write!(fmt, "{}", TAB_STRING));
+    try!(self::expression::plain(&value.node, printer, fmt));
+    try!(self::comments_after(&value.comments, fmt));
+    write!(fmt, "\n")
 }
 
 pub fn root_expression(expression: &Expression,
@@ -238,14 +464,17 @@ pub mod expression
     pub fn expression(expr: &Expression,
                       printer: &mut Printer,
                       fmt: &mut fmt::Formatter) -> fmt::Result {
-        // simple values are not parenthesised.
-        if !expr.is_simple() {
+        // simple values are not parenthesised, and the parser
+        // reads nothing which is.
+        let parenthesise = !expr.is_simple() && !printer.is_exact();
+
+        if parenthesise {
             try!(write!(fmt, "("));
         }
 
         try!(self::plain(expr, printer, fmt));
 
-        if !expr.is_simple() {
+        if parenthesise {
             try!(write!(fmt, ")"));
         }
 
@@ -257,7 +486,7 @@ pub mod expression
                  fmt: &mut fmt::Formatter) -> fmt::Result {
 
         match *expr {
-            Expression::Literal(ref val) => self::literal(val, fmt),
+            Expression::Literal(ref val) => self::literal(val, printer, fmt),
             Expression::Register(ref val) => self::register(val, printer, fmt),
             Expression::Instruction(ref val) => self::instruction::instruction(val, printer, fmt),
             Expression::GlobalRef(ref val) => self::global_ref(val, printer, fmt),
@@ -273,10 +502,16 @@ pub mod expression
     }
 
     pub fn literal(literal: &value::Literal,
+                   printer: &mut Printer,
                    fmt: &mut fmt::Formatter) -> fmt::Result {
         match *literal {
             value::Literal::Integer(ref val) => self::literal_integer(val, fmt),
-            _ => unimplemented!(),
+            value::Literal::Decimal(ref val) => self::literal_decimal(val, fmt),
+            value::Literal::Struct(ref val) => {
+                self::elements(("{ ", " }"), val.fields(), printer, fmt)
+            },
+            value::Literal::Array(ref val) => self::elements(("[", "]"), val.elements(), printer, fmt),
+            value::Literal::Vector(ref val) => self::elements(("<", ">"), val.elements(), printer, fmt),
         }
     }
 
@@ -285,11 +520,43 @@ pub mod expression
         write!(fmt, "{} {}", literal.ty(), literal.value())
     }
 
-    pub fn literal_struct(_literal: &value::literal::Struct,
-                          _printer: &mut Printer,
-                          _fmt: &mut fmt::Formatter) -> fmt::Result {
-        unimplemented!();
-        //write!(fmt, "{{ {} }}", util::comma_separated_values(value.fields()))
+    /// Prints a decimal in the shortest form which reads back as the
+    /// same value.
+    ///
+    /// Every NaN prints as `nan`, so NaN payloads are not kept.
+    pub fn literal_decimal(literal: &value::literal::Decimal,
+                           fmt: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(fmt, "{} ", literal.ty()));
+
+        if let Some(value) = literal.as_f32() {
+            if value.is_nan() { write!(fmt, "nan") } else { write!(fmt, "{:?}", value) }
+        } else if let Some(value) = literal.as_f64() {
+            if value.is_nan() { write!(fmt, "nan") } else { write!(fmt, "{:?}", value) }
+        } else {
+            write!(fmt, "{:?}", literal.bits())
+        }
+    }
+
+    /// Prints the elements of an aggregate literal between a pair of
+    /// delimiters.
+    pub fn elements<'v,I>(delimiters: (&str, &str),
+                          elements: I,
+                          printer: &mut Printer,
+                          fmt: &mut fmt::Formatter) -> fmt::Result
+        where I: ExactSizeIterator<Item=&'v value::Value> {
+        if elements.len() == 0 {
+            return write!(fmt, "{}{}", delimiters.0.trim(), delimiters.1.trim());
+        }
+
+        try!(write!(fmt, "{}", delimiters.0));
+        for (i, element) in elements.enumerate() {
+            if i != 0 {
+                try!(write!(fmt, ", "));
+            }
+
+            try!(super::value(element, printer, fmt));
+        }
+        write!(fmt, "{}", delimiters.1)
     }
 
     pub fn register(reg: &value::Register,
@@ -318,20 +585,24 @@ pub mod expression
                       fmt: &mut fmt::Formatter) -> fmt::Result {
 
         let global = printer.module.get_global(global_ref.global_id());
-        write!(fmt, "%{}", global.name())
+        let sigil = if printer.is_exact() { "@" } else { "%" };
+
+        write!(fmt, "{}{}", sigil, global.name())
     }
 
     pub fn block_ref(block_ref: &value::BlockRef,
                      printer: &mut Printer,
                      fmt: &mut fmt::Formatter) -> fmt::Result {
         let block = printer.module.get_block(block_ref.block_id());
-        write!(fmt, "{}", block.name())
+        let sigil = if printer.is_exact() { "%" } else { "" };
+
+        write!(fmt, "{}{}", sigil, block.name())
     }
 
-    pub fn function_ref(_func_ref: &value::FunctionRef,
+    pub fn function_ref(func_ref: &value::FunctionRef,
                         _printer: &mut Printer,
-                        _fmt: &mut fmt::Formatter) -> fmt::Result {
-        unimplemented!();
+                        fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "@{}", func_ref.name())
     }
 
     pub fn register_ref(reg_ref: &value::RegisterRef,
@@ -357,10 +628,9 @@ pub mod expression
 
     pub mod instruction
     {
-        use {Expression,Instruction};
-        use instruction::{self,Binary};
+        use {Expression,Instruction,Condition,Type};
+        use instruction::{self,Unary,Binary};
         use std::fmt;
-        use util;
         use super::super::value;
         use super::super::Printer;
 
@@ -372,17 +642,45 @@ pub mod expression
                 Instruction::Sub(ref i) => basic_binop("sub", i, printer, fmt),
                 Instruction::Mul(ref i) => basic_binop("mul", i, printer, fmt),
                 Instruction::Div(ref i) => basic_binop("div", i, printer, fmt),
+                Instruction::Rem(ref i) => basic_binop("rem", i, printer, fmt),
+                Instruction::MulHigh(ref i) => basic_binop("mulh", i, printer, fmt),
+                Instruction::And(ref i) => basic_binop("and", i, printer, fmt),
                 Instruction::Shl(ref i) => basic_binop("shl", i, printer, fmt),
                 Instruction::Shr(ref i) => basic_binop("shr", i, printer, fmt),
 
-                Instruction::Call(ref i) => call(i, fmt),
+                Instruction::Call(ref i) => call(i, printer, fmt),
                 Instruction::Break(ref i) => br(i, printer, fmt),
                 Instruction::Return(ref i) => ret(i, printer, fmt),
 
                 Instruction::Copy(ref i) => basic_binop("copy", i, printer, fmt),
+                Instruction::Phi(ref i) => phi(i, printer, fmt),
+                Instruction::Load(ref i) => basic_unop("load", i, printer, fmt),
+                Instruction::Store(ref i) => basic_binop("store", i, printer, fmt),
+
+                Instruction::ExtractValue(ref i) => extractvalue(i, printer, fmt),
+                Instruction::InsertValue(ref i) => insertvalue(i, printer, fmt),
+                Instruction::GetElementPtr(ref i) => getelementptr(i, printer, fmt),
+
+                Instruction::FAdd(ref i) => basic_binop("fadd", i, printer, fmt),
+                Instruction::FSub(ref i) => basic_binop("fsub", i, printer, fmt),
+                Instruction::FMul(ref i) => basic_binop("fmul", i, printer, fmt),
+                Instruction::FDiv(ref i) => basic_binop("fdiv", i, printer, fmt),
+                Instruction::ICmp(ref i) => compare("icmp", i.condition(), printer, fmt),
+                Instruction::FCmp(ref i) => compare("fcmp", i.condition(), printer, fmt),
+                Instruction::IntToFloat(ref i) => convert("itof", i.value(), &i.ty(), printer, fmt),
+                Instruction::FloatToInt(ref i) => convert("ftoi", i.value(), &i.ty(), printer, fmt),
             }
         }
 
+        pub fn basic_unop<I>(mnemonic: &'static str,
+                             inst: &I,
+                             printer: &mut Printer,
+                             fmt: &mut fmt::Formatter) -> fmt::Result
+            where I: Unary {
+            try!(write!(fmt, "{} ", mnemonic));
+            value(inst.operand(), printer, fmt)
+        }
+
         pub fn basic_binop<I>(mnemonic: &'static str,
                                    inst: &I,
                                    printer: &mut Printer,
@@ -399,36 +697,188 @@ pub mod expression
         }
 
         pub fn call(inst: &instruction::Call,
+                    printer: &mut Printer,
                     fmt: &mut fmt::Formatter) -> fmt::Result {
             let func = if let Expression::FunctionRef(ref f) = inst.target().node  {
                 f
             } else {
                 unreachable!(); // target must be function
             };
-            write!(fmt, "call {} {}",
-                   util::comma_separated_values(func.signature().returns()),
-                   func.name())
 
+            if inst.is_tail() {
+                try!(write!(fmt, "tail "));
+            }
+
+            try!(write!(fmt, "call @{}", func.name()));
+
+            // The parser needs the parentheses to tell a call
+            // from a reference to the function.
+            if inst.arguments().len() == 0 && !printer.is_exact() {
+                return Ok(());
+            }
+
+            try!(write!(fmt, "("));
+            for (i, argument) in inst.arguments().enumerate() {
+                if i != 0 {
+                    try!(write!(fmt, ", "));
+                }
+
+                try!(value(argument, printer, fmt));
+            }
+            write!(fmt, ")")
         }
 
         pub fn br(inst: &instruction::Break,
                   printer: &mut Printer,
                   fmt: &mut fmt::Formatter) -> fmt::Result {
+            if printer.is_exact() {
+                try!(write!(fmt, "br "));
+                try!(super::super::condition(inst.condition(), printer, fmt));
+                try!(write!(fmt, ", "));
+                return value(inst.target(), printer, fmt);
+            }
+
             try!(write!(fmt, "break "));
             try!(super::super::condition(inst.condition(), printer, fmt));
             try!(write!(fmt, " "));
             value(inst.target(), printer, fmt)
         }
 
+        pub fn phi(inst: &instruction::Phi,
+                   printer: &mut Printer,
+                   fmt: &mut fmt::Formatter) -> fmt::Result {
+            try!(write!(fmt, "phi {}", inst.ty()));
+
+            for (i, &(ref val, ref block)) in inst.incoming().enumerate() {
+                try!(write!(fmt, "{}[", if i == 0 { " " } else { ", " }));
+                try!(value(val, printer, fmt));
+                try!(write!(fmt, ", "));
+                try!(value(block, printer, fmt));
+                try!(write!(fmt, "]"));
+            }
+
+            Ok(())
+        }
+
+        pub fn extractvalue(inst: &instruction::ExtractValue,
+                            printer: &mut Printer,
+                            fmt: &mut fmt::Formatter) -> fmt::Result {
+            try!(write!(fmt, "extractvalue "));
+            try!(value(inst.aggregate(), printer, fmt));
+            write!(fmt, ", {}", inst.index())
+        }
+
+        pub fn insertvalue(inst: &instruction::InsertValue,
+                           printer: &mut Printer,
+                           fmt: &mut fmt::Formatter) -> fmt::Result {
+            try!(write!(fmt, "insertvalue "));
+            try!(value(inst.aggregate(), printer, fmt));
+            try!(write!(fmt, ", "));
+            try!(value(inst.value(), printer, fmt));
+            write!(fmt, ", {}", inst.index())
+        }
+
+        pub fn getelementptr(inst: &instruction::GetElementPtr,
+                             printer: &mut Printer,
+                             fmt: &mut fmt::Formatter) -> fmt::Result {
+            try!(write!(fmt, "getelementptr "));
+            try!(value(inst.pointer(), printer, fmt));
+
+            for index in inst.indices() {
+                try!(write!(fmt, ", "));
+                try!(value(index, printer, fmt));
+            }
+
+            Ok(())
+        }
+
+        pub fn compare(mnemonic: &'static str,
+                       condition: &Condition,
+                       printer: &mut Printer,
+                       fmt: &mut fmt::Formatter) -> fmt::Result {
+            try!(write!(fmt, "{} ", mnemonic));
+            super::super::condition(condition, printer, fmt)
+        }
+
+        pub fn convert(mnemonic: &'static str,
+                       val: &::Value,
+                       ty: &Type,
+                       printer: &mut Printer,
+                       fmt: &mut fmt::Formatter) -> fmt::Result {
+            try!(write!(fmt, "{} ", mnemonic));
+            try!(value(val, printer, fmt));
+            write!(fmt, ", {}", ty)
+        }
+
         pub fn ret(inst: &instruction::Return,
                    printer: &mut Printer,
                    fmt: &mut fmt::Formatter) -> fmt::Result {
-            try!(write!(fmt, "ret "));
+            try!(write!(fmt, "ret"));
 
             match inst.subvalue() {
-                Some(i) => value(i, printer, fmt),
-                None => write!(fmt, "void"),
+                Some(i) => {
+                    try!(write!(fmt, " "));
+                    value(i, printer, fmt)
+                },
+                None if printer.is_exact() => Ok(()),
+                None => write!(fmt, " void"),
             }
         }
     }
 }
+
+#[cfg(test)]
+mod test
+{
+    use read::Parser;
+
+    fn round_trip(text: &str) -> String {
+        let module = Parser::new(text.chars()).preserve_comments().parse().expect("parsing failed");
+        super::printable_exact(&module).to_string()
+    }
+
+    #[test]
+    fn prints_canonical_modules_exactly() {
+        let text = "; a module\n\
+                    \n\
+                    global internal constant @limit = i8 10 ; the limit\n\
+                    global @count = i8 0\n\
+                    \n\
+                    fn @leaf() cc(c) {\n\
+                    \x20 ret\n\
+                    }\n\
+                    \n\
+                    fn @other(%x: i8, %s: i8) -> i8 {\n\
+                    \x20 call @leaf()\n\
+                    \x20 %y = add %x, %s\n\
+                    \x20 ret %y\n\
+                    }\n\
+                    \n\
+                    ; counts up to the limit\n\
+                    fn @main(%a: i8) -> i8 {\n\
+                    \x20 ; start counting\n\
+                    \x20 br %a lt i8 3, %loop\n\
+                    :loop\n\
+                    \x20 %i = phi i8 [%a, %entry], [%next, %loop] ; the counter\n\
+                    \x20 %next = add %i, add i8 1, i8 0\n\
+                    \x20 store @count, %next\n\
+                    \x20 br %next lt load @limit, %loop\n\
+                    :exit\n\
+                    \x20 %r = tail call @other(%next, i8 2)\n\
+                    \x20 ret %r\n\
+                    }\n\
+                    \n\
+                    ; the end\n";
+
+        let module = Parser::new(text.chars()).preserve_comments().parse().expect("parsing failed");
+        ::verifier::verify(&module).expect("the module is invalid");
+
+        assert_eq!(super::printable_exact(&module).to_string(), text);
+    }
+
+    #[test]
+    fn prints_a_canonical_form() {
+        assert_eq!(round_trip("fn @f (  ) {\n:entry\n\n  ret   i8 1;done\n}\n"),
+                   "fn @f() {\n  ret i8 1 ;done\n}\n");
+    }
+}
@@ -0,0 +1,634 @@
+use {Module,Global,Function,Signature,Parameter,Block,Value,Expression,
+     Instruction,Condition,Type,Name,types,value,instruction};
+use {CallingConvention,InlineHint,ComplexityHint,Linkage,Interrupt};
+use instruction::Binary;
+use write::bitcode::{MAGIC,VERSION};
+use util::{self,Identifiable,Diagnostic};
+
+use std::collections::HashMap;
+use std::io::{self,Read};
+use std;
+
+/// An error encountered while reading bitcode.
+#[derive(Debug)]
+pub enum Error
+{
+    IoError(io::Error),
+    /// The input does not start with the bitcode magic bytes.
+    NotBitcode,
+    /// The input was written by an unknown version of the format.
+    UnsupportedVersion(u16),
+    /// The input is not valid bitcode.
+    Malformed(String),
+}
+
+/// Reads a module from bitcode.
+pub fn module(input: &mut Read) -> Result<Module, Error> {
+    let mut reader = Reader {
+        input: input,
+        ids: HashMap::new(),
+    };
+
+    let magic = try!(reader.bytes(MAGIC.len()));
+    if &magic[..] != MAGIC {
+        return Err(Error::NotBitcode);
+    }
+
+    let version = try!(reader.bytes(2));
+    let version = version[0] as u16 | ((version[1] as u16) << 8);
+    if version != VERSION {
+        return Err(Error::UnsupportedVersion(version));
+    }
+
+    reader.module()
+}
+
+/// Checks if some data starts like bitcode.
+pub fn is_bitcode(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+struct Reader<'a>
+{
+    input: &'a mut Read,
+    /// The ID given to each number seen so far.
+    ids: HashMap<u64, util::Id>,
+}
+
+impl<'a> Reader<'a>
+{
+    fn module(&mut self) -> Result<Module, Error> {
+        let mut module = Module::empty();
+
+        for _ in 0..try!(self.number()) {
+            let global = try!(self.global());
+            module.add_global(global);
+        }
+
+        for _ in 0..try!(self.number()) {
+            let func = try!(self.function());
+            module.add_function(func);
+        }
+
+        Ok(module)
+    }
+
+    fn global(&mut self) -> Result<Global, Error> {
+        let id = try!(self.id());
+        let name = try!(self.string());
+        let linkage = try!(self.linkage());
+        let constant = try!(self.boolean());
+        let value = try!(self.value());
+
+        let mut global = Global::new(name, value).with_linkage(linkage);
+        global.internal_set_id(id);
+        global.constant = constant;
+        Ok(global)
+    }
+
+    fn function(&mut self) -> Result<Function, Error> {
+        let id = try!(self.id());
+        let name = try!(self.string());
+        let signature = try!(self.signature());
+
+        let cc = match try!(self.byte()) {
+            0 => None,
+            1 => Some(CallingConvention::C),
+            cc => return Err(malformed(format!("unknown calling convention {}", cc))),
+        };
+        let linkage = try!(self.linkage());
+        let inline = match try!(self.byte()) {
+            0 => InlineHint::None,
+            1 => InlineHint::Inline,
+            2 => InlineHint::MustInline,
+            hint => return Err(malformed(format!("unknown inline hint {}", hint))),
+        };
+        let complexity = match try!(self.byte()) {
+            0 => ComplexityHint::None,
+            1 => ComplexityHint::ConstantTime,
+            hint => return Err(malformed(format!("unknown complexity hint {}", hint))),
+        };
+        let noreturn = try!(self.boolean());
+        let naked = try!(self.boolean());
+
+        let interrupt = if try!(self.boolean()) {
+            let vector = try!(self.number());
+
+            if vector > ::std::u32::MAX as u64 {
+                return Err(malformed(format!("interrupt vector {} is too large", vector)));
+            }
+            Some(Interrupt { vector: vector as u32 })
+        } else {
+            None
+        };
+
+        let section = if try!(self.boolean()) { Some(try!(self.string())) } else { None };
+
+        let mut blocks = Vec::new();
+        for _ in 0..try!(self.number()) {
+            blocks.push(try!(self.block()));
+        }
+
+        let mut func = Function::new(name, signature, blocks);
+        func.internal_set_id(id);
+        func.cc = cc;
+        func.linkage = linkage;
+        func.inline_hint = inline;
+        func.complexity_hint = complexity;
+        func.noreturn = noreturn;
+        func.naked = naked;
+        func.interrupt = interrupt;
+        func.section = section;
+        Ok(func)
+    }
+
+    fn signature(&mut self) -> Result<Signature, Error> {
+        let mut params = Vec::new();
+        for _ in 0..try!(self.number()) {
+            params.push(try!(self.parameter()));
+        }
+
+        let mut returns = Vec::new();
+        for _ in 0..try!(self.number()) {
+            returns.push(try!(self.ty()));
+        }
+
+        Ok(Signature::new(params, returns))
+    }
+
+    fn parameter(&mut self) -> Result<Parameter, Error> {
+        let id = try!(self.id());
+        let name = try!(self.string());
+        let ty = try!(self.ty());
+
+        let mut param = Parameter::new(name, ty);
+        param.internal_set_id(id);
+        Ok(param)
+    }
+
+    fn block(&mut self) -> Result<Block, Error> {
+        let id = try!(self.id());
+        let name = try!(self.string());
+
+        let mut block = Block::empty(name);
+        block.id = id;
+
+        for _ in 0..try!(self.number()) {
+            block.append_value(try!(self.value()));
+        }
+
+        Ok(block)
+    }
+
+    fn value(&mut self) -> Result<Value, Error> {
+        use write::bitcode::tag::expr;
+
+        let expression: Expression = match try!(self.byte()) {
+            expr::INTEGER => {
+                let ty = try!(self.integer_ty());
+                let sign = if try!(self.boolean()) { util::Sign::Minus } else { util::Sign::Plus };
+                let len = try!(self.number());
+                let magnitude = try!(self.bytes(len as usize));
+
+                match value::literal::Integer::from_bytes_le(ty, sign, &magnitude) {
+                    Some(i) => value::Literal::Integer(i).into(),
+                    None => return Err(malformed("integer does not fit in its type")),
+                }
+            },
+            expr::DECIMAL => {
+                let ty = types::Decimal::new(try!(self.width()));
+                let len = try!(self.number());
+
+                if len > ty.bit_width() as u64 {
+                    return Err(malformed(format!("decimal has {} bits but its type has {}", len, ty.bit_width())));
+                }
+
+                let count = match len.checked_add(7) {
+                    Some(n) => n / 8,
+                    None => return Err(malformed("decimal is too large")),
+                };
+                let len = len as usize;
+                let bytes = try!(self.bytes(count as usize));
+
+                let mut bits = ::bit_vec::BitVec::from_bytes(&bytes);
+                bits.truncate(len);
+                value::Literal::Decimal(value::literal::Decimal::new(ty, bits)).into()
+            },
+            expr::STRUCT => {
+                let fields = try!(self.values());
+                value::Literal::Struct(value::literal::Struct::new(fields)).into()
+            },
+            expr::ARRAY => {
+                let element = try!(self.ty());
+                value::Literal::array(element, try!(self.values())).into()
+            },
+            expr::VECTOR => {
+                let element = try!(self.ty());
+                value::Literal::vector(element, try!(self.values())).into()
+            },
+            expr::REGISTER => {
+                let id = try!(self.id());
+                let name = if try!(self.boolean()) { Name::named(try!(self.string())) } else { Name::unnamed() };
+
+                let mut reg = value::Register::new(name, try!(self.value()));
+                reg.internal_set_id(id);
+                reg.into()
+            },
+            expr::INSTRUCTION => try!(self.instruction()).into(),
+            expr::GLOBAL_REF => {
+                let id = try!(self.id());
+                value::GlobalRef::new(id, try!(self.ty())).into()
+            },
+            expr::BLOCK_REF => value::BlockRef::new(try!(self.id())).into(),
+            expr::FUNCTION_REF => {
+                let id = try!(self.id());
+                let name = try!(self.string());
+                let signature = try!(self.signature());
+
+                value::FunctionRef::new(id, name, types::Function::new(signature)).into()
+            },
+            expr::REGISTER_REF => {
+                let id = try!(self.id());
+                value::RegisterRef::new(id, try!(self.ty())).into()
+            },
+            expr::ARGUMENT_REF => {
+                let id = try!(self.id());
+                value::ArgumentRef::new(id, try!(self.ty())).into()
+            },
+            expr::UNRESOLVED_REF => Expression::UnresolvedRef(try!(self.id())),
+            expr::STRING => value::String::new(try!(self.string())).into(),
+            tag => return Err(malformed(format!("unknown expression tag {}", tag))),
+        };
+
+        Ok(Value::new(expression))
+    }
+
+    fn instruction(&mut self) -> Result<Instruction, Error> {
+        use write::bitcode::tag::inst as tag;
+
+        Ok(match try!(self.byte()) {
+            tag::ADD => try!(self.binary::<instruction::Add>()).into(),
+            tag::SUB => try!(self.binary::<instruction::Sub>()).into(),
+            tag::MUL => try!(self.binary::<instruction::Mul>()).into(),
+            tag::DIV => try!(self.binary::<instruction::Div>()).into(),
+            tag::REM => try!(self.binary::<instruction::Rem>()).into(),
+            tag::MULH => try!(self.binary::<instruction::MulHigh>()).into(),
+            tag::AND => try!(self.binary::<instruction::And>()).into(),
+            tag::SHL => try!(self.binary::<instruction::Shl>()).into(),
+            tag::SHR => try!(self.binary::<instruction::Shr>()).into(),
+            tag::STORE => try!(self.binary::<instruction::Store>()).into(),
+            tag::COPY => {
+                let (dst, src) = (try!(self.value()), try!(self.value()));
+
+                if dst.node.ty() != src.node.ty() {
+                    return Err(malformed("copy operands have different types"));
+                }
+                instruction::Copy::new(dst, src).into()
+            },
+            tag::LOAD => instruction::Load::new(try!(self.value())).into(),
+            tag::CALL => {
+                let tail = try!(self.boolean());
+                let target = try!(self.value());
+                let call = instruction::Call::with_arguments(target, try!(self.values()));
+
+                if tail { call.tail().into() } else { call.into() }
+            },
+            tag::BREAK => {
+                let cond = try!(self.condition());
+                instruction::Break::new(cond, try!(self.value())).into()
+            },
+            tag::RETURN => {
+                let value = if try!(self.boolean()) { Some(try!(self.value())) } else { None };
+                instruction::Return::new(value).into()
+            },
+            tag::PHI => {
+                let ty = try!(self.ty());
+
+                let mut incoming = Vec::new();
+                for _ in 0..try!(self.number()) {
+                    incoming.push((try!(self.value()), try!(self.value())));
+                }
+
+                instruction::Phi::new(ty, incoming).into()
+            },
+            tag::EXTRACT_VALUE => {
+                let aggregate = try!(self.value());
+                instruction::ExtractValue::new(aggregate, try!(self.number())).into()
+            },
+            tag::INSERT_VALUE => {
+                let (aggregate, value) = (try!(self.value()), try!(self.value()));
+                instruction::InsertValue::new(aggregate, value, try!(self.number())).into()
+            },
+            tag::GET_ELEMENT_PTR => {
+                let pointer = try!(self.value());
+                instruction::GetElementPtr::new(pointer, try!(self.values())).into()
+            },
+            tag::FADD => try!(self.binary::<instruction::FAdd>()).into(),
+            tag::FSUB => try!(self.binary::<instruction::FSub>()).into(),
+            tag::FMUL => try!(self.binary::<instruction::FMul>()).into(),
+            tag::FDIV => try!(self.binary::<instruction::FDiv>()).into(),
+            tag::ICMP => instruction::ICmp::new(try!(self.condition())).into(),
+            tag::FCMP => instruction::FCmp::new(try!(self.condition())).into(),
+            tag::INT_TO_FLOAT => {
+                let value = try!(self.value());
+                instruction::IntToFloat::new(value, types::Decimal::new(try!(self.width()))).into()
+            },
+            tag::FLOAT_TO_INT => {
+                let value = try!(self.value());
+                instruction::FloatToInt::new(value, try!(self.integer_ty())).into()
+            },
+            tag => return Err(malformed(format!("unknown instruction tag {}", tag))),
+        })
+    }
+
+    fn binary<I>(&mut self) -> Result<I, Error>
+        where I: Binary {
+        let lhs = try!(self.value());
+        let rhs = try!(self.value());
+
+        Ok(I::with_operands(lhs, rhs))
+    }
+
+    fn condition(&mut self) -> Result<Condition, Error> {
+        use write::bitcode::tag::cond as tag;
+
+        let kind = try!(self.byte());
+        match kind {
+            tag::TRUE => return Ok(Condition::True),
+            tag::FALSE => return Ok(Condition::False),
+            _ => (),
+        }
+
+        let lhs = Box::new(try!(self.value()));
+        let rhs = Box::new(try!(self.value()));
+
+        Ok(match kind {
+            tag::EQUAL => Condition::Equal(lhs, rhs),
+            tag::NOT_EQUAL => Condition::NotEqual(lhs, rhs),
+            tag::GREATER_THAN => Condition::GreaterThan(lhs, rhs),
+            tag::GREATER_THAN_OR_EQ => Condition::GreaterThanOrEq(lhs, rhs),
+            tag::LESS_THAN => Condition::LessThan(lhs, rhs),
+            tag::LESS_THAN_OR_EQ => Condition::LessThanOrEq(lhs, rhs),
+            tag => return Err(malformed(format!("unknown condition tag {}", tag))),
+        })
+    }
+
+    fn values(&mut self) -> Result<Vec<Value>, Error> {
+        let mut values = Vec::new();
+
+        for _ in 0..try!(self.number()) {
+            values.push(try!(self.value()));
+        }
+
+        Ok(values)
+    }
+
+    fn ty(&mut self) -> Result<Type, Error> {
+        use write::bitcode::tag::ty as tag;
+
+        Ok(match try!(self.byte()) {
+            tag::VOID => Type::void(),
+            tag::POINTER => Type::pointer(try!(self.ty())),
+            tag::INTEGER => try!(self.integer_ty()).into(),
+            tag::DECIMAL => Type::decimal(try!(self.width())),
+            tag::VECTOR => {
+                let count = try!(self.number());
+                Type::vector(count, try!(self.ty()))
+            },
+            tag::ARRAY => {
+                let count = try!(self.number());
+                Type::array(count, try!(self.ty()))
+            },
+            tag::STRUCT => {
+                let mut fields = Vec::new();
+                for _ in 0..try!(self.number()) {
+                    fields.push(try!(self.ty()));
+                }
+
+                types::Struct::new(fields.into_iter()).into()
+            },
+            tag::FUNCTION => Type::function(try!(self.signature())),
+            tag::BLOCK => Type::block(),
+            tag => return Err(malformed(format!("unknown type tag {}", tag))),
+        })
+    }
+
+    fn integer_ty(&mut self) -> Result<types::Integer, Error> {
+        let signed = try!(self.boolean());
+        let width = try!(self.width());
+
+        Ok(if signed { types::Integer::signed(width) } else { types::Integer::unsigned(width) })
+    }
+
+    fn width(&mut self) -> Result<u16, Error> {
+        let width = try!(self.number());
+
+        if width > u16::max_value() as u64 {
+            return Err(malformed(format!("bit width {} is too large", width)));
+        }
+        Ok(width as u16)
+    }
+
+    fn linkage(&mut self) -> Result<Linkage, Error> {
+        match try!(self.byte()) {
+            0 => Ok(Linkage::External),
+            1 => Ok(Linkage::Internal),
+            linkage => Err(malformed(format!("unknown linkage {}", linkage))),
+        }
+    }
+
+    fn id(&mut self) -> Result<util::Id, Error> {
+        let number = try!(self.number());
+        Ok(*self.ids.entry(number).or_insert_with(util::Id::next))
+    }
+
+    fn string(&mut self) -> Result<String, Error> {
+        let len = try!(self.number());
+        let bytes = try!(self.bytes(len as usize));
+
+        String::from_utf8(bytes).map_err(|_| malformed("string is not valid UTF-8"))
+    }
+
+    fn boolean(&mut self) -> Result<bool, Error> {
+        match try!(self.byte()) {
+            0 => Ok(false),
+            1 => Ok(true),
+            b => Err(malformed(format!("expected a boolean but got {}", b))),
+        }
+    }
+
+    /// Reads an unsigned LEB128 number.
+    fn number(&mut self) -> Result<u64, Error> {
+        let mut result = 0u64;
+        let mut shift = 0;
+
+        loop {
+            let byte = try!(self.byte());
+
+            if shift >= 64 {
+                return Err(malformed("number is too large"));
+            }
+
+            result |= ((byte & 0x7f) as u64) << shift;
+            shift += 7;
+
+            if byte & 0x80 == 0 {
+                return Ok(result);
+            }
+        }
+    }
+
+    fn byte(&mut self) -> Result<u8, Error> {
+        Ok(try!(self.bytes(1))[0])
+    }
+
+    fn bytes(&mut self, count: usize) -> Result<Vec<u8>, Error> {
+        // Don't trust the count enough to allocate it up front.
+        let mut bytes = Vec::new();
+        try!((&mut *self.input).take(count as u64).read_to_end(&mut bytes));
+
+        if bytes.len() != count {
+            return Err(malformed("unexpected end of input"));
+        }
+        Ok(bytes)
+    }
+}
+
+fn malformed<S>(message: S) -> Error
+    where S: Into<String> {
+    Error::Malformed(message.into())
+}
+
+impl From<io::Error> for Error
+{
+    fn from(e: io::Error) -> Error {
+        Error::IoError(e)
+    }
+}
+
+impl From<Error> for Diagnostic
+{
+    fn from(e: Error) -> Diagnostic {
+        Diagnostic::error(e.to_string())
+    }
+}
+
+impl std::fmt::Display for Error
+{
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            Error::IoError(ref e) => write!(fmt, "{}", e),
+            Error::NotBitcode => write!(fmt, "the input is not bitcode"),
+            Error::UnsupportedVersion(v) => {
+                write!(fmt, "bitcode version {} is not supported (expected {})", v, VERSION)
+            },
+            Error::Malformed(ref msg) => write!(fmt, "malformed bitcode: {}", msg),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use {read,write,printable,Module,Global,Value,Expression};
+
+    /// Checks that a module prints the same after going through bitcode.
+    fn round_trip_module(module: Module) {
+        let mut bytes = Vec::new();
+        write::bitcode(&module, &mut bytes).unwrap();
+        let read_back = read::bitcode(&mut &bytes[..]).expect("reading bitcode failed");
+
+        assert_eq!(printable(&read_back).to_string(), printable(&module).to_string());
+    }
+
+    fn round_trip(text: &str) {
+        round_trip_module(read::textual(text.chars()).expect("parsing failed"));
+    }
+
+    #[test]
+    fn round_trips_globals() {
+        round_trip("global @a = i8 5\nglobal internal constant @b = u127 38\nglobal @c = i1200 9000000000000000000\n");
+    }
+
+    #[test]
+    fn round_trips_integers_the_parser_cannot_read() {
+        round_trip_module(Module::empty()
+            .global(Global::new("a".to_owned(), Value::new(Expression::i8(-5))))
+            .global(Global::new("b".to_owned(), Value::new(Expression::i64(-1 << 40))))
+            .global(Global::new("c".to_owned(), Value::new(Expression::u(1200, 123456789012345678901234567890u128)))));
+    }
+
+    #[test]
+    fn round_trips_functions() {
+        round_trip("fn @add(%a: i8, %b: i8) -> i8 {\n  %c = add %a, %b\n  ret %c\n}\n\
+                    fn @main() -> i8 {\n  %r = call @add(i8 1, i8 2)\n  br %r eq i8 3, %done\n\
+                    :other\n  ret i8 0\n:done\n  ret %r\n}\n\
+                    fn @again() -> i8 {\n  %r = tail call @main()\n  ret %r\n}\n");
+    }
+
+    #[test]
+    fn round_trips_function_attributes() {
+        round_trip("fn @f() internal must_inline constant_time {\n  ret\n}\n\
+                    fn @g() noreturn naked interrupt(7) section(\".vectors\") {\n  br true, %entry\n}\n");
+    }
+
+    #[test]
+    fn round_trips_memory() {
+        round_trip("global @value = i32 5\n\
+                    fn @swap() -> i32 {\n  %a = load @value\n  store @value, i32 7\n  ret %a\n}\n");
+    }
+
+    #[test]
+    fn round_trips_aggregates() {
+        round_trip("global @pair = { i8 1, u16 2 }\nglobal @table = [i8 1, i8 2]\n\
+                    fn @f(%p: { i8, u16 }) -> u16 {\n  %v = <i8 1, i8 2>\n  %a = insertvalue %p, u16 7, 1\n  %q = \
+                    getelementptr @table, i8 0, i8 1\n  %b = extractvalue %a, 1\n  ret %b\n}\n");
+    }
+
+    #[test]
+    fn round_trips_floats() {
+        round_trip("global @half = f32 0.5\n\
+                    fn @f(%a: f64, %n: i16) -> i16 {\n  %b = fadd %a, f64 -1.5e-7\n  %m = fmul %b, f64 inf\n  \
+                    %i = itof %n, f64\n  %c = fdiv %m, %i\n  %d = fcmp %c lt f64 nan\n  \
+                    %e = icmp %n neq i16 0\n  %s = fsub %c, %a\n  %r = ftoi %s, i16\n  ret %r\n}\n");
+    }
+
+    #[test]
+    fn rejects_other_input() {
+        let bytes = b"fn @main() {}";
+        assert!(!super::is_bitcode(bytes));
+
+        match read::bitcode(&mut &bytes[..]) {
+            Err(super::Error::NotBitcode) => (),
+            r => panic!("expected an error, got {:?}", r.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn rejects_decimals_wider_than_their_type() {
+        let module = read::textual("global @half = f32 0.5\n".chars()).unwrap();
+
+        let mut bytes = Vec::new();
+        write::bitcode(&module, &mut bytes).unwrap();
+
+        // Claim the literal has one more bit than an f32.
+        let at = bytes.windows(3).position(|w| w == [write::bitcode::tag::expr::DECIMAL, 32, 32]).unwrap();
+        bytes[at + 2] = 33;
+
+        match read::bitcode(&mut &bytes[..]) {
+            Err(super::Error::Malformed(..)) => (),
+            r => panic!("expected an error, got {:?}", r.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn rejects_truncated_input() {
+        let module = read::textual("global @a = i8 1\n".chars()).unwrap();
+
+        let mut bytes = Vec::new();
+        write::bitcode(&module, &mut bytes).unwrap();
+        bytes.pop();
+
+        assert!(read::bitcode(&mut &bytes[..]).is_err());
+    }
+}
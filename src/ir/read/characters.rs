@@ -1,3 +1,4 @@
+use Location;
 use std;
 
 /// An iterator over a set of characters.
@@ -12,6 +13,9 @@ pub struct Characters<I: Iterator<Item=char>>
     it: std::iter::Peekable<I>,
     finished: bool,
     peek_buf: Option<char>,
+
+    /// The location of the next character.
+    location: Location,
 }
 
 impl<I> Characters<I>
@@ -22,12 +26,18 @@ impl<I> Characters<I>
             it: it.peekable(),
             finished: false,
             peek_buf: None,
+            location: Location::start(),
         }
     }
 
+    /// Gets the location of the next character.
+    pub fn location(&self) -> Location {
+        self.location
+    }
+
     pub fn peek(&mut self) -> Option<char> {
         if self.peek_buf.is_none() {
-            self.peek_buf = self.next();
+            self.peek_buf = self.read();
         }
 
         self.peek_buf
@@ -62,20 +72,9 @@ impl<I> Characters<I>
 
         result.into_iter()
     }
-}
-
-impl<I> Iterator for Characters<I>
-    where I: Iterator<Item=char>
-{
-    type Item = char;
-
-    fn next(&mut self) -> Option<char> {
-        if self.peek_buf.is_some() {
-            let c = self.peek_buf.unwrap();
-            self.peek_buf = None;
-            return Some(c);
-        }
 
+    /// Reads a character without moving the location.
+    fn read(&mut self) -> Option<char> {
         match self.it.next() {
             Some(c) => Some(c),
             None => {
@@ -90,3 +89,22 @@ impl<I> Iterator for Characters<I>
     }
 }
 
+impl<I> Iterator for Characters<I>
+    where I: Iterator<Item=char>
+{
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        let c = match self.peek_buf.take() {
+            Some(c) => Some(c),
+            None => self.read(),
+        };
+
+        if let Some(c) = c {
+            self.location = self.location.advance(c);
+        }
+
+        c
+    }
+}
+
@@ -0,0 +1,44 @@
+use Span;
+use util::Diagnostic;
+use std;
+
+/// An error encountered while reading IR.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct Error
+{
+    pub message: String,
+    /// Where in the source the error is.
+    pub span: Span,
+}
+
+impl Error
+{
+    /// Creates a new error.
+    pub fn new<S>(message: S, span: Span) -> Self
+        where S: Into<String> {
+        Error {
+            message: message.into(),
+            span: span,
+        }
+    }
+
+    /// Renders the error along with the line of source it is on,
+    /// with the offending characters underlined.
+    pub fn render(&self, file_name: &str, source: &str) -> String {
+        Diagnostic::from(self.clone()).render(file_name, Some(source))
+    }
+}
+
+impl From<Error> for Diagnostic
+{
+    fn from(e: Error) -> Diagnostic {
+        Diagnostic::error(e.message).with_span(e.span)
+    }
+}
+
+impl std::fmt::Display for Error
+{
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(fmt, "{}: {}", self.span, self.message)
+    }
+}
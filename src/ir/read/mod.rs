@@ -3,14 +3,31 @@ pub use self::token::Token;
 pub use self::tokenizer::Tokenizer;
 pub use self::parse::Parser;
 pub use self::resolve::Resolve;
+pub use self::error::Error;
 
 pub mod characters;
 pub mod token;
 pub mod tokenizer;
 pub mod parse;
 pub mod resolve;
+pub mod error;
+pub mod bitcode;
 
-pub fn textual<I>(characters: I) -> Result<::Module,String>
+pub fn textual<I>(characters: I) -> Result<::Module,Error>
     where I: Iterator<Item=char> {
     Parser::new(characters).parse()
 }
+
+/// Reads a module, carrying on after errors.
+///
+/// Every error is returned, along with a module holding
+/// whatever could be read.
+pub fn textual_recovering<I>(characters: I) -> (::Module,Vec<Error>)
+    where I: Iterator<Item=char> {
+    Parser::new(characters).parse_recovering()
+}
+
+/// Reads a module in the binary bitcode format.
+pub fn bitcode(input: &mut ::std::io::Read) -> Result<::Module,bitcode::Error> {
+    bitcode::module(input)
+}
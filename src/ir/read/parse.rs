@@ -1,13 +1,13 @@
-use super::{Tokenizer,Token,Resolve};
+use super::{Tokenizer,Token,Resolve,Error};
 
 use {
     Global,Module,Value,Expression,Type,Block,
     Signature,Function,Parameter,Instruction,types,
-    Unary, Binary, Condition, Register, Name,
+    Unary, Binary, Condition, Register, Name, Comment,
 };
 use std;
 
-pub type Result<T> = std::result::Result<T,String>;
+pub type Result<T> = std::result::Result<T,Error>;
 
 pub const ENTRY_LABEL_NAME: &'static str = "entry";
 
@@ -18,6 +18,12 @@ pub struct Parser<I: Iterator<Item=char>>
 
     module: Module,
     resolve: Resolve,
+
+    /// The errors that have been recovered from.
+    errors: Vec<Error>,
+    /// Comments which have been read, but not yet given to
+    /// the item or value following them.
+    comments: Vec<Comment>,
 }
 
 impl<I> Parser<I>
@@ -30,23 +36,100 @@ impl<I> Parser<I>
 
             module: Module::empty(),
             resolve: Resolve::new(),
+            errors: Vec::new(),
+            comments: Vec::new(),
         }
     }
 
     /// Gets parser which preserves comments.
+    ///
+    /// Comments are attached to the item or value which follows
+    /// them, or to the one they share a line with. Comments at the
+    /// top of the module which are set apart by a blank line, or
+    /// which come after every item, are kept by the module.
     pub fn preserve_comments(mut self) -> Self {
-        self.tokenizer = self.tokenizer.preserve_comments();
+        self.tokenizer = self.tokenizer.collect_comments();
         self
     }
 
-    /// Eats tokens while a predicate is true.
-    pub fn parse(mut self) -> Result<Module> {
+    /// Parses the module, returning the first error.
+    pub fn parse(self) -> Result<Module> {
+        let (module, errors) = self.parse_recovering();
+
+        match errors.into_iter().next() {
+            Some(e) => Err(e),
+            None => Ok(module),
+        }
+    }
+
+    /// Parses the module, carrying on after errors.
+    ///
+    /// Bad instructions are skipped up to the end of their line, and
+    /// bad items up to the next `fn` or `global`. Every error is
+    /// returned, along with a module holding what could be parsed.
+    pub fn parse_recovering(mut self) -> (Module, Vec<Error>) {
         while self.tokenizer.peek().is_some() {
-            try!(self.parse_next());
+            if let Err(e) = self.parse_next() {
+                self.errors.push(e);
+                self.recover_item();
+            }
         }
 
+        self.module.trailing_comments = self.take_comments();
+
         self.module = self.resolve.resolve(self.module);
-        Ok(self.module)
+        (self.module, self.errors)
+    }
+
+    /// Skips tokens up to the start of the next item.
+    fn recover_item(&mut self) {
+        let mut after_sigil = false;
+
+        loop {
+            let token = match self.tokenizer.peek() {
+                Some(Ok(token)) => token,
+                Some(Err(..)) => continue,
+                None => return,
+            };
+
+            if !after_sigil && util::is_item(&token) {
+                return;
+            }
+
+            after_sigil = token == Token::at_sign() || token == Token::percent_sign();
+            self.tokenizer.eat();
+        }
+    }
+
+    /// Skips tokens up to the end of the line.
+    ///
+    /// Fails if the next item is reached, as the block it was
+    /// in was never closed.
+    fn recover_value(&mut self) -> Result<()> {
+        let mut after_sigil = false;
+
+        loop {
+            let token = match self.tokenizer.peek() {
+                Some(Ok(token)) => token,
+                Some(Err(..)) => continue,
+                None => return Ok(()),
+            };
+
+            if token == Token::right_curly_brace() {
+                return Ok(());
+            }
+
+            if !after_sigil && util::is_item(&token) {
+                return Err(self.error(format!("expected }} before {}", token)));
+            }
+
+            after_sigil = token == Token::at_sign() || token == Token::percent_sign();
+            self.tokenizer.eat();
+
+            if token.is_new_line() {
+                return Ok(());
+            }
+        }
     }
 
     fn parse_next(&mut self) -> Result<()> {
@@ -57,12 +140,16 @@ impl<I> Parser<I>
             None => return Ok(()),
         };
 
+        if self.module.globals().next().is_none() && self.module.functions().next().is_none() {
+            self.take_leading_comments();
+        }
+
         if first_token == keywords::global() {
             self.parse_global()
         } else if first_token == keywords::function() {
             self.parse_function()
         } else {
-            Err(format!("unexpected token: {}", first_token))
+            Err(self.error(format!("unexpected token: {}", first_token)))
         }
     }
 
@@ -71,15 +158,34 @@ impl<I> Parser<I>
     }
 
     fn parse_global(&mut self) -> Result<()> {
+        let mut comments = self.take_comments();
         self.assert(keywords::global());
 
+        let mut linkage = ::Linkage::default();
+        let mut constant = false;
+
+        // Attributes come between the keyword and the name.
+        while let Ok(word) = self.peek_word() {
+            match &*word {
+                "internal" => linkage = ::Linkage::Internal,
+                "external" => linkage = ::Linkage::External,
+                "constant" => constant = true,
+                _ => return Err(self.error(format!("unknown global attribute: {}", word))),
+            }
+
+            self.assert_word();
+        }
+
         let name = try!(self.parse_global_identifier());
         try!(self.expect(Token::equal_sign()));
         let value = try!(self.parse_value());
 
         try!(self.expect(Token::new_line()));
+        comments.extend(self.take_comments());
 
-        let mut global = Global::new(name, value);
+        let mut global = Global::new(name, value).with_linkage(linkage);
+        global.constant = constant;
+        global.comments = comments;
         self.resolve.give(&mut global);
 
         self.module.add_global(global);
@@ -88,26 +194,60 @@ impl<I> Parser<I>
     }
 
     fn parse_function(&mut self) -> Result<()> {
+        let comments = self.take_comments();
         self.assert(keywords::function());
 
         self.resolve.begin_scope();
 
+        let result = self.parse_function_scoped(comments);
+
+        if result.is_err() {
+            self.resolve.abandon_scope();
+        }
+
+        result
+    }
+
+    /// Parses the rest of a function once its scope has been opened.
+    fn parse_function_scoped(&mut self, comments: Vec<Comment>) -> Result<()> {
         let name = try!(self.parse_global_identifier());
         let params = try!(self.parse_parameter_list());
         let returns = try!(self.parse_function_returns());
-        let body = try!(self.parse_body());
 
+        // The function is given before its body is parsed, so that
+        // recursive calls know its signature.
         let signature = Signature::new(params, returns);
-        let mut function = Function::new(name, signature, body);
-
-        self.resolve.end_scope();
+        let mut function = Function::new(name, signature, Vec::new());
+        function.comments = comments;
+        try!(self.parse_function_attributes(&mut function));
+        self.resolve.give_global(&mut function);
+
+        // Functions without a body are declarations of functions
+        // defined elsewhere.
+        let body = if try!(self.is_body_next()) {
+            try!(self.parse_body())
+        } else {
+            Vec::new()
+        };
+        let function = self.resolve.end_scope(function.with_blocks(body.into_iter()));
 
-        self.resolve.give(&mut function);
         self.module.add_function(function);
 
         Ok(())
     }
 
+    /// Checks if a function body follows, eating the new lines
+    /// before it.
+    fn is_body_next(&mut self) -> Result<bool> {
+        try!(self.eat_whitespace());
+
+        match self.tokenizer.peek() {
+            Some(Ok(token)) => Ok(token == Token::left_curly_brace()),
+            Some(Err(e)) => Err(e),
+            None => Ok(false),
+        }
+    }
+
     /// Parses a curly-brace contained list of blocks.
     fn parse_body(&mut self) -> Result<Vec<Block>> {
         try!(self.eat_whitespace());
@@ -171,8 +311,29 @@ impl<I> Parser<I>
         let mut values = Vec::new();
 
         while !try!(self.is_end_of_block_next()) {
-            let value = try!(self.parse_value());
-            values.push(value);
+            let next = try!(self.peek_something());
+            if util::is_item(&next) {
+                return Err(self.error(format!("expected }} before {}", next)));
+            }
+
+            let comments = self.take_comments();
+
+            match self.parse_value() {
+                Ok(value) => {
+                    // Read up to the end of the line so that a comment
+                    // sharing the line is attached to the value.
+                    try!(self.peek_something());
+
+                    let mut value = value.with_comments(comments);
+                    value.comments.extend(self.take_comments());
+                    values.push(value);
+                },
+                Err(e) => {
+                    self.errors.push(e);
+                    try!(self.recover_value());
+                },
+            }
+
             try!(self.eat_whitespace());
         }
 
@@ -227,19 +388,17 @@ impl<I> Parser<I>
         Ok(params)
     }
 
+    /// Parses a comma separated list of at least one type.
     fn parse_type_list(&mut self) -> Result<Vec<Type>> {
         try!(self.eat_whitespace());
 
-        let mut types = Vec::new();
+        let mut types = vec![try!(self.parse_type())];
 
-        while try!(self.peek_something()) != Token::left_curly_brace() {
+        while try!(self.peek_something()) == Token::comma() {
+            self.assert(Token::comma());
             try!(self.eat_whitespace());
 
-            let ty = try!(self.parse_type());
-            types.push(ty);
-
-            try!(self.maybe_eat(Token::comma()));
-            try!(self.eat_whitespace());
+            types.push(try!(self.parse_type()));
         }
 
         Ok(types)
@@ -247,6 +406,12 @@ impl<I> Parser<I>
 
     fn parse_function_returns(&mut self) -> Result<Vec<Type>> {
         try!(self.eat_whitespace());
+
+        // A declaration may end the file.
+        if self.tokenizer.peek().is_none() {
+            return Ok(Vec::new());
+        }
+
         let first_token = try!(self.peek_something());
 
         if first_token == Token::function_arrow() {
@@ -254,15 +419,79 @@ impl<I> Parser<I>
             try!(self.eat_whitespace());
 
             self.parse_type_list()
-        } else if first_token == Token::left_curly_brace() {
+        } else if first_token == Token::left_curly_brace() || first_token.is_word() {
             Ok(Vec::new())
         } else {
-            Err(format!("expected -> or {{ but got {}", first_token))
+            Err(self.error(format!("expected ->, {{ or an attribute but got {}", first_token)))
         }
     }
 
+    /// Parses the attributes between the signature of a function
+    /// and its body.
+    fn parse_function_attributes(&mut self, function: &mut Function) -> Result<()> {
+        try!(self.eat_whitespace());
+
+        while let Ok(word) = self.peek_word() {
+            // The next item follows a declaration.
+            let token = Token::word(&*word);
+            if token == keywords::function() || token == keywords::global() {
+                break;
+            }
+
+            self.assert_word();
+
+            match &*word {
+                "internal" => function.linkage = ::Linkage::Internal,
+                "external" => function.linkage = ::Linkage::External,
+                "inline" => function.inline_hint = ::InlineHint::Inline,
+                "must_inline" => function.inline_hint = ::InlineHint::MustInline,
+                "constant_time" => function.complexity_hint = ::ComplexityHint::ConstantTime,
+                "noreturn" => function.noreturn = true,
+                "naked" => function.naked = true,
+                "cc" => {
+                    try!(self.expect(Token::left_parenthesis()));
+                    let name = try!(self.expect_word());
+
+                    function.cc = match &*name {
+                        "c" => Some(::CallingConvention::C),
+                        _ => return Err(self.error(format!("unknown calling convention: {}", name))),
+                    };
+
+                    try!(self.expect(Token::right_parenthesis()));
+                },
+                "interrupt" => {
+                    try!(self.expect(Token::left_parenthesis()));
+                    let vector = try!(self.expect_integer());
+
+                    if vector < 0 || vector > ::std::u32::MAX as i64 {
+                        return Err(self.error(format!("{} is not a valid interrupt vector", vector)));
+                    }
+
+                    function.interrupt = Some(::Interrupt { vector: vector as u32 });
+                    try!(self.expect(Token::right_parenthesis()));
+                },
+                "section" => {
+                    try!(self.expect(Token::left_parenthesis()));
+                    function.section = Some(try!(self.expect_string()));
+                    try!(self.expect(Token::right_parenthesis()));
+                },
+                _ => return Err(self.error(format!("unknown function attribute: {}", word))),
+            }
+
+            try!(self.eat_whitespace());
+        }
+
+        Ok(())
+    }
+
     fn parse_value(&mut self) -> Result<Value> {
-        self.parse_expression().map(|expr| Value::new(expr))
+        try!(self.peek_something());
+        let start = self.tokenizer.span();
+
+        let expr = try!(self.parse_expression());
+        let span = start.to(self.tokenizer.previous_span());
+
+        Ok(Value::new(expr).with_span(span))
     }
 
     fn parse_condition(&mut self) -> Result<Condition> {
@@ -271,7 +500,14 @@ impl<I> Parser<I>
         if first_token.is_boolean() {
             Ok(Condition::from_boolean(try!(self.parse_boolean())))
         } else {
-            unimplemented!();
+            let lhs = try!(self.parse_value());
+            let comparison = try!(self.expect_word());
+            let rhs = try!(self.parse_value());
+
+            match Condition::from_abbreviation(&comparison, lhs, rhs) {
+                Some(cond) => Ok(cond),
+                None => Err(self.error(format!("unknown comparison: {}", comparison))),
+            }
         }
     }
 
@@ -281,7 +517,7 @@ impl<I> Parser<I>
         match &*value {
             "true" => Ok(true),
             "false" => Ok(false),
-            _ => Err(format!("{} is not a valid boolean value", value)),
+            _ => Err(self.error(format!("{} is not a valid boolean value", value))),
         }
     }
 
@@ -290,17 +526,56 @@ impl<I> Parser<I>
 
         match first_token {
             Token::Word(first_word) => self.parse_word_type(first_word),
-            _ => Err(format!("unknown token for type: {}",
-                             first_token)),
+            ref t if *t == Token::left_curly_brace() => self.parse_struct_type(),
+            ref t if *t == Token::left_square_bracket() => {
+                let (count, element) = try!(self.parse_sequence_type(Token::right_square_bracket()));
+                Ok(Type::array(count, element))
+            },
+            ref t if *t == Token::left_angle_bracket() => {
+                let (count, element) = try!(self.parse_sequence_type(Token::right_angle_bracket()));
+                Ok(Type::vector(count, element))
+            },
+            _ => Err(self.error(format!("unknown token for type: {}", first_token))),
+        }
+    }
+
+    /// Parses the fields of a struct type, after the opening brace.
+    fn parse_struct_type(&mut self) -> Result<Type> {
+        if try!(self.peek_something()) == Token::right_curly_brace() {
+            self.assert(Token::right_curly_brace());
+            return Ok(Type::unit_struct());
         }
+
+        let fields = try!(self.parse_type_list());
+        try!(self.expect(Token::right_curly_brace()));
+
+        Ok(Type::strukt(fields))
+    }
+
+    /// Parses the `N x T` inside an array or vector type, up to and
+    /// including the closing bracket.
+    fn parse_sequence_type(&mut self, close: Token) -> Result<(u64, Type)> {
+        let count = try!(self.expect_integer());
+
+        let x = try!(self.expect_word());
+        if x != "x" {
+            return Err(self.error(format!("expected x but got {}", x)));
+        }
+
+        let element = try!(self.parse_type());
+        try!(self.expect(close));
+
+        Ok((count as u64, element))
     }
 
     fn parse_word_type(&mut self, first_word: String)
         -> Result<Type> {
         if util::is_integer_type(&first_word) {
             self.parse_integer_type(&first_word).map(|t| t.into())
+        } else if util::is_decimal_type(&first_word) {
+            self.parse_decimal_type(&first_word).map(|t| t.into())
         } else {
-            Err(format!("unknown type: {}", first_word))
+            Err(self.error(format!("unknown type: {}", first_word)))
         }
     }
 
@@ -310,8 +585,11 @@ impl<I> Parser<I>
         match first_token {
             Token::Word(..) => self.parse_word_expression(),
             Token::String(..) => self.parse_string_expression(),
+            ref t if *t == Token::left_curly_brace() ||
+                     *t == Token::left_square_bracket() ||
+                     *t == Token::left_angle_bracket() => self.parse_aggregate_expression(),
             Token::Symbol(..) => self.parse_symbol_expression(),
-            _ => Err(format!("unknown token for expression: {}", first_token)),
+            _ => Err(self.error(format!("unknown token for expression: {}", first_token))),
         }
     }
 
@@ -321,6 +599,8 @@ impl<I> Parser<I>
 
         if util::is_integer_type(&first_word) {
             self.parse_integer_expression()
+        } else if util::is_decimal_type(&first_word) {
+            self.parse_decimal_expression()
         } else {
             self.parse_instruction()
         }
@@ -332,9 +612,102 @@ impl<I> Parser<I>
         debug_assert!(type_word.starts_with('i') || type_word.starts_with('u'));
 
         let ty = try!(self.parse_integer_type(&type_word));
+        let negative = try!(self.parse_sign());
         let value = try!(self.expect_integer());
+        let value = if negative { -value } else { value };
+
+        match Expression::integer(ty, value) {
+            Some(expr) => Ok(expr),
+            None => Err(self.error(format!("{} does not fit in {}", value, ty))),
+        }
+    }
+
+    /// Parses a decimal literal, such as `f32 1.5`, `f64 -2e10`
+    /// or `f32 nan`.
+    fn parse_decimal_expression(&mut self)
+        -> Result<Expression> {
+        let type_word = self.assert_word();
+        let ty = try!(self.parse_decimal_type(&type_word));
+        let negative = try!(self.parse_sign());
+
+        let mut text = match try!(self.expect_something()) {
+            Token::Decimal(text) => text,
+            Token::Integer(i) => i.to_string(),
+            Token::Word(ref w) if w == "inf" || w == "nan" => w.clone(),
+            token => return Err(self.error(format!("expected a decimal but got {}", token))),
+        };
 
-        Ok(Expression::integer(ty, value).unwrap())
+        if negative {
+            text.insert(0, '-');
+        }
+
+        let expr = if ty == types::Decimal::f32() {
+            text.parse::<f32>().map(Expression::f32).ok()
+        } else if ty == types::Decimal::f64() {
+            text.parse::<f64>().map(Expression::f64).ok()
+        } else {
+            return Err(self.error(format!("{} literals are not supported", ty)));
+        };
+
+        expr.ok_or_else(|| self.error(format!("{} is not a valid {}", text, ty)))
+    }
+
+    /// Eats a minus sign, returning whether there was one.
+    fn parse_sign(&mut self) -> Result<bool> {
+        if try!(self.peek_something()) == Token::minus_sign() {
+            self.assert(Token::minus_sign());
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    /// Parses a struct, array or vector literal.
+    fn parse_aggregate_expression(&mut self) -> Result<Expression> {
+        let open = try!(self.expect_something());
+
+        let close = if open == Token::left_curly_brace() {
+            Token::right_curly_brace()
+        } else if open == Token::left_square_bracket() {
+            Token::right_square_bracket()
+        } else {
+            Token::right_angle_bracket()
+        };
+
+        let mut elements = Vec::new();
+        try!(self.eat_whitespace());
+
+        while try!(self.peek_something()) != close {
+            let element = try!(self.parse_value());
+
+            if !element.node.is_literal() {
+                return Err(self.error("aggregate literals can only contain literals"));
+            }
+
+            elements.push(element);
+
+            if try!(self.peek_something()) == Token::comma() {
+                self.assert(Token::comma());
+            }
+            try!(self.eat_whitespace());
+        }
+
+        self.assert(close.clone());
+
+        if close == Token::right_curly_brace() {
+            return Ok(Expression::strukt(elements));
+        }
+
+        let element_ty = match elements.first() {
+            Some(element) => element.node.ty(),
+            None => return Err(self.error("cannot tell the element type of an empty literal")),
+        };
+
+        if close == Token::right_square_bracket() {
+            Ok(Expression::array(element_ty, elements))
+        } else {
+            Ok(Expression::vector(element_ty, elements))
+        }
     }
 
     fn parse_string_expression(&mut self)
@@ -352,11 +725,19 @@ impl<I> Parser<I>
         };
 
         let width_str: String = type_str.chars().skip(1).collect();
-        let width = try!(util::parse_integer(&width_str, 10));
+        let width = try!(util::parse_integer(&width_str, 10).map_err(|e| self.error(e)));
 
         Ok(types::Integer::new(kind, width as u16))
     }
 
+    fn parse_decimal_type(&mut self, type_str: &str)
+        -> Result<types::Decimal> {
+        let width_str: String = type_str.chars().skip(1).collect();
+        let width = try!(util::parse_integer(&width_str, 10).map_err(|e| self.error(e)));
+
+        Ok(types::Decimal::new(width as u16))
+    }
+
     fn parse_global_identifier(&mut self) -> Result<String> {
         try!(self.expect(Token::at_sign()));
         self.expect_word()
@@ -385,7 +766,7 @@ impl<I> Parser<I>
                     self.parse_local_reference(name)
                 }
             },
-            _ => Err(format!("unknown expression: {}", symbol)),
+            _ => Err(self.error(format!("unknown expression: {}", symbol))),
         }
     }
 
@@ -425,21 +806,39 @@ impl<I> Parser<I>
             "sub" => self.parse_binary_instruction::<Sub>(),
             "mul" => self.parse_binary_instruction::<Mul>(),
             "div" => self.parse_binary_instruction::<Div>(),
+            "rem" => self.parse_binary_instruction::<Rem>(),
+            "mulh" => self.parse_binary_instruction::<MulHigh>(),
+            "and" => self.parse_binary_instruction::<And>(),
             "shl" => self.parse_binary_instruction::<Shl>(),
             "shr" => self.parse_binary_instruction::<Shr>(),
-            "call" => self.parse_unary_instruction::<Call>(),
+            "call" => self.parse_call_instruction(),
+            "tail" => self.parse_tail_call_instruction(),
             "ret" => self.parse_ret_instruction(),
             "br" => self.parse_br_instruction(),
-
-            _ => Err(format!("unknown instruction: {}", mnemonic)),
+            "phi" => self.parse_phi_instruction(),
+            "load" => self.parse_unary_instruction::<Load>(),
+            "store" => self.parse_binary_instruction::<Store>(),
+            "extractvalue" => self.parse_extractvalue_instruction(),
+            "insertvalue" => self.parse_insertvalue_instruction(),
+            "getelementptr" => self.parse_getelementptr_instruction(),
+            "fadd" => self.parse_binary_instruction::<FAdd>(),
+            "fsub" => self.parse_binary_instruction::<FSub>(),
+            "fmul" => self.parse_binary_instruction::<FMul>(),
+            "fdiv" => self.parse_binary_instruction::<FDiv>(),
+            "icmp" => Ok(Instruction::icmp(try!(self.parse_condition())).into()),
+            "fcmp" => Ok(Instruction::fcmp(try!(self.parse_condition())).into()),
+            "itof" => self.parse_itof_instruction(),
+            "ftoi" => self.parse_ftoi_instruction(),
+
+            _ => Err(self.error(format!("unknown instruction: {}", mnemonic))),
         }
     }
 
     fn parse_unary_instruction<U>(&mut self) -> Result<Expression>
         where U: Unary {
-        let op = try!(self.parse_value());
+        let operand = try!(self.parse_value());
 
-        Ok(U::with_operand(op).into())
+        Ok(U::with_operand(operand).into())
     }
 
     fn parse_binary_instruction<B>(&mut self) -> Result<Expression>
@@ -451,6 +850,54 @@ impl<I> Parser<I>
         Ok(B::with_operands(lhs, rhs).into())
     }
 
+    fn parse_call_instruction(&mut self) -> Result<Expression> {
+        self.parse_call().map(|call| call.into())
+    }
+
+    fn parse_tail_call_instruction(&mut self) -> Result<Expression> {
+        let mnemonic = try!(self.expect_word());
+
+        if mnemonic != "call" {
+            return Err(self.error(format!("expected call after tail but got {}", mnemonic)));
+        }
+
+        self.parse_call().map(|call| call.tail().into())
+    }
+
+    fn parse_call(&mut self) -> Result<::instruction::Call> {
+        use instruction::Call;
+
+        let target = try!(self.parse_value());
+
+        let arguments = if try!(self.peek_something()) == Token::left_parenthesis() {
+            try!(self.parse_argument_list())
+        } else {
+            Vec::new()
+        };
+
+        Ok(Call::with_arguments(target, arguments))
+    }
+
+    /// Parses a parenthesised list of values passed to a call.
+    fn parse_argument_list(&mut self) -> Result<Vec<Value>> {
+        try!(self.expect(Token::left_parenthesis()));
+        try!(self.eat_whitespace());
+
+        let mut arguments = Vec::new();
+
+        while try!(self.peek_something()) != Token::right_parenthesis() {
+            let argument = try!(self.parse_value());
+            arguments.push(argument);
+
+            try!(self.maybe_eat(Token::comma()));
+            try!(self.eat_whitespace());
+        }
+
+        self.assert(Token::right_parenthesis());
+
+        Ok(arguments)
+    }
+
     fn parse_ret_instruction(&mut self) -> Result<Expression> {
         use instruction::Return;
 
@@ -473,23 +920,78 @@ impl<I> Parser<I>
         Ok(Instruction::br(condition, target).into())
     }
 
+    fn parse_phi_instruction(&mut self) -> Result<Expression> {
+        let ty = try!(self.parse_type());
+        let mut incoming = Vec::new();
+
+        loop {
+            try!(self.expect(Token::left_square_bracket()));
+            let value = try!(self.parse_value());
+            try!(self.expect(Token::comma()));
+            let block = try!(self.parse_value());
+            try!(self.expect(Token::right_square_bracket()));
+
+            incoming.push((value, block));
+
+            if try!(self.peek_something()) == Token::comma() {
+                self.assert(Token::comma());
+            } else {
+                break;
+            }
+        }
+
+        Ok(Instruction::phi(ty, incoming).into())
+    }
+
+    fn parse_extractvalue_instruction(&mut self) -> Result<Expression> {
+        let aggregate = try!(self.parse_value());
+        try!(self.expect(Token::comma()));
+        let index = try!(self.expect_integer());
+
+        Ok(Instruction::extractvalue(aggregate, index as u64).into())
+    }
+
+    fn parse_insertvalue_instruction(&mut self) -> Result<Expression> {
+        let aggregate = try!(self.parse_value());
+        try!(self.expect(Token::comma()));
+        let value = try!(self.parse_value());
+        try!(self.expect(Token::comma()));
+        let index = try!(self.expect_integer());
+
+        Ok(Instruction::insertvalue(aggregate, value, index as u64).into())
+    }
+
+    fn parse_getelementptr_instruction(&mut self) -> Result<Expression> {
+        let pointer = try!(self.parse_value());
+        let mut indices = Vec::new();
+
+        while try!(self.peek_something()) == Token::comma() {
+            self.assert(Token::comma());
+            indices.push(try!(self.parse_value()));
+        }
+
+        Ok(Instruction::getelementptr(pointer, indices).into())
+    }
+
     fn assert(&mut self, expected: Token) -> Token {
         self.expect(expected).unwrap()
     }
 
     fn expect_something(&mut self) -> Result<Token> {
-        util::expect(self.tokenizer.next())
+        let token = self.tokenizer.next();
+        util::expect(token, self.tokenizer.span())
     }
 
     fn peek_something(&mut self) -> Result<Token> {
-        util::expect(self.tokenizer.peek())
+        let token = self.tokenizer.peek();
+        util::expect(token, self.tokenizer.span())
     }
 
     fn peek_word(&mut self) -> Result<String> {
         if let Token::Word(word) = try!(self.peek_something()) {
             Ok(word)
         } else {
-            Err("expected word".to_owned())
+            Err(self.error("expected word"))
         }
     }
 
@@ -501,7 +1003,7 @@ impl<I> Parser<I>
         if let Token::String(s) = try!(self.expect_something()) {
             Ok(s)
         } else {
-            Err("expected a string".to_owned())
+            Err(self.error("expected a string"))
         }
     }
 
@@ -509,7 +1011,7 @@ impl<I> Parser<I>
         if let Token::Symbol(s) = try!(self.expect_something()) {
             Ok(s)
         } else {
-            Err("expected a symbol".to_owned())
+            Err(self.error("expected a symbol"))
         }
     }
 
@@ -534,27 +1036,47 @@ impl<I> Parser<I>
 
     fn expect_one_of(&mut self, expected: &[Token]) -> Result<Token> {
         assert!(!expected.is_empty());
-        let token = try!(util::expect(self.tokenizer.next()));
+        let token = try!(self.expect_something());
 
         if expected.iter().any(|e| e==&token) {
             Ok(token)
         } else {
             if expected.len() == 1 {
-                Err(format!("expected {} but got {}",
-                            expected[0], token))
+                Err(self.error(format!("expected {} but got {}",
+                                       expected[0], token)))
             } else { // multiple expected tokens
-                Err(format!("expected one of {} but got {}",
-                            ::util::comma_separated_values(expected.iter()),
-                            token))
+                Err(self.error(format!("expected one of {} but got {}",
+                                       ::util::comma_separated_values(expected.iter()),
+                                       token)))
             }
         }
     }
 
+    fn parse_itof_instruction(&mut self) -> Result<Expression> {
+        let value = try!(self.parse_value());
+        try!(self.expect(Token::comma()));
+
+        match try!(self.parse_type()) {
+            Type::Decimal(ty) => Ok(Instruction::itof(value, ty).into()),
+            ty => Err(self.error(format!("itof converts to floating point types, not {}", ty))),
+        }
+    }
+
+    fn parse_ftoi_instruction(&mut self) -> Result<Expression> {
+        let value = try!(self.parse_value());
+        try!(self.expect(Token::comma()));
+
+        match try!(self.parse_type()) {
+            Type::Integer(ty) => Ok(Instruction::ftoi(value, ty).into()),
+            ty => Err(self.error(format!("ftoi converts to integer types, not {}", ty))),
+        }
+    }
+
     fn expect_word(&mut self) -> Result<String> {
         match self.expect_something() {
             Ok(token) => match token {
                 Token::Word(w) => Ok(w.clone()),
-                _ => Err("expected a word".into()),
+                _ => Err(self.error("expected a word")),
             },
             Err(e) => Err(e),
         }
@@ -564,7 +1086,7 @@ impl<I> Parser<I>
         match self.expect_something() {
             Ok(ref token) => match *token {
                 Token::Integer(i) => Ok(i),
-                _ => Err("expected an integer".into()),
+                _ => Err(self.error("expected an integer")),
             },
             Err(e) => Err(e),
         }
@@ -573,19 +1095,64 @@ impl<I> Parser<I>
     fn eat_whitespace(&mut self) -> Result<()> {
         self.tokenizer.eat_while(|t| t.is_new_line())
     }
+
+    /// Takes the comments which have been read so far.
+    fn take_comments(&mut self) -> Vec<Comment> {
+        let mut comments = std::mem::replace(&mut self.comments, Vec::new());
+        comments.extend(self.tokenizer.take_comments().into_iter().map(|(c, _)| c));
+        comments
+    }
+
+    /// Gives the module the comments which are set apart from the
+    /// next item by a blank line.
+    ///
+    /// This is used before the first item, so that a header at
+    /// the top of the module is not mistaken for a comment about
+    /// the item.
+    fn take_leading_comments(&mut self) {
+        let item_line = self.tokenizer.span().start.line;
+        let comments = self.tokenizer.take_comments();
+
+        // Find the last comment which has a blank line after it.
+        let split = (0..comments.len()).rev().find(|&i| {
+            let next_line = comments.get(i + 1).map_or(item_line, |&(_, span)| span.start.line);
+            next_line > comments[i].1.end.line + 1
+        }).map_or(0, |i| i + 1);
+
+        let mut comments = comments.into_iter().map(|(c, _)| c);
+        self.module.leading_comments.extend(comments.by_ref().take(split));
+        self.comments.extend(comments);
+    }
+
+    /// Creates an error at the last token that was peeked or read.
+    fn error<S>(&self, message: S) -> Error
+        where S: Into<String> {
+        Error::new(message, self.tokenizer.span())
+    }
 }
 
 pub mod util
 {
+    use read::{Error,Token};
+    use super::keywords;
+    use Span;
+
     /// Expects that an `Option<Result>` is `Some`.
+    ///
+    /// The span is used for the error if there is nothing.
     // TODO: give this a name that doesn't clash with Parser::expect
-    pub fn expect<T>(val: Option<Result<T,String>>) -> Result<T,String> {
+    pub fn expect<T>(val: Option<Result<T,Error>>, span: Span) -> Result<T,Error> {
         match val {
             Some(result) => result,
-            None => Err("expected a token".into()),
+            None => Err(Error::new("expected a token", span)),
         }
     }
 
+    /// Checks if a token starts a new item.
+    pub fn is_item(token: &Token) -> bool {
+        *token == keywords::function() || *token == keywords::global()
+    }
+
     pub fn is_integer_type(word: &str) -> bool {
         if word.starts_with('i') || word.starts_with('u') {
             let next_part: String = word.chars().skip(1).collect();
@@ -596,6 +1163,16 @@ pub mod util
         }
     }
 
+    pub fn is_decimal_type(word: &str) -> bool {
+        if word.starts_with('f') {
+            let next_part: String = word.chars().skip(1).collect();
+
+            is_integer(&next_part, 10)
+        } else {
+            false
+        }
+    }
+
     pub fn is_integer(string: &str, radix: u32) -> bool {
         parse_integer(string, radix).is_ok()
     }
@@ -625,7 +1202,7 @@ pub mod keywords
 mod test
 {
     use super::Parser;
-    use {Value,Expression,Function,Signature};
+    use {Value,Expression,Function,Signature,Location,Span,Type};
 
     #[cfg(test)]
     fn parse(text: &str) -> ::Module {
@@ -655,6 +1232,154 @@ mod test
                        => "hello_world", Expression::u(127, 38));
 
         expect_global!("global @ewf = i16 52" => "ewf", Expression::i(16, 52));
+        expect_global!("global internal constant @ewf = i16 52" => "ewf", Expression::i(16, 52));
+    }
+
+    #[test]
+    fn aggregate_literals() {
+        let pair = vec![Value::new(Expression::i8(1)), Value::new(Expression::u16(2))];
+        let bytes = vec![Value::new(Expression::u8(3)), Value::new(Expression::u8(4))];
+
+        expect_global!("global @s = { i8 1, u16 2 }" => "s", Expression::strukt(pair));
+        expect_global!("global @u = {}" => "u", Expression::unit_struct());
+        expect_global!("global @a = [u8 3, u8 4]" => "a", Expression::array(Type::u8(), bytes.clone()));
+        expect_global!("global @v = <u8 3, u8 4>" => "v", Expression::vector(Type::u8(), bytes));
+    }
+
+    #[test]
+    fn decimal_literals() {
+        expect_global!("global @a = f32 0.1" => "a", Expression::f32(0.1));
+        expect_global!("global @b = f64 -2.5e-3" => "b", Expression::f64(-2.5e-3));
+        expect_global!("global @c = f64 3" => "c", Expression::f64(3.0));
+        expect_global!("global @d = f32 -inf" => "d", Expression::f32(-::std::f32::INFINITY));
+        expect_global!("global @e = f64 nan" => "e", Expression::f64(::std::f64::NAN));
+        expect_global!("global @f = i8 -128" => "f", Expression::i8(-128));
+
+        assert!(Parser::new("global @g = f16 1.0".chars()).parse().is_err());
+        assert!(Parser::new("global @h = u8 -1".chars()).parse().is_err());
+    }
+
+    #[test]
+    fn aggregate_types() {
+        let module = parse("fn @f(%s: { i8, [2 x u16] }, %v: <4 x i8>, %u: {}) {\n  ret\n}\n");
+        let types: Vec<_> = module.functions().next().unwrap().signature().parameters()
+                                  .map(|p| p.ty().clone()).collect();
+
+        assert_eq!(types, vec![
+            Type::strukt(vec![Type::i8(), Type::array(2, Type::u16())]),
+            Type::vector(4, Type::i8()),
+            Type::unit_struct(),
+        ]);
+    }
+
+    #[test]
+    fn declarations() {
+        let module = parse("fn @sqrtf(%x: f32) -> f32\nfn @f() {\n  ret\n}\nfn @g()\n");
+        let blocks: Vec<_> = module.functions().map(|f| (f.name().to_owned(), f.blocks().count())).collect();
+
+        assert_eq!(blocks, vec![("sqrtf".to_owned(), 0), ("f".to_owned(), 1), ("g".to_owned(), 0)]);
+    }
+
+    #[test]
+    fn global_attributes() {
+        let module = parse("global internal constant @a = i8 1\nglobal @b = i8 2\n");
+        let globals: Vec<_> = module.globals().map(|g| (g.is_exported(), g.is_constant())).collect();
+
+        assert_eq!(globals, vec![(false, true), (true, false)]);
+    }
+
+    #[test]
+    fn values_have_spans() {
+        let module = parse("global @a = i8 1\nglobal @b =   u16 300\n");
+        let spans: Vec<_> = module.globals().map(|g| g.value().span).collect();
+
+        assert_eq!(spans, vec![
+            Some(Span::new(Location::new(1, 13), Location::new(1, 17))),
+            Some(Span::new(Location::new(2, 15), Location::new(2, 22))),
+        ]);
+    }
+
+    #[test]
+    fn errors_have_locations() {
+        let text = "fn @main() {\n  ret\n  bogus i8 1\n}\n";
+        let error = Parser::new(text.chars()).parse().unwrap_err();
+
+        assert_eq!(error.message, "unknown instruction: bogus");
+        assert_eq!(error.span, Span::new(Location::new(3, 3), Location::new(3, 8)));
+        assert_eq!(error.to_string(), "3:3: unknown instruction: bogus");
+        assert_eq!(error.render("main.ir", text),
+                   "error: unknown instruction: bogus\n --> main.ir:3:3\n  |\n3 |   bogus i8 1\n  |   ^^^^^");
+    }
+
+    #[test]
+    fn reports_every_error() {
+        let text = "fn @main() {\n  bogus i8 1\n  ret i8 2\n  ret wat\n}\nglobal @a = i8 1\n";
+        let (module, errors) = Parser::new(text.chars()).parse_recovering();
+
+        let messages: Vec<_> = errors.iter().map(|e| e.to_string()).collect();
+        assert_eq!(messages.len(), 2, "{:?}", messages);
+        assert_eq!(messages[0], "2:3: unknown instruction: bogus");
+        assert!(messages[1].starts_with("4:"), "{:?}", messages);
+
+        let function = module.functions().next().expect("the function was dropped");
+        assert_eq!(function.values().count(), 1);
+        assert_eq!(module.globals().count(), 1);
+    }
+
+    #[test]
+    fn recovers_at_next_item() {
+        let text = "global @a = \nfn @main( {\n  ret\n}\nglobal @b = i8 1\n";
+        let (module, errors) = Parser::new(text.chars()).parse_recovering();
+
+        assert_eq!(errors.len(), 2, "{:?}", errors);
+        let globals: Vec<_> = module.globals().map(|g| g.name().to_owned()).collect();
+        assert_eq!(globals, vec!["b".to_owned()]);
+    }
+
+    #[test]
+    fn function_attributes() {
+        use {Linkage,InlineHint,ComplexityHint,Interrupt};
+
+        let module = parse("fn @f() -> i8, i16 internal inline constant_time cc(c)\n\
+                            \x20   noreturn naked interrupt(4) section(\".init\") {\n}\n\
+                            fn @g() must_inline {\n}\n");
+        let functions: Vec<_> = module.functions().collect();
+
+        assert_eq!(functions[0].signature().returns().count(), 2);
+        assert_eq!(functions[0].linkage(), Linkage::Internal);
+        assert_eq!(functions[0].inline_hint(), InlineHint::Inline);
+        assert_eq!(functions[0].complexity_hint(), ComplexityHint::ConstantTime);
+        assert!(functions[0].is_noreturn() && functions[0].is_naked());
+        assert_eq!(functions[0].interrupt(), Some(Interrupt { vector: 4 }));
+        assert_eq!(functions[0].section(), Some(".init"));
+        assert_eq!(functions[1].inline_hint(), InlineHint::MustInline);
+
+        let error = Parser::new("fn @f() fast {\n}\n".chars()).parse().unwrap_err();
+        assert_eq!(error.to_string(), "1:9: unknown function attribute: fast");
+    }
+
+    #[test]
+    fn keeps_comments() {
+        use Comment;
+
+        let text = "; header\n\n; about f\nfn @f() {\n  ; about ret\n  ret ; inline\n}\n; footer\n";
+        let module = Parser::new(text.chars()).preserve_comments().parse().unwrap();
+        let function = module.functions().next().unwrap();
+
+        assert_eq!(module.leading_comments, vec![Comment::new(" header")]);
+        assert_eq!(function.comments, vec![Comment::new(" about f")]);
+        assert_eq!(function.values().next().unwrap().comments,
+                   vec![Comment::new(" about ret"), Comment::inline(" inline")]);
+        assert_eq!(module.trailing_comments, vec![Comment::new(" footer")]);
     }
-}
 
+    #[test]
+    fn recovers_from_unclosed_functions() {
+        let text = "fn @f() {\n  ret\nfn @g() {\n  ret\n}\n";
+        let (module, errors) = Parser::new(text.chars()).parse_recovering();
+
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        let functions: Vec<_> = module.functions().map(|f| f.name().to_owned()).collect();
+        assert_eq!(functions, vec!["g".to_owned()]);
+    }
+}
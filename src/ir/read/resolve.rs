@@ -1,6 +1,8 @@
 use {Function,Global,Parameter,Module,Value,Expression,Type,Register,Block};
 use util::{Id,Identifiable};
 
+use std::collections::HashMap;
+
 pub enum Info
 {
     Function {
@@ -98,9 +100,9 @@ impl Resolve
             }
         }
 
-        // if the item has not been defined yet, it must
-        // lay in the global scope.
-        self.global_scope_mut().reference(name)
+        // if the item has not been defined yet, it may be defined
+        // later on in the current scope, or in an enclosing one.
+        self.local_scope_mut().reference(name)
     }
 
     pub fn give<T>(&mut self, item: &mut T)
@@ -108,6 +110,12 @@ impl Resolve
         self.local_scope_mut().give(item);
     }
 
+    /// Gives an item which is visible from every scope.
+    pub fn give_global<T>(&mut self, item: &mut T)
+        where T: Resolvable + 'static {
+        self.scope_stack.first_mut().unwrap().give(item);
+    }
+
     pub fn resolve(&mut self, module: Module)
         -> Module {
         self.scope_stack.iter_mut().fold(module, |m,scope| {
@@ -119,15 +127,32 @@ impl Resolve
         self.scope_stack.push(Scope::new());
     }
 
-    pub fn end_scope(&mut self) {
+    /// Closes the scope of a function which could not be parsed,
+    /// forgetting everything defined inside it.
+    pub fn abandon_scope(&mut self) {
         assert!(self.scope_stack.len() > 1,
                 "cannot close the global scope");
 
         self.scope_stack.pop();
     }
 
-    fn global_scope_mut(&mut self) -> &mut Scope {
-        self.scope_stack.first_mut().unwrap()
+    /// Closes the scope of a function, resolving the references
+    /// made inside it.
+    ///
+    /// References to items which were never defined in the scope
+    /// are handed to the enclosing scope.
+    pub fn end_scope(&mut self, function: Function) -> Function {
+        assert!(self.scope_stack.len() > 1,
+                "cannot close the global scope");
+
+        let mut scope = self.scope_stack.pop().unwrap();
+        let function = function.map_values(|v| scope.resolve_value(v));
+
+        let outer: HashMap<_,_> = scope.items.iter().filter(|item| !item.is_resolved()).map(|item| {
+            (item.id, self.local_scope_mut().reference(item.name.clone()))
+        }).collect();
+
+        function.map_values(|v| self::map_unresolved(v, &mut |id| outer[&id].clone()))
     }
 
     fn local_scope_mut(&mut self) -> &mut Scope {
@@ -187,14 +212,7 @@ impl Scope
     }
 
     fn resolve_value(&mut self, value: Value) -> Value {
-        let a = value.node.map_subvalues(|v| self.resolve_value(v));
-
-        Value {
-            node: match a {
-                Expression::UnresolvedRef(id) => self.resolve_reference(id),
-                other => other,
-            },
-        }
+        self::map_unresolved(value, &mut |id| self.resolve_reference(id))
     }
 
     fn resolve_reference(&self, id: Id) -> Expression {
@@ -224,6 +242,23 @@ impl Scope
     }
 }
 
+/// Replaces the unresolved references inside a value.
+fn map_unresolved<F>(value: Value, f: &mut F) -> Value
+    where F: FnMut(Id) -> Expression {
+    let span = value.span;
+    let comments = value.comments;
+    let node = match value.node {
+        Expression::UnresolvedRef(id) => f(id),
+        Expression::Register(mut reg) => {
+            reg.value = Box::new(self::map_unresolved(*reg.value, f));
+            Expression::Register(reg)
+        },
+        node => node.map_subvalues(|v| self::map_unresolved(v, f)),
+    };
+
+    Value { node: node, span: span, comments: comments }
+}
+
 struct Item
 {
     id: Id,
@@ -293,7 +328,6 @@ impl Item
                 ).into())
             },
             Info::Block => {
-                println!("ref");
                 Some(BlockRef::new(self.id).into())
             }
         }
@@ -11,6 +11,11 @@ pub enum Token
     /// An integer literal.
     // TODO: use BigNum
     Integer(i64),
+    /// A decimal literal, such as `1.5` or `2e-3`.
+    ///
+    /// The text is kept so that it can be rounded straight to the
+    /// precision of the literal's type.
+    Decimal(String),
     /// A comment.
     ///
     /// If the comment is inline, it existed on the same line
@@ -41,8 +46,13 @@ impl Token
     pub fn percent_sign() -> Self { Token::symbol("%") }
     pub fn left_curly_brace() -> Self { Token::symbol("{") }
     pub fn right_curly_brace() -> Self { Token::symbol("}") }
+    pub fn left_square_bracket() -> Self { Token::symbol("[") }
+    pub fn right_square_bracket() -> Self { Token::symbol("]") }
+    pub fn left_angle_bracket() -> Self { Token::symbol("<") }
+    pub fn right_angle_bracket() -> Self { Token::symbol(">") }
     pub fn equal_sign() -> Self { Token::symbol("=") }
     pub fn function_arrow() -> Self { Token::symbol("->") }
+    pub fn minus_sign() -> Self { Token::symbol("-") }
     pub fn boolean_true() -> Self { Token::word("true") }
     pub fn boolean_false() -> Self { Token::word("false") }
 
@@ -61,6 +71,11 @@ impl Token
         Token::Integer(integer.into())
     }
 
+    pub fn decimal<S>(text: S) -> Self
+        where S: Into<String> {
+        Token::Decimal(text.into())
+    }
+
     pub fn comment<S>(text: S) -> Self
         where S: Into<String> {
         Token::Comment {
@@ -123,6 +138,7 @@ impl std::fmt::Display for Token
             &Token::Word(ref w) => write!(fmt, "{}", w),
             &Token::String(ref s) => write!(fmt, "\"{}\"", s),
             &Token::Integer(ref i) => write!(fmt, "{}", i),
+            &Token::Decimal(ref d) => write!(fmt, "{}", d),
             &Token::Symbol(ref s) => write!(fmt, "{}", s),
             &Token::Comment { ref text, .. } => write!(fmt, " {}", text),
             &Token::NewLine => write!(fmt, "new line"),
@@ -1,10 +1,10 @@
-use super::{Token,Characters};
+use super::{Token,Characters,Error};
+use {Location,Span,Comment};
 use util;
 
 use std;
-use std::error::Error;
 
-pub type Result<T> = std::result::Result<T,String>;
+pub type Result<T> = std::result::Result<T,Error>;
 
 /// A list of symbols to be tokenized.
 ///
@@ -14,7 +14,7 @@ pub const SYMBOL_LIST: &'static [&'static str] = &[
     // Symbols with two characters
     "->",
     // Symbols with a single character
-    ",", ":", "(", ")", "@", "%", "{", "}", "=",
+    ",", ":", "(", ")", "@", "%", "{", "}", "[", "]", "<", ">", "=", "-",
 ];
 
 pub struct Tokenizer<I: Iterator<Item=char>>
@@ -22,18 +22,40 @@ pub struct Tokenizer<I: Iterator<Item=char>>
     chars: Characters<I>,
 
     preserve_comments: bool,
-
-    peek_buf: Option<Token>,
+    collect_comments: bool,
+
+    /// The comments which have been set aside, but not yet taken.
+    comments: Vec<(Comment, Span)>,
+    /// Whether a token other than a new line has been read on
+    /// the current line.
+    line_has_token: bool,
+
+    peek_buf: Option<(Token, Span)>,
+
+    /// The start of the token being read.
+    start: Location,
+    /// The span of the last token that was peeked or read.
+    current: Span,
+    /// The span of the last token that was read.
+    previous: Span,
 }
 
 impl<I> Tokenizer<I>
     where I: Iterator<Item=char>
 {
     pub fn new(chars: I) -> Self {
+        let start = Span::new(Location::start(), Location::start());
+
         Tokenizer {
             chars: Characters::new(chars),
             preserve_comments: false,
+            collect_comments: false,
+            comments: Vec::new(),
+            line_has_token: false,
             peek_buf: None,
+            start: Location::start(),
+            current: start,
+            previous: start,
         }
     }
 
@@ -43,16 +65,41 @@ impl<I> Tokenizer<I>
         self
     }
 
+    /// Sets comments aside instead of yielding them as tokens.
+    ///
+    /// The comments can be taken with `take_comments`.
+    pub fn collect_comments(mut self) -> Self {
+        self.collect_comments = true;
+        self
+    }
+
+    /// Takes the comments which have been set aside so far,
+    /// along with where they were written.
+    pub fn take_comments(&mut self) -> Vec<(Comment, Span)> {
+        std::mem::replace(&mut self.comments, Vec::new())
+    }
+
+    /// Gets the span of the last token that was peeked or read.
+    pub fn span(&self) -> Span { self.current }
+
+    /// Gets the span of the last token that was read.
+    pub fn previous_span(&self) -> Span { self.previous }
+
     pub fn peek(&mut self) -> Option<Result<Token>> {
-        let token = match self.next() {
+        if let Some((ref token, span)) = self.peek_buf {
+            self.current = span;
+            return Some(Ok(token.clone()));
+        }
+
+        let token = match self.read() {
             Some(Ok(token)) => token,
-            Some(Err(token)) => return Some(Err(token)),
-            None => return None,
+            other => return other,
         };
 
-        assert!(self.peek_buf.is_none());
+        let span = Span::new(self.start, self.chars.location());
+        self.peek_buf = Some((token.clone(), span));
+        self.current = span;
 
-        self.peek_buf = Some(token.clone());
         Some(Ok(token))
     }
 
@@ -79,6 +126,60 @@ impl<I> Tokenizer<I>
         Ok(())
     }
 
+    /// Reads the next token from the characters.
+    fn read(&mut self) -> Option<Result<Token>> {
+        let token = self.read_token();
+
+        if let Some(Ok(ref token)) = token {
+            self.line_has_token = !token.is_new_line();
+        }
+
+        token
+    }
+
+    fn read_token(&mut self) -> Option<Result<Token>> {
+        self.eat_whitespace();
+        self.start = self.chars.location();
+
+        let first_char = match self.chars.peek() {
+            Some(c) => c,
+            None => return None,
+        };
+
+        if first_char == '"' {
+            self.next_string()
+        } else if first_char == '\n' {
+            self.chars.eat();
+            Some(Ok(Token::new_line()))
+        } else if first_char == ';' {
+            if self.preserve_comments {
+                self.next_comment()
+            } else {
+
+                // TODO: This could be nicer
+                match self.next_comment() {
+                    Some(Ok(Token::Comment { inline, text })) => if self.collect_comments {
+                        let span = Span::new(self.start, self.chars.location());
+                        self.comments.push((Comment { text: text, inline: inline }, span));
+                    },
+                    Some(Err(e)) => return Some(Err(e)),
+                    _ => panic!("expected a comment to be parsed"),
+                }
+
+                // TODO: fix needless recursion
+                self.read()
+            }
+        } else if first_char.is_numeric() {
+            self.next_number()
+        } else if internal::can_word_start_with(first_char) {
+            self.next_word()
+        } else {
+            // Try to parse the token as a symbol.
+            // This is our final fallback.
+            self.next_symbol()
+        }
+    }
+
     fn eat_whitespace(&mut self) {
         self.chars.eat_while(|c| c.is_whitespace() && c != '\n')
     }
@@ -91,18 +192,57 @@ impl<I> Tokenizer<I>
         Some(Ok(Token::String(string.collect())))
     }
 
-    fn next_integer(&mut self) -> Option<Result<Token>> {
-        let string: String = self.chars.consume_while(|c| c.is_numeric()).collect();
+    /// Reads an integer, or a decimal if it has a fraction or an
+    /// exponent such as `1.5e-3`.
+    fn next_number(&mut self) -> Option<Result<Token>> {
+        let mut string: String = self.chars.consume_while(|c| c.is_numeric()).collect();
+        let mut is_decimal = false;
+
+        if self.chars.peek() == Some('.') {
+            self.chars.eat();
+            string.push('.');
+
+            if let Err(e) = self.next_digits(&mut string) { return Some(Err(e)) }
+            is_decimal = true;
+        }
+
+        if self.chars.peek() == Some('e') || self.chars.peek() == Some('E') {
+            self.chars.eat();
+            string.push('e');
+
+            if let Some(sign) = self.chars.peek().filter(|&c| c == '-' || c == '+') {
+                self.chars.eat();
+                string.push(sign);
+            }
+
+            if let Err(e) = self.next_digits(&mut string) { return Some(Err(e)) }
+            is_decimal = true;
+        }
+
+        if is_decimal {
+            return Some(Ok(Token::Decimal(string)));
+        }
 
         let int = match i64::from_str_radix(&string, 10) {
             Ok(int) => int,
-            Err(e) => return Some(Err(format!("Could not parse integer: {}",
-                                              e.description()))),
+            Err(e) => return Some(Err(self.error(format!("could not parse integer: {}", e)))),
         };
 
         Some(Ok(Token::Integer(int)))
     }
 
+    /// Reads one or more digits onto the end of a number.
+    fn next_digits(&mut self, string: &mut String) -> Result<()> {
+        let digits: String = self.chars.consume_while(|c| c.is_numeric()).collect();
+
+        if digits.is_empty() {
+            return Err(self.error(format!("expected digits after {}", string)));
+        }
+
+        string.push_str(&digits);
+        Ok(())
+    }
+
     fn next_word(&mut self) -> Option<Result<Token>> {
         let word: String = self.chars.consume_while(internal::can_word_contain).collect();
 
@@ -140,18 +280,18 @@ impl<I> Tokenizer<I>
             }
         }
 
-        Some(Err(format!("unknown token: {}", first_char)))
+        Some(Err(self.error(format!("unknown token: {}", first_char))))
     }
 
     fn next_comment(&mut self) -> Option<Result<Token>> {
         self.assert(';');
         let text: String = self.chars.consume_while(|c| c != '\n').collect();
 
-        Some(Ok(Token::comment(text)))
-    }
-
-    fn eat_next_comment(&mut self) -> Option<Result<()>> {
-        self.next_comment().map(|result| result.map(|_| ()))
+        if self.line_has_token {
+            Some(Ok(Token::inline_comment(text)))
+        } else {
+            Some(Ok(Token::comment(text)))
+        }
     }
 
     fn assert(&mut self, expected: char) -> char {
@@ -166,7 +306,7 @@ impl<I> Tokenizer<I>
     fn expect_something(&mut self) -> Result<char> {
         match self.chars.next() {
             Some(c) => Ok(c),
-            None => Err(format!("expected a token but found nothing")),
+            None => Err(self.error("expected a token but found nothing")),
         }
     }
 
@@ -176,16 +316,22 @@ impl<I> Tokenizer<I>
         let next = match self.chars.next() {
             Some(c) => c,
             None => {
-                return Err(format!("expected one of {} but got nothing", expected_str))
+                return Err(self.error(format!("expected one of {} but got nothing", expected_str)))
             },
         };
 
         if expected.iter().any(|&c| c == next) {
             Ok(next)
         } else {
-            Err(format!("Expected one of {} but got {}", expected_str, next))
+            Err(self.error(format!("expected one of {} but got {}", expected_str, next)))
         }
     }
+
+    /// Creates an error covering the token being read.
+    fn error<S>(&self, message: S) -> Error
+        where S: Into<String> {
+        Error::new(message, Span::new(self.start, self.chars.location()))
+    }
 }
 
 impl<I> Iterator for Tokenizer<I>
@@ -194,48 +340,18 @@ impl<I> Iterator for Tokenizer<I>
     type Item = Result<Token>;
 
     fn next(&mut self) -> Option<Result<Token>> {
-        if self.peek_buf.is_some() {
-            let token = self.peek_buf.clone().unwrap();
-            self.peek_buf = None;
-            return Some(Ok(token));
-        }
-
-        self.eat_whitespace();
-
-        let first_char = match self.chars.peek() {
-            Some(c) => c,
-            None => return None,
+        let (token, span) = match self.peek_buf.take() {
+            Some(peeked) => peeked,
+            None => match self.read() {
+                Some(Ok(token)) => (token, Span::new(self.start, self.chars.location())),
+                other => return other,
+            },
         };
 
-        if first_char == '"' {
-            self.next_string()
-        } else if first_char == '\n' {
-            self.chars.eat();
-            Some(Ok(Token::new_line()))
-        } else if first_char == ';' {
-            if self.preserve_comments {
-                self.next_comment()
-            } else {
-
-                // TODO: This could be nicer
-                match self.eat_next_comment() {
-                    Some(Ok(..)) => (),
-                    Some(Err(e)) => return Some(Err(e)),
-                    None => panic!("expected a comment to be parsed"),
-                }
+        self.current = span;
+        self.previous = span;
 
-                // TODO: fix needless recursion
-                self.next()
-            }
-        } else if first_char.is_numeric() {
-            self.next_integer()
-        } else if internal::can_word_start_with(first_char) {
-            self.next_word()
-        } else {
-            // Try to parse the token as a symbol.
-            // This is our final fallback.
-            self.next_symbol()
-        }
+        Some(Ok(token))
     }
 }
 
@@ -316,6 +432,21 @@ mod test
                                      Token::new_line());
     }
 
+    #[test]
+    fn test_decimal() {
+        expect_mapping!("1.5 0.25e3 7e-10 2E+2" => Token::decimal("1.5"),
+                                                   Token::decimal("0.25e3"),
+                                                   Token::decimal("7e-10"),
+                                                   Token::decimal("2e+2"),
+                                                   Token::new_line());
+
+        expect_mapping!("-1.5 -2" => Token::symbol("-"),
+                                     Token::decimal("1.5"),
+                                     Token::symbol("-"),
+                                     Token::integer(2),
+                                     Token::new_line());
+    }
+
     #[test]
     fn test_word() {
         expect_mapping!("hello" => Token::word("hello"));
@@ -345,7 +476,7 @@ mod test
                              .preserve_comments()
                              => Token::word("hello"),
                                 Token::word("world"),
-                                Token::comment(" this is me"),
+                                Token::inline_comment(" this is me"),
                                 Token::new_line());
 
         expect_mapping_with!(Tokenizer::new(";this is a test".chars())
@@ -429,4 +560,26 @@ mod test
         assert_eq!(tokenizer.next().unwrap().unwrap(),
                    Token::new_line());
     }
+
+    #[test]
+    fn test_spans() {
+        use {Location,Span};
+
+        let mut tokenizer = Tokenizer::new("ab ; comment\n  -> 12".chars());
+
+        tokenizer.next();
+        assert_eq!(tokenizer.span(), Span::new(Location::new(1, 1), Location::new(1, 3)));
+
+        assert_eq!(tokenizer.next().unwrap().unwrap(), Token::new_line());
+        assert_eq!(tokenizer.span(), Span::new(Location::new(1, 13), Location::new(2, 1)));
+
+        // Peeking moves the current span but not the previous one.
+        tokenizer.peek();
+        assert_eq!(tokenizer.span(), Span::new(Location::new(2, 3), Location::new(2, 5)));
+        assert_eq!(tokenizer.previous_span(), Span::new(Location::new(1, 13), Location::new(2, 1)));
+
+        tokenizer.next();
+        tokenizer.next();
+        assert_eq!(tokenizer.previous_span(), Span::new(Location::new(2, 6), Location::new(2, 8)));
+    }
 }
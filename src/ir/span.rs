@@ -1,9 +1,32 @@
+pub use util::{Location,Span};
+use Comment;
+
 use std::cmp;
 use std::fmt;
+use std::hash;
 
 pub struct Spanned<T>
 {
     pub node: T,
+    /// Where the node came from, if it was read from a file.
+    pub span: Option<Span>,
+    /// The comments written around the node, if they were kept.
+    pub comments: Vec<Comment>,
+}
+
+impl<T> Spanned<T>
+{
+    /// Gives the node a location.
+    pub fn with_span(mut self, span: Span) -> Self {
+        self.span = Some(span);
+        self
+    }
+
+    /// Attaches comments to the node.
+    pub fn with_comments(mut self, comments: Vec<Comment>) -> Self {
+        self.comments = comments;
+        self
+    }
 }
 
 impl<T: Clone> Clone for Spanned<T>
@@ -11,6 +34,8 @@ impl<T: Clone> Clone for Spanned<T>
     fn clone(&self) -> Self {
         Spanned {
             node: self.node.clone(),
+            span: self.span,
+            comments: self.comments.clone(),
         }
     }
 }
@@ -29,3 +54,23 @@ impl<T: cmp::PartialEq> cmp::PartialEq for Spanned<T>
 
 impl<T: cmp::Eq> cmp::Eq for Spanned<T> { }
 
+impl<T: hash::Hash> hash::Hash for Spanned<T>
+{
+    fn hash<H: hash::Hasher>(&self, state: &mut H) { self.node.hash(state) }
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::{Location,Span,Spanned};
+
+    #[test]
+    fn equality_ignores_spans() {
+        let span = Span::new(Location::new(3, 4), Location::new(3, 9));
+
+        let a = Spanned { node: 5, span: None, comments: Vec::new() };
+        let b = Spanned { node: 5, span: Some(span), comments: Vec::new() };
+
+        assert_eq!(a, b);
+    }
+}
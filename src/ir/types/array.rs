@@ -2,7 +2,7 @@ use types::{Type,TypeTrait};
 use std::fmt;
 
 /// Represents an array of values of a single `Type`.
-#[derive(Clone,Eq,PartialEq,Debug)]
+#[derive(Clone,Eq,PartialEq,Debug,Hash)]
 pub struct Array
 {
     count: u64,
@@ -21,6 +21,12 @@ impl Array
             ty: Box::new(ty),
         }
     }
+
+    /// Gets the number of elements.
+    pub fn count(&self) -> u64 { self.count }
+
+    /// Gets the type of the elements.
+    pub fn element(&self) -> &Type { &self.ty }
 }
 
 impl TypeTrait for Array
@@ -2,7 +2,7 @@ use types;
 use std::fmt;
 
 /// A basic block type.
-#[derive(Copy,Clone,Eq,PartialEq,Debug)]
+#[derive(Copy,Clone,Eq,PartialEq,Debug,Hash)]
 pub struct Block;
 
 impl Block
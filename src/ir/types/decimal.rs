@@ -2,7 +2,7 @@ use types;
 use std::fmt;
 
 /// A decimal type.
-#[derive(Copy,Clone,Eq,PartialEq,Debug)]
+#[derive(Copy,Clone,Eq,PartialEq,Debug,Hash)]
 pub struct Decimal
 {
     bit_width: u16,
@@ -23,6 +23,9 @@ impl Decimal
     pub fn f16() -> Decimal { Decimal::f(16) }
     pub fn f32() -> Decimal { Decimal::f(32) }
     pub fn f64() -> Decimal { Decimal::f(64) }
+
+    /// Gets the number of bits in the type.
+    pub fn bit_width(&self) -> u16 { self.bit_width }
 }
 
 impl fmt::Display for Decimal
@@ -3,7 +3,7 @@ use Signature;
 use std;
 
 /// A function signature in IR.
-#[derive(Clone,Eq,PartialEq,Debug)]
+#[derive(Clone,Eq,PartialEq,Debug,Hash)]
 pub struct Function
 {
     signature: Signature,
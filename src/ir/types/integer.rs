@@ -3,7 +3,7 @@ use util::IntegerKind;
 use std::fmt;
 
 /// An integer type.
-#[derive(Copy,Clone,Eq,PartialEq,Debug)]
+#[derive(Copy,Clone,Eq,PartialEq,Debug,Hash)]
 pub struct Integer
 {
     pub kind: IntegerKind,
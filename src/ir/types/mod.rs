@@ -42,7 +42,7 @@ pub mod ty
         }
     }
 
-    #[derive(Clone,Eq,PartialEq,Debug)]
+    #[derive(Clone,Eq,PartialEq,Debug,Hash)]
     pub enum Type
     {
         Void(Void),
@@ -112,6 +112,12 @@ pub mod ty
             Type::Array(Array::new(count,ty))
         }
 
+        /// Creates a new struct.
+        pub fn strukt<I>(fields: I) -> Type
+            where I: IntoIterator<Item=Type> {
+            Type::Struct(Struct::new(fields.into_iter()))
+        }
+
         /// Creates a new unit struct.
         pub fn unit_struct() -> Type { Type::Struct(Struct::unit()) }
 
@@ -122,6 +128,56 @@ pub mod ty
         /// Creates a new block type.
         pub fn block() -> Type { Type::Block(Block::new()) }
 
+        /// Gets the type a pointer points to, or `None` if
+        /// the type is not a pointer.
+        pub fn pointee(&self) -> Option<&Type> {
+            if let Type::Pointer(ref ptr) = *self {
+                Some(ptr.pointee())
+            } else {
+                None
+            }
+        }
+
+        /// Checks if the type is a struct, array or vector.
+        pub fn is_aggregate(&self) -> bool {
+            match *self {
+                Type::Vector(..) | Type::Array(..) | Type::Struct(..) => true,
+                _ => false,
+            }
+        }
+
+        /// Gets the number of elements in an aggregate, or `None` if
+        /// the type is not an aggregate.
+        pub fn element_count(&self) -> Option<u64> {
+            match *self {
+                Type::Vector(ref ty) => Some(ty.count()),
+                Type::Array(ref ty) => Some(ty.count()),
+                Type::Struct(ref ty) => Some(ty.fields.len() as u64),
+                _ => None,
+            }
+        }
+
+        /// Gets the type of an element in an aggregate, or `None` if
+        /// the type is not an aggregate or the index is out of range.
+        pub fn element(&self, index: u64) -> Option<&Type> {
+            match *self {
+                Type::Vector(ref ty) if index < ty.count() => Some(ty.element()),
+                Type::Array(ref ty) if index < ty.count() => Some(ty.element()),
+                Type::Struct(ref ty) => ty.fields.get(index as usize),
+                _ => None,
+            }
+        }
+
+        /// Checks if the type is an integer.
+        pub fn is_integer(&self) -> bool {
+            if let Type::Integer(..) = *self { true } else { false }
+        }
+
+        /// Checks if the type is a floating point type.
+        pub fn is_decimal(&self) -> bool {
+            if let Type::Decimal(..) = *self { true } else { false }
+        }
+
         /// Checks if the type is void.
         pub fn is_void(&self) -> bool {
             if let Type::Void(..) = *self {
@@ -2,7 +2,7 @@ use {Type,TypeTrait};
 use std::fmt;
 
 /// A pointer.
-#[derive(Clone,Debug,Eq,PartialEq)]
+#[derive(Clone,Debug,Eq,PartialEq,Hash)]
 pub struct Pointer
 {
     to: Box<Type>,
@@ -16,6 +16,11 @@ impl Pointer
             to: Box::new(value),
         }
     }
+
+    /// Gets the type being pointed to.
+    pub fn pointee(&self) -> &Type {
+        &self.to
+    }
 }
 
 impl fmt::Display for Pointer
@@ -2,7 +2,7 @@ use types::{Type,TypeTrait};
 use std::{self, fmt};
 
 /// Represents a struct.
-#[derive(Clone,Eq,PartialEq,Debug)]
+#[derive(Clone,Eq,PartialEq,Debug,Hash)]
 pub struct Struct
 {
     pub fields: Vec<Type>,
@@ -43,7 +43,11 @@ impl fmt::Display for Struct
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         use util;
 
-        write!(fmt, "type {{ {} }}", util::comma_separated_values(self.fields.iter()))
+        if self.fields.is_empty() {
+            write!(fmt, "{{}}")
+        } else {
+            write!(fmt, "{{ {} }}", util::comma_separated_values(self.fields.iter()))
+        }
     }
 }
 
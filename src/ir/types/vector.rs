@@ -2,7 +2,7 @@ use types::{Type,TypeTrait};
 use std::fmt;
 
 /// A vector value.
-#[derive(Clone,Eq,PartialEq,Debug)]
+#[derive(Clone,Eq,PartialEq,Debug,Hash)]
 pub struct Vector
 {
     count: u64,
@@ -18,6 +18,12 @@ impl Vector
             ty: Box::new(ty),
         }
     }
+
+    /// Gets the number of elements.
+    pub fn count(&self) -> u64 { self.count }
+
+    /// Gets the type of the elements.
+    pub fn element(&self) -> &Type { &self.ty }
 }
 
 impl fmt::Display for Vector
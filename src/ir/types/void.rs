@@ -2,7 +2,7 @@ use types;
 use std::fmt;
 
 /// An empty type.
-#[derive(Copy,Clone,Eq,PartialEq,Debug)]
+#[derive(Copy,Clone,Eq,PartialEq,Debug,Hash)]
 pub struct Void;
 
 impl Void
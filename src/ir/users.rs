@@ -1,4 +1,5 @@
-use {Value, Expression, ItemTrait, Module, Function, Global, Block};
+use {Value, Expression, ItemTrait, Module, Function, Global, Block, Register};
+use util::{self, Identifiable};
 
 /// Stores the users of a value.
 pub struct Users<'a>
@@ -19,62 +20,106 @@ impl<'a> Users<'a>
         let mut users = Vec::new();
 
         for f in module.functions() {
-            users_in_function(item, f, &mut users);
+            users_in_function(item.get_id(), f, &mut users);
         }
 
         for g in module.globals() {
-            users_in_global(item, g, &mut users);
+            users_in_global(item.get_id(), g, &mut users);
         }
 
         Users { users: users }
     }
 
+    /// Gets all of the users of an item inside a single function.
+    pub fn of_in_function(item: &ItemTrait, function: &'a Function) -> Self {
+        let mut users = Vec::new();
+        users_in_function(item.get_id(), function, &mut users);
+
+        Users { users: users }
+    }
+
+    /// Gets all of the users of an item inside the value of a global.
+    pub fn of_in_global(item: &ItemTrait, global: &'a Global) -> Self {
+        let mut users = Vec::new();
+        users_in_global(item.get_id(), global, &mut users);
+
+        Users { users: users }
+    }
+
+    /// Gets all of the users of a register in the function which defines it.
+    pub fn of_register(register: &Register, function: &'a Function) -> Self {
+        let mut users = Vec::new();
+        users_in_function(register.get_id(), function, &mut users);
+
+        Users { users: users }
+    }
+
+    /// Gets all of the users of a block in the function which contains it.
+    pub fn of_block(block: &Block, function: &'a Function) -> Self {
+        let mut users = Vec::new();
+        users_in_function(block.get_id(), function, &mut users);
+
+        Users { users: users }
+    }
+
     pub fn len(&self) -> usize {
         self.users.len()
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.users.is_empty()
+    }
+
     pub fn users(&self) -> ::std::slice::Iter<&Value> {
         self.users.iter()
     }
 }
 
-fn users_in_global<'a>(item: &ItemTrait,
+fn users_in_global<'a>(id: util::Id,
                        g: &'a Global,
                        users: &mut Vec<&'a Value>) {
-    users_in_value(item, &g.value, users);
+    users_in_value(id, &g.value, users);
 }
 
-fn users_in_function<'a>(item: &ItemTrait,
+fn users_in_function<'a>(id: util::Id,
                          f: &'a Function,
                          users: &mut Vec<&'a Value>) {
     for block in f.blocks() {
-        users_in_block(item, block, users);
+        users_in_block(id, block, users);
     }
 }
 
-fn users_in_block<'a>(item: &ItemTrait,
+fn users_in_block<'a>(id: util::Id,
                       block: &'a Block,
                       users: &mut Vec<&'a Value>) {
     for value in block.values() {
-        users_in_value(item, value, users);
+        users_in_value(id, value, users);
     }
 }
 
-fn users_in_value<'a>(item: &ItemTrait,
+fn users_in_value<'a>(id: util::Id,
                       value: &'a Value,
                       users: &mut Vec<&'a Value>) {
     match value.node {
-        Expression::GlobalRef(ref global_ref) => if item.get_id() == global_ref.global_id {
+        Expression::GlobalRef(ref global_ref) => if id == global_ref.global_id {
+            users.push(value);
+        },
+        Expression::FunctionRef(ref func_ref) => if id == func_ref.func_id {
             users.push(value);
         },
-        Expression::FunctionRef(ref func_ref) => if item.get_id() == func_ref.func_id {
+        Expression::RegisterRef(ref reg_ref) => if id == reg_ref.register_id {
             users.push(value);
         },
+        Expression::BlockRef(ref block_ref) => if id == block_ref.block_id() {
+            users.push(value);
+        },
+        Expression::Register(ref reg) => {
+            users_in_value(id, &reg.value, users);
+        },
         _ => {
             for subvalue in value.node.subvalues() {
-                users_in_value(item, subvalue, users);
+                users_in_value(id, subvalue, users);
             }
         },
     }
 }
-
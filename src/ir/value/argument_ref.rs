@@ -1,7 +1,7 @@
 use {Type,Parameter};
 use util;
 
-#[derive(Clone,Debug,PartialEq,Eq)]
+#[derive(Clone,Debug,PartialEq,Eq,Hash)]
 pub struct ArgumentRef
 {
     pub param_id: util::Id,
@@ -2,7 +2,7 @@ use {Type,Block};
 use util::Identifiable;
 use util;
 
-#[derive(Clone,Debug,PartialEq,Eq)]
+#[derive(Clone,Debug,PartialEq,Eq,Hash)]
 pub struct BlockRef
 {
     block_id: util::Id,
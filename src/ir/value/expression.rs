@@ -10,7 +10,7 @@ pub trait ExpressionTrait : Clone + fmt::Debug + Into<Expression>
 {
 }
 
-#[derive(Clone,Debug,PartialEq,Eq)]
+#[derive(Clone,Debug,PartialEq,Eq,Hash)]
 pub enum Expression
 {
     Literal(value::Literal),
@@ -31,16 +31,30 @@ pub enum Expression
 
 impl Expression
 {
-    pub fn decimal(_ty: types::Decimal, _bits: BitVec) -> Self {
-        unimplemented!();
+    /// Creates a decimal from the bits of its IEEE 754 encoding.
+    pub fn decimal(ty: types::Decimal, bits: BitVec) -> Self {
+        value::Literal::decimal(ty, bits).into()
     }
 
-    pub fn strukt(_fields: Vec<Value>) -> Self {
-        unimplemented!();
+    pub fn f32(value: f32) -> Self { value::literal::Decimal::f32(value).into() }
+    pub fn f64(value: f64) -> Self { value::literal::Decimal::f64(value).into() }
+
+    pub fn strukt(fields: Vec<Value>) -> Self {
+        value::Literal::strukt(fields).into()
     }
 
     pub fn unit_struct() -> Self {
-        unimplemented!();
+        value::Literal::unit_struct().into()
+    }
+
+    /// Creates an array of values of type `element`.
+    pub fn array(element: Type, elements: Vec<Value>) -> Self {
+        value::Literal::array(element, elements).into()
+    }
+
+    /// Creates a vector of values of type `element`.
+    pub fn vector(element: Type, elements: Vec<Value>) -> Self {
+        value::Literal::vector(element, elements).into()
     }
 
     /// Creates an integer, returning `None` if `val` cannot fit into `ty`.
@@ -142,6 +156,27 @@ impl Expression
         )
     }
 
+    pub fn rem<V>(lhs: V, rhs: V) -> Self
+        where V: Into<Value> {
+        Expression::instruction(
+            Instruction::rem(lhs, rhs)
+        )
+    }
+
+    pub fn mulh<V>(lhs: V, rhs: V) -> Self
+        where V: Into<Value> {
+        Expression::instruction(
+            Instruction::mulh(lhs, rhs)
+        )
+    }
+
+    pub fn and<V>(lhs: V, rhs: V) -> Self
+        where V: Into<Value> {
+        Expression::instruction(
+            Instruction::and(lhs, rhs)
+        )
+    }
+
     pub fn shl<V>(value: V, amount: V) -> Self
         where V: Into<Value> {
         Expression::instruction(
@@ -156,6 +191,20 @@ impl Expression
         )
     }
 
+    pub fn load<V>(ptr: V) -> Self
+        where V: Into<Value> {
+        Expression::instruction(
+            Instruction::load(ptr)
+        )
+    }
+
+    pub fn store<V>(ptr: V, value: V) -> Self
+        where V: Into<Value> {
+        Expression::instruction(
+            Instruction::store(ptr, value)
+        )
+    }
+
     pub fn call<V>(target: V) -> Self
         where V: Into<Value> {
         Expression::instruction(
@@ -163,6 +212,95 @@ impl Expression
         )
     }
 
+    pub fn call_with_arguments<V,I>(target: V, arguments: I) -> Self
+        where V: Into<Value>, I: IntoIterator<Item=Value> {
+        Expression::instruction(
+            Instruction::call_with_arguments(target, arguments)
+        )
+    }
+
+    pub fn phi<I>(ty: Type, incoming: I) -> Self
+        where I: IntoIterator<Item=(Value,Value)> {
+        Expression::instruction(
+            Instruction::phi(ty, incoming)
+        )
+    }
+
+    pub fn extractvalue<V>(aggregate: V, index: u64) -> Self
+        where V: Into<Value> {
+        Expression::instruction(
+            Instruction::extractvalue(aggregate, index)
+        )
+    }
+
+    pub fn insertvalue<V1,V2>(aggregate: V1, value: V2, index: u64) -> Self
+        where V1: Into<Value>, V2: Into<Value> {
+        Expression::instruction(
+            Instruction::insertvalue(aggregate, value, index)
+        )
+    }
+
+    pub fn getelementptr<V,I>(pointer: V, indices: I) -> Self
+        where V: Into<Value>, I: IntoIterator<Item=Value> {
+        Expression::instruction(
+            Instruction::getelementptr(pointer, indices)
+        )
+    }
+
+    pub fn fadd<V>(lhs: V, rhs: V) -> Self
+        where V: Into<Value> {
+        Expression::instruction(
+            Instruction::fadd(lhs, rhs)
+        )
+    }
+
+    pub fn fsub<V>(lhs: V, rhs: V) -> Self
+        where V: Into<Value> {
+        Expression::instruction(
+            Instruction::fsub(lhs, rhs)
+        )
+    }
+
+    pub fn fmul<V>(lhs: V, rhs: V) -> Self
+        where V: Into<Value> {
+        Expression::instruction(
+            Instruction::fmul(lhs, rhs)
+        )
+    }
+
+    pub fn fdiv<V>(lhs: V, rhs: V) -> Self
+        where V: Into<Value> {
+        Expression::instruction(
+            Instruction::fdiv(lhs, rhs)
+        )
+    }
+
+    pub fn icmp(condition: Condition) -> Self {
+        Expression::instruction(
+            Instruction::icmp(condition)
+        )
+    }
+
+    pub fn fcmp(condition: Condition) -> Self {
+        Expression::instruction(
+            Instruction::fcmp(condition)
+        )
+    }
+
+    pub fn itof<V>(value: V, ty: types::Decimal) -> Self
+        where V: Into<Value> {
+        Expression::instruction(
+            Instruction::itof(value, ty)
+        )
+    }
+
+    pub fn ftoi<V>(value: V, ty: types::Integer) -> Self
+        where V: Into<Value> {
+        Expression::instruction(
+            Instruction::ftoi(value, ty)
+        )
+    }
+
     pub fn br<V>(condition: Condition, target: V) -> Self
         where V: Into<Value> {
         Expression::instruction(
@@ -347,8 +485,8 @@ macro_rules! impl_expression {
     }
 }
 
-/// Implements Into<Expression> for u8,i32,etc
-macro_rules! impl_into_value_for_integers {
+/// Implements Into<Expression> for u8,i32,f32,etc
+macro_rules! impl_into_value_for_numbers {
     (
         $( $ty:ident ),*
     ) => {
@@ -370,4 +508,4 @@ macro_rules! impl_into_value_for_integers {
     }
 }
 
-impl_into_value_for_integers!(u8,u16,u32,u64,i8,i16,i32,i64);
+impl_into_value_for_numbers!(u8,u16,u32,u64,i8,i16,i32,i64,f32,f64);
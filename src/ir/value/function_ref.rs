@@ -4,7 +4,7 @@ use util;
 // FIXME: remove 'name'
 // Also clean up 'ir::read::resolve' afterwards
 
-#[derive(Clone,Debug,PartialEq,Eq)]
+#[derive(Clone,Debug,PartialEq,Eq,Hash)]
 pub struct FunctionRef
 {
     pub func_id: util::Id,
@@ -1,7 +1,7 @@
 use {Global,Type};
 use util;
 
-#[derive(Clone,Debug,PartialEq,Eq)]
+#[derive(Clone,Debug,PartialEq,Eq,Hash)]
 pub struct GlobalRef
 {
     pub global_id: util::Id,
@@ -0,0 +1,48 @@
+use {value,types,Value,Expression,ExpressionTrait,Type};
+use std;
+
+#[derive(Clone,Debug,PartialEq,Eq,Hash)]
+pub struct Array
+{
+    element: Type,
+    elements: Vec<Value>,
+}
+
+impl Array
+{
+    /// Creates an array of values of type `element`.
+    pub fn new(element: Type, elements: Vec<Value>) -> Self {
+        Array {
+            element: element,
+            elements: elements,
+        }
+    }
+
+    /// Gets the type of the elements.
+    pub fn element_ty(&self) -> &Type { &self.element }
+
+    pub fn elements(&self) -> std::slice::Iter<Value> {
+        self.elements.iter()
+    }
+
+    pub fn ty(&self) -> Type {
+        types::Array::new(self.elements.len() as u64, self.element.clone()).into()
+    }
+}
+
+impl value::LiteralTrait for Array { }
+
+impl ExpressionTrait for Array { }
+
+impl Into<Expression> for Array
+{
+    fn into(self) -> Expression {
+        Expression::Literal(self.into())
+    }
+}
+
+impl Into<value::Literal> for Array {
+    fn into(self) -> value::Literal {
+        value::Literal::Array(self)
+    }
+}
@@ -3,7 +3,7 @@ use bit_vec::BitVec;
 
 
 /// A decimal value.
-#[derive(Clone,Debug,PartialEq,Eq)]
+#[derive(Clone,Debug,PartialEq,Eq,Hash)]
 pub struct Decimal
 {
     ty: types::Decimal,
@@ -19,7 +19,61 @@ impl Decimal
         }
     }
 
+    /// Creates a single precision value.
+    pub fn f32(value: f32) -> Self {
+        Decimal::from_bits(types::Decimal::f32(), value.to_bits() as u64)
+    }
+
+    /// Creates a double precision value.
+    pub fn f64(value: f64) -> Self {
+        Decimal::from_bits(types::Decimal::f64(), value.to_bits())
+    }
+
+    /// Creates a value from its IEEE 754 encoding.
+    ///
+    /// The most significant bit comes first.
+    fn from_bits(ty: types::Decimal, bits: u64) -> Self {
+        let width = ty.bit_width() as usize;
+        let bits = (0..width).map(|i| (bits >> (width - 1 - i)) & 1 == 1).collect();
+
+        Decimal::new(ty, bits)
+    }
+
     pub fn ty(&self) -> Type { self.ty.clone().into() }
+
+    /// Gets the value if it is single precision.
+    pub fn as_f32(&self) -> Option<f32> {
+        if self.ty == types::Decimal::f32() {
+            Some(f32::from_bits(self.to_bits() as u32))
+        } else {
+            None
+        }
+    }
+
+    /// Gets the value if it is double precision.
+    pub fn as_f64(&self) -> Option<f64> {
+        if self.ty == types::Decimal::f64() {
+            Some(f64::from_bits(self.to_bits()))
+        } else {
+            None
+        }
+    }
+
+    /// Gets the value as a double, converting from single
+    /// precision if necessary.
+    pub fn to_f64(&self) -> Option<f64> {
+        self.as_f64().or_else(|| self.as_f32().map(|f| f as f64))
+    }
+
+    fn to_bits(&self) -> u64 {
+        self.bits.iter().fold(0, |bits, bit| (bits << 1) | bit as u64)
+    }
+
+    /// Gets the type of the decimal.
+    pub fn decimal_ty(&self) -> types::Decimal { self.ty }
+
+    /// Gets the bits that make up the value.
+    pub fn bits(&self) -> &BitVec { &self.bits }
 }
 
 impl value::LiteralTrait for Decimal { }
@@ -2,11 +2,12 @@ use {value,types,Expression,ExpressionTrait,Type};
 use util;
 use std;
 
-use num::BigInt;
+use num::{BigInt,Zero,One,Signed};
 use num::bigint::ToBigInt;
+use num::traits::ToPrimitive;
 
 /// A constant integral value.
-#[derive(Clone,Debug,PartialEq,Eq)]
+#[derive(Clone,Debug,PartialEq,Eq,Hash)]
 pub struct Integer
 {
     ty: types::Integer,
@@ -38,6 +39,29 @@ impl Integer
         }
     }
 
+    /// Creates a new constant integer from a `BigInt`, wrapping the
+    /// value around if it cannot fit into `ty`.
+    ///
+    /// This matches two's complement overflow behaviour.
+    pub fn wrapping(ty: types::Integer, val: BigInt) -> Self {
+        let modulus = BigInt::one() << (ty.width() as usize);
+
+        let mut value = val % &modulus;
+
+        if value.is_negative() {
+            value = value + &modulus;
+        }
+
+        if ty.is_signed() && value >= (&modulus >> 1usize) {
+            value = value - &modulus;
+        }
+
+        Integer {
+            ty: ty,
+            value: value,
+        }
+    }
+
     /// Creates a constant integer from an array of bytes representing an integer.
     /// Returns `None` if the integer cannot fit into `ty`.
     pub fn from_bytes_le(ty: types::Integer, sign: util::Sign, bytes: &[u8]) -> Option<Self> {
@@ -58,16 +82,64 @@ impl Integer
     }
 
     /// Checks whether the value can fit in a given integer type.
-    /// Note that if `ty` is unsigned, the function will check
-    /// whether the value can fit in `ty` if it were unsigned.
     pub fn fits_in_type(&self, ty: types::Integer) -> bool {
-        // if we need a sign bit, we have to take it
-        // into account.
-        let sign_bit_size = if ty.is_signed() { 1 } else { 0 };
+        let (min, max) = helpers::bounds(ty);
+
+        self.value >= min && self.value <= max
+    }
 
-        let magnitude_max_size: u64 = (ty.width() as u64) - sign_bit_size;
+    /// Checks if the value is zero.
+    pub fn is_zero(&self) -> bool {
+        self.value.is_zero()
+    }
+
+    /// Divides two integers, returning `None` if `rhs` is zero.
+    pub fn checked_div(self, rhs: Integer) -> Option<Integer> {
+        if rhs.is_zero() {
+            None
+        } else {
+            // Signed division can overflow, e.g. `-128 / -1` for `i8`.
+            Some(Integer::wrapping(self.ty, self.value / rhs.value))
+        }
+    }
+
+    /// Takes the remainder of a division, returning `None` if `rhs` is zero.
+    pub fn checked_rem(self, rhs: Integer) -> Option<Integer> {
+        if rhs.is_zero() {
+            None
+        } else {
+            Some(Integer::wrapping(self.ty, self.value % rhs.value))
+        }
+    }
 
-        self.count_magnitude_bits() <= magnitude_max_size
+    /// Gets the upper half of the double-width product of two integers.
+    pub fn mul_high(self, rhs: Integer) -> Integer {
+        let product = self.value * rhs.value;
+        Integer::wrapping(self.ty, product >> (self.ty.width() as usize))
+    }
+
+    /// Shifts an integer left, returning `None` if the shift amount
+    /// is negative or not smaller than the bit width.
+    pub fn checked_shl(self, rhs: Integer) -> Option<Integer> {
+        if self.is_valid_shift_amount(&rhs) {
+            Some(self << rhs)
+        } else {
+            None
+        }
+    }
+
+    /// Shifts an integer right, returning `None` if the shift amount
+    /// is negative or not smaller than the bit width.
+    pub fn checked_shr(self, rhs: Integer) -> Option<Integer> {
+        if self.is_valid_shift_amount(&rhs) {
+            Some(self >> rhs)
+        } else {
+            None
+        }
+    }
+
+    fn is_valid_shift_amount(&self, amount: &Integer) -> bool {
+        amount.value.to_u16().map_or(false, |a| a < self.ty.width())
     }
 
     pub fn integer_ty(&self) -> types::Integer { self.ty.clone() }
@@ -79,10 +151,8 @@ impl std::ops::Add for Integer
 {
     type Output = Integer;
 
-    fn add(mut self, rhs: Integer) -> Integer {
-        let val = self.value + rhs.value;
-        self.value = val;
-        self
+    fn add(self, rhs: Integer) -> Integer {
+        Integer::wrapping(self.ty, self.value + rhs.value)
     }
 }
 
@@ -90,10 +160,8 @@ impl std::ops::Sub for Integer
 {
     type Output = Integer;
 
-    fn sub(mut self, rhs: Integer) -> Integer {
-        let val = self.value - rhs.value;
-        self.value = val;
-        self
+    fn sub(self, rhs: Integer) -> Integer {
+        Integer::wrapping(self.ty, self.value - rhs.value)
     }
 }
 
@@ -101,22 +169,17 @@ impl std::ops::Mul for Integer
 {
     type Output = Integer;
 
-    fn mul(mut self, rhs: Integer) -> Integer {
-        let val = self.value * rhs.value;
-        self.value = val;
-        self
+    fn mul(self, rhs: Integer) -> Integer {
+        Integer::wrapping(self.ty, self.value * rhs.value)
     }
 }
 
-impl std::ops::Div for Integer
+impl std::ops::BitAnd for Integer
 {
     type Output = Integer;
 
-    fn div(mut self, rhs: Integer) -> Integer {
-        // FIXME: this will break with division by zero
-        let val = self.value / rhs.value;
-        self.value = val;
-        self
+    fn bitand(self, rhs: Integer) -> Integer {
+        Integer::wrapping(self.ty, self.value & rhs.value)
     }
 }
 
@@ -124,12 +187,9 @@ impl std::ops::Shl<Integer> for Integer
 {
     type Output = Integer;
 
-    fn shl(mut self, rhs: Integer) -> Integer {
-        use num::traits::ToPrimitive;
-
+    fn shl(self, rhs: Integer) -> Integer {
         let val = self.value << rhs.value.to_usize().unwrap();
-        self.value = val;
-        self
+        Integer::wrapping(self.ty, val)
     }
 }
 
@@ -138,8 +198,6 @@ impl std::ops::Shr<Integer> for Integer
     type Output = Integer;
 
     fn shr(mut self, rhs: Integer) -> Integer {
-        use num::traits::ToPrimitive;
-
         let val = self.value >> rhs.value.to_usize().unwrap();
         self.value = val;
         self
@@ -165,8 +223,22 @@ impl Into<value::Literal> for Integer {
 
 mod helpers
 {
+    use types;
     use util;
     use num;
+    use num::{BigInt,One,Zero};
+
+    /// Gets the smallest and largest values that fit into a type.
+    pub fn bounds(ty: types::Integer) -> (BigInt, BigInt) {
+        let width = ty.width() as usize;
+
+        if ty.is_signed() {
+            let half = BigInt::one() << (width - 1);
+            (-half.clone(), half - BigInt::one())
+        } else {
+            (BigInt::zero(), (BigInt::one() << width) - BigInt::one())
+        }
+    }
 
     pub fn bigint_sign(sign: util::Sign) -> num::bigint::Sign {
         match sign {
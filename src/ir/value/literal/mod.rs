@@ -2,6 +2,8 @@
 pub use self::integer::Integer;
 pub use self::decimal::Decimal;
 pub use self::strukt::Struct;
+pub use self::array::Array;
+pub use self::vector::Vector;
 
 use {types,Value,Expression,ExpressionTrait,Type};
 use bit_vec::BitVec;
@@ -14,17 +16,23 @@ pub mod integer;
 pub mod decimal;
 /// Structure-constant implementation.
 pub mod strukt;
+/// Array-constant implementation.
+pub mod array;
+/// Vector-constant implementation.
+pub mod vector;
 
 pub trait LiteralTrait : Into<Literal> + ExpressionTrait
 {
 }
 
-#[derive(Clone,Debug,PartialEq,Eq)]
+#[derive(Clone,Debug,PartialEq,Eq,Hash)]
 pub enum Literal
 {
     Integer(Integer),
     Decimal(Decimal),
     Struct(Struct),
+    Array(Array),
+    Vector(Vector),
 }
 
 impl Literal
@@ -46,6 +54,25 @@ impl Literal
         Literal::strukt(Vec::new())
     }
 
+    pub fn array(element: Type, elements: Vec<Value>) -> Literal {
+        Array::new(element, elements).into()
+    }
+
+    pub fn vector(element: Type, elements: Vec<Value>) -> Literal {
+        Vector::new(element, elements).into()
+    }
+
+    /// Gets the values inside an aggregate, or `None` if the
+    /// literal is not an aggregate.
+    pub fn elements(&self) -> Option<Vec<&Value>> {
+        match *self {
+            Literal::Struct(ref val) => Some(val.fields().collect()),
+            Literal::Array(ref val) => Some(val.elements().collect()),
+            Literal::Vector(ref val) => Some(val.elements().collect()),
+            _ => None,
+        }
+    }
+
     pub fn as_integer(&self) -> Option<&Integer> {
         if let Literal::Integer(ref i) = *self {
             Some(i)
@@ -75,6 +102,8 @@ impl Literal
             Literal::Integer(ref val) => val.ty(),
             Literal::Decimal(ref val) => val.ty(),
             Literal::Struct(ref val) => val.ty(),
+            Literal::Array(ref val) => val.ty(),
+            Literal::Vector(ref val) => val.ty(),
         }
     }
 }
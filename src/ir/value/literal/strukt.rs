@@ -1,7 +1,7 @@
 use {value,types,Value,Expression,ExpressionTrait,Type};
 use std;
 
-#[derive(Clone,Debug,PartialEq,Eq)]
+#[derive(Clone,Debug,PartialEq,Eq,Hash)]
 pub struct Struct
 {
     fields: Vec<Value>,
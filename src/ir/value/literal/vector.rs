@@ -0,0 +1,48 @@
+use {value,types,Value,Expression,ExpressionTrait,Type};
+use std;
+
+#[derive(Clone,Debug,PartialEq,Eq,Hash)]
+pub struct Vector
+{
+    element: Type,
+    elements: Vec<Value>,
+}
+
+impl Vector
+{
+    /// Creates a vector of values of type `element`.
+    pub fn new(element: Type, elements: Vec<Value>) -> Self {
+        Vector {
+            element: element,
+            elements: elements,
+        }
+    }
+
+    /// Gets the type of the elements.
+    pub fn element_ty(&self) -> &Type { &self.element }
+
+    pub fn elements(&self) -> std::slice::Iter<Value> {
+        self.elements.iter()
+    }
+
+    pub fn ty(&self) -> Type {
+        types::Vector::new(self.elements.len() as u64, self.element.clone()).into()
+    }
+}
+
+impl value::LiteralTrait for Vector { }
+
+impl ExpressionTrait for Vector { }
+
+impl Into<Expression> for Vector
+{
+    fn into(self) -> Expression {
+        Expression::Literal(self.into())
+    }
+}
+
+impl Into<value::Literal> for Vector {
+    fn into(self) -> value::Literal {
+        value::Literal::Vector(self)
+    }
+}
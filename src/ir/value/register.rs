@@ -2,7 +2,7 @@ use {Value,Type,Name};
 use util;
 
 /// A register.
-#[derive(Clone,Debug,PartialEq,Eq)]
+#[derive(Clone,Debug,PartialEq,Eq,Hash)]
 pub struct Register
 {
     id: util::Id,
@@ -1,7 +1,7 @@
 use {Type,Register};
 use util;
 
-#[derive(Clone,Debug,PartialEq,Eq)]
+#[derive(Clone,Debug,PartialEq,Eq,Hash)]
 pub struct RegisterRef
 {
     pub register_id: util::Id,
@@ -23,7 +23,7 @@ impl RegisterRef
 
         RegisterRef {
             register_id: register.get_id(),
-            ty: register.ty().clone(),
+            ty: register.subvalue().node.ty(),
         }
     }
 
@@ -1,6 +1,6 @@
 use Type;
 
-#[derive(Clone,Debug,PartialEq,Eq)]
+#[derive(Clone,Debug,PartialEq,Eq,Hash)]
 pub struct String
 {
     text: ::std::string::String,
@@ -8,6 +8,8 @@ impl Value
     pub fn new(expression: Expression) -> Self {
         Value {
             node: expression,
+            span: None,
+            comments: Vec::new(),
         }
     }
 }
@@ -24,6 +26,8 @@ impl Into<Value> for Expression
     fn into(self) -> Value {
         Value {
             node: self,
+            span: None,
+            comments: Vec::new(),
         }
     }
 }
@@ -1,7 +1,8 @@
-use {Module,Function,Block,Value,Expression};
-use std;
+use {Module,Function,Block,Value,Expression,Instruction};
+use util::{Diagnostic,Identifiable};
 
-// TODO: check that each Register has only one user
+use std::collections::{HashMap,HashSet};
+use std;
 
 macro_rules! condition {
     ($cond:expr, $msg:expr) => {
@@ -12,110 +13,372 @@ macro_rules! condition {
 }
 
 /// The result of verification.
-pub type Result = std::result::Result<(),String>;
+pub type Result = std::result::Result<(),Diagnostic>;
 
 /// Verifies the well-formedness of a module.
+///
+/// Only the first problem is returned.
 pub fn verify(module: &Module) -> Result {
+    self::first(self::diagnostics(module))
+}
 
-    for func in module.functions() {
-        try!(self::verify_function(module, func));
-    }
-
-    Ok(())
+/// Finds every problem in a module.
+pub fn diagnostics(module: &Module) -> Vec<Diagnostic> {
+    module.functions().flat_map(|f| self::function_diagnostics(module, f)).collect()
 }
 
 /// Verifies that a function is well-formed.
+///
+/// Only the first problem is returned.
 pub fn verify_function(module: &Module,
                        func: &Function) -> Result {
+    self::first(self::function_diagnostics(module, func))
+}
 
-    try!(util::verify_ident(func.name()));
+/// Finds every problem in a function.
+///
+/// Each diagnostic has a note saying where in the function it is.
+pub fn function_diagnostics(module: &Module,
+                            func: &Function) -> Vec<Diagnostic> {
+    let mut verifier = Verifier::new(module, func);
+    verifier.function();
+    verifier.diagnostics
+}
 
-    condition!(func.blocks().next().is_some(),
-               "every function must have at least one basic block");
+/// Verifies that a block is well-formed on its own.
+pub fn verify_block(block: &Block) -> Result {
+    try!(util::verify_ident(block.name()));
 
-    for block in func.blocks() {
-        try!(self::verify_block(module, block));
+    let last = match block.values().last() {
+        Some(value) => value,
+        None => return Err("there must be at least one instruction in a basic block".into()),
+    };
 
-        condition!(func.blocks().filter(|a| a.name() == block.name()).count() == 1,
-                   "basic blocks must have unique names for each function");
-    }
+    condition!(last.node.is_terminator(),
+               "every basic block must end with a terminating instruction");
+
+    condition!(block.values().rev().skip(1).all(|v| !v.node.is_terminator()),
+               "terminating instructions must only appear at the end of a basic block");
 
     Ok(())
 }
 
-/// Verifies that a block is well-formed.
-pub fn verify_block(_module: &Module,
-                    block: &Block) -> Result {
-    try!(util::verify_ident(block.name()));
+/// Gets the values inside an expression, including the value
+/// held by a register.
+fn subvalues(expr: &Expression) -> Vec<&Value> {
+    match *expr {
+        Expression::Register(ref reg) => vec![reg.subvalue()],
+        ref expr => expr.subvalues(),
+    }
+}
 
-    if let Some(value) = block.values().last() {
-        condition!(value.node.is_terminator(),
-                   "every basic block must end with a terminating instruction");
-    } else { // block is empty
-        return Err("there must be at least one instruction in a basic block".into());
+fn first(diagnostics: Vec<Diagnostic>) -> Result {
+    match diagnostics.into_iter().next() {
+        Some(d) => Err(d),
+        None => Ok(()),
     }
+}
 
-    Ok(())
+/// Where in a function a problem is.
+#[derive(Copy,Clone)]
+enum Place<'a>
+{
+    Function,
+    Block(&'a Block),
+    /// A top-level value, along with its position in the block.
+    Value(&'a Block, usize, &'a Value),
 }
 
-/// Verifies that a value is well-formed.
-pub fn verify_value(module: &Module,
-                    value: &Value) -> Result {
-    verify_expression(module, &value.node)
+/// Walks over a function, collecting problems.
+struct Verifier<'a>
+{
+    module: &'a Module,
+    func: &'a Function,
+
+    /// Where each register is defined, as a block and value index.
+    definitions: HashMap<::util::Id, (usize, usize)>,
+    /// The dominators of each block, or `None` if it is unreachable.
+    dominators: Vec<Option<HashSet<usize>>>,
+
+    diagnostics: Vec<Diagnostic>,
 }
 
-/// Verifies that an expression is well-formed.
-pub fn verify_expression(module: &Module,
-                         expr: &Expression) -> Result {
-    match *expr {
-        Expression::Instruction(ref val) => values::instruction(module, val),
-        Expression::UnresolvedRef(id) => {
-            Err(format!("item with id {} was not resolved", id))
-        },
-        _ => Ok(()),
+impl<'a> Verifier<'a>
+{
+    fn new(module: &'a Module, func: &'a Function) -> Self {
+        Verifier {
+            module: module,
+            func: func,
+            definitions: HashMap::new(),
+            dominators: dominance::dominators(func),
+            diagnostics: Vec::new(),
+        }
+    }
+
+    fn function(&mut self) {
+        let func = self.func;
+
+        self.report(util::verify_ident(func.name()), Place::Function);
+
+        // Declarations have no body, so something else must define them.
+        if func.blocks().next().is_none() && !func.is_exported() {
+            self.report(Err("internal functions must have at least one basic block".into()), Place::Function);
+        }
+
+        if func.interrupt().is_some() {
+            if func.signature().parameters().next().is_some() || func.signature().has_returns() {
+                self.report(Err("interrupt handlers cannot take parameters or return values".into()),
+                            Place::Function);
+            }
+        }
+
+        for (block_idx, block) in func.blocks().enumerate() {
+            self.report(self::verify_block(block), Place::Block(block));
+
+            if func.blocks().filter(|a| a.name() == block.name()).count() != 1 {
+                self.report(Err("basic blocks must have unique names for each function".into()),
+                            Place::Block(block));
+            }
+
+            for (value_idx, value) in block.values().enumerate() {
+                self.define(value, block_idx, Place::Value(block, value_idx, value));
+            }
+        }
+
+        for (block_idx, block) in func.blocks().enumerate() {
+            for (value_idx, value) in block.values().enumerate() {
+                let place = Place::Value(block, value_idx, value);
+                self.value(value, (block_idx, value_idx), place);
+            }
+        }
+    }
+
+    /// Records the registers defined in a value.
+    fn define(&mut self, value: &Value, block_idx: usize, place: Place) {
+        if let Expression::Register(ref reg) = value.node {
+            let value_idx = match place {
+                Place::Value(_, idx, _) => idx,
+                _ => unreachable!(),
+            };
+
+            if self.definitions.insert(reg.get_id(), (block_idx, value_idx)).is_some() {
+                let msg = format!("register %{} is defined more than once", reg.name());
+                self.report(Err(Diagnostic::error(msg).or_span(value.span)), place);
+            }
+        }
+
+        for subvalue in self::subvalues(&value.node) {
+            self.define(subvalue, block_idx, place);
+        }
+    }
+
+    /// Verifies a value and everything inside it.
+    ///
+    /// The position is the block and value index the value is used at.
+    fn value(&mut self, value: &Value, at: (usize, usize), place: Place) {
+        let result = self.expression(&value.node, at);
+        self.report(result.map_err(|d| d.or_span(value.span)), place);
+
+        match value.node {
+            // Incoming values are used at the end of the block they come from.
+            Expression::Instruction(Instruction::Phi(ref phi)) => {
+                for &(ref incoming, ref block) in phi.incoming() {
+                    let from = match block.node {
+                        Expression::BlockRef(ref r) => self.block_index(r.block_id()).map(|b| (b, usize::max_value())),
+                        _ => None,
+                    };
+
+                    self.value(incoming, from.unwrap_or(at), place);
+                    self.value(block, at, place);
+                }
+            },
+            ref node => {
+                for subvalue in self::subvalues(node) {
+                    self.value(subvalue, at, place);
+                }
+            },
+        }
+    }
+
+    /// Verifies an expression, ignoring its subvalues.
+    fn expression(&self, expr: &Expression, at: (usize, usize)) -> Result {
+        match *expr {
+            Expression::Instruction(ref i) => values::instruction(self.module, self.func, i),
+            Expression::Literal(ref l) => values::literal(l),
+            Expression::UnresolvedRef(id) => {
+                Err(format!("item with id {} was not resolved", id).into())
+            },
+            Expression::BlockRef(ref r) => {
+                condition!(self.func.find_block(r.block_id()).is_some(),
+                           "basic blocks can only be referred to from within their function");
+                Ok(())
+            },
+            Expression::ArgumentRef(ref r) => {
+                condition!(self.func.signature().find_parameter_by_id(r.parameter_id()).is_some(),
+                           "arguments can only be referred to from within their function");
+                Ok(())
+            },
+            Expression::RegisterRef(ref r) => self.register_use(r.register_id(), at),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks that a register is defined before a use of it.
+    fn register_use(&self, id: ::util::Id, (block, index): (usize, usize)) -> Result {
+        let (def_block, def_index) = match self.definitions.get(&id) {
+            Some(&def) => def,
+            None => return Err("registers can only be referred to from within their function".into()),
+        };
+
+        let dominators = match self.dominators[block] {
+            Some(ref dominators) => dominators,
+            // Nothing can go wrong in code that never runs.
+            None => return Ok(()),
+        };
+
+        let dominated = if def_block == block {
+            def_index <= index
+        } else {
+            dominators.contains(&def_block)
+        };
+
+        condition!(dominated, "registers must be defined before every use");
+        Ok(())
+    }
+
+    fn block_index(&self, id: ::util::Id) -> Option<usize> {
+        self.func.blocks().position(|b| b.get_id() == id)
+    }
+
+    fn report(&mut self, result: Result, place: Place) {
+        let diagnostic = match result {
+            Ok(()) => return,
+            Err(d) => d,
+        };
+
+        let name = self.func.name();
+        let path = match place {
+            Place::Function => format!("in @{}", name),
+            Place::Block(block) => format!("in block '{}' of @{}", block.name(), name),
+            Place::Value(block, idx, _) => {
+                format!("in value {} of block '{}' of @{}", idx + 1, block.name(), name)
+            },
+        };
+
+        let span = match place {
+            Place::Value(_, _, value) => value.span,
+            _ => None,
+        };
+
+        self.diagnostics.push(diagnostic.or_span(span).with_note(path));
     }
 }
 
 mod values
 {
-    use {Module,Instruction};
+    use {Module,Function,Instruction,value};
     use super::Result;
 
+    pub fn literal(literal: &value::Literal) -> Result {
+        let elements = match literal.elements() {
+            Some(elements) => elements,
+            None => return Ok(()),
+        };
+
+        let element_ty = match *literal {
+            value::Literal::Array(ref a) => Some(a.element_ty()),
+            value::Literal::Vector(ref v) => Some(v.element_ty()),
+            _ => None,
+        };
+
+        for element in elements {
+            condition!(element.node.is_literal(),
+                       "aggregate literals can only contain literals");
+
+            if let Some(ty) = element_ty {
+                condition!(element.node.ty() == *ty,
+                           format!("elements of {} must be {}, not {}",
+                                   literal.ty(), ty, element.node.ty()));
+            }
+
+            try!(self::literal(element.node.as_literal().unwrap()));
+        }
+
+        if let value::Literal::Vector(ref v) = *literal {
+            condition!(!v.element_ty().is_aggregate(),
+                       "vectors can only hold scalars");
+        }
+
+        Ok(())
+    }
+
     pub fn instruction(module: &Module,
+                       func: &Function,
                        inst: &Instruction) -> Result
     {
         match *inst {
             Instruction::Call(ref i) => instruction::call(module, i),
             Instruction::Break(ref i) => instruction::br(module, i),
-            Instruction::Return(ref i) => instruction::ret(module, i),
+            Instruction::Return(ref i) => instruction::ret(func, i),
             Instruction::Add(ref i) => instruction::binary_arith(module,i),
             Instruction::Sub(ref i) => instruction::binary_arith(module,i),
             Instruction::Mul(ref i) => instruction::binary_arith(module,i),
             Instruction::Div(ref i) => instruction::binary_arith(module,i),
+            Instruction::Rem(ref i) => instruction::binary_arith(module,i),
+            Instruction::MulHigh(ref i) => instruction::binary_arith(module,i),
+            Instruction::And(ref i) => instruction::binary_arith(module,i),
             Instruction::Shl(ref i) => instruction::binary_arith(module,i),
             Instruction::Shr(ref i) => instruction::binary_arith(module,i),
             Instruction::Copy(ref i) => instruction::copy(module, i),
+            Instruction::Phi(ref i) => instruction::phi(module, i),
+            Instruction::Load(ref i) => instruction::load(module, i),
+            Instruction::Store(ref i) => instruction::store(module, i),
+            Instruction::ExtractValue(ref i) => instruction::extractvalue(module, i),
+            Instruction::InsertValue(ref i) => instruction::insertvalue(module, i),
+            Instruction::GetElementPtr(ref i) => instruction::getelementptr(module, i),
+            Instruction::FAdd(ref i) => instruction::binary_float(module,i),
+            Instruction::FSub(ref i) => instruction::binary_float(module,i),
+            Instruction::FMul(ref i) => instruction::binary_float(module,i),
+            Instruction::FDiv(ref i) => instruction::binary_float(module,i),
+            Instruction::ICmp(ref i) => instruction::compare(module, "icmp", i.condition(), false),
+            Instruction::FCmp(ref i) => instruction::compare(module, "fcmp", i.condition(), true),
+            Instruction::IntToFloat(ref i) => instruction::itof(module, i),
+            Instruction::FloatToInt(ref i) => instruction::ftoi(module, i),
         }
     }
 
     pub mod instruction
     {
-        use {Module,instruction};
+        use {Module,Function,Global,Expression,Type,Condition,instruction};
+        use instruction::Binary;
         use super::super::Result;
 
-        // TODO: check that arguments are the same in type and number
         pub fn call(_module: &Module,
                     inst: &instruction::Call) -> Result {
 
-            condition!(inst.target().node.is_function_ref(),
-                       "call instructions must refer to functions");
+            let callee = match inst.target().node {
+                Expression::FunctionRef(ref f) => f,
+                _ => return Err("call instructions must refer to functions".into()),
+            };
+
+            let params: Vec<_> = callee.signature().parameters().collect();
+            let args: Vec<_> = inst.arguments().collect();
+
+            condition!(args.len() == params.len(),
+                       format!("@{} takes {} arguments but {} were given",
+                               callee.name(), params.len(), args.len()));
+
+            for (i, (arg, param)) in args.iter().zip(params.iter()).enumerate() {
+                let ty = arg.node.ty();
 
+                condition!(ty == *param.ty(),
+                           format!("argument {} to @{} must be {}, not {}",
+                                   i + 1, callee.name(), param.ty(), ty));
+            }
 
             Ok(())
         }
 
-        // TODO: check that the referenced block is in the same function
-        // as the instruction.
         pub fn br(_module: &Module,
                   inst: &instruction::Break) -> Result {
 
@@ -125,10 +388,16 @@ mod values
             Ok(())
         }
 
-        // TODO: check that the type is consistent with the function.
-        pub fn ret(_module: &Module,
-                   _inst: &instruction::Return) -> Result {
+        pub fn ret(func: &Function,
+                   inst: &instruction::Return) -> Result {
+            condition!(!func.is_noreturn(),
+                       format!("@{} is marked noreturn, so it cannot return", func.name()));
 
+            let expected = func.signature().return_type();
+            let ty = inst.subvalue().map_or(Type::void(), |v| v.node.ty());
+
+            condition!(ty == expected,
+                       format!("return value must be {}, not {}", expected, ty));
             Ok(())
         }
 
@@ -140,14 +409,262 @@ mod values
 
             condition!(lhs.node.ty() == rhs.node.ty(),
                        format!("binary arithmetic operations must have operands of the same type"));
+            condition!(!lhs.node.ty().is_decimal(),
+                       format!("{} operands need the floating point instructions", lhs.node.ty()));
+            Ok(())
+        }
+
+        pub fn binary_float<I>(_module: &Module,
+                               inst: &I) -> Result
+            where I: instruction::Binary {
+
+            let (lhs,rhs) = inst.operands();
+
+            condition!(lhs.node.ty() == rhs.node.ty(),
+                       format!("binary arithmetic operations must have operands of the same type"));
+            condition!(lhs.node.ty().is_decimal(),
+                       format!("floating point instructions cannot operate on {}", lhs.node.ty()));
+            Ok(())
+        }
+
+        pub fn compare(_module: &Module,
+                       mnemonic: &str,
+                       cond: &Condition,
+                       float: bool) -> Result {
+            let values = cond.values();
+            let (lhs, rhs) = match (values.get(0), values.get(1)) {
+                (Some(lhs), Some(rhs)) => (lhs.node.ty(), rhs.node.ty()),
+                _ => return Ok(()),
+            };
+
+            condition!(lhs == rhs,
+                       format!("{} must compare values of the same type", mnemonic));
+            condition!(lhs.is_decimal() == float,
+                       format!("{} cannot compare {}", mnemonic, lhs));
+            Ok(())
+        }
+
+        pub fn itof(_module: &Module,
+                    inst: &instruction::IntToFloat) -> Result {
+            let ty = inst.value().node.ty();
+
+            condition!(ty.is_integer(),
+                       format!("itof converts from integers, not {}", ty));
+            Ok(())
+        }
+
+        pub fn ftoi(_module: &Module,
+                    inst: &instruction::FloatToInt) -> Result {
+            let ty = inst.value().node.ty();
+
+            condition!(ty.is_decimal(),
+                       format!("ftoi converts from floating point values, not {}", ty));
             Ok(())
         }
 
         pub fn copy(_module: &Module,
-                    _inst: &instruction::Copy) -> Result {
-            // TODO: make sure the destination is a register
+                    inst: &instruction::Copy) -> Result {
+            let (dst, _) = inst.operands();
+
+            condition!(dst.node.is_register_ref(),
+                       "copy instructions must write to registers");
+            Ok(())
+        }
+
+        pub fn load(_module: &Module,
+                    inst: &instruction::Load) -> Result {
+            condition!(inst.pointer().node.ty().pointee().is_some(),
+                       "load instructions must read from pointers");
+            Ok(())
+        }
+
+        pub fn store(module: &Module,
+                     inst: &instruction::Store) -> Result {
+            let ptr_ty = inst.pointer().node.ty();
+
+            condition!(ptr_ty.pointee() == Some(&inst.value().node.ty()),
+                       "store instructions must write a value of the type pointed to");
+
+            if let Expression::GlobalRef(ref r) = inst.pointer().node {
+                let global: Option<&Global> = module.find_global(r.global_id()).into();
+
+                condition!(!global.map_or(false, |g| g.is_constant()),
+                           "constant globals cannot be stored to");
+            }
+
+            Ok(())
+        }
+
+        pub fn extractvalue(_module: &Module,
+                            inst: &instruction::ExtractValue) -> Result {
+            let ty = inst.aggregate().node.ty();
+
+            condition!(ty.is_aggregate(),
+                       format!("extractvalue can only read from aggregates, not {}", ty));
+            condition!(ty.element(inst.index()).is_some(),
+                       format!("index {} is out of range for {}", inst.index(), ty));
+            Ok(())
+        }
+
+        pub fn insertvalue(_module: &Module,
+                           inst: &instruction::InsertValue) -> Result {
+            let ty = inst.aggregate().node.ty();
+
+            condition!(ty.is_aggregate(),
+                       format!("insertvalue can only write to aggregates, not {}", ty));
+
+            let element = match ty.element(inst.index()) {
+                Some(element) => element.clone(),
+                None => return Err(format!("index {} is out of range for {}", inst.index(), ty).into()),
+            };
+
+            let value_ty = inst.value().node.ty();
+            condition!(value_ty == element,
+                       format!("element {} of {} is {}, not {}", inst.index(), ty, element, value_ty));
             Ok(())
         }
+
+        pub fn getelementptr(_module: &Module,
+                             inst: &instruction::GetElementPtr) -> Result {
+            condition!(inst.pointer().node.ty().pointee().is_some(),
+                       "getelementptr can only compute addresses from pointers");
+            condition!(inst.indices().count() > 0,
+                       "getelementptr needs at least one index");
+
+            for index in inst.indices() {
+                if let Type::Integer(..) = index.node.ty() { continue; }
+                return Err("getelementptr indices must be integers".into());
+            }
+
+            condition!(inst.element_ty().is_some(),
+                       "getelementptr indices must select an element, and indices into structs must be integer literals");
+            Ok(())
+        }
+
+        // TODO: check that the incoming blocks are the predecessors
+        // of the block containing the phi.
+        pub fn phi(_module: &Module,
+                   inst: &instruction::Phi) -> Result {
+            for &(ref value, ref block) in inst.incoming() {
+                condition!(block.node.is_block_ref(),
+                           "phi instructions must refer to basic blocks");
+                condition!(value.node.ty() == inst.ty(),
+                           "phi incoming values must have the type of the phi");
+            }
+
+            Ok(())
+        }
+    }
+}
+
+/// Dominance information.
+///
+/// The verifier cannot use the analyses in the pass crate,
+/// so it has its own simple version.
+mod dominance
+{
+    use {Function,Block,Expression,Instruction,Condition};
+    use util::Identifiable;
+
+    use std::collections::HashSet;
+
+    /// Finds the dominators of each block, by index.
+    ///
+    /// Unreachable blocks have no dominators.
+    pub fn dominators(func: &Function) -> Vec<Option<HashSet<usize>>> {
+        let blocks: Vec<_> = func.blocks().collect();
+        let successors: Vec<_> = (0..blocks.len()).map(|i| self::successors(&blocks, i)).collect();
+
+        let mut predecessors = vec![Vec::new(); blocks.len()];
+        for (block, succs) in successors.iter().enumerate() {
+            for &succ in succs.iter() {
+                predecessors[succ].push(block);
+            }
+        }
+
+        let reachable = self::reachable(&successors);
+        let everything: HashSet<usize> = reachable.iter().cloned().collect();
+
+        let mut dominators: Vec<Option<HashSet<usize>>> = (0..blocks.len()).map(|i| {
+            if i == 0 {
+                Some(Some(0).into_iter().collect())
+            } else if reachable.contains(&i) {
+                Some(everything.clone())
+            } else {
+                None
+            }
+        }).collect();
+
+        loop {
+            let mut changed = false;
+
+            for block in 1..blocks.len() {
+                if dominators[block].is_none() {
+                    continue;
+                }
+
+                let mut new: Option<HashSet<usize>> = None;
+
+                for &pred in predecessors[block].iter() {
+                    if let Some(ref doms) = dominators[pred] {
+                        new = Some(match new {
+                            Some(set) => set.intersection(doms).cloned().collect(),
+                            None => doms.clone(),
+                        });
+                    }
+                }
+
+                let mut new = new.unwrap_or_else(HashSet::new);
+                new.insert(block);
+
+                if dominators[block].as_ref() != Some(&new) {
+                    dominators[block] = Some(new);
+                    changed = true;
+                }
+            }
+
+            if !changed {
+                return dominators;
+            }
+        }
+    }
+
+    fn reachable(successors: &[Vec<usize>]) -> HashSet<usize> {
+        let mut visited = HashSet::new();
+        let mut worklist: Vec<_> = if successors.is_empty() { Vec::new() } else { vec![0] };
+
+        while let Some(block) = worklist.pop() {
+            if visited.insert(block) {
+                worklist.extend(successors[block].iter().cloned());
+            }
+        }
+
+        visited
+    }
+
+    /// Gets the blocks that control can flow to from a block.
+    ///
+    /// Control falls through to the next block unless the block
+    /// ends in a return or an unconditional branch.
+    fn successors(blocks: &[&Block], index: usize) -> Vec<usize> {
+        let next = if index + 1 < blocks.len() { Some(index + 1) } else { None };
+
+        let target = |br: &::instruction::Break| match br.target().node {
+            Expression::BlockRef(ref r) => blocks.iter().position(|b| b.get_id() == r.block_id()),
+            _ => None,
+        };
+
+        match blocks[index].values().last().map(|v| &v.node) {
+            Some(&Expression::Instruction(Instruction::Return(..))) => Vec::new(),
+            Some(&Expression::Instruction(Instruction::Break(ref br))) => {
+                match *br.condition() {
+                    Condition::True => target(br).into_iter().collect(),
+                    Condition::False => next.into_iter().collect(),
+                    _ => target(br).into_iter().chain(next).collect(),
+                }
+            },
+            _ => next.into_iter().collect(),
+        }
     }
 }
 
@@ -178,3 +695,88 @@ mod util
         }
     }
 }
+
+#[cfg(test)]
+mod test
+{
+    use read;
+
+    fn diagnostics(text: &str) -> Vec<String> {
+        let module = read::textual(text.chars()).expect("parsing failed");
+
+        super::diagnostics(&module).iter().map(|d| {
+            format!("{} ({})", d.message, d.notes.join(", "))
+        }).collect()
+    }
+
+    #[test]
+    fn accepts_well_formed_functions() {
+        let text = "fn @f(%a: i8) -> i8 {\n  %x = add %a, i8 1\n  ret %x\n}\n\
+                    fn @main() -> i8 {\n  %r = call @f(i8 2)\n  ret %r\n}\n";
+
+        assert_eq!(diagnostics(text), Vec::<String>::new());
+    }
+
+    #[test]
+    fn reports_every_problem() {
+        let text = "fn @f(%a: i8) -> i8 {\n  ret i16 1\n:exit\n  ret i8 2\n  ret i8 3\n}\n\
+                    fn @main() -> i8 {\n  %r = call @f()\n  ret %r\n}\n";
+
+        assert_eq!(diagnostics(text), vec![
+            "terminating instructions must only appear at the end of a basic block (in block 'exit' of @f)",
+            "return value must be i8, not i16 (in value 1 of block 'entry' of @f)",
+            "@f takes 1 arguments but 0 were given (in value 1 of block 'entry' of @main)",
+        ]);
+    }
+
+    #[test]
+    fn rejects_uses_before_definitions() {
+        let text = "fn @f(%a: i8) -> i8 {\n  br %a eq i8 0, %other\n:middle\n  %x = add %a, i8 1\n  br true, %other\n\
+                    :other\n  ret %x\n}\n";
+
+        assert_eq!(diagnostics(text), vec![
+            "registers must be defined before every use (in value 1 of block 'other' of @f)",
+        ]);
+    }
+
+    #[test]
+    fn checks_function_attributes() {
+        let text = "fn @stop() noreturn {\n  ret\n}\n\
+                    fn @isr(%a: i8) interrupt(3) {\n  ret\n}\n";
+
+        assert_eq!(diagnostics(text), vec![
+            "@stop is marked noreturn, so it cannot return (in value 1 of block 'entry' of @stop)",
+            "interrupt handlers cannot take parameters or return values (in @isr)",
+        ]);
+    }
+
+    #[test]
+    fn checks_aggregates() {
+        let text = "global @pair = { i8 1, i16 2 }\n\
+                    fn @f(%p: { i8, i16 }, %n: i8) {\n  %a = insertvalue %p, i8 7, 1\n  %x = extractvalue %p, 2\n  %y = \
+                    extractvalue %n, 0\n  %q = getelementptr @pair, i8 0, %n\n  %z = getelementptr %n, i8 0\n  ret\n}\n";
+
+        assert_eq!(diagnostics(text), vec![
+            "element 1 of { i8, i16 } is i16, not i8 (in value 1 of block 'entry' of @f)",
+            "index 2 is out of range for { i8, i16 } (in value 2 of block 'entry' of @f)",
+            "extractvalue can only read from aggregates, not i8 (in value 3 of block 'entry' of @f)",
+            "getelementptr indices must select an element, and indices into structs must be integer literals (in value 4 of block 'entry' of @f)",
+            "getelementptr can only compute addresses from pointers (in value 5 of block 'entry' of @f)",
+        ]);
+    }
+
+    #[test]
+    fn checks_floats() {
+        let text = "fn @f(%a: f32, %b: f64, %n: i8) {\n  %x = fadd %n, i8 1\n  %y = add %a, f32 1.0\n  \
+                    %c = fcmp %a lt %b\n  %d = icmp %a eq %a\n  %e = itof %a, f64\n  %f = ftoi %n, i8\n  ret\n}\n";
+
+        assert_eq!(diagnostics(text), vec![
+            "floating point instructions cannot operate on i8 (in value 1 of block 'entry' of @f)",
+            "f32 operands need the floating point instructions (in value 2 of block 'entry' of @f)",
+            "fcmp must compare values of the same type (in value 3 of block 'entry' of @f)",
+            "icmp cannot compare f32 (in value 4 of block 'entry' of @f)",
+            "itof converts from integers, not f32 (in value 5 of block 'entry' of @f)",
+            "ftoi converts from floating point values, not i8 (in value 6 of block 'entry' of @f)",
+        ]);
+    }
+}
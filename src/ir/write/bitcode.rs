@@ -0,0 +1,533 @@
+//! The binary bitcode format.
+//!
+//! A file starts with the magic bytes `IRBC` followed by the format
+//! version as a little-endian `u16`. The module follows.
+//!
+//! Numbers are written as unsigned LEB128, and strings as their
+//! byte length followed by UTF-8. Every kind of type, expression,
+//! instruction and condition starts with one of the tags in `tag`.
+//!
+//! IDs are renumbered in the order they are first seen, so that
+//! reading a module never reuses an ID which is already in use.
+
+use {Module,Global,Function,Signature,Parameter,Block,Value,Expression,
+     Instruction,Condition,Type,Name,types,value};
+use {CallingConvention,InlineHint,ComplexityHint,Linkage};
+use instruction::{Unary,Binary};
+use util::{self,Identifiable};
+
+use std::collections::HashMap;
+use std::io::{self,Write};
+
+/// The bytes every bitcode file starts with.
+pub const MAGIC: &'static [u8] = b"IRBC";
+/// The version of the format which is written.
+pub const VERSION: u16 = 5;
+
+/// The tags which say what kind of thing follows.
+pub mod tag
+{
+    pub mod ty
+    {
+        pub const VOID: u8 = 0;
+        pub const POINTER: u8 = 1;
+        pub const INTEGER: u8 = 2;
+        pub const DECIMAL: u8 = 3;
+        pub const VECTOR: u8 = 4;
+        pub const ARRAY: u8 = 5;
+        pub const STRUCT: u8 = 6;
+        pub const FUNCTION: u8 = 7;
+        pub const BLOCK: u8 = 8;
+    }
+
+    pub mod expr
+    {
+        pub const INTEGER: u8 = 0;
+        pub const DECIMAL: u8 = 1;
+        pub const STRUCT: u8 = 2;
+        pub const REGISTER: u8 = 3;
+        pub const INSTRUCTION: u8 = 4;
+        pub const GLOBAL_REF: u8 = 5;
+        pub const BLOCK_REF: u8 = 6;
+        pub const FUNCTION_REF: u8 = 7;
+        pub const REGISTER_REF: u8 = 8;
+        pub const ARGUMENT_REF: u8 = 9;
+        pub const UNRESOLVED_REF: u8 = 10;
+        pub const STRING: u8 = 11;
+        pub const ARRAY: u8 = 12;
+        pub const VECTOR: u8 = 13;
+    }
+
+    pub mod inst
+    {
+        pub const ADD: u8 = 0;
+        pub const SUB: u8 = 1;
+        pub const MUL: u8 = 2;
+        pub const DIV: u8 = 3;
+        pub const REM: u8 = 4;
+        pub const MULH: u8 = 5;
+        pub const AND: u8 = 6;
+        pub const SHL: u8 = 7;
+        pub const SHR: u8 = 8;
+        pub const CALL: u8 = 9;
+        pub const BREAK: u8 = 10;
+        pub const RETURN: u8 = 11;
+        pub const COPY: u8 = 12;
+        pub const LOAD: u8 = 13;
+        pub const STORE: u8 = 14;
+        pub const PHI: u8 = 15;
+        pub const EXTRACT_VALUE: u8 = 16;
+        pub const INSERT_VALUE: u8 = 17;
+        pub const GET_ELEMENT_PTR: u8 = 18;
+        pub const FADD: u8 = 19;
+        pub const FSUB: u8 = 20;
+        pub const FMUL: u8 = 21;
+        pub const FDIV: u8 = 22;
+        pub const ICMP: u8 = 23;
+        pub const FCMP: u8 = 24;
+        pub const INT_TO_FLOAT: u8 = 25;
+        pub const FLOAT_TO_INT: u8 = 26;
+    }
+
+    pub mod cond
+    {
+        pub const TRUE: u8 = 0;
+        pub const FALSE: u8 = 1;
+        pub const EQUAL: u8 = 2;
+        pub const NOT_EQUAL: u8 = 3;
+        pub const GREATER_THAN: u8 = 4;
+        pub const GREATER_THAN_OR_EQ: u8 = 5;
+        pub const LESS_THAN: u8 = 6;
+        pub const LESS_THAN_OR_EQ: u8 = 7;
+    }
+}
+
+/// Writes a module as bitcode.
+pub fn module(module: &Module, output: &mut Write) -> io::Result<()> {
+    let mut writer = Writer {
+        output: output,
+        ids: HashMap::new(),
+    };
+
+    try!(writer.output.write_all(MAGIC));
+    try!(writer.bytes(&[VERSION as u8, (VERSION >> 8) as u8]));
+    writer.module(module)
+}
+
+struct Writer<'a>
+{
+    output: &'a mut Write,
+    /// The number given to each ID seen so far.
+    ids: HashMap<util::Id, u64>,
+}
+
+impl<'a> Writer<'a>
+{
+    fn module(&mut self, module: &Module) -> io::Result<()> {
+        try!(self.number(module.globals().count() as u64));
+        for global in module.globals() {
+            try!(self.global(global));
+        }
+
+        try!(self.number(module.functions().count() as u64));
+        for func in module.functions() {
+            try!(self.function(func));
+        }
+
+        Ok(())
+    }
+
+    fn global(&mut self, global: &Global) -> io::Result<()> {
+        try!(self.id(global.id()));
+        try!(self.string(global.name()));
+        try!(self.linkage(global.linkage()));
+        try!(self.boolean(global.is_constant()));
+        self.value(global.value())
+    }
+
+    fn function(&mut self, func: &Function) -> io::Result<()> {
+        try!(self.id(func.id()));
+        try!(self.string(func.name()));
+        try!(self.signature(func.signature()));
+
+        let cc = match func.cc {
+            None => 0,
+            Some(CallingConvention::C) => 1,
+        };
+        let inline = match func.inline_hint() {
+            InlineHint::None => 0,
+            InlineHint::Inline => 1,
+            InlineHint::MustInline => 2,
+        };
+        let complexity = match func.complexity_hint() {
+            ComplexityHint::None => 0,
+            ComplexityHint::ConstantTime => 1,
+        };
+
+        try!(self.bytes(&[cc]));
+        try!(self.linkage(func.linkage()));
+        try!(self.bytes(&[inline, complexity]));
+        try!(self.boolean(func.is_noreturn()));
+        try!(self.boolean(func.is_naked()));
+
+        try!(self.boolean(func.interrupt().is_some()));
+        if let Some(interrupt) = func.interrupt() {
+            try!(self.number(interrupt.vector as u64));
+        }
+
+        try!(self.boolean(func.section().is_some()));
+        if let Some(section) = func.section() {
+            try!(self.string(section));
+        }
+
+        try!(self.number(func.blocks().count() as u64));
+        for block in func.blocks() {
+            try!(self.block(block));
+        }
+
+        Ok(())
+    }
+
+    fn signature(&mut self, signature: &Signature) -> io::Result<()> {
+        try!(self.number(signature.parameters().count() as u64));
+        for param in signature.parameters() {
+            try!(self.parameter(param));
+        }
+
+        try!(self.number(signature.returns().count() as u64));
+        for ty in signature.returns() {
+            try!(self.ty(ty));
+        }
+
+        Ok(())
+    }
+
+    fn parameter(&mut self, param: &Parameter) -> io::Result<()> {
+        try!(self.id(param.get_id()));
+        try!(self.string(param.name()));
+        self.ty(param.ty())
+    }
+
+    fn block(&mut self, block: &Block) -> io::Result<()> {
+        try!(self.id(block.get_id()));
+        try!(self.string(block.name()));
+
+        try!(self.number(block.values().count() as u64));
+        for value in block.values() {
+            try!(self.value(value));
+        }
+
+        Ok(())
+    }
+
+    fn value(&mut self, value: &Value) -> io::Result<()> {
+        use self::tag::expr;
+
+        match value.node {
+            Expression::Literal(value::Literal::Integer(ref i)) => {
+                let (sign, magnitude) = i.value().to_bytes_le();
+                let negative = sign == ::num::bigint::Sign::Minus;
+
+                try!(self.bytes(&[expr::INTEGER]));
+                try!(self.integer_ty(&i.integer_ty()));
+                try!(self.boolean(negative));
+                try!(self.number(magnitude.len() as u64));
+                self.bytes(&magnitude)
+            },
+            Expression::Literal(value::Literal::Decimal(ref d)) => {
+                try!(self.bytes(&[expr::DECIMAL]));
+                try!(self.number(d.decimal_ty().bit_width() as u64));
+                try!(self.number(d.bits().len() as u64));
+                self.bytes(&d.bits().to_bytes())
+            },
+            Expression::Literal(value::Literal::Struct(ref s)) => {
+                try!(self.bytes(&[expr::STRUCT]));
+                self.values(s.fields())
+            },
+            Expression::Literal(value::Literal::Array(ref a)) => {
+                try!(self.bytes(&[expr::ARRAY]));
+                try!(self.ty(a.element_ty()));
+                self.values(a.elements())
+            },
+            Expression::Literal(value::Literal::Vector(ref v)) => {
+                try!(self.bytes(&[expr::VECTOR]));
+                try!(self.ty(v.element_ty()));
+                self.values(v.elements())
+            },
+            Expression::Register(ref reg) => {
+                try!(self.bytes(&[expr::REGISTER]));
+                try!(self.id(reg.get_id()));
+                try!(self.name(reg.name()));
+                self.value(&reg.value)
+            },
+            Expression::Instruction(ref inst) => {
+                try!(self.bytes(&[expr::INSTRUCTION]));
+                self.instruction(inst)
+            },
+            Expression::GlobalRef(ref r) => {
+                try!(self.bytes(&[expr::GLOBAL_REF]));
+                try!(self.id(r.global_id()));
+                self.ty(&r.ty)
+            },
+            Expression::BlockRef(ref r) => {
+                try!(self.bytes(&[expr::BLOCK_REF]));
+                self.id(r.block_id())
+            },
+            Expression::FunctionRef(ref r) => {
+                try!(self.bytes(&[expr::FUNCTION_REF]));
+                try!(self.id(r.function_id()));
+                try!(self.string(r.name()));
+                self.signature(r.signature())
+            },
+            Expression::RegisterRef(ref r) => {
+                try!(self.bytes(&[expr::REGISTER_REF]));
+                try!(self.id(r.register_id()));
+                self.ty(&r.ty)
+            },
+            Expression::ArgumentRef(ref r) => {
+                try!(self.bytes(&[expr::ARGUMENT_REF]));
+                try!(self.id(r.parameter_id()));
+                self.ty(&r.ty)
+            },
+            Expression::UnresolvedRef(id) => {
+                try!(self.bytes(&[expr::UNRESOLVED_REF]));
+                self.id(id)
+            },
+            Expression::String(ref s) => {
+                try!(self.bytes(&[expr::STRING]));
+                self.string(s.text())
+            },
+        }
+    }
+
+    fn instruction(&mut self, inst: &Instruction) -> io::Result<()> {
+        use self::tag::inst as tag;
+
+        match *inst {
+            Instruction::Add(ref i) => self.binary(tag::ADD, i),
+            Instruction::Sub(ref i) => self.binary(tag::SUB, i),
+            Instruction::Mul(ref i) => self.binary(tag::MUL, i),
+            Instruction::Div(ref i) => self.binary(tag::DIV, i),
+            Instruction::Rem(ref i) => self.binary(tag::REM, i),
+            Instruction::MulHigh(ref i) => self.binary(tag::MULH, i),
+            Instruction::And(ref i) => self.binary(tag::AND, i),
+            Instruction::Shl(ref i) => self.binary(tag::SHL, i),
+            Instruction::Shr(ref i) => self.binary(tag::SHR, i),
+            Instruction::Copy(ref i) => self.binary(tag::COPY, i),
+            Instruction::Store(ref i) => self.binary(tag::STORE, i),
+            Instruction::Load(ref i) => {
+                try!(self.bytes(&[tag::LOAD]));
+                self.value(i.operand())
+            },
+            Instruction::Call(ref i) => {
+                try!(self.bytes(&[tag::CALL]));
+                try!(self.boolean(i.is_tail()));
+                try!(self.value(i.target()));
+                self.values(i.arguments())
+            },
+            Instruction::Break(ref i) => {
+                try!(self.bytes(&[tag::BREAK]));
+                try!(self.condition(i.condition()));
+                self.value(i.target())
+            },
+            Instruction::Return(ref i) => {
+                try!(self.bytes(&[tag::RETURN]));
+                try!(self.boolean(i.subvalue().is_some()));
+
+                match i.subvalue() {
+                    Some(value) => self.value(value),
+                    None => Ok(()),
+                }
+            },
+            Instruction::Phi(ref i) => {
+                try!(self.bytes(&[tag::PHI]));
+                try!(self.ty(&i.ty()));
+
+                try!(self.number(i.incoming().count() as u64));
+                for &(ref value, ref block) in i.incoming() {
+                    try!(self.value(value));
+                    try!(self.value(block));
+                }
+
+                Ok(())
+            },
+            Instruction::ExtractValue(ref i) => {
+                try!(self.bytes(&[tag::EXTRACT_VALUE]));
+                try!(self.value(i.aggregate()));
+                self.number(i.index())
+            },
+            Instruction::InsertValue(ref i) => {
+                try!(self.bytes(&[tag::INSERT_VALUE]));
+                try!(self.value(i.aggregate()));
+                try!(self.value(i.value()));
+                self.number(i.index())
+            },
+            Instruction::GetElementPtr(ref i) => {
+                try!(self.bytes(&[tag::GET_ELEMENT_PTR]));
+                try!(self.value(i.pointer()));
+                self.values(i.indices())
+            },
+            Instruction::FAdd(ref i) => self.binary(tag::FADD, i),
+            Instruction::FSub(ref i) => self.binary(tag::FSUB, i),
+            Instruction::FMul(ref i) => self.binary(tag::FMUL, i),
+            Instruction::FDiv(ref i) => self.binary(tag::FDIV, i),
+            Instruction::ICmp(ref i) => {
+                try!(self.bytes(&[tag::ICMP]));
+                self.condition(i.condition())
+            },
+            Instruction::FCmp(ref i) => {
+                try!(self.bytes(&[tag::FCMP]));
+                self.condition(i.condition())
+            },
+            Instruction::IntToFloat(ref i) => {
+                try!(self.bytes(&[tag::INT_TO_FLOAT]));
+                try!(self.value(i.value()));
+                self.number(i.decimal_ty().bit_width() as u64)
+            },
+            Instruction::FloatToInt(ref i) => {
+                try!(self.bytes(&[tag::FLOAT_TO_INT]));
+                try!(self.value(i.value()));
+                self.integer_ty(&i.integer_ty())
+            },
+        }
+    }
+
+    fn binary<I>(&mut self, tag: u8, inst: &I) -> io::Result<()>
+        where I: Binary {
+        let (lhs, rhs) = inst.operands();
+
+        try!(self.bytes(&[tag]));
+        try!(self.value(lhs));
+        self.value(rhs)
+    }
+
+    fn condition(&mut self, cond: &Condition) -> io::Result<()> {
+        use self::tag::cond as tag;
+
+        let (tag, operands) = match *cond {
+            Condition::True => return self.bytes(&[tag::TRUE]),
+            Condition::False => return self.bytes(&[tag::FALSE]),
+            Condition::Equal(ref a, ref b) => (tag::EQUAL, (a, b)),
+            Condition::NotEqual(ref a, ref b) => (tag::NOT_EQUAL, (a, b)),
+            Condition::GreaterThan(ref a, ref b) => (tag::GREATER_THAN, (a, b)),
+            Condition::GreaterThanOrEq(ref a, ref b) => (tag::GREATER_THAN_OR_EQ, (a, b)),
+            Condition::LessThan(ref a, ref b) => (tag::LESS_THAN, (a, b)),
+            Condition::LessThanOrEq(ref a, ref b) => (tag::LESS_THAN_OR_EQ, (a, b)),
+        };
+
+        try!(self.bytes(&[tag]));
+        try!(self.value(operands.0));
+        self.value(operands.1)
+    }
+
+    fn values<'b, I>(&mut self, values: I) -> io::Result<()>
+        where I: ExactSizeIterator<Item=&'b Value> {
+        try!(self.number(values.len() as u64));
+
+        for value in values {
+            try!(self.value(value));
+        }
+
+        Ok(())
+    }
+
+    fn ty(&mut self, ty: &Type) -> io::Result<()> {
+        use self::tag::ty as tag;
+
+        match *ty {
+            Type::Void(..) => self.bytes(&[tag::VOID]),
+            Type::Pointer(ref p) => {
+                try!(self.bytes(&[tag::POINTER]));
+                self.ty(p.pointee())
+            },
+            Type::Integer(ref i) => {
+                try!(self.bytes(&[tag::INTEGER]));
+                self.integer_ty(i)
+            },
+            Type::Decimal(ref d) => {
+                try!(self.bytes(&[tag::DECIMAL]));
+                self.number(d.bit_width() as u64)
+            },
+            Type::Vector(ref v) => {
+                try!(self.bytes(&[tag::VECTOR]));
+                try!(self.number(v.count()));
+                self.ty(v.element())
+            },
+            Type::Array(ref a) => {
+                try!(self.bytes(&[tag::ARRAY]));
+                try!(self.number(a.count()));
+                self.ty(a.element())
+            },
+            Type::Struct(ref s) => {
+                try!(self.bytes(&[tag::STRUCT]));
+                try!(self.number(s.fields().count() as u64));
+
+                for field in s.fields() {
+                    try!(self.ty(field));
+                }
+                Ok(())
+            },
+            Type::Function(ref f) => {
+                try!(self.bytes(&[tag::FUNCTION]));
+                self.signature(f.signature())
+            },
+            Type::Block(..) => self.bytes(&[tag::BLOCK]),
+        }
+    }
+
+    fn integer_ty(&mut self, ty: &types::Integer) -> io::Result<()> {
+        try!(self.boolean(ty.is_signed()));
+        self.number(ty.width() as u64)
+    }
+
+    fn linkage(&mut self, linkage: Linkage) -> io::Result<()> {
+        self.bytes(&[match linkage {
+            Linkage::External => 0,
+            Linkage::Internal => 1,
+        }])
+    }
+
+    fn name(&mut self, name: &Name) -> io::Result<()> {
+        match *name {
+            Name::Unnamed => self.boolean(false),
+            Name::Named(ref name) => {
+                try!(self.boolean(true));
+                self.string(name)
+            },
+        }
+    }
+
+    fn id(&mut self, id: util::Id) -> io::Result<()> {
+        let next = self.ids.len() as u64;
+        let number = *self.ids.entry(id).or_insert(next);
+
+        self.number(number)
+    }
+
+    fn string(&mut self, s: &str) -> io::Result<()> {
+        try!(self.number(s.len() as u64));
+        self.bytes(s.as_bytes())
+    }
+
+    fn boolean(&mut self, b: bool) -> io::Result<()> {
+        self.bytes(&[b as u8])
+    }
+
+    /// Writes an unsigned LEB128 number.
+    fn number(&mut self, mut n: u64) -> io::Result<()> {
+        loop {
+            let byte = (n & 0x7f) as u8;
+            n >>= 7;
+
+            if n == 0 {
+                return self.bytes(&[byte]);
+            }
+
+            try!(self.bytes(&[byte | 0x80]));
+        }
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.output.write_all(bytes)
+    }
+}
@@ -0,0 +1,8 @@
+use std::io;
+
+pub mod bitcode;
+
+/// Writes a module in the binary bitcode format.
+pub fn bitcode(module: &::Module, output: &mut io::Write) -> io::Result<()> {
+    bitcode::module(module, output)
+}
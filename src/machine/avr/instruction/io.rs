@@ -0,0 +1,93 @@
+use {Instruction, Operand, OperandInfo, EncodedInstruction, SideEffects};
+use mir;
+use std;
+
+/// Reads an I/O register into a register.
+#[derive(Clone)]
+pub struct INRdA
+{
+    pub rd: Operand,
+    pub a: Operand,
+}
+
+impl INRdA
+{
+    pub fn new(rd: Operand, a: Operand) -> Self {
+        INRdA { rd: rd, a: a }
+    }
+
+    pub fn from_pattern(_node: &mir::Node) -> Box<Instruction> {
+        unreachable!(); // there are no patterns
+    }
+}
+
+impl Instruction for INRdA
+{
+    fn mnemonic(&self) -> String { "in".to_owned() }
+
+    fn operands(&self) -> Vec<OperandInfo> {
+        vec![
+            OperandInfo::output(self.rd.clone()),
+            OperandInfo::input(self.a.clone()),
+        ]
+    }
+
+    fn operands_mut(&mut self) -> Vec<&mut Operand> {
+        vec![&mut self.rd, &mut self.a]
+    }
+
+    fn side_effects(&self) -> SideEffects {
+        SideEffects::none()
+    }
+
+    fn encode(&self) -> EncodedInstruction {
+        unimplemented!();
+    }
+}
+
+impl_debug_for_instruction!(INRdA);
+
+/// Writes a register into an I/O register.
+#[derive(Clone)]
+pub struct OUTARr
+{
+    pub a: Operand,
+    pub rr: Operand,
+}
+
+impl OUTARr
+{
+    pub fn new(a: Operand, rr: Operand) -> Self {
+        OUTARr { a: a, rr: rr }
+    }
+
+    pub fn from_pattern(_node: &mir::Node) -> Box<Instruction> {
+        unreachable!(); // there are no patterns
+    }
+}
+
+impl Instruction for OUTARr
+{
+    fn mnemonic(&self) -> String { "out".to_owned() }
+
+    fn operands(&self) -> Vec<OperandInfo> {
+        vec![
+            OperandInfo::input(self.a.clone()),
+            OperandInfo::input(self.rr.clone()),
+        ]
+    }
+
+    fn operands_mut(&mut self) -> Vec<&mut Operand> {
+        vec![&mut self.a, &mut self.rr]
+    }
+
+    fn side_effects(&self) -> SideEffects {
+        SideEffects::none()
+    }
+
+    fn encode(&self) -> EncodedInstruction {
+        unimplemented!();
+    }
+}
+
+impl_debug_for_instruction!(OUTARr);
@@ -4,6 +4,7 @@ pub use self::rd::*;
 pub use self::simple::*;
 
 pub use self::ldi::LDIRdK;
+pub use self::io::{INRdA, OUTARr};
 pub use self::mov::MOVRdRr;
 
 #[macro_export]
@@ -30,5 +31,6 @@ pub mod simple;
 
 // Individual instructions.
 pub mod ldi;
+pub mod io;
 pub mod mov;
 
@@ -7,7 +7,7 @@ use util;
 
 macro_rules! pattern {
     ($ty:ident, $node:expr) => {
-        pattern!($ty, $node, Vec::new());
+        pattern!($ty, $node, Vec::new())
     };
 
     ($ty:ident, $node:expr, $constraints:expr) => {
@@ -127,6 +127,15 @@ pub fn patterns() -> Vec<Pattern> {
 
         pattern!(RET, { node!(Ret) }),
 
+        pattern!(MOVRdRr, {
+            node!(Set,
+                  operands!(
+                      value!("rd" => PatternOperand::register(&registers::GPR8)),
+                      value!("rr" => PatternOperand::register(&registers::GPR8))
+                  )
+            )
+        }),
+
         pattern!(LDIRdK, {
             node!(Set,
                   operands!(
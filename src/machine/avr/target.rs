@@ -46,6 +46,7 @@ impl target::Target for AVR
 impl MachineTarget for AVR
 {
     fn pointer_width(&self) -> u16 { 16 }
+    fn int_width(&self) -> u16 { 16 }
 
     fn create_legalizer(&self) -> select::Legalizer {
         avr::legalize::legalizer()
@@ -54,6 +55,60 @@ impl MachineTarget for AVR
     fn create_selector(&self) -> Selector {
         avr::select::selector()
     }
+
+    fn is_return(&self, instruction: &Instruction) -> bool {
+        instruction.mnemonic() == "ret"
+    }
+
+    fn create_interrupt_return(&self) -> Box<Instruction> {
+        Box::new(instruction::RETI)
+    }
+
+    fn create_interrupt_prologue(&self, clobbers: &[&'static Register]) -> Vec<Box<Instruction>> {
+        let r0 = self::physical(&avr::registers::R0);
+
+        // The status register is saved through r0.
+        let mut instructions: Vec<Box<Instruction>> = vec![
+            Box::new(instruction::PUSHRd::new(r0.clone())),
+            Box::new(instruction::INRdA::new(r0.clone(), self::io_address(SREG))),
+            Box::new(instruction::PUSHRd::new(r0)),
+        ];
+
+        for register in clobbers.iter().filter(|r| r.number != avr::registers::R0.number) {
+            instructions.push(Box::new(instruction::PUSHRd::new(self::physical(register))));
+        }
+
+        instructions
+    }
+
+    fn create_interrupt_epilogue(&self, clobbers: &[&'static Register]) -> Vec<Box<Instruction>> {
+        let r0 = self::physical(&avr::registers::R0);
+
+        let mut instructions: Vec<Box<Instruction>> = clobbers.iter().rev()
+            .filter(|r| r.number != avr::registers::R0.number)
+            .map(|register| Box::new(instruction::POPRd::new(self::physical(register))) as Box<Instruction>)
+            .collect();
+
+        instructions.push(Box::new(instruction::POPRd::new(r0.clone())));
+        instructions.push(Box::new(instruction::OUTARr::new(self::io_address(SREG), r0.clone())));
+        instructions.push(Box::new(instruction::POPRd::new(r0)));
+        instructions
+    }
+
+    fn interrupt_symbol(&self, vector: u32) -> String {
+        format!("__vector_{}", vector)
+    }
+}
+
+/// The I/O address of the status register.
+const SREG: i64 = 0x3f;
+
+fn physical(register: &'static Register) -> Operand {
+    Operand::Register(regalloc::Register::Physical(register))
+}
+
+fn io_address(address: i64) -> Operand {
+    Operand::Immediate { bit_width: 6, value: address }
 }
 
 impl regalloc::Target for AVR
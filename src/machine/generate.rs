@@ -1,5 +1,5 @@
-use MachineTarget;
-use {ir, mir, target, regalloc};
+use {MachineTarget, Instruction, Operand, Register};
+use {ir, mir, pass, target, regalloc};
 use target::OutputType;
 
 use std::io;
@@ -20,39 +20,123 @@ fn assemble<T>(target: &T,
     where T: MachineTarget {
     let module = try!(self::parse_ir(input));
 
-    for func in module.functions() {
+    // Instruction selection only knows about scalars, and none of the
+    // targets have floating point hardware, so floats become calls to
+    // the runtime.
+    let int = ir::types::Integer::i(target.int_width());
+    let module = pass::Manager::empty().add_pass(pass::transforms::Scalarize)
+                                       .add_pass(pass::transforms::SoftFloat::new(int))
+                                       .run(module);
+
+    // Declarations are defined elsewhere.
+    for func in module.functions().filter(|f| f.blocks().next().is_some()) {
+        if let Some(section) = func.section() {
+            try!(write!(output, "  .pushsection {}\n", section));
+        }
+
+        if let Some(interrupt) = func.interrupt() {
+            let symbol = target.interrupt_symbol(interrupt.vector);
+            try!(write!(output, "  .global {}\n{}:\n", symbol, symbol));
+        }
+
         try!(write!(output, "{}:\n", func.name));
 
-        let dags = mir::Dag::from_function(func);
+        let dags = try!(mir::Dag::from_function(func).map_err(target::Error::Codegen));
+        let dags: Result<Vec<_>, _> = dags.into_iter().map(|dag| {
+            target.create_legalizer().legalize(dag)
+        }).collect();
+        let dags = try!(dags.map_err(|d| target::Error::Codegen(d.with_note(format!("in @{}", func.name())))));
+
+        // Registers may be set and used in different blocks, so the
+        // function is verified as a whole.
+        try!(mir::verifier::verify_function(&dags).map_err(target::Error::Codegen));
+
+        let mut instructions = Vec::new();
 
         for dag in dags {
-            let legalizer = target.create_legalizer();
             let mut selector = target.create_selector();
 
-            let dag = legalizer.legalize(dag);
+            let selected = try!(selector.select(dag).map_err(target::Error::Codegen));
+            instructions.extend(regalloc::allocate(target, selected));
+        }
 
-            let instructions = selector.select(dag);
-            let instructions = regalloc::allocate(target, instructions);
+        // Interrupt handlers must leave the interrupted code's state
+        // as they found it. Naked functions save nothing.
+        let saves_state = func.interrupt().is_some() && !func.is_naked() &&
+            instructions.iter().any(|i| !target.is_return(&**i));
+        let clobbers = self::clobbered_registers(&instructions);
 
-            for instruction in instructions {
-                let inst_str = format!("  {:?}\n", instruction);
-                try!(output.write(inst_str.as_bytes()));
+        if saves_state {
+            for instruction in target.create_interrupt_prologue(&clobbers) {
+                try!(write!(output, "  {:?}\n", instruction));
             }
         }
 
+        for instruction in instructions {
+            let instruction = if target.is_return(&*instruction) {
+                if saves_state {
+                    for restore in target.create_interrupt_epilogue(&clobbers) {
+                        try!(write!(output, "  {:?}\n", restore));
+                    }
+                }
+
+                match func.interrupt() {
+                    Some(..) => target.create_interrupt_return(),
+                    None => instruction,
+                }
+            } else {
+                instruction
+            };
+
+            let inst_str = format!("  {:?}\n", instruction);
+            try!(output.write(inst_str.as_bytes()));
+        }
+
+        if func.section().is_some() {
+            try!(write!(output, "  .popsection\n"));
+        }
+
         try!(write!(output, "\n"));
     }
 
     Ok(())
 }
 
+/// Gets the physical registers which a list of instructions use.
+fn clobbered_registers(instructions: &[Box<Instruction>]) -> Vec<&'static Register> {
+    let mut registers: Vec<&'static Register> = Vec::new();
+
+    for instruction in instructions {
+        for operand in instruction.operands() {
+            if let Operand::Register(regalloc::Register::Physical(register)) = operand.value {
+                // Register pairs are saved one half at a time.
+                let parts = if register.subregs.is_empty() { vec![register] } else { register.subregs.to_vec() };
+
+                for part in parts {
+                    if !registers.iter().any(|r| r.number == part.number) {
+                        registers.push(part);
+                    }
+                }
+            }
+        }
+    }
+
+    registers.sort_by_key(|r| r.number);
+    registers
+}
+
 fn parse_ir(input: &mut io::Read) -> Result<ir::Module, target::Error> {
-    let mut input_module_str = String::new();
-    try!(input.read_to_string(&mut input_module_str));
+    let mut data = Vec::new();
+    try!(input.read_to_end(&mut data));
+
+    let module = if ir::read::bitcode::is_bitcode(&data) {
+        try!(ir::read::bitcode(&mut &data[..]).map_err(|e| target::Error::InvalidIR(e.into())))
+    } else {
+        let text = try!(String::from_utf8(data).map_err(|_| {
+            target::Error::InvalidIR("the input is neither text nor bitcode".into())
+        }));
 
-    let module = match ir::read::textual(input_module_str.chars()) {
-        Ok(module) => module,
-        Err(e) => return Err(target::Error::InvalidIR(e)),
+        try!(ir::read::textual(text.chars()).map_err(|e| target::Error::InvalidIR(e.into())))
     };
 
     if let Err(e) = ir::verifier::verify(&module) {
@@ -62,3 +146,56 @@ fn parse_ir(input: &mut io::Read) -> Result<ir::Module, target::Error> {
     Ok(module)
 }
 
+
+#[cfg(test)]
+mod test
+{
+    use avr::AVR;
+    use target::{self, OutputType};
+
+    fn assemble(text: &str) -> Result<String, target::Error> {
+        let mut output = Vec::new();
+        try!(super::generate(&AVR, OutputType::Assembly, &mut text.as_bytes(), &mut output));
+
+        Ok(String::from_utf8(output).unwrap())
+    }
+
+    fn codegen_error(text: &str) -> String {
+        match assemble(text) {
+            Err(target::Error::Codegen(d)) => format!("{} ({})", d.message, d.notes.join(", ")),
+            Err(e) => panic!("expected a codegen error but got {:?}", e),
+            Ok(output) => panic!("expected a codegen error but got {}", output),
+        }
+    }
+
+    #[test]
+    fn lowers_float_arithmetic_to_runtime_calls() {
+        // The soft-float routines are called, but calls can't be
+        // selected yet.
+        assert_eq!(codegen_error("fn @f(%x: i8) {\n  fadd f32 1.0, f32 2.0\n  ret\n}\n"),
+                   "the backend cannot lower 'call' instructions yet (in @f)");
+    }
+
+    #[test]
+    fn reports_float_values_instead_of_panicking() {
+        assert_eq!(codegen_error("fn @f(%a: f32) -> f32 {\n  %b = fadd %a, f32 1.0\n  ret %b\n}\n"),
+                   "the backend cannot lower values of type f32 yet (in @f)");
+    }
+
+    #[test]
+    fn reports_nodes_without_a_legalization_action() {
+        // The note with the node has argument numbers which vary.
+        let error = codegen_error("fn @f(%a: i8, %b: i8) -> i8 {\n  %c = mul %a, %b\n  ret %c\n}\n");
+
+        assert!(error.starts_with("the backend cannot lower 'mul' nodes of type i8 yet (the node is (mul "));
+        assert!(error.ends_with(", in @f)"));
+    }
+
+    #[test]
+    fn skips_the_declared_runtime_routines() {
+        let output = assemble("fn @f() {\n  %a = add i8 4, i8 5\n  ret\n}\n\
+                               fn @__addsf3(%a: f32, %b: f32) -> f32\n").unwrap();
+
+        assert_eq!(output, "f:\n  ldi r16, 4\n  ldi r17, 5\n  add r16, r17\n  ret \n\n");
+    }
+}
@@ -17,6 +17,7 @@ pub mod avr;
 
 extern crate compiler_ir as ir;
 extern crate compiler_mir as mir;
+extern crate compiler_pass as pass;
 extern crate compiler_regalloc as regalloc;
 extern crate compiler_select as select;
 extern crate compiler_target as target;
@@ -32,9 +33,24 @@ pub trait MachineTarget : target::Target + regalloc::Target<Instruction=Box<Inst
 {
     /// Gets the width of a pointer.
     fn pointer_width(&self) -> u16;
+    /// Gets the width of the C `int` type.
+    fn int_width(&self) -> u16;
 
     fn create_legalizer(&self) -> select::Legalizer;
     fn create_selector(&self) -> Selector;
+
+    /// Checks if an instruction returns to the caller.
+    fn is_return(&self, instruction: &Instruction) -> bool;
+    /// Creates the instruction which returns from an interrupt handler.
+    fn create_interrupt_return(&self) -> Box<Instruction>;
+    /// Creates the instructions which save the state an interrupt
+    /// handler clobbers, given the registers it uses.
+    fn create_interrupt_prologue(&self, clobbers: &[&'static Register]) -> Vec<Box<Instruction>>;
+    /// Creates the instructions which restore the state saved by
+    /// the interrupt prologue.
+    fn create_interrupt_epilogue(&self, clobbers: &[&'static Register]) -> Vec<Box<Instruction>>;
+    /// Gets the symbol given to the handler of an interrupt vector.
+    fn interrupt_symbol(&self, vector: u32) -> String;
 }
 
 pub type Selector = select::Selector<Box<Instruction>, PatternOperand>;
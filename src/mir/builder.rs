@@ -1,6 +1,6 @@
 use {Node,Value,Dag,OpCode,Type,RegisterRef,ConstantInteger};
 use ir;
-use util::{self, Identifiable};
+use util::{self, Diagnostic, Identifiable};
 
 use std::collections::HashMap;
 
@@ -21,14 +21,14 @@ impl Context
         }
     }
 
-    fn put_register(&mut self, register: &ir::Register) -> Value {
+    fn put_register(&mut self, register: &ir::Register) -> Result<Value, Diagnostic> {
         let old_id = register.get_id();
         let new_id = util::Id::next();
-        let ty = self::convert_type(&register.value.node.ty()).unwrap();
+        let ty = try!(self::convert_type(&register.value.node.ty()));
 
         self.register_map.insert(old_id, new_id);
 
-        Value::register_ref(new_id, 0, ty)
+        Ok(Value::register_ref(new_id, 0, ty))
     }
 
     fn put_parameter_id(&mut self, id: util::Id) {
@@ -47,132 +47,107 @@ impl Context
     }
 }
 
-pub fn from_function(func: &ir::Function) -> Vec<Dag> {
+/// Builds one DAG per block of a function.
+///
+/// Returns a diagnostic if the function uses something the backend
+/// cannot lower yet.
+pub fn from_function(func: &ir::Function) -> Result<Vec<Dag>, Diagnostic> {
+    self::build(func).map_err(|d| d.with_note(format!("in @{}", func.name())))
+}
+
+fn build(func: &ir::Function) -> Result<Vec<Dag>, Diagnostic> {
     let mut context = Context::new();
 
     for param in func.signature.parameters() {
+        try!(self::convert_type(&param.ty));
         context.put_parameter_id(param.get_id());
     }
 
+    for ty in func.signature.returns() {
+        try!(self::convert_type(ty));
+    }
+
     func.blocks().map(|block| {
-        let nodes: Vec<_> = block.values().map(|value| {
+        let nodes: Result<Vec<_>, _> = block.values().map(|value| {
             self::node_from_value(&mut context, value)
         }).collect();
 
-        Dag::new(nodes)
+        nodes.map(Dag::new)
     }).collect()
 }
 
-fn node_from_value(context: &mut Context, value: &ir::Value) -> Node {
+fn node_from_value(context: &mut Context, value: &ir::Value) -> Result<Node, Diagnostic> {
     use num::traits::ToPrimitive;
 
     match value.node {
         ir::Expression::Instruction(ref i) => self::node_from_instruction(context, i),
         ir::Expression::Register(ref r) => {
-            let register_ref = Node::leaf(context.put_register(r));
-            Node::set(register_ref, node_from_value(context, &r.value))
+            let register_ref = Node::leaf(try!(context.put_register(r)));
+            Ok(Node::set(register_ref, try!(node_from_value(context, &r.value))))
         },
-        ir::Expression::Literal(ref literal) => {
-            match *literal {
-                ir::value::Literal::Integer(ref i) => {
-                    Node::leaf(Value::ConstantInteger(ConstantInteger {
-                        bit_width: i.integer_ty().width() as _,
-                        value: i.value().to_i64().unwrap(),
-                    }))
-                },
-                _ => unimplemented!(),
-            }
+        ir::Expression::Literal(ir::value::Literal::Integer(ref i)) => {
+            Ok(Node::leaf(Value::ConstantInteger(ConstantInteger {
+                bit_width: i.integer_ty().width() as _,
+                value: i.value().to_i64().unwrap(),
+            })))
         },
         ir::Expression::ArgumentRef(ref r) => {
             let id = context.map_parameter_id(r.param_id);
-            let ty = self::convert_type(&r.ty).unwrap();
-            Node::leaf(Value::ArgumentRef { id: id, ty: ty, })
+            let ty = try!(self::convert_type(&r.ty));
+            Ok(Node::leaf(Value::ArgumentRef { id: id, ty: ty, }))
         },
         ir::Expression::RegisterRef(ref r) => {
             let id = context.map_register_id(r.register_id);
-            let ty = self::convert_type(&r.ty).unwrap();
-            Node::leaf(Value::RegisterRef(RegisterRef {
+            let ty = try!(self::convert_type(&r.ty));
+            Ok(Node::leaf(Value::RegisterRef(RegisterRef {
                 register_id: id,
                 result_number: 0,
                 ty: ty,
-            }))
+            })))
         },
-        _ => {
-            panic!("do not know how to handle this IR value: {:#?}", value.node);
+        ref node => {
+            Err(Diagnostic::error(format!("the backend cannot lower values of type {} yet", node.ty())))
         },
     }
 }
 
-fn node_from_instruction(context: &mut Context, inst: &ir::Instruction) -> Node {
+fn node_from_instruction(context: &mut Context, inst: &ir::Instruction) -> Result<Node, Diagnostic> {
     use ir::Instruction;
-    use ir::Binary;
 
     match *inst {
-        Instruction::Add(ref i) => {
-            let (lhs, rhs) = i.operands();
-
-            Node::branch(
-                OpCode::Add,
-                (vec![node_from_value(context, lhs), node_from_value(context, rhs)].into_iter()),
-            )
-        },
-        Instruction::Sub(ref i) => {
-            let (lhs, rhs) = i.operands();
-
-            Node::branch(
-                OpCode::Sub,
-                vec![node_from_value(context, lhs), node_from_value(context, rhs)].into_iter(),
-            )
-        },
-        Instruction::Mul(ref i) => {
-            let (lhs, rhs) = i.operands();
-
-            Node::branch(
-                OpCode::Mul,
-                vec![node_from_value(context, lhs), node_from_value(context, rhs)].into_iter(),
-            )
-        },
-        Instruction::Div(ref i) => {
-            let (lhs, rhs) = i.operands();
-
-            Node::branch(
-                OpCode::Div,
-                vec![node_from_value(context, lhs), node_from_value(context, rhs)].into_iter(),
-            )
-        },
-        Instruction::Shl(ref i) => {
-            let (lhs, rhs) = i.operands();
-
-            Node::branch(
-                OpCode::Shl,
-                vec![node_from_value(context, lhs), node_from_value(context, rhs)].into_iter(),
-            )
-        },
-        Instruction::Shr(ref i) => {
-            let (lhs, rhs) = i.operands();
-
-            Node::branch(
-                OpCode::Shr,
-                vec![node_from_value(context, lhs), node_from_value(context, rhs)].into_iter(),
-            )
-        },
+        Instruction::Add(ref i) => self::binary(context, OpCode::Add, i),
+        Instruction::Sub(ref i) => self::binary(context, OpCode::Sub, i),
+        Instruction::Mul(ref i) => self::binary(context, OpCode::Mul, i),
+        Instruction::Div(ref i) => self::binary(context, OpCode::Div, i),
+        Instruction::Shl(ref i) => self::binary(context, OpCode::Shl, i),
+        Instruction::Shr(ref i) => self::binary(context, OpCode::Shr, i),
         Instruction::Return(ref i) => {
             match i.subvalue() {
-                Some(value) => Node::branch(OpCode::Ret, vec![node_from_value(context, value)].into_iter()),
-                None => Node::branch(OpCode::Ret, vec![])
+                Some(value) => Ok(Node::branch(OpCode::Ret, vec![try!(node_from_value(context, value))])),
+                None => Ok(Node::branch(OpCode::Ret, vec![])),
             }
         },
-        _ => unimplemented!(),
+        ref i => Err(Diagnostic::error(format!("the backend cannot lower '{}' instructions yet", i.mnemonic()))),
     }
 }
 
+fn binary<I>(context: &mut Context, opcode: OpCode, inst: &I) -> Result<Node, Diagnostic>
+    where I: ir::Binary {
+    let (lhs, rhs) = inst.operands();
+
+    Ok(Node::branch(
+        opcode,
+        vec![try!(node_from_value(context, lhs)), try!(node_from_value(context, rhs))].into_iter(),
+    ))
+}
+
 /// Converts an IR type to a MIR type.
-/// Returns `Some` if the type could be converted.
-fn convert_type(ty: &ir::Type) -> Option<Type>
+/// Returns an error if the type cannot be lowered.
+fn convert_type(ty: &ir::Type) -> Result<Type, Diagnostic>
 {
     match *ty {
-        ir::Type::Integer(ref i) => Some(Type::Integer { bit_width: i.bit_width as _ }),
-        _ => unimplemented!(),
+        ir::Type::Integer(ref i) => Ok(Type::Integer { bit_width: i.bit_width as _ }),
+        ref ty => Err(Diagnostic::error(format!("the backend cannot lower values of type {} yet", ty))),
     }
 }
 
@@ -1,5 +1,6 @@
 use Node;
 use ir;
+use util::Diagnostic;
 
 use builder;
 use verifier;
@@ -20,7 +21,7 @@ impl Dag
         }
     }
 
-    pub fn from_function(function: &ir::Function) -> Vec<Dag> {
+    pub fn from_function(function: &ir::Function) -> Result<Vec<Dag>, Diagnostic> {
         builder::from_function(function)
     }
 
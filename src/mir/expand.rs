@@ -78,7 +78,9 @@ impl Context
                 match value {
                     Value::RegisterRef(register_ref) => {
                         if let Some(initial_value) = self.possible_register_substitution(register_ref.register_id) {
-                            initial_value
+                            // The value may refer to registers which are
+                            // substituted themselves.
+                            self.expand_node(initial_value)
                         } else {
                             Node { kind: NodeKind::Leaf(Value::RegisterRef(register_ref)), ..node }
                         }
@@ -28,17 +28,57 @@ impl OpCode
     }
 
     pub fn value_infos(&self, operands: &[Node]) -> Vec<ValueInfo> {
-        match *self {
-            OpCode::Set => {
-                assert_eq!(operands.len(), 2);
-
-                vec![ValueInfo::Output, ValueInfo::Input]
+        match self.fixed_value_infos() {
+            Some(infos) => {
+                assert_eq!(operands.len(), infos.len());
+                infos
             },
             // Everything else is all-inputs.
-            _ => {
+            None => {
                 operands.iter().map(|_| ValueInfo::Input).collect()
             },
         }
     }
+
+    /// Gets the operands the opcode takes, if it always
+    /// takes the same number of them.
+    pub fn fixed_value_infos(&self) -> Option<Vec<ValueInfo>> {
+        match *self {
+            OpCode::Set => Some(vec![ValueInfo::Output, ValueInfo::Input]),
+            OpCode::Mul |
+            OpCode::Div |
+            OpCode::Shl |
+            OpCode::Shr |
+            OpCode::Sext |
+            OpCode::Zext => Some(vec![ValueInfo::Input, ValueInfo::Input]),
+            OpCode::Add |
+            OpCode::Sub |
+            OpCode::Ret => None,
+        }
+    }
+
+    /// Checks if the opcode can take a number of operands.
+    pub fn accepts_operand_count(&self, count: usize) -> bool {
+        match self.fixed_value_infos() {
+            Some(infos) => infos.len() == count,
+            None => match *self {
+                OpCode::Ret => count <= 1,
+                _ => count >= 2,
+            },
+        }
+    }
+
+    /// Checks if the operands of the opcode must all have the same type.
+    pub fn is_arithmetic(&self) -> bool {
+        match *self {
+            OpCode::Add |
+            OpCode::Sub |
+            OpCode::Mul |
+            OpCode::Div |
+            OpCode::Shl |
+            OpCode::Shr => true,
+            _ => false,
+        }
+    }
 }
 
@@ -22,3 +22,13 @@ impl Type
     pub fn is_nothing(&self) -> bool { *self == Type::Nothing }
 }
 
+
+impl ::std::fmt::Display for Type
+{
+    fn fmt(&self, fmt: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        match *self {
+            Type::Integer { bit_width } => write!(fmt, "i{}", bit_width),
+            Type::Nothing => write!(fmt, "nothing"),
+        }
+    }
+}
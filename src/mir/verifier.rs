@@ -1,23 +1,232 @@
-use Dag;
+use {Dag,Node,NodeKind,Branch,OpCode,Value};
+use util::{self,Diagnostic};
 
-pub type Result = ::std::result::Result<(), String>;
+use std::collections::HashSet;
 
-// TODO:
-// - add/sub operations must have the same type for all operands
-// - register values can't refer to themselves
+pub type Result = ::std::result::Result<(), Diagnostic>;
+
+/// Verifies a DAG, returning the first problem.
 pub fn verify_dag(dag: &Dag) -> Result {
-    try!(self::all_top_level_nodes_are_typeless(dag));
+    self::first(self::diagnostics(dag))
+}
+
+/// Verifies the DAGs of a function, returning the first problem.
+pub fn verify_function(dags: &[Dag]) -> Result {
+    self::first(self::function_diagnostics(dags))
+}
 
-    Ok(())
+/// Finds every problem in a DAG.
+///
+/// Each diagnostic has a note giving the path to the node it is about.
+pub fn diagnostics(dag: &Dag) -> Vec<Diagnostic> {
+    let mut verifier = Verifier::new();
+    verifier.dag(dag, "");
+    verifier.diagnostics
 }
 
-fn all_top_level_nodes_are_typeless(dag: &Dag) -> Result {
-    for node in dag.nodes.iter() {
-        if !node.ty().is_nothing() {
-            return Err(format!("all top level nodes must be typeless: {:?}", node));
+/// Finds every problem in the DAGs of a function.
+///
+/// There is one DAG per block, in order. Registers set in one DAG
+/// may be used by the DAGs after it.
+pub fn function_diagnostics(dags: &[Dag]) -> Vec<Diagnostic> {
+    let mut verifier = Verifier::new();
+
+    for (index, dag) in dags.iter().enumerate() {
+        verifier.dag(dag, &format!("dag {}, ", index + 1));
+    }
+
+    verifier.diagnostics
+}
+
+fn first(diagnostics: Vec<Diagnostic>) -> Result {
+    match diagnostics.into_iter().next() {
+        Some(d) => Err(d),
+        None => Ok(()),
+    }
+}
+
+struct Verifier
+{
+    /// The registers which have been set so far.
+    registers: HashSet<util::Id>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Verifier
+{
+    fn new() -> Self {
+        Verifier {
+            registers: HashSet::new(),
+            diagnostics: Vec::new(),
+        }
+    }
+
+    fn dag(&mut self, dag: &Dag, prefix: &str) {
+        for (index, node) in dag.nodes.iter().enumerate() {
+            let path = format!("{}node {}", prefix, index + 1);
+
+            if self.node(node, &path) && !node.ty().is_nothing() {
+                self.report("all top level nodes must be typeless", node, &path);
+            }
+        }
+    }
+
+    /// Verifies a node and its operands.
+    ///
+    /// Returns `false` if the node is too broken for its type to be known.
+    fn node(&mut self, node: &Node, path: &str) -> bool {
+        match node.kind {
+            NodeKind::Leaf(ref value) => {
+                if let Value::RegisterRef(ref r) = *value {
+                    if !self.registers.contains(&r.register_id) {
+                        self.report("registers must be set before they are used", node, path);
+                    }
+                }
+
+                true
+            },
+            NodeKind::Branch(ref branch) => self.branch(node, branch, path),
+        }
+    }
+
+    fn branch(&mut self, node: &Node, branch: &Branch, path: &str) -> bool {
+        let opcode = branch.opcode;
+        let operand_path = |i: usize| format!("{}, operand {}", path, i + 1);
+
+        if !opcode.accepts_operand_count(branch.operands.len()) {
+            let msg = format!("'{}' cannot take {} operands", opcode.mnemonic(), branch.operands.len());
+            self.report(msg, node, path);
+
+            for (i, operand) in branch.operands.iter().enumerate() {
+                self.node(operand, &operand_path(i));
+            }
+            return false;
+        }
+
+        if opcode == OpCode::Set {
+            // The value is evaluated before the register is set, so
+            // it cannot refer to the register itself.
+            let valid = self.node(&branch.operands[1], &operand_path(1));
+
+            match branch.operands[0].kind {
+                NodeKind::Leaf(Value::RegisterRef(ref r)) => {
+                    self.registers.insert(r.register_id);
+                },
+                _ => self.report("the destination of a set must be a register",
+                                 &branch.operands[0], &operand_path(0)),
+            }
+
+            return valid;
+        }
+
+        let mut valid = true;
+        for (i, operand) in branch.operands.iter().enumerate() {
+            valid &= self.node(operand, &operand_path(i));
+        }
+
+        if !valid {
+            return false;
+        }
+
+        if opcode.is_arithmetic() {
+            let ty = branch.operands[0].ty();
+
+            if branch.operands.iter().any(|op| op.ty() != ty) {
+                let msg = format!("the operands of '{}' must all have the same type", opcode.mnemonic());
+                self.report(msg, node, path);
+            }
         }
+
+        if opcode == OpCode::Sext || opcode == OpCode::Zext {
+            let to = match branch.operands[0].kind {
+                NodeKind::Leaf(Value::ConstantInteger(ref c)) => c.value,
+                _ => {
+                    let msg = format!("the width given to '{}' must be a constant integer", opcode.mnemonic());
+                    self.report(msg, &branch.operands[0], &operand_path(0));
+                    return false;
+                },
+            };
+
+            let from = branch.operands[1].ty().bit_width() as i64;
+
+            if to <= from {
+                let msg = format!("'{}' must widen its operand, but {} bits is not wider than {}",
+                                  opcode.mnemonic(), to, from);
+                self.report(msg, node, path);
+            }
+        }
+
+        true
     }
 
-    Ok(())
+    fn report<S>(&mut self, message: S, node: &Node, path: &str)
+        where S: Into<String> {
+        self.diagnostics.push(Diagnostic::error(message)
+            .with_note(format!("at {}", path))
+            .with_note(format!("the node is {:?}", node)));
+    }
 }
 
+#[cfg(test)]
+mod test
+{
+    use {Dag,Node,OpCode,Type};
+
+    fn messages(nodes: Vec<Node>) -> Vec<String> {
+        super::diagnostics(&Dag::new(nodes)).into_iter().map(|d| {
+            format!("{} ({})", d.message, d.notes[0])
+        }).collect()
+    }
+
+    #[test]
+    fn accepts_valid_dags() {
+        let reg = Node::new_register_ref(Type::i8());
+
+        assert!(messages(vec![
+            Node::set(reg.clone(), Node::add(&[Node::i(8, 1), Node::i(8, 2)])),
+            Node::branch(OpCode::Ret, vec![Node::zext(16, reg)]),
+        ]).is_empty());
+    }
+
+    #[test]
+    fn rejects_bad_operands() {
+        assert_eq!(messages(vec![
+            Node::branch(OpCode::Ret, vec![Node::add(&[Node::i(8, 1), Node::i(16, 2)])]),
+            Node::branch(OpCode::Ret, vec![Node::branch(OpCode::Mul, vec![Node::i(8, 1)])]),
+            Node::branch(OpCode::Ret, vec![Node::sext(8, Node::i(16, 1))]),
+        ]), vec![
+            "the operands of 'add' must all have the same type (at node 1, operand 1)",
+            "'mul' cannot take 1 operands (at node 2, operand 1)",
+            "'sext' must widen its operand, but 8 bits is not wider than 16 (at node 3, operand 1)",
+        ]);
+    }
+
+    #[test]
+    fn accepts_registers_set_in_earlier_dags() {
+        let reg = Node::new_register_ref(Type::i8());
+
+        let dags = vec![
+            Dag::new(vec![Node::set(reg.clone(), Node::i(8, 1))]),
+            Dag::new(vec![Node::branch(OpCode::Ret, vec![reg.clone()])]),
+        ];
+
+        assert!(super::function_diagnostics(&dags).is_empty());
+
+        let messages: Vec<_> = super::function_diagnostics(&[dags[1].clone(), dags[0].clone()])
+            .into_iter().map(|d| format!("{} ({})", d.message, d.notes[0])).collect();
+        assert_eq!(messages, vec!["registers must be set before they are used (at dag 1, node 1, operand 1)"]);
+    }
+
+    #[test]
+    fn rejects_registers_used_before_being_set() {
+        let reg = Node::new_register_ref(Type::i8());
+
+        assert_eq!(messages(vec![
+            Node::set(reg.clone(), Node::add(&[reg.clone(), Node::i(8, 2)])),
+            Node::set(Node::i(8, 1), Node::i(8, 2)),
+        ]), vec![
+            "registers must be set before they are used (at node 1, operand 2, operand 1)",
+            "the destination of a set must be a register (at node 2, operand 1)",
+        ]);
+    }
+}
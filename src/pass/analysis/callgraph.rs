@@ -0,0 +1,285 @@
+use ir;
+use util::{self, Identifiable};
+
+use std::collections::{HashMap,HashSet};
+use std::fmt::Write;
+
+/// A call graph for a module.
+///
+/// Functions are identified by their IDs. There is an edge from
+/// a function to every function it directly calls.
+#[derive(Clone,Debug)]
+pub struct CallGraph
+{
+    /// The functions in the order they appear in the module.
+    functions: Vec<util::Id>,
+    names: HashMap<util::Id, String>,
+
+    callees: HashMap<util::Id, Vec<util::Id>>,
+    callers: HashMap<util::Id, Vec<util::Id>>,
+
+    /// The strongly connected components, in bottom-up order.
+    sccs: Vec<Vec<util::Id>>,
+}
+
+impl CallGraph
+{
+    /// Builds the call graph of a module.
+    pub fn new(module: &ir::Module) -> Self {
+        let functions: Vec<_> = module.functions().map(|f| f.get_id()).collect();
+        let names = module.functions().map(|f| (f.get_id(), f.name().to_owned())).collect();
+
+        let mut callees = HashMap::new();
+        let mut callers: HashMap<_,Vec<_>> = HashMap::new();
+
+        for &id in functions.iter() {
+            callers.insert(id, Vec::new());
+        }
+
+        for func in module.functions() {
+            let mut targets = Vec::new();
+
+            for value in func.values() {
+                self::calls_in_value(value, &mut targets);
+            }
+
+            for &target in targets.iter() {
+                let preds = callers.entry(target).or_insert_with(Vec::new);
+
+                if !preds.contains(&func.get_id()) {
+                    preds.push(func.get_id());
+                }
+            }
+
+            callees.insert(func.get_id(), targets);
+        }
+
+        let mut graph = CallGraph {
+            functions: functions,
+            names: names,
+            callees: callees,
+            callers: callers,
+            sccs: Vec::new(),
+        };
+
+        graph.sccs = graph.find_sccs();
+        graph
+    }
+
+    /// Gets the functions in the order they appear in the module.
+    pub fn functions(&self) -> ::std::slice::Iter<util::Id> {
+        self.functions.iter()
+    }
+
+    /// Gets the functions that a function calls.
+    pub fn callees(&self, func: util::Id) -> &[util::Id] {
+        self.callees.get(&func).map(|c| &c[..]).unwrap_or(&[])
+    }
+
+    /// Gets the functions that call a function.
+    pub fn callers(&self, func: util::Id) -> &[util::Id] {
+        self.callers.get(&func).map(|c| &c[..]).unwrap_or(&[])
+    }
+
+    /// Checks if a function can call itself, either directly or
+    /// through other functions.
+    pub fn is_recursive(&self, func: util::Id) -> bool {
+        self.callees(func).contains(&func) ||
+            self.sccs.iter().any(|scc| scc.len() > 1 && scc.contains(&func))
+    }
+
+    /// Gets the strongly connected components of the graph.
+    ///
+    /// The components are in bottom-up order, so every component comes
+    /// after the components it calls into. Functions which call each
+    /// other are in the same component.
+    pub fn sccs(&self) -> &[Vec<util::Id>] {
+        &self.sccs
+    }
+
+    /// Gets the functions in bottom-up order, so that callees
+    /// are visited before their callers where possible.
+    pub fn bottom_up(&self) -> Vec<util::Id> {
+        self.sccs.iter().flat_map(|scc| scc.iter().cloned()).collect()
+    }
+
+    fn find_sccs(&self) -> Vec<Vec<util::Id>> {
+        let mut state = Tarjan {
+            graph: self,
+            index: 0,
+            indices: HashMap::new(),
+            lowlinks: HashMap::new(),
+            stack: Vec::new(),
+            on_stack: HashSet::new(),
+            sccs: Vec::new(),
+        };
+
+        for &func in self.functions.iter() {
+            if !state.indices.contains_key(&func) {
+                state.visit(func);
+            }
+        }
+
+        state.sccs
+    }
+
+    /// Writes the graph in the Graphviz DOT format.
+    pub fn dot(&self) -> String {
+        let mut dot = String::new();
+
+        writeln!(dot, "digraph callgraph {{").unwrap();
+
+        for &func in self.functions.iter() {
+            writeln!(dot, "  \"{}\";", self.name(func)).unwrap();
+        }
+
+        for &func in self.functions.iter() {
+            for &callee in self.callees(func) {
+                writeln!(dot, "  \"{}\" -> \"{}\";", self.name(func), self.name(callee)).unwrap();
+            }
+        }
+
+        writeln!(dot, "}}").unwrap();
+        dot
+    }
+
+    fn name(&self, func: util::Id) -> String {
+        self.names.get(&func).cloned().unwrap_or_else(|| format!("{}", func))
+    }
+}
+
+/// The state of Tarjan's strongly connected components algorithm.
+struct Tarjan<'a>
+{
+    graph: &'a CallGraph,
+    index: usize,
+    indices: HashMap<util::Id, usize>,
+    lowlinks: HashMap<util::Id, usize>,
+    stack: Vec<util::Id>,
+    on_stack: HashSet<util::Id>,
+    sccs: Vec<Vec<util::Id>>,
+}
+
+impl<'a> Tarjan<'a>
+{
+    fn visit(&mut self, func: util::Id) {
+        self.indices.insert(func, self.index);
+        self.lowlinks.insert(func, self.index);
+        self.index += 1;
+
+        self.stack.push(func);
+        self.on_stack.insert(func);
+
+        for &callee in self.graph.callees(func) {
+            if !self.indices.contains_key(&callee) {
+                self.visit(callee);
+
+                let lowlink = ::std::cmp::min(self.lowlinks[&func], self.lowlinks[&callee]);
+                self.lowlinks.insert(func, lowlink);
+            } else if self.on_stack.contains(&callee) {
+                let lowlink = ::std::cmp::min(self.lowlinks[&func], self.indices[&callee]);
+                self.lowlinks.insert(func, lowlink);
+            }
+        }
+
+        // `func` is the root of a component.
+        if self.lowlinks[&func] == self.indices[&func] {
+            let mut scc = Vec::new();
+
+            loop {
+                let member = self.stack.pop().unwrap();
+                self.on_stack.remove(&member);
+                scc.push(member);
+
+                if member == func {
+                    break;
+                }
+            }
+
+            scc.reverse();
+            self.sccs.push(scc);
+        }
+    }
+}
+
+/// Finds the functions directly called inside a value.
+fn calls_in_value(value: &ir::Value, targets: &mut Vec<util::Id>) {
+    match value.node {
+        ir::Expression::Register(ref reg) => return self::calls_in_value(&reg.value, targets),
+        ir::Expression::Instruction(ir::Instruction::Call(ref call)) => {
+            if let ir::Expression::FunctionRef(ref f) = call.target().node {
+                if !targets.contains(&f.function_id()) {
+                    targets.push(f.function_id());
+                }
+            }
+        },
+        _ => (),
+    }
+
+    for subvalue in value.node.subvalues() {
+        self::calls_in_value(subvalue, targets);
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::CallGraph;
+    use ir::{self,Expression};
+    use util::Identifiable;
+
+    fn function(name: &str, callees: &[&ir::Function]) -> ir::Function {
+        let mut entry = ir::Block::empty("entry");
+        for callee in callees {
+            entry.append_value(Expression::call(Expression::function_ref(callee)));
+        }
+        entry.append_value(Expression::ret_void());
+
+        let mut func = ir::Function::empty(name, ir::Signature::empty());
+        func.append_block(entry);
+        func
+    }
+
+    #[test]
+    fn orders_components_bottom_up() {
+        // main -> even <-> odd -> leaf
+        let leaf = function("leaf", &[]);
+        let mut even = function("even", &[]);
+        let odd = function("odd", &[&even, &leaf]);
+        even = {
+            let id = even.get_id();
+            let mut f = function("even", &[&odd]);
+            f.internal_set_id(id);
+            f
+        };
+        let main = function("main", &[&even]);
+
+        let module = ir::Module::empty().function(main.clone())
+                                        .function(even.clone())
+                                        .function(odd.clone())
+                                        .function(leaf.clone());
+        let graph = CallGraph::new(&module);
+
+        let sccs = graph.sccs();
+        assert_eq!(sccs.len(), 3);
+        assert_eq!(sccs[0], vec![leaf.get_id()]);
+        assert_eq!(sccs[1].len(), 2);
+        assert_eq!(sccs[2], vec![main.get_id()]);
+
+        assert!(graph.is_recursive(even.get_id()));
+        assert!(graph.is_recursive(odd.get_id()));
+        assert!(!graph.is_recursive(main.get_id()));
+        assert_eq!(graph.callers(leaf.get_id()), &[odd.get_id()]);
+    }
+
+    #[test]
+    fn exports_dot() {
+        let leaf = function("leaf", &[]);
+        let main = function("main", &[&leaf]);
+
+        let module = ir::Module::empty().function(main).function(leaf);
+        let dot = CallGraph::new(&module).dot();
+
+        assert_eq!(dot, "digraph callgraph {\n  \"main\";\n  \"leaf\";\n  \"main\" -> \"leaf\";\n}\n");
+    }
+}
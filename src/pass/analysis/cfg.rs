@@ -0,0 +1,173 @@
+use ir;
+use util::{self, Identifiable};
+
+use std::collections::{HashMap,HashSet};
+
+/// A control flow graph for a single function.
+///
+/// Blocks are identified by their IDs. The edges are derived
+/// from the terminator of each block.
+///
+/// A `br` only has a single target, so if its condition is not
+/// `true`, control falls through to the next block in the function.
+#[derive(Clone,Debug)]
+pub struct ControlFlowGraph
+{
+    /// The blocks in the order they appear in the function.
+    blocks: Vec<util::Id>,
+
+    successors: HashMap<util::Id, Vec<util::Id>>,
+    predecessors: HashMap<util::Id, Vec<util::Id>>,
+}
+
+impl ControlFlowGraph
+{
+    /// Builds the control flow graph of a function.
+    pub fn new(func: &ir::Function) -> Self {
+        let blocks: Vec<_> = func.blocks().map(|b| b.get_id()).collect();
+
+        let mut successors = HashMap::new();
+        let mut predecessors: HashMap<_,Vec<_>> = HashMap::new();
+
+        for &id in blocks.iter() {
+            predecessors.insert(id, Vec::new());
+        }
+
+        for (index, block) in func.blocks().enumerate() {
+            let next = blocks.get(index + 1).cloned();
+            let succs = self::successors(block, next);
+
+            for &succ in succs.iter() {
+                let preds = predecessors.entry(succ).or_insert_with(Vec::new);
+
+                if !preds.contains(&block.get_id()) {
+                    preds.push(block.get_id());
+                }
+            }
+
+            successors.insert(block.get_id(), succs);
+        }
+
+        ControlFlowGraph {
+            blocks: blocks,
+            successors: successors,
+            predecessors: predecessors,
+        }
+    }
+
+    /// Gets the entry block, if the function has any blocks.
+    pub fn entry(&self) -> Option<util::Id> {
+        self.blocks.first().cloned()
+    }
+
+    /// Gets the blocks in the order they appear in the function.
+    pub fn blocks(&self) -> ::std::slice::Iter<util::Id> {
+        self.blocks.iter()
+    }
+
+    /// Gets the blocks that control can flow to from a block.
+    pub fn successors(&self, block: util::Id) -> &[util::Id] {
+        self.successors.get(&block).map(|s| &s[..]).unwrap_or(&[])
+    }
+
+    /// Gets the blocks that can flow into a block.
+    pub fn predecessors(&self, block: util::Id) -> &[util::Id] {
+        self.predecessors.get(&block).map(|p| &p[..]).unwrap_or(&[])
+    }
+
+    /// Gets the set of blocks that are reachable from the entry block.
+    pub fn reachable(&self) -> HashSet<util::Id> {
+        let mut visited = HashSet::new();
+        let mut worklist: Vec<_> = self.entry().into_iter().collect();
+
+        while let Some(id) = worklist.pop() {
+            if visited.insert(id) {
+                worklist.extend(self.successors(id).iter().cloned());
+            }
+        }
+
+        visited
+    }
+
+    /// Gets the blocks in reverse postorder, starting from the entry block.
+    ///
+    /// Unreachable blocks are not included.
+    pub fn reverse_postorder(&self) -> Vec<util::Id> {
+        let mut visited = HashSet::new();
+        let mut order = Vec::new();
+
+        if let Some(entry) = self.entry() {
+            self.postorder_visit(entry, &mut visited, &mut order);
+        }
+
+        order.reverse();
+        order
+    }
+
+    fn postorder_visit(&self,
+                       id: util::Id,
+                       visited: &mut HashSet<util::Id>,
+                       order: &mut Vec<util::Id>) {
+        if !visited.insert(id) {
+            return;
+        }
+
+        for &succ in self.successors(id) {
+            self.postorder_visit(succ, visited, order);
+        }
+
+        order.push(id);
+    }
+}
+
+/// Gets the successors of a block, given the block which follows it.
+pub fn successors(block: &ir::Block,
+                  next: Option<util::Id>) -> Vec<util::Id> {
+    let terminator = match block.values().last() {
+        Some(value) => value,
+        None => return next.into_iter().collect(),
+    };
+
+    match terminator.node {
+        ir::Expression::Instruction(ir::Instruction::Return(..)) => Vec::new(),
+        ir::Expression::Instruction(ir::Instruction::Break(ref br)) => self::branch_successors(br, next),
+        _ => next.into_iter().collect(),
+    }
+}
+
+/// Gets the blocks a branch may go to, given the block which
+/// follows it.
+pub fn branch_successors(br: &ir::instruction::Break,
+                         next: Option<util::Id>) -> Vec<util::Id> {
+    let target = match br.target().node {
+        ir::Expression::BlockRef(ref r) => Some(r.block_id()),
+        _ => None,
+    };
+
+    match *br.condition() {
+        ir::Condition::True => target.into_iter().collect(),
+        ir::Condition::False => next.into_iter().collect(),
+        _ => {
+            let mut succs: Vec<_> = target.into_iter().collect();
+
+            if let Some(next) = next {
+                if !succs.contains(&next) {
+                    succs.push(next);
+                }
+            }
+            succs
+        },
+    }
+}
+
+/// Checks if control can flow from the end of a block into the block
+/// which follows it.
+pub fn falls_through(block: &ir::Block) -> bool {
+    match block.values().last().map(|v| &v.node) {
+        Some(&ir::Expression::Instruction(ir::Instruction::Return(..))) => false,
+        Some(&ir::Expression::Instruction(ir::Instruction::Break(ref br))) => {
+            *br.condition() != ir::Condition::True
+        },
+        _ => true,
+    }
+}
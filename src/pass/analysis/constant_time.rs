@@ -0,0 +1,376 @@
+use {Metadata,Id,Info,Analysis};
+use ir::{self,Value,Expression,Instruction,ComplexityHint};
+use analysis::{ControlFlowGraph,DominatorTree,LoopInfo};
+use util::{self,Identifiable,Diagnostic};
+
+use std::collections::HashMap;
+use std;
+
+/// Checks that functions marked as constant-time cannot leak
+/// their arguments through timing.
+///
+/// Every argument of a constant-time function is treated as secret,
+/// along with every value computed from one. This rejects
+///   * branches whose conditions depend on a secret
+///   * divisions and remainders of secrets, which take a variable
+///     amount of time on most hardware
+///   * loops which can be left from more than one place
+pub struct ConstantTimeVerifier
+{
+    diagnostics: Vec<Violation>,
+}
+
+impl ConstantTimeVerifier
+{
+    /// Creates a new constant-time verifier.
+    pub fn new() -> Self {
+        ConstantTimeVerifier {
+            diagnostics: Vec::new(),
+        }
+    }
+
+    /// Gets the problems found in constant-time functions.
+    pub fn diagnostics(&self) -> std::slice::Iter<Violation> {
+        self.diagnostics.iter()
+    }
+}
+
+impl Default for ConstantTimeVerifier
+{
+    fn default() -> Self { ConstantTimeVerifier::new() }
+}
+
+impl Metadata for ConstantTimeVerifier
+{
+    fn id(&self) -> Id { Id(0x4c9e07b2) }
+    fn name(&self) -> &'static str { "constant time verifier" }
+
+    fn report(&self) -> Vec<Diagnostic> {
+        self.diagnostics.iter().map(|v| Diagnostic::error(v.to_string())).collect()
+    }
+}
+
+impl Analysis for ConstantTimeVerifier
+{
+    fn run_function(&mut self,
+                    function: &ir::Function) {
+        self.diagnostics.extend(self::check(function));
+    }
+}
+
+// TODO: blamket impl for all passes
+impl Into<Info> for Box<ConstantTimeVerifier>
+{
+    fn into(self) -> Info {
+        Info::Analysis(self)
+    }
+}
+
+/// A construct which makes the running time of a constant-time
+/// function depend on its arguments.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub enum Violation
+{
+    /// A branch condition depends on a secret.
+    SecretBranch {
+        function: String,
+        /// The name of the secret the condition depends on.
+        value: String,
+    },
+    /// A secret is divided, or used as a divisor.
+    VariableTimeDivision {
+        function: String,
+        /// The name of the secret being divided.
+        value: String,
+    },
+    /// A loop can be left from more than one place.
+    EarlyExitLoop {
+        function: String,
+        /// The name of the loop header.
+        header: String,
+    },
+}
+
+impl std::fmt::Display for Violation
+{
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            Violation::SecretBranch { ref function, ref value } => {
+                write!(fmt, "branch depends on secret %{} in constant-time function @{}", value, function)
+            },
+            Violation::VariableTimeDivision { ref function, ref value } => {
+                write!(fmt, "variable-time division of secret %{} in constant-time function @{}", value, function)
+            },
+            Violation::EarlyExitLoop { ref function, ref header } => {
+                write!(fmt, "loop '{}' has more than one exit in constant-time function @{}", header, function)
+            },
+        }
+    }
+}
+
+/// Checks if a function is marked as constant-time.
+pub fn is_constant_time(func: &ir::Function) -> bool {
+    func.complexity_hint() == ComplexityHint::ConstantTime
+}
+
+/// Finds the violations in a function.
+///
+/// Functions which are not marked as constant-time have none.
+pub fn check(func: &ir::Function) -> Vec<Violation> {
+    if !self::is_constant_time(func) {
+        return Vec::new();
+    }
+
+    let params = self::parameters(func);
+    let secrets = self::secrets(func, &params);
+    let mut violations = Vec::new();
+
+    for value in func.values() {
+        self::check_value(func, value, &params, &secrets, &mut violations);
+    }
+
+    let graph = ControlFlowGraph::new(func);
+    let loops = LoopInfo::new(&graph, &DominatorTree::new(&graph));
+
+    for l in loops.loops() {
+        // Returning from inside the loop also leaves it.
+        let exits = l.blocks().iter().filter(|&&block| {
+            let successors = graph.successors(block);
+            successors.is_empty() || successors.iter().any(|&s| !l.contains(s))
+        }).count();
+
+        if exits > 1 {
+            let header = func.find_block(l.header()).unwrap();
+
+            violations.push(Violation::EarlyExitLoop {
+                function: func.name().to_owned(),
+                header: header.name().to_owned(),
+            });
+        }
+    }
+
+    violations
+}
+
+/// Counts the branches in a function which are not unconditional.
+pub fn conditional_branches(func: &ir::Function) -> usize {
+    func.values().filter(|v| match v.node {
+        Expression::Instruction(Instruction::Break(ref br)) => !br.condition().is_trivial(),
+        _ => false,
+    }).count()
+}
+
+/// Runs a transform over a function.
+///
+/// If the function is constant-time and the transform would add
+/// conditional branches to it, the function is left unchanged.
+pub fn transform<F>(func: ir::Function, f: F) -> ir::Function
+    where F: FnOnce(ir::Function) -> ir::Function {
+    if !self::is_constant_time(&func) {
+        return f(func);
+    }
+
+    let transformed = f(func.clone());
+
+    if self::conditional_branches(&transformed) > self::conditional_branches(&func) {
+        func
+    } else {
+        transformed
+    }
+}
+
+/// Finds the registers which are computed from arguments.
+///
+/// Each register is mapped to the name of an argument it depends on.
+fn secrets(func: &ir::Function,
+           params: &HashMap<util::Id, String>) -> HashMap<util::Id, String> {
+    let mut secrets = HashMap::new();
+
+    // Phis may refer to registers defined later on.
+    loop {
+        let mut changed = false;
+
+        for value in func.values() {
+            if let Expression::Register(ref reg) = value.node {
+                if secrets.contains_key(&reg.get_id()) {
+                    continue;
+                }
+
+                if let Some(source) = self::source(&reg.value, params, &secrets) {
+                    secrets.insert(reg.get_id(), source);
+                    changed = true;
+                }
+            }
+        }
+
+        if !changed {
+            return secrets;
+        }
+    }
+}
+
+/// Maps the parameters of a function to their names.
+fn parameters(func: &ir::Function) -> HashMap<util::Id, String> {
+    func.signature().parameters().map(|p| (p.get_id(), p.name().to_owned())).collect()
+}
+
+/// Gets the name of an argument that a value depends on.
+fn source(value: &Value,
+          params: &HashMap<util::Id, String>,
+          secrets: &HashMap<util::Id, String>) -> Option<String> {
+    match value.node {
+        Expression::ArgumentRef(ref a) => params.get(&a.parameter_id()).cloned(),
+        Expression::RegisterRef(ref r) => secrets.get(&r.register_id()).cloned(),
+        Expression::Register(ref reg) => self::source(&reg.value, params, secrets),
+        ref node => node.subvalues().into_iter().filter_map(|v| self::source(v, params, secrets)).next(),
+    }
+}
+
+fn check_value(func: &ir::Function,
+               value: &Value,
+               params: &HashMap<util::Id, String>,
+               secrets: &HashMap<util::Id, String>,
+               violations: &mut Vec<Violation>) {
+    let secret = |values: Vec<&Value>| {
+        values.into_iter().filter_map(|v| self::source(v, params, secrets)).next()
+    };
+
+    match value.node {
+        Expression::Register(ref reg) => {
+            return self::check_value(func, &reg.value, params, secrets, violations);
+        },
+        Expression::Instruction(Instruction::Break(ref br)) => {
+            if let Some(value) = secret(br.condition().values()) {
+                violations.push(Violation::SecretBranch { function: func.name().to_owned(), value: value });
+            }
+        },
+        Expression::Instruction(ref i @ Instruction::Div(..)) |
+        Expression::Instruction(ref i @ Instruction::Rem(..)) => {
+            if let Some(value) = secret(i.subvalues()) {
+                violations.push(Violation::VariableTimeDivision { function: func.name().to_owned(), value: value });
+            }
+        },
+        _ => (),
+    }
+
+    for subvalue in value.node.subvalues() {
+        self::check_value(func, subvalue, params, secrets, violations);
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::Violation;
+    use ir::{self,Expression,Value,Condition,ComplexityHint,Type};
+
+    fn function(blocks: Vec<ir::Block>) -> (ir::Function, ir::Parameter) {
+        let param = ir::Parameter::new("key".to_owned(), Type::i8());
+        let sig = ir::Signature::new(vec![param.clone()], vec![Type::i8()]);
+
+        let mut func = ir::Function::new("f", sig, blocks);
+        func.complexity_hint = ComplexityHint::ConstantTime;
+        (func, param)
+    }
+
+    fn compare(lhs: Expression) -> Condition {
+        Condition::Equal(Box::new(lhs.into()), Box::new(Expression::i8(0).into()))
+    }
+
+    #[test]
+    fn rejects_secret_branches_through_registers() {
+        let (mut func, param) = function(Vec::new());
+
+        let shifted = Expression::register_named("shifted", Expression::shl(
+            Expression::argument_ref(&param), Expression::i8(1)));
+        let shifted_ref = match shifted {
+            Expression::Register(ref r) => Expression::register_ref(r),
+            _ => unreachable!(),
+        };
+
+        let mut entry = ir::Block::empty("entry");
+        let mut exit = ir::Block::empty("exit");
+        entry.append_value(shifted.clone());
+        entry.append_value(Expression::br(compare(shifted_ref), Expression::block_ref(&exit)));
+        entry.append_value(Expression::ret(Value::new(Expression::i8(0))));
+        exit.append_value(Expression::ret(Value::new(Expression::i8(1))));
+        func.append_block(entry);
+        func.append_block(exit);
+
+        let violations = super::check(&func);
+
+        assert_eq!(violations, vec![Violation::SecretBranch { function: "f".to_owned(), value: "key".to_owned() }]);
+        assert_eq!(violations[0].to_string(), "branch depends on secret %key in constant-time function @f");
+    }
+
+    #[test]
+    fn rejects_secret_division() {
+        let (mut func, param) = function(Vec::new());
+
+        let mut entry = ir::Block::empty("entry");
+        entry.append_value(Expression::ret(Value::new(Expression::add(
+            Expression::i8(1), Expression::div(Expression::argument_ref(&param), Expression::i8(3))))));
+        func.append_block(entry);
+
+        assert_eq!(super::check(&func),
+                   vec![Violation::VariableTimeDivision { function: "f".to_owned(), value: "key".to_owned() }]);
+    }
+
+    #[test]
+    fn rejects_early_exit_loops() {
+        let (mut func, _) = function(Vec::new());
+
+        let mut header = ir::Block::empty("header");
+        let mut body = ir::Block::empty("body");
+        let mut latch = ir::Block::empty("latch");
+        let mut exit = ir::Block::empty("exit");
+
+        // The loop conditions are public, but the loop can be left
+        // from both the header and the body.
+        header.append_value(Expression::br(compare(Expression::i8(1)), Expression::block_ref(&exit)));
+        body.append_value(Expression::br(compare(Expression::i8(2)), Expression::block_ref(&exit)));
+        latch.append_value(Expression::br(Condition::True, Expression::block_ref(&header)));
+        exit.append_value(Expression::ret(Value::new(Expression::i8(0))));
+
+        func.append_block(header);
+        func.append_block(body);
+        func.append_block(latch);
+        func.append_block(exit);
+
+        assert_eq!(super::check(&func),
+                   vec![Violation::EarlyExitLoop { function: "f".to_owned(), header: "header".to_owned() }]);
+    }
+
+    #[test]
+    fn ignores_functions_without_hint() {
+        let (mut func, param) = function(Vec::new());
+        func.complexity_hint = ComplexityHint::None;
+
+        let mut entry = ir::Block::empty("entry");
+        entry.append_value(Expression::ret(Value::new(Expression::div(
+            Expression::argument_ref(&param), Expression::i8(3)))));
+        func.append_block(entry);
+
+        assert!(super::check(&func).is_empty());
+    }
+
+    #[test]
+    fn discards_transforms_which_add_branches() {
+        let (mut func, _) = function(Vec::new());
+
+        let mut entry = ir::Block::empty("entry");
+        entry.append_value(Expression::ret(Value::new(Expression::i8(0))));
+        func.append_block(entry);
+
+        let add_branch = |mut f: ir::Function| {
+            let target = Expression::block_ref(&f.blocks[0]);
+            f.blocks[0].body.insert(0, Expression::br(compare(Expression::i8(1)), target).into());
+            f
+        };
+
+        assert_eq!(super::transform(func.clone(), add_branch).blocks[0].body.len(), 1);
+
+        func.complexity_hint = ComplexityHint::None;
+        assert_eq!(super::transform(func, add_branch).blocks[0].body.len(), 2);
+    }
+}
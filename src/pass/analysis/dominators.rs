@@ -0,0 +1,160 @@
+use analysis::ControlFlowGraph;
+use util;
+
+use std::collections::HashMap;
+
+/// The dominator tree of a function.
+///
+/// A block `a` dominates a block `b` if every path from the entry
+/// block to `b` goes through `a`.
+///
+/// Blocks which are unreachable from the entry block are not part
+/// of the tree.
+#[derive(Clone,Debug)]
+pub struct DominatorTree
+{
+    root: Option<util::Id>,
+
+    /// The immediate dominator of each block, excluding the root.
+    idoms: HashMap<util::Id, util::Id>,
+    children: HashMap<util::Id, Vec<util::Id>>,
+}
+
+impl DominatorTree
+{
+    /// Builds the dominator tree from a control flow graph.
+    ///
+    /// This uses the algorithm from "A Simple, Fast Dominance Algorithm"
+    /// by Cooper, Harvey and Kennedy.
+    pub fn new(cfg: &ControlFlowGraph) -> Self {
+        let order = cfg.reverse_postorder();
+        let indices: HashMap<_,_> = order.iter().enumerate().map(|(i,&id)| (id,i)).collect();
+
+        let root = match order.first() {
+            Some(&root) => root,
+            None => return DominatorTree {
+                root: None,
+                idoms: HashMap::new(),
+                children: HashMap::new(),
+            },
+        };
+
+        let mut idoms: HashMap<util::Id, util::Id> = HashMap::new();
+        idoms.insert(root, root);
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+
+            for &block in order.iter().skip(1) {
+                let mut new_idom = None;
+
+                for &pred in cfg.predecessors(block) {
+                    if !idoms.contains_key(&pred) {
+                        continue;
+                    }
+
+                    new_idom = Some(match new_idom {
+                        Some(current) => self::intersect(pred, current, &idoms, &indices),
+                        None => pred,
+                    });
+                }
+
+                if let Some(new_idom) = new_idom {
+                    if idoms.get(&block) != Some(&new_idom) {
+                        idoms.insert(block, new_idom);
+                        changed = true;
+                    }
+                }
+            }
+        }
+
+        idoms.remove(&root);
+
+        let mut children: HashMap<_,Vec<_>> = HashMap::new();
+        for &block in order.iter().skip(1) {
+            children.entry(idoms[&block]).or_insert_with(Vec::new).push(block);
+        }
+
+        DominatorTree {
+            root: Some(root),
+            idoms: idoms,
+            children: children,
+        }
+    }
+
+    /// Gets the root of the tree, which is the entry block.
+    pub fn root(&self) -> Option<util::Id> {
+        self.root
+    }
+
+    /// Gets the immediate dominator of a block.
+    ///
+    /// The root has no immediate dominator.
+    pub fn immediate_dominator(&self, block: util::Id) -> Option<util::Id> {
+        self.idoms.get(&block).cloned()
+    }
+
+    /// Gets the blocks which are immediately dominated by a block.
+    pub fn children(&self, block: util::Id) -> &[util::Id] {
+        self.children.get(&block).map(|c| &c[..]).unwrap_or(&[])
+    }
+
+    /// Checks if a block is part of the tree.
+    pub fn contains(&self, block: util::Id) -> bool {
+        self.root == Some(block) || self.idoms.contains_key(&block)
+    }
+
+    /// Checks if `a` dominates `b`.
+    ///
+    /// Every block dominates itself.
+    pub fn dominates(&self, a: util::Id, b: util::Id) -> bool {
+        if !self.contains(b) {
+            return false;
+        }
+
+        let mut current = Some(b);
+
+        while let Some(block) = current {
+            if block == a {
+                return true;
+            }
+
+            current = self.immediate_dominator(block);
+        }
+
+        false
+    }
+
+    /// Gets the blocks in the tree, with each block visited
+    /// before the blocks that it dominates.
+    pub fn preorder(&self) -> Vec<util::Id> {
+        let mut order = Vec::new();
+        let mut worklist: Vec<_> = self.root.into_iter().collect();
+
+        while let Some(block) = worklist.pop() {
+            order.push(block);
+            worklist.extend(self.children(block).iter().rev().cloned());
+        }
+
+        order
+    }
+}
+
+/// Finds the closest common dominator of two blocks.
+fn intersect(mut a: util::Id,
+             mut b: util::Id,
+             idoms: &HashMap<util::Id, util::Id>,
+             indices: &HashMap<util::Id, usize>) -> util::Id {
+    while a != b {
+        while indices[&a] > indices[&b] {
+            a = idoms[&a];
+        }
+
+        while indices[&b] > indices[&a] {
+            b = idoms[&b];
+        }
+    }
+
+    a
+}
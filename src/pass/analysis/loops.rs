@@ -0,0 +1,206 @@
+use analysis::{ControlFlowGraph,DominatorTree};
+use util;
+
+use std::collections::HashSet;
+
+/// A natural loop.
+#[derive(Clone,Debug)]
+pub struct Loop
+{
+    header: util::Id,
+    blocks: HashSet<util::Id>,
+    latches: Vec<util::Id>,
+
+    parent: Option<usize>,
+    depth: usize,
+}
+
+impl Loop
+{
+    /// Gets the header of the loop.
+    ///
+    /// The header dominates every block in the loop.
+    pub fn header(&self) -> util::Id { self.header }
+
+    /// Gets the blocks in the loop, including the header.
+    pub fn blocks(&self) -> &HashSet<util::Id> { &self.blocks }
+
+    /// Gets the blocks which branch back to the header.
+    pub fn latches(&self) -> &[util::Id] { &self.latches }
+
+    /// Gets the index of the loop which immediately contains this loop.
+    pub fn parent(&self) -> Option<usize> { self.parent }
+
+    /// Gets the number of loops which contain this loop, plus one.
+    pub fn depth(&self) -> usize { self.depth }
+
+    /// Checks if a block is part of the loop.
+    pub fn contains(&self, block: util::Id) -> bool {
+        self.blocks.contains(&block)
+    }
+
+    /// Gets the blocks outside the loop which branch into the header.
+    pub fn entries(&self, cfg: &ControlFlowGraph) -> Vec<util::Id> {
+        cfg.predecessors(self.header).iter().cloned()
+           .filter(|pred| !self.contains(*pred))
+           .collect()
+    }
+}
+
+/// The natural loops in a function.
+#[derive(Clone,Debug)]
+pub struct LoopInfo
+{
+    loops: Vec<Loop>,
+}
+
+impl LoopInfo
+{
+    /// Finds the natural loops of a function.
+    ///
+    /// Each back edge, which is an edge to a block that dominates
+    /// its source, forms a loop. Loops which share a header are merged.
+    pub fn new(cfg: &ControlFlowGraph,
+               dominators: &DominatorTree) -> Self {
+        let mut loops: Vec<Loop> = Vec::new();
+
+        for block in dominators.preorder() {
+            for &succ in cfg.successors(block) {
+                if !dominators.dominates(succ, block) {
+                    continue;
+                }
+
+                let body = self::natural_loop(succ, block, cfg);
+
+                match loops.iter_mut().find(|l| l.header == succ) {
+                    Some(l) => {
+                        l.blocks.extend(body);
+                        l.latches.push(block);
+                    },
+                    None => loops.push(Loop {
+                        header: succ,
+                        blocks: body,
+                        latches: vec![block],
+                        parent: None,
+                        depth: 1,
+                    }),
+                }
+            }
+        }
+
+        // The parent of a loop is the smallest other loop containing its header.
+        let parents: Vec<_> = loops.iter().enumerate().map(|(index, l)| {
+            loops.iter().enumerate()
+                 .filter(|&(other, outer)| other != index && outer.contains(l.header) &&
+                                           outer.blocks.len() > l.blocks.len())
+                 .min_by_key(|&(_, outer)| outer.blocks.len())
+                 .map(|(other, _)| other)
+        }).collect();
+
+        for (l, parent) in loops.iter_mut().zip(parents.iter()) {
+            l.parent = *parent;
+        }
+
+        for index in 0..loops.len() {
+            let mut depth = 1;
+            let mut current = loops[index].parent;
+
+            while let Some(parent) = current {
+                depth += 1;
+                current = loops[parent].parent;
+            }
+
+            loops[index].depth = depth;
+        }
+
+        LoopInfo { loops: loops }
+    }
+
+    /// Gets all of the loops.
+    pub fn loops(&self) -> ::std::slice::Iter<Loop> {
+        self.loops.iter()
+    }
+
+    /// Gets a loop by its index.
+    pub fn get(&self, index: usize) -> &Loop {
+        &self.loops[index]
+    }
+
+    /// Gets the innermost loop containing a block.
+    pub fn innermost(&self, block: util::Id) -> Option<&Loop> {
+        self.loops.iter()
+                  .filter(|l| l.contains(block))
+                  .max_by_key(|l| l.depth)
+    }
+
+    /// Gets the loop nesting depth of a block.
+    ///
+    /// Blocks which are not inside a loop have a depth of zero.
+    pub fn depth(&self, block: util::Id) -> usize {
+        self.innermost(block).map_or(0, |l| l.depth)
+    }
+}
+
+/// Finds the blocks of the loop formed by the back edge `latch -> header`.
+fn natural_loop(header: util::Id,
+                latch: util::Id,
+                cfg: &ControlFlowGraph) -> HashSet<util::Id> {
+    let mut blocks = HashSet::new();
+    blocks.insert(header);
+
+    let mut worklist = vec![latch];
+
+    while let Some(block) = worklist.pop() {
+        if blocks.insert(block) {
+            worklist.extend(cfg.predecessors(block).iter().cloned());
+        }
+    }
+
+    blocks
+}
+
+#[cfg(test)]
+mod test
+{
+    use ir::{self,Expression,Condition};
+    use analysis::{ControlFlowGraph,DominatorTree};
+    use util::Identifiable;
+    use super::LoopInfo;
+
+    #[test]
+    fn finds_nested_loops() {
+        let param = ir::Parameter::new("x".to_owned(), ir::Type::i8());
+        let condition = || Condition::Equal(Box::new(Expression::argument_ref(&param).into()),
+                                            Box::new(Expression::i8(0).into()));
+
+        let mut outer = ir::Block::empty("outer");
+        let mut inner = ir::Block::empty("inner");
+        let mut latch = ir::Block::empty("latch");
+        let mut exit = ir::Block::empty("exit");
+
+        inner.append_value(Expression::br(condition(), Expression::block_ref(&inner)));
+        latch.append_value(Expression::br(condition(), Expression::block_ref(&outer)));
+        exit.append_value(Expression::ret_void());
+
+        let ids: Vec<_> = [&outer, &inner, &latch, &exit].iter().map(|b| b.get_id()).collect();
+
+        outer.append_value(Expression::register(Expression::add(Expression::i8(1), Expression::i8(2))));
+
+        let mut func = ir::Function::empty("f", ir::Signature::empty());
+        func.append_block(outer);
+        func.append_block(inner);
+        func.append_block(latch);
+        func.append_block(exit);
+
+        let cfg = ControlFlowGraph::new(&func);
+        let loops = LoopInfo::new(&cfg, &DominatorTree::new(&cfg));
+
+        assert_eq!(loops.loops().count(), 2);
+        assert_eq!(loops.depth(ids[0]), 1);
+        assert_eq!(loops.depth(ids[1]), 2);
+        assert_eq!(loops.depth(ids[2]), 1);
+        assert_eq!(loops.depth(ids[3]), 0);
+        assert_eq!(loops.innermost(ids[1]).unwrap().header(), ids[1]);
+        assert_eq!(loops.innermost(ids[2]).unwrap().latches(), &[ids[2]]);
+    }
+}
@@ -1,2 +1,16 @@
+pub use self::cfg::ControlFlowGraph;
+pub use self::dominators::DominatorTree;
+pub use self::loops::{Loop,LoopInfo};
+pub use self::callgraph::CallGraph;
+pub use self::constant_time::ConstantTimeVerifier;
 
-
+/// Control flow graph construction.
+pub mod cfg;
+/// Dominator tree construction.
+pub mod dominators;
+/// Natural loop detection.
+pub mod loops;
+/// Call graph construction.
+pub mod callgraph;
+/// Constant-time verification.
+pub mod constant_time;
@@ -46,6 +46,13 @@ impl Metadata for Info
             Info::Transform(ref p) => p.name(),
         }
     }
+
+    fn report(&self) -> Vec<util::Diagnostic> {
+        match *self {
+            Info::Analysis(ref p) => p.report(),
+            Info::Transform(ref p) => p.report(),
+        }
+    }
 }
 
 /// A pass over a set of instructions.
@@ -60,6 +67,11 @@ pub trait Metadata
     }
 
     fn name(&self) -> &'static str;
+
+    /// Gets the problems that the pass has found.
+    fn report(&self) -> Vec<util::Diagnostic> {
+        Vec::new()
+    }
 }
 
 pub trait Analysis : Metadata
@@ -165,9 +177,22 @@ pub trait Transform : Metadata
     fn run_value_recursive(&mut self,
                            value: ir::Value) -> ir::Value {
 
+        let span = value.span;
+        let comments = value.comments;
+        let node = match value.node {
+            // Registers are not instructions, but their values are.
+            ir::Expression::Register(mut reg) => {
+                reg.value = Box::new(self.run_value_recursive(*reg.value));
+                ir::Expression::Register(reg)
+            },
+            node => node,
+        };
+
         let val = ir::Value {
             // Recurse from the deepest node to the root node.
-            node: value.node.map_subvalues(|v| self.run_value_recursive(v)),
+            node: node.map_subvalues(|v| self.run_value_recursive(v)),
+            span: span,
+            comments: comments,
         };
 
         self.run_value(val)
@@ -1,11 +1,14 @@
 use {Metadata,Info,Id};
 use ir;
+use util::Diagnostic;
 use std;
 
 /// The pass manager.
 pub struct Manager
 {
     passes: Vec<Info>,
+    /// The problems which stopped the passes from running.
+    invalid: Vec<Diagnostic>,
 }
 
 impl Manager
@@ -13,6 +16,7 @@ impl Manager
     pub fn empty() -> Self {
         Manager {
             passes: Vec::new(),
+            invalid: Vec::new(),
         }
     }
 
@@ -29,8 +33,23 @@ impl Manager
         self.passes.iter()
     }
 
+    /// Gets the problems found by every pass that has run.
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        self.invalid.iter().cloned().chain(self.passes.iter().flat_map(|p| p.report())).collect()
+    }
+
     /// Runs the pass manager.
+    ///
+    /// The passes only run on a module which passes the verifier,
+    /// otherwise it is returned unchanged and the problems are
+    /// reported as diagnostics.
     pub fn run(&mut self, mut module: ir::Module) -> ir::Module {
+        self.invalid = ir::verifier::diagnostics(&module);
+
+        if !self.invalid.is_empty() {
+            return module;
+        }
+
         let pass_list = self::build_pass_list(&self.passes);
 
         for pass_id in pass_list {
@@ -57,3 +76,24 @@ fn lookup_pass_mut(id: Id, passes: &mut [Info])
     -> Option<&mut Info> {
     passes.iter_mut().find(|p| p.id() == id)
 }
+
+#[cfg(test)]
+mod test
+{
+    use super::Manager;
+    use transforms::SimplifyCfg;
+    use ir;
+
+    #[test]
+    fn does_not_run_passes_on_invalid_modules() {
+        // The first branch is not at the end of its block.
+        let text = "fn @f(%a: i8) {\n  br %a eq i8 0, %zero\n  ret\n:zero\n  ret\n}\n";
+        let module = ir::read::textual(text.chars()).expect("parsing failed");
+
+        let mut pm = Manager::empty().add_pass(SimplifyCfg);
+        let module = pm.run(module);
+
+        assert_eq!(module.functions().next().unwrap().blocks().count(), 2);
+        assert!(pm.diagnostics().iter().any(|d| d.is_error()));
+    }
+}
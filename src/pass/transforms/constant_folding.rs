@@ -1,19 +1,64 @@
 use {Metadata,Id,Info,Transform};
 use ir;
+use util;
 
-/// An IR strength reduction pass.
-pub struct ConstantFolding;
+/// An IR constant folding pass.
+///
+/// Integer arithmetic wraps around according to the width and
+/// signedness of the operand type. Floating point arithmetic is
+/// done in the precision of the operand type, rounding to nearest.
+///
+/// Operations which cannot be folded, such as division by zero,
+/// are left as-is and recorded as diagnostics.
+pub struct ConstantFolding
+{
+    /// The problems found, along with where they were found.
+    diagnostics: Vec<(fold::Error, Option<util::Span>)>,
+}
+
+impl ConstantFolding
+{
+    /// Creates a new constant folding pass.
+    pub fn new() -> Self {
+        ConstantFolding {
+            diagnostics: Vec::new(),
+        }
+    }
+
+    /// Gets the problems found while folding, along with where
+    /// they were found.
+    pub fn diagnostics(&self) -> ::std::slice::Iter<(fold::Error, Option<util::Span>)> {
+        self.diagnostics.iter()
+    }
+}
+
+impl Default for ConstantFolding
+{
+    fn default() -> Self { ConstantFolding::new() }
+}
 
 impl Metadata for ConstantFolding
 {
     fn id(&self) -> Id { Id(0x32fabb11) }
     fn name(&self) -> &'static str { "constant folding" }
+
+    fn report(&self) -> Vec<util::Diagnostic> {
+        self.diagnostics.iter().map(|&(ref e, span)| {
+            util::Diagnostic::warning(e.to_string()).or_span(span)
+        }).collect()
+    }
 }
 
 impl Transform for ConstantFolding
 {
     fn run_value(&mut self, value: ir::Value) -> ir::Value {
-        self::fold::value(value)
+        match self::fold::try_value(value.clone()) {
+            Ok(folded) => folded,
+            Err(e) => {
+                self.diagnostics.push((e, value.span));
+                value
+            },
+        }
     }
 }
 
@@ -27,32 +72,90 @@ impl Into<Info> for Box<ConstantFolding>
 
 pub mod fold
 {
-    use ir::{self,Value,Expression,Instruction};
-    use ir::value::literal::{Literal,Integer};
+    use ir::{self,types,Value,Expression,Instruction,Condition};
+    use ir::value::literal::{Literal,Integer,Decimal};
+    use num::BigInt;
+    use num::traits::{ToPrimitive,FromPrimitive};
+    use std;
+
+    /// A reason that a constant operation could not be folded.
+    #[derive(Clone,Debug,PartialEq,Eq)]
+    pub enum Error
+    {
+        /// An integer was divided by zero.
+        DivisionByZero(Instruction),
+        /// A shift amount was negative, or not smaller than the
+        /// bit width of the shifted value.
+        ShiftOutOfRange(Instruction),
+        /// A floating point value was NaN, or did not fit in the
+        /// integer type it was converted to.
+        ConversionOutOfRange(Instruction),
+    }
+
+    impl Error
+    {
+        /// Gets the instruction that could not be folded.
+        pub fn instruction(&self) -> &Instruction {
+            match *self {
+                Error::DivisionByZero(ref i) |
+                Error::ShiftOutOfRange(ref i) |
+                Error::ConversionOutOfRange(ref i) => i,
+            }
+        }
+    }
 
+    impl std::fmt::Display for Error
+    {
+        fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+            match *self {
+                Error::DivisionByZero(..) => write!(fmt, "constant division by zero"),
+                Error::ShiftOutOfRange(..) => write!(fmt, "constant shift amount is out of range"),
+                Error::ConversionOutOfRange(..) => write!(fmt, "constant conversion is out of range"),
+            }
+        }
+    }
+
+    /// Folds a value, leaving it unchanged if it cannot be folded.
     pub fn value(value: Value) -> Value {
+        try_value(value.clone()).unwrap_or(value)
+    }
+
+    /// Folds a value.
+    pub fn try_value(value: Value) -> Result<Value, Error> {
         match value.node {
-            Expression::Instruction(i) => Value::new(instruction(i)),
-            e => Value::new(e),
+            Expression::Instruction(i) => instruction(i).map(Value::new),
+            e => Ok(Value::new(e)),
         }
     }
 
-    pub fn instruction(inst: Instruction) -> Expression {
-
+    pub fn instruction(inst: Instruction) -> Result<Expression, Error> {
         match inst {
-            Instruction::Add(i) => arithmetic_binop(i, |a,b| a+b),
-            Instruction::Sub(i) => arithmetic_binop(i, |a,b| a-b),
-            Instruction::Mul(i) => arithmetic_binop(i, |a,b| a*b),
-            Instruction::Div(i) => arithmetic_binop(i, |a,b| a/b),
-            Instruction::Shl(i) => arithmetic_binop(i, |a,b| a<<b),
-            Instruction::Shr(i) => arithmetic_binop(i, |a,b| a>>b),
-            _ => inst.into(),
+            Instruction::Add(i) => Ok(arithmetic_binop(i, |a,b| a+b)),
+            Instruction::Sub(i) => Ok(arithmetic_binop(i, |a,b| a-b)),
+            Instruction::Mul(i) => Ok(arithmetic_binop(i, |a,b| a*b)),
+            Instruction::Div(i) => checked_binop(i, Integer::checked_div, Error::DivisionByZero),
+            Instruction::Rem(i) => checked_binop(i, Integer::checked_rem, Error::DivisionByZero),
+            Instruction::MulHigh(i) => Ok(arithmetic_binop(i, |a,b| a.mul_high(b))),
+            Instruction::And(i) => Ok(arithmetic_binop(i, |a,b| a&b)),
+            Instruction::Shl(i) => checked_binop(i, Integer::checked_shl, Error::ShiftOutOfRange),
+            Instruction::Shr(i) => checked_binop(i, Integer::checked_shr, Error::ShiftOutOfRange),
+            Instruction::FAdd(i) => Ok(float_binop(i, |a,b| a+b, |a,b| a+b)),
+            Instruction::FSub(i) => Ok(float_binop(i, |a,b| a-b, |a,b| a-b)),
+            Instruction::FMul(i) => Ok(float_binop(i, |a,b| a*b, |a,b| a*b)),
+            Instruction::FDiv(i) => Ok(float_binop(i, |a,b| a/b, |a,b| a/b)),
+            Instruction::ICmp(i) => Ok(compare_instruction(i.condition(), i.clone().into())),
+            Instruction::FCmp(i) => Ok(compare_instruction(i.condition(), i.clone().into())),
+            Instruction::IntToFloat(i) => Ok(itof(i)),
+            Instruction::FloatToInt(i) => ftoi(i),
+            Instruction::Break(br) => Ok(self::br(br).into()),
+            _ => Ok(inst.into()),
         }
     }
 
+    /// Folds a binary operation which is defined for all operands.
     pub fn arithmetic_binop<I,FI>(inst: I,
                                   mut f_int: FI) -> Expression
-        where I: ir::instruction::Binary,
+        where I: ir::instruction::Binary + Into<Instruction>,
               FI: FnMut(Integer,Integer) -> Integer {
         // make sure the values are constants
         let (lhs,rhs) = match inst.operand_expressions() {
@@ -60,15 +163,161 @@ pub mod fold
             _ => return inst.clone().into(), // we can only fold constants
         };
 
+        match (lhs,rhs) {
+            (Literal::Integer(li),Literal::Integer(ri)) => f_int(li,ri).into(),
+            _ => inst.into(),
+        }
+    }
+
+    /// Folds a binary operation which may be undefined.
+    ///
+    /// If `f_int` returns `None`, the operation cannot be folded and
+    /// `error` is used to describe why.
+    pub fn checked_binop<I,FI,E>(inst: I,
+                                 mut f_int: FI,
+                                 error: E) -> Result<Expression, Error>
+        where I: ir::instruction::Binary + Into<Instruction>,
+              FI: FnMut(Integer,Integer) -> Option<Integer>,
+              E: FnOnce(Instruction) -> Error {
+        // make sure the values are constants
+        let (lhs,rhs) = match inst.operand_expressions() {
+            (&Expression::Literal(ref a),&Expression::Literal(ref b)) => (a.clone(),b.clone()),
+            _ => return Ok(inst.clone().into()), // we can only fold constants
+        };
+
         match (lhs,rhs) {
             (Literal::Integer(li),Literal::Integer(ri)) => {
-                f_int(li,ri).into()
+                match f_int(li,ri) {
+                    Some(result) => Ok(result.into()),
+                    None => Err(error(inst.into())),
+                }
+            },
+            _ => Ok(inst.into()),
+        }
+    }
+
+    /// Folds a floating point operation.
+    ///
+    /// The operation is done in the precision of the operands, so
+    /// the result is rounded exactly as IEEE 754 requires.
+    /// NaN results are always the positive quiet NaN.
+    pub fn float_binop<I,F32,F64>(inst: I,
+                                  f32_op: F32,
+                                  f64_op: F64) -> Expression
+        where I: ir::instruction::Binary + Into<Instruction>,
+              F32: FnOnce(f32,f32) -> f32,
+              F64: FnOnce(f64,f64) -> f64 {
+        let (lhs, rhs) = match (as_decimal(&inst.operands().0), as_decimal(&inst.operands().1)) {
+            (Some(a), Some(b)) => (a, b),
+            _ => return inst.into(),
+        };
+
+        // The host may give NaNs any sign or payload, so always
+        // fold to the same one.
+        match (lhs.as_f32(), rhs.as_f32(), lhs.as_f64(), rhs.as_f64()) {
+            (Some(a), Some(b), _, _) => {
+                let result = f32_op(a, b);
+                Expression::f32(if result.is_nan() { std::f32::NAN } else { result })
+            },
+            (_, _, Some(a), Some(b)) => {
+                let result = f64_op(a, b);
+                Expression::f64(if result.is_nan() { std::f64::NAN } else { result })
             },
             _ => inst.into(),
         }
     }
-}
 
+    /// Folds an `icmp` or `fcmp` of constants into `u1 1` or `u1 0`.
+    pub fn compare_instruction(cond: &Condition, inst: Instruction) -> Expression {
+        match condition(cond.clone()) {
+            Condition::True => Expression::u(1, 1),
+            Condition::False => Expression::u(1, 0),
+            _ => inst.into(),
+        }
+    }
+
+    /// Folds a conversion of a constant integer to the nearest
+    /// floating point value.
+    pub fn itof(inst: ir::instruction::IntToFloat) -> Expression {
+        let value = match inst.value().node.as_literal().and_then(|l| l.as_integer()) {
+            Some(i) => i.value(),
+            None => return inst.into(),
+        };
+
+        let ty = inst.decimal_ty();
+
+        if ty == types::Decimal::f32() {
+            value.to_f32().map(Expression::f32).unwrap_or_else(|| inst.into())
+        } else if ty == types::Decimal::f64() {
+            value.to_f64().map(Expression::f64).unwrap_or_else(|| inst.into())
+        } else {
+            inst.into()
+        }
+    }
+
+    /// Folds a conversion of a constant floating point value to an
+    /// integer, rounding towards zero.
+    pub fn ftoi(inst: ir::instruction::FloatToInt) -> Result<Expression, Error> {
+        let value = match as_decimal(inst.value()).and_then(|d| d.to_f64()) {
+            Some(value) => value,
+            None => return Ok(inst.into()),
+        };
+
+        let integer = BigInt::from_f64(value.trunc())
+            .and_then(|i| Integer::from_bigint(inst.integer_ty(), i));
+
+        match integer {
+            Some(integer) => Ok(integer.into()),
+            None => Err(Error::ConversionOutOfRange(inst.into())),
+        }
+    }
+
+    fn as_decimal(value: &Value) -> Option<&Decimal> {
+        value.node.as_literal().and_then(|l| l.as_decimal())
+    }
+
+    /// Folds a branch with a constant condition.
+    pub fn br(br: ir::instruction::Break) -> ir::instruction::Break {
+        ir::instruction::Break::new(condition(br.condition().clone()),
+                                    br.target().clone())
+    }
+
+    /// Folds a condition which compares constants into `true` or `false`.
+    pub fn condition(cond: Condition) -> Condition {
+        let result = match cond {
+            Condition::True | Condition::False => None,
+            Condition::Equal(ref a, ref b) => compare(a, b, |a,b| a == b, |a,b| a == b),
+            Condition::NotEqual(ref a, ref b) => compare(a, b, |a,b| a != b, |a,b| a != b),
+            Condition::GreaterThan(ref a, ref b) => compare(a, b, |a,b| a > b, |a,b| a > b),
+            Condition::GreaterThanOrEq(ref a, ref b) => compare(a, b, |a,b| a >= b, |a,b| a >= b),
+            Condition::LessThan(ref a, ref b) => compare(a, b, |a,b| a < b, |a,b| a < b),
+            Condition::LessThanOrEq(ref a, ref b) => compare(a, b, |a,b| a <= b, |a,b| a <= b),
+        };
+
+        result.map(Condition::from_boolean).unwrap_or(cond)
+    }
+
+    /// Compares two constants.
+    ///
+    /// Floating point values are compared as doubles, which holds
+    /// every single precision value exactly.
+    fn compare<F,G>(lhs: &Value, rhs: &Value, f_int: F, f_float: G) -> Option<bool>
+        where F: Fn(&BigInt, &BigInt) -> bool,
+              G: Fn(f64, f64) -> bool {
+        let as_integer = |v: &Value| v.node.as_literal().and_then(|l| l.as_integer()).cloned();
+        let as_float = |v: &Value| self::as_decimal(v).and_then(|d| d.to_f64());
+
+        match (as_integer(lhs), as_integer(rhs)) {
+            (Some(a), Some(b)) => return Some(f_int(&a.value(), &b.value())),
+            _ => (),
+        }
+
+        match (as_float(lhs), as_float(rhs)) {
+            (Some(a), Some(b)) => Some(f_float(a, b)),
+            _ => None,
+        }
+    }
+}
 
 value_mapping_test!(test_binops : fold::value {
     Expression::add(1 as i8, 8 as i8) => Expression::i8(9),
@@ -76,6 +325,122 @@ value_mapping_test!(test_binops : fold::value {
     Expression::mul(1 as i8, 8 as i8) => Expression::i8(8),
     Expression::div(10 as i8,2 as i8) => Expression::i8(5),
     Expression::shl(1 as u8,1 as u8) => Expression::u8(2),
-    Expression::shr(32 as u8,1 as u8) => Expression::u8(16)
+    Expression::shr(32 as u8,1 as u8) => Expression::u8(16),
+    Expression::rem(-7 as i8, 2 as i8) => Expression::i8(-1),
+    Expression::mulh(200 as u8, 200 as u8) => Expression::u8(156),
+    Expression::mulh(-100 as i8, 100 as i8) => Expression::i8(-40),
+    Expression::and(-3 as i8, 6 as i8) => Expression::i8(4)
+});
+
+value_mapping_test!(test_wrapping : fold::value {
+    Expression::add(127 as i8, 1 as i8) => Expression::i8(-128),
+    Expression::sub(1 as u8, 8 as u8) => Expression::u8(249),
+    Expression::mul(16 as u8, 16 as u8) => Expression::u8(0),
+    Expression::div(-128 as i8, -1 as i8) => Expression::i8(-128),
+    Expression::shl(3 as u8, 7 as u8) => Expression::u8(128),
+    Expression::shr(-16 as i8, 2 as i8) => Expression::i8(-4)
+});
+
+value_mapping_test!(test_unfoldable : fold::value {
+    Expression::div(1 as i8, 0 as i8) => Expression::div(1 as i8, 0 as i8),
+    Expression::shl(1 as u8, 8 as u8) => Expression::shl(1 as u8, 8 as u8),
+    Expression::shr(1 as i8, -1 as i8) => Expression::shr(1 as i8, -1 as i8)
 });
 
+value_mapping_test!(test_floats : fold::value {
+    Expression::fadd(0.1 as f64, 0.2 as f64) => Expression::f64(0.30000000000000004),
+    Expression::fadd(16777216.0 as f32, 1.0 as f32) => Expression::f32(16777216.0),
+    Expression::fsub(1.0 as f32, 1.5 as f32) => Expression::f32(-0.5),
+    Expression::fmul(-0.0 as f64, 2.0 as f64) => Expression::f64(-0.0),
+    Expression::fdiv(1.0 as f32, 0.0 as f32) => Expression::f32(::std::f32::INFINITY),
+    Expression::fdiv(0.0 as f64, 0.0 as f64) => Expression::f64(::std::f64::NAN)
+});
+
+value_mapping_test!(test_conversions : fold::value {
+    Expression::itof(-3 as i16, ir::types::Decimal::f32()) => Expression::f32(-3.0),
+    Expression::itof(16777217 as u32, ir::types::Decimal::f32()) => Expression::f32(16777216.0),
+    Expression::itof(16777217 as u32, ir::types::Decimal::f64()) => Expression::f64(16777217.0),
+    Expression::ftoi(-2.75 as f32, ir::types::Integer::i8()) => Expression::i8(-2),
+    Expression::ftoi(255.9 as f64, ir::types::Integer::u8()) => Expression::u8(255),
+    Expression::ftoi(256.0 as f64, ir::types::Integer::u8()) => Expression::ftoi(256.0 as f64, ir::types::Integer::u8()),
+    Expression::ftoi(::std::f32::NAN, ir::types::Integer::i32()) => Expression::ftoi(::std::f32::NAN, ir::types::Integer::i32())
+});
+
+#[test]
+fn test_float_comparisons() {
+    use ir::{Expression,Value,Condition};
+
+    let compare = |c: fn(Box<Value>, Box<Value>) -> Condition, a: f64, b: f64| {
+        Expression::fcmp(c(Box::new(Expression::f64(a).into()), Box::new(Expression::f64(b).into())))
+    };
+    let nan = ::std::f64::NAN;
+
+    let cases = [
+        (compare(Condition::LessThan, 1.0, 2.0), Expression::u(1, 1)),
+        (compare(Condition::Equal, 0.0, -0.0), Expression::u(1, 1)),
+        (compare(Condition::Equal, nan, nan), Expression::u(1, 0)),
+        (compare(Condition::GreaterThanOrEq, nan, 1.0), Expression::u(1, 0)),
+        (compare(Condition::NotEqual, nan, nan), Expression::u(1, 1)),
+    ];
+
+    for &(ref input, ref expected) in cases.iter() {
+        assert_eq!(fold::value(input.clone().into()), expected.clone().into());
+    }
+}
+
+#[test]
+fn test_division_by_zero_is_diagnosed() {
+    use ir::{Expression,Instruction};
+
+    let div = Expression::div(1 as i8, 0 as i8);
+    let mut pass = ConstantFolding::new();
+
+    assert_eq!(pass.run_value(div.clone().into()), div.clone().into());
+
+    let diagnostics: Vec<_> = pass.diagnostics().map(|&(ref e, _)| e.clone()).collect();
+    assert_eq!(diagnostics, vec![fold::Error::DivisionByZero(match div {
+        Expression::Instruction(i @ Instruction::Div(..)) => i,
+        _ => unreachable!(),
+    })]);
+}
+
+#[test]
+fn test_diagnostics_point_at_the_value() {
+    use ir::{Expression,Value};
+    use util::{Location,Span};
+
+    let span = Span::new(Location::new(3, 8), Location::new(3, 24));
+    let mut value: Value = Expression::rem(1 as i8, 0 as i8).into();
+    value.span = Some(span);
+
+    let mut pass = ConstantFolding::new();
+    pass.run_value(value);
+
+    let diagnostics = pass.report();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].span, Some(span));
+}
+
+#[test]
+fn test_constant_branches() {
+    use ir::{self,Expression,Value,Condition};
+
+    let block = ir::Block::empty("target");
+    let compare = |c: fn(Box<Value>, Box<Value>) -> Condition, a: i8, b: i8| {
+        Expression::br(c(Box::new(Expression::i8(a).into()), Box::new(Expression::i8(b).into())),
+                       Expression::block_ref(&block))
+    };
+    let trivial = |c| Expression::br(c, Expression::block_ref(&block));
+
+    let cases = [
+        (compare(Condition::Equal, 1, 1), trivial(Condition::True)),
+        (compare(Condition::NotEqual, 1, 1), trivial(Condition::False)),
+        (compare(Condition::LessThan, -1, 0), trivial(Condition::True)),
+        (compare(Condition::GreaterThanOrEq, -1, 0), trivial(Condition::False)),
+    ];
+
+    for &(ref input, ref expected) in cases.iter() {
+        assert_eq!(fold::value(input.clone().into()), expected.clone().into());
+    }
+}
+
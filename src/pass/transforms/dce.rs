@@ -2,11 +2,9 @@ use {Metadata,Id,Info,Transform};
 use ir;
 
 // TODO: This removes all but the most trivial dead code.
-// We should handle the cases where
-//   * function isn't used
-//   * bb isn't used
-//   * global isn't used
-// and perform a full analysis using the dominator tree.
+// Unused items, unreachable blocks and unused registers are handled
+// by `GlobalDeadCodeElimination`, but we should still perform a full
+// analysis using the dominator tree.
 
 /// A dead code elimination pass.
 pub struct DeadCodeElimination;
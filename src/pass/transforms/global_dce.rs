@@ -0,0 +1,281 @@
+use {Metadata,Id,Info,Transform};
+use ir;
+
+/// A whole-module dead code elimination pass.
+///
+/// This removes
+///   * internal functions and globals which are never referenced
+///   * blocks which are unreachable from the entry block
+///   * registers which are never used
+pub struct GlobalDeadCodeElimination;
+
+impl Metadata for GlobalDeadCodeElimination
+{
+    fn id(&self) -> Id { Id(0x4c1ed0a7) }
+    fn name(&self) -> &'static str { "global dead code elimination" }
+}
+
+impl Transform for GlobalDeadCodeElimination
+{
+    fn run_module(&mut self,
+                  module: ir::Module) -> ir::Module {
+        self::eliminate::module(module)
+    }
+}
+
+// TODO: blamket impl for all passes
+impl Into<Info> for Box<GlobalDeadCodeElimination>
+{
+    fn into(self) -> Info {
+        Info::Transform(self)
+    }
+}
+
+pub mod eliminate
+{
+    use ir::{self,Expression,Users};
+    use analysis::ControlFlowGraph;
+    use transforms::ipcp::propagate;
+    use util::{self,Identifiable};
+
+    /// Eliminates dead code from an entire module.
+    pub fn module(module: ir::Module) -> ir::Module {
+        let module = module.map_functions(|f,_| self::function(f));
+        self::unused_items(module)
+    }
+
+    /// Eliminates dead blocks and registers from a function.
+    pub fn function(func: ir::Function) -> ir::Function {
+        let func = self::unreachable_blocks(func);
+        self::unused_registers(func)
+    }
+
+    /// Removes functions and globals which cannot be reached from
+    /// an exported item.
+    pub fn unused_items(mut module: ir::Module) -> ir::Module {
+        let live = self::live_items(&module);
+
+        module.functions = module.functions.into_iter()
+                                           .filter(|f| live.contains(&f.get_id()))
+                                           .collect();
+        module.globals = module.globals.into_iter()
+                                       .filter(|g| live.contains(&g.get_id()))
+                                       .collect();
+        module
+    }
+
+    /// Removes all blocks which are unreachable from the entry block.
+    ///
+    /// The function must pass the verifier, so that only the last
+    /// value of a block can branch.
+    pub fn unreachable_blocks(mut func: ir::Function) -> ir::Function {
+        let reachable = ControlFlowGraph::new(&func).reachable();
+
+        let blocks: Vec<_> = func.blocks.drain(..).collect();
+        func.with_blocks(blocks.into_iter().filter(|b| reachable.contains(&b.get_id())))
+    }
+
+    /// Removes registers which have no users.
+    ///
+    /// If the value of an unused register has side effects, the
+    /// register is replaced by its value.
+    pub fn unused_registers(mut func: ir::Function) -> ir::Function {
+        loop {
+            let unused: Vec<util::Id> = func.values().filter_map(|value| {
+                match value.node {
+                    Expression::Register(ref reg) => {
+                        if Users::of_register(reg, &func).is_empty() {
+                            Some(reg.get_id())
+                        } else {
+                            None
+                        }
+                    },
+                    _ => None,
+                }
+            }).collect();
+
+            if unused.is_empty() {
+                return func;
+            }
+
+            func = func.map_blocks(|block| {
+                let body = block.body.into_iter().filter_map(|value| {
+                    match value.node {
+                        Expression::Register(reg) => {
+                            if !unused.contains(&reg.get_id()) {
+                                Some(Expression::Register(reg).into())
+                            } else if propagate::has_side_effects(&reg.value) {
+                                Some(*reg.value)
+                            } else {
+                                None
+                            }
+                        },
+                        node => Some(ir::Value::new(node)),
+                    }
+                }).collect();
+
+                ir::Block { body: body, ..block }
+            });
+        }
+    }
+
+    /// Finds the IDs of all items which are exported, handle an
+    /// interrupt, or are referenced by another live item.
+    fn live_items(module: &ir::Module) -> Vec<util::Id> {
+        let mut live: Vec<util::Id> = module.functions()
+            .filter(|f| f.is_exported() || f.interrupt().is_some()).map(|f| f.get_id())
+            .chain(module.globals().filter(|g| g.is_exported()).map(|g| g.get_id()))
+            .collect();
+
+        // Keep marking items until we stop finding new ones.
+        loop {
+            let mut changed = false;
+
+            for func in module.functions() {
+                if !live.contains(&func.get_id()) && self::is_used_by(func, &live, module) {
+                    live.push(func.get_id());
+                    changed = true;
+                }
+            }
+
+            for global in module.globals() {
+                if !live.contains(&global.get_id()) && self::is_used_by(global, &live, module) {
+                    live.push(global.get_id());
+                    changed = true;
+                }
+            }
+
+            if !changed {
+                return live;
+            }
+        }
+    }
+
+    /// Checks if an item is used by any of a set of items.
+    fn is_used_by(item: &ir::ItemTrait,
+                  users: &[util::Id],
+                  module: &ir::Module) -> bool {
+        let used_by_function = module.functions()
+            .filter(|f| users.contains(&f.get_id()))
+            .any(|f| !Users::of_in_function(item, f).is_empty());
+
+        let used_by_global = module.globals()
+            .filter(|g| users.contains(&g.get_id()))
+            .any(|g| !Users::of_in_global(item, g).is_empty());
+
+        used_by_function || used_by_global
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use ir::{self,Expression,Value,Linkage};
+    use util::Identifiable;
+
+    fn function_returning_call(name: &str, callee: &ir::Function) -> ir::Function {
+        let call = Expression::call(Value::new(Expression::function_ref(callee)));
+
+        let mut block = ir::Block::empty("entry");
+        block.append_value(call);
+        block.append_value(Expression::ret_void());
+
+        let mut func = ir::Function::empty(name, ir::Signature::empty());
+        func.append_block(block);
+        func
+    }
+
+    fn leaf_function(name: &str, linkage: Linkage) -> ir::Function {
+        let mut block = ir::Block::empty("entry");
+        block.append_value(Expression::ret_void());
+
+        let mut func = ir::Function::empty(name, ir::Signature::empty());
+        func.linkage = linkage;
+        func.append_block(block);
+        func
+    }
+
+    #[test]
+    fn removes_unreferenced_internal_items() {
+        let used = leaf_function("used", Linkage::Internal);
+        let unused = leaf_function("unused", Linkage::Internal);
+        let exported = leaf_function("exported", Linkage::External);
+        let main = function_returning_call("main", &used);
+
+        let mut handler = leaf_function("handler", Linkage::Internal);
+        handler.interrupt = Some(ir::Interrupt { vector: 1 });
+
+        let mut dead_global = ir::Global::new("dead".into(), Expression::i8(1).into());
+        dead_global.linkage = Linkage::Internal;
+
+        let module = ir::Module::empty().function(used)
+                                        .function(unused)
+                                        .function(exported)
+                                        .function(main)
+                                        .function(handler)
+                                        .global(dead_global);
+
+        let module = super::eliminate::module(module);
+        let names: Vec<_> = module.functions().map(|f| f.name()).collect();
+
+        assert_eq!(names, vec!["used", "exported", "main", "handler"]);
+        assert_eq!(module.globals().count(), 0);
+    }
+
+    #[test]
+    fn removes_unreachable_blocks_and_unused_registers() {
+        let exit = {
+            let mut block = ir::Block::empty("exit");
+            block.append_value(Expression::ret_void());
+            block
+        };
+
+        let dead = {
+            let mut block = ir::Block::empty("dead");
+            block.append_value(Expression::br(ir::Condition::True,
+                                              Expression::block_ref(&exit)));
+            block
+        };
+
+        let entry = {
+            let mut block = ir::Block::empty("entry");
+            block.append_value(Expression::register(Expression::add(Expression::i8(1),
+                                                                    Expression::i8(2))));
+            block.append_value(Expression::br(ir::Condition::True,
+                                              Expression::block_ref(&exit)));
+            block
+        };
+
+        let mut func = ir::Function::empty("f", ir::Signature::empty());
+        let exit_id = exit.get_id();
+        func.append_block(entry);
+        func.append_block(dead);
+        func.append_block(exit);
+
+        let func = super::eliminate::function(func);
+        let blocks: Vec<_> = func.blocks().map(|b| b.name()).collect();
+
+        assert_eq!(blocks, vec!["entry", "exit"]);
+        assert_eq!(func.blocks[0].body.len(), 1);
+        assert_eq!(func.blocks[1].get_id(), exit_id);
+    }
+
+    #[test]
+    fn removes_unused_arithmetic_completely() {
+        let text = "fn @g() -> i8 {\n  ret i8 1\n}\n\
+                    fn @f(%x: i8, %y: i8) {\n  %a = add %x, %y\n  %b = call @g()\n  ret\n}\n";
+        let module = ir::read::textual(text.chars()).expect("parsing failed");
+
+        let func = module.functions().find(|f| f.name() == "f").unwrap().clone();
+        let func = super::eliminate::function(func);
+
+        // The call is kept for its side effects.
+        let body: Vec<_> = func.values().map(|v| v.node.clone()).collect();
+        assert_eq!(body.len(), 2);
+        assert!(match body[0] {
+            Expression::Instruction(ir::Instruction::Call(..)) => true,
+            _ => false,
+        });
+        assert_eq!(body[1], Expression::ret_void());
+    }
+}
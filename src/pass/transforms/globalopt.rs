@@ -0,0 +1,200 @@
+use {Metadata,Id,Info,Transform};
+use ir;
+
+/// A global variable optimization pass.
+///
+/// This
+///   * marks internal globals which are never written to as constant
+///   * replaces loads from constant globals with their values
+///   * removes internal globals which are never referenced
+pub struct GlobalOptimization;
+
+impl Metadata for GlobalOptimization
+{
+    fn id(&self) -> Id { Id(0x6d20fb38) }
+    fn name(&self) -> &'static str { "global optimization" }
+}
+
+impl Transform for GlobalOptimization
+{
+    fn run_module(&mut self,
+                  module: ir::Module) -> ir::Module {
+        self::optimize::module(module)
+    }
+}
+
+// TODO: blamket impl for all passes
+impl Into<Info> for Box<GlobalOptimization>
+{
+    fn into(self) -> Info {
+        Info::Transform(self)
+    }
+}
+
+pub mod optimize
+{
+    use ir::{self,Value,Expression,Instruction};
+    use util::{self,Identifiable};
+
+    /// Optimizes the globals of a module.
+    pub fn module(module: ir::Module) -> ir::Module {
+        let module = self::demote_globals(module);
+        let module = self::fold_loads(module);
+        self::unused_globals(module)
+    }
+
+    /// Marks internal globals as constant if they are only ever loaded from.
+    pub fn demote_globals(module: ir::Module) -> ir::Module {
+        let read_only: Vec<util::Id> = module.globals().filter(|g| {
+            !g.is_exported() && !g.is_constant() && self::is_read_only(g, &module)
+        }).map(|g| g.get_id()).collect();
+
+        module.map_globals(|g| {
+            if read_only.contains(&g.get_id()) { g.constant() } else { g }
+        })
+    }
+
+    /// Replaces loads from constant globals with the values of the globals.
+    ///
+    /// Only globals which hold literals are folded.
+    pub fn fold_loads(module: ir::Module) -> ir::Module {
+        let constants: Vec<(util::Id, Value)> = module.globals().filter(|g| {
+            g.is_constant() && g.value().node.is_literal()
+        }).map(|g| (g.get_id(), g.value().clone())).collect();
+
+        if constants.is_empty() {
+            return module;
+        }
+
+        module.map_functions(|f, _| f.map_values(|v| self::fold_value(v, &constants)))
+    }
+
+    /// Removes internal globals which are never referenced.
+    pub fn unused_globals(mut module: ir::Module) -> ir::Module {
+        let unused: Vec<util::Id> = module.globals().filter(|g| {
+            !g.is_exported() && module.users_of(*g).is_empty()
+        }).map(|g| g.get_id()).collect();
+
+        module.globals = module.globals.into_iter()
+                                       .filter(|g| !unused.contains(&g.get_id()))
+                                       .collect();
+        module
+    }
+
+    /// Checks if every use of a global only reads from it.
+    ///
+    /// A reference which is used in any other way, such as being
+    /// stored or passed to a call, may be written through.
+    pub fn is_read_only(global: &ir::Global, module: &ir::Module) -> bool {
+        let loads: usize = module.values().map(|v| self::count_loads(global.get_id(), v)).sum();
+
+        loads == module.users_of(global).len()
+    }
+
+    fn count_loads(global: util::Id, value: &Value) -> usize {
+        match value.node {
+            Expression::Instruction(Instruction::Load(ref load)) if self::is_global(load.pointer(), global) => 1,
+            Expression::Register(ref reg) => self::count_loads(global, &reg.value),
+            ref node => node.subvalues().into_iter().map(|v| self::count_loads(global, v)).sum(),
+        }
+    }
+
+    fn fold_value(value: Value, constants: &[(util::Id, Value)]) -> Value {
+        match value.node {
+            Expression::Instruction(Instruction::Load(load)) => {
+                let folded = match load.pointer().node {
+                    Expression::GlobalRef(ref r) => {
+                        constants.iter().find(|&&(id, _)| id == r.global_id()).map(|&(_, ref v)| v.clone())
+                    },
+                    _ => None,
+                };
+
+                folded.unwrap_or_else(|| Value::new(load.into()))
+            },
+            Expression::Register(mut reg) => {
+                reg.value = Box::new(self::fold_value(*reg.value, constants));
+                Value::new(Expression::Register(reg))
+            },
+            node => Value::new(node.map_subvalues(|v| self::fold_value(v, constants))),
+        }
+    }
+
+    fn is_global(value: &Value, global: util::Id) -> bool {
+        match value.node {
+            Expression::GlobalRef(ref r) => r.global_id() == global,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use ir::{self,Expression,Value,Type};
+    use ir::value::GlobalRef;
+
+    fn global(name: &str, value: i8) -> ir::Global {
+        ir::Global::new(name.to_owned(), Value::new(Expression::i8(value)))
+            .with_linkage(ir::Linkage::Internal)
+    }
+
+    fn function(values: Vec<Expression>) -> ir::Function {
+        let mut entry = ir::Block::empty("entry");
+        for value in values {
+            entry.append_value(value);
+        }
+
+        let mut func = ir::Function::empty("main", ir::Signature::empty().ret(Type::i8()));
+        func.append_block(entry);
+        func
+    }
+
+    fn global_ref(global: &ir::Global) -> Value {
+        Value::new(GlobalRef::reference(global).into())
+    }
+
+    #[test]
+    fn folds_loads_from_read_only_globals() {
+        let read = global("read", 5);
+        let unused = global("unused", 1);
+
+        let main = function(vec![Expression::ret(Value::new(Expression::load(global_ref(&read))))]);
+
+        let module = ir::Module::empty().global(read).global(unused).function(main);
+        let module = super::optimize::module(module);
+
+        assert_eq!(module.functions().next().unwrap().blocks[0].body,
+                   vec![Expression::ret(Value::new(Expression::i8(5))).into()]);
+
+        // Both globals are now unreferenced.
+        assert_eq!(module.globals().count(), 0);
+    }
+
+    #[test]
+    fn keeps_written_globals() {
+        let written = global("written", 5);
+
+        let main = function(vec![
+            Expression::store(global_ref(&written), Value::new(Expression::i8(2))),
+            Expression::ret(Value::new(Expression::load(global_ref(&written)))),
+        ]);
+
+        let module = ir::Module::empty().global(written).function(main);
+        let module = super::optimize::module(module);
+
+        let global = module.globals().next().unwrap();
+        assert!(!global.is_constant());
+        assert_eq!(module.functions().next().unwrap().blocks[0].body.len(), 2);
+    }
+
+    #[test]
+    fn keeps_exported_globals() {
+        let exported = global("exported", 5).with_linkage(ir::Linkage::External);
+
+        let module = ir::Module::empty().global(exported);
+        let module = super::optimize::module(module);
+
+        let global = module.globals().next().unwrap();
+        assert!(!global.is_constant());
+    }
+}
@@ -0,0 +1,277 @@
+use {Metadata,Id,Info,Transform};
+use ir;
+
+/// A global value numbering pass.
+///
+/// This finds instructions which compute the same value as an
+/// instruction in a dominating register, and replaces them with a
+/// reference to that register.
+pub struct GlobalValueNumbering;
+
+impl Metadata for GlobalValueNumbering
+{
+    fn id(&self) -> Id { Id(0x9a3c51e2) }
+    fn name(&self) -> &'static str { "global value numbering" }
+}
+
+impl Transform for GlobalValueNumbering
+{
+    fn run_function(&mut self,
+                    function: ir::Function,
+                    _module: &ir::Module) -> ir::Function {
+        self::number::function(function)
+    }
+}
+
+// TODO: blamket impl for all passes
+impl Into<Info> for Box<GlobalValueNumbering>
+{
+    fn into(self) -> Info {
+        Info::Transform(self)
+    }
+}
+
+pub mod number
+{
+    use ir::{self,Value,Expression,Instruction};
+    use ir::value::RegisterRef;
+    use analysis::{ControlFlowGraph,DominatorTree};
+    use util::{self,Identifiable};
+
+    use std::collections::HashMap;
+
+    /// Maps an instruction to the register that first computed it.
+    pub type ValueTable = HashMap<Instruction, RegisterRef>;
+
+    /// Maps the ID of a redundant register to the register it is
+    /// replaced by.
+    pub type Replacements = HashMap<util::Id, RegisterRef>;
+
+    /// Eliminates redundant computations in a function.
+    ///
+    /// Blocks are visited in dominator tree order, so that an instruction
+    /// is only ever replaced by a register which dominates it.
+    pub fn function(mut func: ir::Function) -> ir::Function {
+        let dominators = DominatorTree::new(&ControlFlowGraph::new(&func));
+
+        let order: Vec<_> = func.blocks().map(|b| b.get_id()).collect();
+        let mut blocks: HashMap<_,_> = func.blocks.drain(..).map(|b| (b.get_id(), b)).collect();
+        let mut replacements = Replacements::new();
+
+        if let Some(root) = dominators.root() {
+            self::visit(root, &dominators, ValueTable::new(),
+                        &mut blocks, &mut replacements);
+        }
+
+        // Unreachable blocks are not visited, but may still refer to
+        // a register which was removed.
+        let blocks = order.into_iter().map(|id| blocks.remove(&id).unwrap());
+        func.with_blocks(blocks).map_values(|v| self::substitute(v, &ValueTable::new(), &replacements))
+    }
+
+    /// Numbers a block and the blocks that it dominates.
+    fn visit(block_id: util::Id,
+             dominators: &DominatorTree,
+             mut table: ValueTable,
+             blocks: &mut HashMap<util::Id, ir::Block>,
+             replacements: &mut Replacements) {
+        let block = blocks.remove(&block_id).unwrap();
+
+        let body = block.body.into_iter().filter_map(|value| {
+            self::value(value, &mut table, replacements)
+        }).collect();
+
+        blocks.insert(block_id, ir::Block { body: body, ..block });
+
+        for &child in dominators.children(block_id) {
+            self::visit(child, dominators, table.clone(), blocks, replacements);
+        }
+    }
+
+    /// Numbers a value in a block.
+    ///
+    /// Returns `None` if the value is a redundant register.
+    pub fn value(value: Value,
+                 table: &mut ValueTable,
+                 replacements: &mut Replacements) -> Option<Value> {
+        match value.node {
+            Expression::Register(mut reg) => {
+                let reg_id = reg.get_id();
+                let reg_value = self::substitute_operands(*reg.value, table, replacements);
+
+                if let Expression::Instruction(ref inst) = reg_value.node {
+                    if self::is_pure(inst) {
+                        if let Some(existing) = table.get(inst) {
+                            replacements.insert(reg_id, existing.clone());
+                            return None;
+                        }
+
+                        table.insert(inst.clone(), RegisterRef::new(reg_id, reg_value.node.ty()));
+                    }
+                }
+
+                reg.value = Box::new(reg_value);
+                Some(Expression::Register(reg).into())
+            },
+            node => Some(self::substitute(Value::new(node), table, replacements)),
+        }
+    }
+
+    /// Replaces references to redundant registers, and instructions
+    /// which have already been computed.
+    pub fn substitute(value: Value,
+                      table: &ValueTable,
+                      replacements: &Replacements) -> Value {
+        let value = self::substitute_operands(value, table, replacements);
+
+        if let Expression::Instruction(ref inst) = value.node {
+            if let Some(existing) = table.get(inst) {
+                return Expression::RegisterRef(existing.clone()).into();
+            }
+        }
+
+        value
+    }
+
+    /// Substitutes the operands of a value, but not the value itself.
+    fn substitute_operands(value: Value,
+                           table: &ValueTable,
+                           replacements: &Replacements) -> Value {
+        let node = match value.node {
+            Expression::RegisterRef(reg_ref) => {
+                match replacements.get(&reg_ref.register_id) {
+                    Some(replacement) => Expression::RegisterRef(replacement.clone()),
+                    None => Expression::RegisterRef(reg_ref),
+                }
+            },
+            node => node.map_subvalues(|v| self::substitute(v, table, replacements)),
+        };
+
+        Value::new(node)
+    }
+
+    /// Checks if an instruction always computes the same value
+    /// given the same operands, and has no side effects.
+    pub fn is_pure(inst: &Instruction) -> bool {
+        match *inst {
+            Instruction::Add(..) |
+            Instruction::Sub(..) |
+            Instruction::Mul(..) |
+            Instruction::Div(..) |
+            Instruction::Rem(..) |
+            Instruction::MulHigh(..) |
+            Instruction::And(..) |
+            Instruction::Shl(..) |
+            Instruction::Shr(..) |
+            Instruction::ExtractValue(..) |
+            Instruction::InsertValue(..) |
+            Instruction::GetElementPtr(..) |
+            Instruction::FAdd(..) |
+            Instruction::FSub(..) |
+            Instruction::FMul(..) |
+            Instruction::FDiv(..) |
+            Instruction::ICmp(..) |
+            Instruction::FCmp(..) |
+            Instruction::IntToFloat(..) |
+            Instruction::FloatToInt(..) => true,
+            Instruction::Call(..) |
+            Instruction::Break(..) |
+            Instruction::Return(..) |
+            Instruction::Copy(..) |
+            Instruction::Load(..) |
+            Instruction::Store(..) |
+            Instruction::Phi(..) => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use ir::{self,Expression,Value};
+    use ir::value::RegisterRef;
+    use util::Identifiable;
+
+    fn register(value: Expression) -> (Expression, RegisterRef) {
+        let reg = Expression::register(value);
+        let reg_ref = match reg {
+            Expression::Register(ref r) => RegisterRef::new(r.get_id(), r.value.node.ty()),
+            _ => unreachable!(),
+        };
+
+        (reg, reg_ref)
+    }
+
+    fn reference(reg_ref: &RegisterRef) -> Value {
+        Expression::RegisterRef(reg_ref.clone()).into()
+    }
+
+    #[test]
+    fn replaces_redundant_registers() {
+        let (a, a_ref) = register(Expression::add(Expression::i8(1), Expression::i8(2)));
+        let (b, b_ref) = register(Expression::add(Expression::i8(1), Expression::i8(2)));
+        let (c, _) = register(Expression::shl(reference(&a_ref), reference(&b_ref)));
+
+        let mut block = ir::Block::empty("entry");
+        block.append_value(a);
+        block.append_value(b);
+        block.append_value(c);
+        block.append_value(Expression::ret(Expression::add(Expression::i8(1), Expression::i8(2)).into()));
+
+        let mut func = ir::Function::empty("f", ir::Signature::empty());
+        func.append_block(block);
+
+        let func = super::number::function(func);
+        let body = &func.blocks[0].body;
+
+        assert_eq!(body.len(), 3);
+
+        match body[1].node {
+            Expression::Register(ref reg) => {
+                assert_eq!(*reg.value, Expression::shl(reference(&a_ref), reference(&a_ref)).into());
+            },
+            _ => panic!("expected a register"),
+        }
+
+        assert_eq!(body[2], Expression::ret(reference(&a_ref)).into());
+    }
+
+    #[test]
+    fn only_replaces_dominated_values() {
+        let (a, _) = register(Expression::mul(Expression::i8(3), Expression::i8(3)));
+        let (b, b_ref) = register(Expression::mul(Expression::i8(3), Expression::i8(3)));
+
+        let exit = {
+            let mut block = ir::Block::empty("exit");
+            block.append_value(b);
+            block.append_value(Expression::ret(reference(&b_ref)));
+            block
+        };
+
+        // `a` is computed on only one path to `exit`, so `b` must stay.
+        let left = {
+            let mut block = ir::Block::empty("left");
+            block.append_value(a);
+            block.append_value(Expression::br(ir::Condition::True, Expression::block_ref(&exit)));
+            block
+        };
+
+        let entry = {
+            let mut block = ir::Block::empty("entry");
+            block.append_value(Expression::br(
+                ir::Condition::Equal(Box::new(Expression::i8(1).into()), Box::new(Expression::i8(2).into())),
+                Expression::block_ref(&exit)));
+            block
+        };
+
+        let mut func = ir::Function::empty("f", ir::Signature::empty());
+        func.append_block(entry);
+        func.append_block(left);
+        func.append_block(exit);
+
+        let func = super::number::function(func);
+
+        assert_eq!(func.blocks[1].body.len(), 2);
+        assert_eq!(func.blocks[2].body.len(), 2);
+    }
+}
@@ -1,4 +1,5 @@
 use {Metadata,Id,Info,Transform};
+use analysis::CallGraph;
 use ir;
 
 use util::Identifiable;
@@ -6,7 +7,11 @@ use util::Identifiable;
 /// The threshold to inline stuff.
 pub const INLINING_WEIGHT_THRESHOLD: f64 = 0.6;
 
-/// An IR strength reduction pass.
+/// The number of values a function can contain before it is never
+/// considered cheap enough to inline on size alone.
+pub const INLINING_COST_LIMIT: usize = 24;
+
+/// An IR inlining pass.
 pub struct Inliner;
 
 #[derive(Copy,Clone,Debug)]
@@ -31,7 +36,15 @@ impl Transform for Inliner
     fn run_module(&mut self,
                   module: ir::Module)
         -> ir::Module {
+        let graph = CallGraph::new(&module);
+
         let function_ids_to_inline: Vec<_> = module.functions().filter_map(|function| {
+            // Functions which call each other would be inlined into
+            // each other forever.
+            if graph.is_recursive(function.get_id()) {
+                return None;
+            }
+
             let weight = inlining_weight(function, &module);
 
             if weight.should_inline() {
@@ -41,44 +54,8 @@ impl Transform for Inliner
             }
         }).collect();
 
-        // FIXME: This won't work for most cases.
-        // Because at this point the IR is in SSA form, we need to inspect the basic block
-        // and look at the subvalues of the top level values to inline.
-
-        module.map_functions(|f, _module| {
-            f.map_blocks(|block| {
-                let values = block.body.into_iter().flat_map(|value| {
-                    println!("handling value: {:#?}", value);
-
-                    let exprs = match value.node {
-                        ir::Expression::Instruction(inst) => match inst {
-                            ir::Instruction::Call(call_inst) => {
-                                println!("call_inst_id: {}, func_ids: {:#?}", call_inst.target_id(), function_ids_to_inline);
-
-                                if let Some(_func_id) = function_ids_to_inline.iter().find(|&id| *id == call_inst.target_id()) {
-                                    // module.get_function(function_id);
-                                    unimplemented!();
-                                } else {
-                                    vec![ir::Expression::Instruction(ir::Instruction::Call(call_inst))]
-                                }
-                            },
-                            i => {
-                                vec![ir::Expression::Instruction(i)]
-                            },
-                        },
-                        e => vec![e],
-                    };
-
-                    exprs.into_iter().map(|expr| {
-                        ir::Value::new(expr)
-                    })
-                }).collect();
-
-                ir::Block {
-                    body: values,
-                    ..block
-                }
-            })
+        module.map_functions(|f, module| {
+            self::inline::function(f, &function_ids_to_inline, module)
         })
     }
 }
@@ -91,13 +68,534 @@ impl Into<Info> for Box<Inliner>
     }
 }
 
+/// Calculates how desirable it is to inline a function.
 pub fn inlining_weight(f: &ir::Function, module: &ir::Module) -> Weight {
-    let uses = module.users_of(f);
+    if !self::can_inline(f) {
+        return Weight::never();
+    }
+
+    let mut weight = match f.inline_hint() {
+        ir::InlineHint::MustInline => return Weight::always(),
+        ir::InlineHint::Inline => 0.3,
+        ir::InlineHint::None => 0.0,
+    };
+
+    let cost: usize = f.values().map(self::cost).sum();
+    weight += 1.0 - (cost as f64 / INLINING_COST_LIMIT as f64);
+
+    // The original function can be removed once its only call is inlined.
+    if !f.is_exported() && module.users_of(f).len() == 1 {
+        weight += 0.5;
+    }
+
+    Weight(weight.max(0.0).min(1.0))
+}
+
+/// Checks whether it is possible to inline a function.
+pub fn can_inline(f: &ir::Function) -> bool {
+    // We can't inline a declaration.
+    if f.blocks().next().is_none() {
+        return false;
+    }
+
+    // Naked functions must be emitted exactly as they were written.
+    if f.is_naked() {
+        return false;
+    }
+
+    // Inlining a recursive function would never terminate.
+    ir::Users::of_in_function(f, f).is_empty()
+}
+
+/// Gets the number of values that make up a value.
+fn cost(value: &ir::Value) -> usize {
+    let subvalues: usize = match value.node {
+        ir::Expression::Register(ref reg) => self::cost(&reg.value),
+        ref node => node.subvalues().into_iter().map(self::cost).sum(),
+    };
 
-    if uses.len() == 1 {
-        Weight::always()
+    1 + subvalues
+}
+
+fn is_return(value: &ir::Value) -> bool {
+    if let ir::Expression::Instruction(ir::Instruction::Return(..)) = value.node {
+        true
     } else {
-        Weight::never()
+        false
     }
 }
 
+pub mod inline
+{
+    use ir::{self,Expression,Instruction,Value,Name};
+    use ir::value::{BlockRef,RegisterRef};
+    use util::{self,Identifiable};
+    use std::collections::HashMap;
+
+    /// Inlines all calls to a set of functions.
+    pub fn function(mut caller: ir::Function,
+                    callees: &[util::Id],
+                    module: &ir::Module) -> ir::Function {
+        let mut blocks = Vec::new();
+        let mut worklist: Vec<_> = caller.blocks.drain(..).rev().collect();
+        let mut counter = 0;
+        let mut splits = Vec::new();
+
+        while let Some(block) = worklist.pop() {
+            let block = self::flatten_nested_calls(block, callees);
+
+            let site = block.values().position(|v| {
+                self::call_site(v).and_then(|call| self::lookup_callee(call, callees, module))
+                                  .is_some()
+            });
+
+            match site {
+                Some(index) => {
+                    let (head, inlined, continuation) =
+                        self::call(block, index, callees, module, counter);
+                    splits.push((head.get_id(), continuation.get_id()));
+
+                    blocks.push(head);
+                    blocks.extend(inlined);
+
+                    // The rest of the block may contain more calls.
+                    worklist.push(continuation);
+                    counter += 1;
+                },
+                None => blocks.push(block),
+            }
+        }
+
+        // The successors of a split block are now reached from the
+        // continuation, so their phis must say so. Splits are renamed
+        // in order, as a continuation may itself have been split.
+        let blocks = blocks.into_iter().map(|block| {
+            splits.iter().fold(block, |block, &(from, to)| {
+                block.map_values(|v| self::rename_incoming(v, from, to))
+            })
+        });
+
+        caller.with_blocks(blocks)
+    }
+
+    fn rename_incoming(value: Value, from: util::Id, to: util::Id) -> Value {
+        let node = match value.node {
+            Expression::Instruction(Instruction::Phi(phi)) => {
+                Instruction::Phi(phi.rename_incoming(from, to)).into()
+            },
+            Expression::Register(mut reg) => {
+                reg.value = Box::new(self::rename_incoming(*reg.value, from, to));
+                Expression::Register(reg)
+            },
+            node => node.map_subvalues(|v| self::rename_incoming(v, from, to)),
+        };
+
+        Value::new(node)
+    }
+
+    /// Inlines the call at `index` in a block.
+    ///
+    /// The block is split at the call. Returns the part of the block
+    /// before the call, the copy of the callee's blocks, and the part
+    /// of the block after the call.
+    ///
+    /// If the callee returns from several blocks, the result is a phi
+    /// at the start of the continuation joining the returned values.
+    pub fn call(block: ir::Block,
+                index: usize,
+                callees: &[util::Id],
+                module: &ir::Module,
+                counter: usize)
+        -> (ir::Block, Vec<ir::Block>, ir::Block) {
+        let ir::Block { id, name, mut body } = block;
+
+        let after = body.split_off(index + 1);
+        let site = body.pop().unwrap();
+
+        // If the result is assigned to a register, the register
+        // is redefined in the callee's returning block.
+        let (result, call) = match site.node {
+            Expression::Register(reg) => {
+                let call = match reg.value.node {
+                    Expression::Instruction(Instruction::Call(ref call)) => call.clone(),
+                    _ => unreachable!(),
+                };
+
+                (Some((reg.get_id(), reg.name.clone())), call)
+            },
+            Expression::Instruction(Instruction::Call(call)) => (None, call),
+            _ => unreachable!(),
+        };
+
+        let callee = self::lookup_callee(&call, callees, module).unwrap();
+
+        let mut continuation = ir::Block::new(format!("{}_cont{}", name, counter), after);
+        let continuation_ref = Value::new(Expression::block_ref(&continuation));
+
+        let joins = callee.values().filter(|v| super::is_return(v)).count() > 1;
+        let mut incoming = Vec::new();
+
+        let mut mapping = Mapping {
+            registers: HashMap::new(),
+            blocks: HashMap::new(),
+            arguments: HashMap::new(),
+        };
+
+        for callee_block in callee.blocks() {
+            mapping.blocks.insert(callee_block.get_id(), util::Id::next());
+        }
+
+        for value in callee.values() {
+            if let Expression::Register(ref reg) = value.node {
+                mapping.registers.insert(reg.get_id(), util::Id::next());
+            }
+        }
+
+        // Arguments which aren't simple are evaluated once before
+        // the call so that they aren't duplicated inside the body.
+        for (param, argument) in callee.signature().parameters().zip(call.arguments()) {
+            let argument = if argument.node.is_simple() {
+                argument.clone()
+            } else {
+                let reg = ir::Register::unnamed(argument.clone());
+                let reg_ref = RegisterRef::new(reg.get_id(), argument.node.ty());
+
+                body.push(Expression::Register(reg).into());
+                Value::new(reg_ref.into())
+            };
+
+            mapping.arguments.insert(param.get_id(), argument);
+        }
+
+        let entry_id = mapping.blocks[&callee.entry_block().unwrap().get_id()];
+        body.push(Expression::br(ir::Condition::True,
+                                 Value::new(BlockRef::new(entry_id).into())).into());
+
+        let inlined: Vec<_> = callee.blocks().map(|callee_block| {
+            let mut inlined_block = ir::Block::empty(
+                format!("{}_{}{}", callee.name(), callee_block.name(), counter)
+            );
+            inlined_block.internal_set_id(mapping.blocks[&callee_block.get_id()]);
+
+            for value in callee_block.values() {
+                let value = mapping.value(value.clone());
+
+                let ret = match value.node {
+                    Expression::Instruction(Instruction::Return(ret)) => ret,
+                    node => {
+                        inlined_block.append_value(node);
+                        continue;
+                    },
+                };
+
+                match (ret.subvalue(), result.clone()) {
+                    (Some(value), Some(..)) if joins => {
+                        // Phis take simple values, so anything else is
+                        // computed in the returning block.
+                        let value = if value.node.is_simple() {
+                            value.clone()
+                        } else {
+                            let reg = ir::Register::unnamed(value.clone());
+                            let reg_ref = RegisterRef::new(reg.get_id(), value.node.ty());
+
+                            inlined_block.append_value(Expression::Register(reg));
+                            Value::new(reg_ref.into())
+                        };
+
+                        incoming.push((value, Value::new(BlockRef::new(inlined_block.get_id()).into())));
+                    },
+                    (Some(value), Some((reg_id, reg_name))) => {
+                        let mut reg = ir::Register::new(reg_name, value.clone());
+                        reg.internal_set_id(reg_id);
+
+                        inlined_block.append_value(Expression::Register(reg));
+                    },
+                    (Some(value), None) => if value.node.is_critical() {
+                        inlined_block.append_value(value.clone());
+                    },
+                    (None, _) => (),
+                }
+
+                inlined_block.append_value(Expression::br(ir::Condition::True,
+                                                          continuation_ref.clone()));
+            }
+
+            inlined_block
+        }).collect();
+
+        if let (true, Some((reg_id, reg_name))) = (joins, result) {
+            let mut reg = ir::Register::new(reg_name, Expression::phi(call.ty(), incoming).into());
+            reg.internal_set_id(reg_id);
+
+            continuation.body.insert(0, Expression::Register(reg).into());
+        }
+
+        let head = ir::Block { id: id, name: name, body: body };
+        (head, inlined, continuation)
+    }
+
+    /// Gets the call performed by a value, if the value is either a
+    /// call or a register assigned to a call.
+    pub fn call_site(value: &Value) -> Option<&ir::instruction::Call> {
+        let value = match value.node {
+            Expression::Register(ref reg) => &reg.value,
+            _ => value,
+        };
+
+        match value.node {
+            Expression::Instruction(Instruction::Call(ref call)) => Some(call),
+            _ => None,
+        }
+    }
+
+    /// Looks up the function called by `call` if it should be inlined.
+    fn lookup_callee<'a>(call: &ir::instruction::Call,
+                         callees: &[util::Id],
+                         module: &'a ir::Module) -> Option<&'a ir::Function> {
+        let id = match call.target().node {
+            Expression::FunctionRef(ref f) => f.function_id(),
+            _ => return None,
+        };
+
+        if !callees.contains(&id) {
+            return None;
+        }
+
+        // The function currently being mapped is locked.
+        module.find_function(id).into()
+    }
+
+    /// Hoists calls that are nested inside other values into registers.
+    fn flatten_nested_calls(block: ir::Block,
+                            callees: &[util::Id]) -> ir::Block {
+        let mut flattened = ir::Block { body: Vec::new(), ..block.clone() };
+
+        for value in block.body {
+            if self::call_site(&value).is_some() || !self::contains_call(&value, callees) {
+                flattened.append_value(value);
+                continue;
+            }
+
+            let node = match value.node {
+                Expression::Register(mut reg) => {
+                    let inner = reg.value.node.flatten(&mut flattened);
+                    reg.value = Box::new(Value::new(inner));
+                    Expression::Register(reg)
+                },
+                node => node.flatten(&mut flattened),
+            };
+
+            flattened.append_value(node);
+        }
+
+        flattened
+    }
+
+    fn contains_call(value: &Value, callees: &[util::Id]) -> bool {
+        match value.node {
+            Expression::Instruction(Instruction::Call(ref call))
+                if callees.contains(&call.target_id()) => true,
+            Expression::Register(ref reg) => self::contains_call(&reg.value, callees),
+            ref node => node.subvalues().into_iter().any(|v| self::contains_call(v, callees)),
+        }
+    }
+
+    /// Maps the values of a callee into the caller.
+    struct Mapping
+    {
+        registers: HashMap<util::Id, util::Id>,
+        blocks: HashMap<util::Id, util::Id>,
+        arguments: HashMap<util::Id, Value>,
+    }
+
+    impl Mapping
+    {
+        fn value(&self, value: Value) -> Value {
+            let node = match value.node {
+                Expression::Register(reg) => {
+                    let old_id = reg.get_id();
+
+                    // Names could clash with registers in the caller.
+                    let mut new_reg = ir::Register::new(Name::Unnamed, self.value(*reg.value));
+
+                    if let Some(&id) = self.registers.get(&old_id) {
+                        new_reg.internal_set_id(id);
+                    }
+                    new_reg.into()
+                },
+                Expression::RegisterRef(r) => {
+                    let id = self.registers.get(&r.register_id).cloned().unwrap_or(r.register_id);
+                    RegisterRef::new(id, r.ty).into()
+                },
+                Expression::BlockRef(r) => {
+                    let id = self.blocks.get(&r.block_id()).cloned().unwrap_or(r.block_id());
+                    BlockRef::new(id).into()
+                },
+                Expression::ArgumentRef(a) => match self.arguments.get(&a.param_id) {
+                    Some(argument) => return argument.clone(),
+                    None => Expression::ArgumentRef(a),
+                },
+                node => node.map_subvalues(|v| self.value(v)),
+            };
+
+            Value::new(node)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use ir::{self,Expression,Instruction,Value,Type};
+    use util::Identifiable;
+
+    /// Creates `fn @add(%a: i8, %b: i8) -> i8 { ret (add %a, %b) }`.
+    fn create_add() -> ir::Function {
+        let sig = ir::Signature::empty().param("a".into(), Type::i8())
+                                        .param("b".into(), Type::i8())
+                                        .ret(Type::i8());
+        let (a, b) = {
+            let mut params = sig.parameters();
+            (Expression::argument_ref(params.next().unwrap()),
+             Expression::argument_ref(params.next().unwrap()))
+        };
+
+        let mut block = ir::Block::empty("entry");
+        block.append_value(Expression::ret(Expression::add(a, b).into()));
+
+        let mut f = ir::Function::empty("add", sig);
+        f.linkage = ir::Linkage::Internal;
+        f.append_block(block);
+        f
+    }
+
+    fn create_caller(callee: &ir::Function) -> ir::Function {
+        let call = Expression::call_with_arguments(
+            Expression::function_ref(callee),
+            vec![Expression::i8(1).into(), Expression::i8(2).into()]
+        );
+        let reg = ir::Register::unnamed(call.into());
+        let reg_ref = ir::value::RegisterRef::new(reg.get_id(), Type::i8());
+
+        let mut block = ir::Block::empty("entry");
+        block.append_value(Expression::Register(reg));
+        block.append_value(Expression::ret(Value::new(reg_ref.into())));
+
+        let sig = ir::Signature::empty().ret(Type::i8());
+        let mut f = ir::Function::empty("main", sig);
+        f.append_block(block);
+        f
+    }
+
+    #[test]
+    fn inlines_call_with_arguments() {
+        let callee = create_add();
+        let caller = create_caller(&callee);
+        let module = ir::Module::empty().function(callee).function(caller);
+
+        let module = ::Transform::run_module(&mut super::Inliner, module);
+        let main = module.functions().find(|f| f.name() == "main").unwrap();
+
+        let names: Vec<_> = main.blocks().map(|b| b.name()).collect();
+        assert_eq!(names, vec!["entry", "add_entry0", "entry_cont0"]);
+
+        let has_call = main.values().any(|v| super::inline::call_site(v).is_some());
+        assert!(!has_call);
+
+        let inlined = &main.blocks[1].body[0];
+        match inlined.node {
+            Expression::Register(ref reg) => {
+                assert_eq!(reg.value.node, Expression::add(Expression::i8(1), Expression::i8(2)));
+            },
+            _ => panic!("expected the return value to be assigned to the call register"),
+        }
+
+        assert!(main.blocks[2].body[0].node.is_terminator());
+        assert!(match main.blocks[0].body[0].node {
+            Expression::Instruction(Instruction::Break(..)) => true,
+            _ => false,
+        });
+    }
+
+    #[test]
+    fn renames_phi_incoming_blocks_after_splitting() {
+        let text = "fn @id(%a: i8) -> i8 internal must_inline {\n  ret %a\n}\n\
+                    fn @main(%x: i8) -> i8 {\n  \
+                      %y = call @id(%x)\n  \
+                      br %x eq i8 0, %join\n\
+                    :other\n  \
+                      br true, %join\n\
+                    :join\n  \
+                      %p = phi i8 [%y, %entry], [i8 1, %other]\n  \
+                      ret %p\n}\n";
+        let module = ir::read::textual(text.chars()).unwrap();
+
+        let module = ::Transform::run_module(&mut super::Inliner, module);
+        let main = module.functions().find(|f| f.name() == "main").unwrap();
+        let continuation = main.blocks().find(|b| b.name() == "entry_cont0").unwrap().get_id();
+
+        let phi = main.values().filter_map(|v| match v.node {
+            Expression::Register(ref reg) => match reg.value.node {
+                Expression::Instruction(Instruction::Phi(ref phi)) => Some(phi.clone()),
+                _ => None,
+            },
+            _ => None,
+        }).next().unwrap();
+
+        assert!(phi.incoming_from(continuation).is_some());
+        assert!(phi.incoming_from(main.blocks[0].get_id()).is_none());
+    }
+
+    #[test]
+    fn joins_several_returns_with_a_phi() {
+        let text = "fn @abs(%a: i8) -> i8 internal must_inline {\n  \
+                      br %a lt i8 0, %negative\n\
+                    :positive\n  \
+                      ret %a\n\
+                    :negative\n  \
+                      ret sub i8 0, %a\n}\n\
+                    fn @main(%x: i8) -> i8 {\n  \
+                      %y = call @abs(%x)\n  \
+                      %z = add %y, i8 1\n  \
+                      ret %z\n}\n";
+        let module = ir::read::textual(text.chars()).unwrap();
+
+        let module = ::Transform::run_module(&mut super::Inliner, module);
+        let main = module.functions().find(|f| f.name() == "main").unwrap();
+
+        let has_call = main.values().any(|v| super::inline::call_site(v).is_some());
+        assert!(!has_call);
+
+        let positive = main.blocks().find(|b| b.name() == "abs_positive0").unwrap().get_id();
+        let negative = main.blocks().find(|b| b.name() == "abs_negative0").unwrap().get_id();
+        let continuation = main.blocks().find(|b| b.name() == "entry_cont0").unwrap();
+
+        match continuation.body[0].node {
+            Expression::Register(ref reg) => match reg.value.node {
+                Expression::Instruction(Instruction::Phi(ref phi)) => {
+                    assert_eq!(phi.incoming().count(), 2);
+                    assert!(phi.incoming_from(positive).is_some());
+                    assert!(phi.incoming_from(negative).is_some());
+                },
+                _ => panic!("expected the returned values to be joined by a phi"),
+            },
+            _ => panic!("expected the continuation to start with the call register"),
+        }
+    }
+
+    #[test]
+    fn never_inlines_recursive_functions() {
+        let mut f = ir::Function::empty("f", ir::Signature::empty());
+        f.inline_hint = ir::InlineHint::MustInline;
+
+        let mut block = ir::Block::empty("entry");
+        block.append_value(Expression::call(Expression::function_ref(&f)));
+        block.append_value(Expression::ret_void());
+        f.append_block(block);
+
+        let module = ir::Module::empty().function(f);
+        let f = module.functions().next().unwrap();
+
+        assert!(!super::inlining_weight(f, &module).should_inline());
+    }
+}
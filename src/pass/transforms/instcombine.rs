@@ -0,0 +1,374 @@
+use {Metadata,Id,Info,Transform};
+use ir;
+
+/// An instruction combining pass.
+///
+/// This performs algebraic simplifications which do not require
+/// all operands to be constant, such as `x + 0 => x`.
+pub struct InstCombine;
+
+impl Metadata for InstCombine
+{
+    fn id(&self) -> Id { Id(0x1ac0b3b7) }
+    fn name(&self) -> &'static str { "instruction combining" }
+}
+
+impl Transform for InstCombine
+{
+    fn run_function(&mut self,
+                    function: ir::Function,
+                    _module: &ir::Module) -> ir::Function {
+        self::combine::function(function)
+    }
+}
+
+// TODO: blamket impl for all passes
+impl Into<Info> for Box<InstCombine>
+{
+    fn into(self) -> Info {
+        Info::Transform(self)
+    }
+}
+
+pub mod combine
+{
+    use ir::{self,Value,Expression,Instruction,Type};
+    use ir::instruction::Binary;
+    use ir::value::literal::Integer;
+    use util::{self,Identifiable};
+
+    use std::collections::HashMap;
+
+    /// The values of the registers in a function.
+    pub type Definitions = HashMap<util::Id, Value>;
+
+    /// A simplification rule.
+    pub struct Rule
+    {
+        /// A description of the rule.
+        pub name: &'static str,
+        /// Applies the rule, returning `None` if the instruction
+        /// does not match.
+        ///
+        /// Operands which refer to registers may be looked up in
+        /// the definitions.
+        pub apply: fn(&Instruction, &Definitions) -> Option<Expression>,
+    }
+
+    /// The rules, in the order they are tried.
+    pub const RULES: &'static [Rule] = &[
+        Rule { name: "c + x => x + c", apply: rules::canonicalize_commutative },
+        Rule { name: "x + 0 => x", apply: rules::add_zero },
+        Rule { name: "x - 0 => x", apply: rules::sub_zero },
+        Rule { name: "x - x => 0", apply: rules::sub_self },
+        Rule { name: "x * 1 => x", apply: rules::mul_one },
+        Rule { name: "x * 0 => 0", apply: rules::mul_zero },
+        Rule { name: "x / 1 => x", apply: rules::div_one },
+        Rule { name: "x << 0 => x", apply: rules::shl_zero },
+        Rule { name: "x >> 0 => x", apply: rules::shr_zero },
+        Rule { name: "(x + c1) + c2 => x + (c1 + c2)", apply: rules::reassociate_add },
+        Rule { name: "(x * c1) * c2 => x * (c1 * c2)", apply: rules::reassociate_mul },
+    ];
+
+    /// Simplifies every value in a function, looking through the
+    /// registers which name intermediate results.
+    pub fn function(func: ir::Function) -> ir::Function {
+        let definitions = self::definitions(&func);
+        func.map_values(|v| self::value_recursive(v, &definitions))
+    }
+
+    /// Simplifies a value on its own.
+    pub fn value(value: Value) -> Value {
+        self::value_with(value, &Definitions::new())
+    }
+
+    /// Simplifies a value, given the values of the registers.
+    pub fn value_with(value: Value, definitions: &Definitions) -> Value {
+        match value.node {
+            Expression::Instruction(i) => Value::new(instruction(i, definitions)),
+            e => Value::new(e),
+        }
+    }
+
+    /// Applies rules to an instruction until none of them match.
+    pub fn instruction(inst: Instruction, definitions: &Definitions) -> Expression {
+        let mut expr: Expression = inst.into();
+
+        loop {
+            let simplified = match expr {
+                Expression::Instruction(ref i) => {
+                    RULES.iter().filter_map(|rule| (rule.apply)(i, definitions)).next()
+                },
+                _ => None,
+            };
+
+            match simplified {
+                Some(e) => expr = e,
+                None => return expr,
+            }
+        }
+    }
+
+    /// Simplifies a value from the deepest node up.
+    fn value_recursive(value: Value, definitions: &Definitions) -> Value {
+        let node = match value.node {
+            Expression::Register(mut reg) => {
+                reg.value = Box::new(self::value_recursive(*reg.value, definitions));
+                Expression::Register(reg)
+            },
+            node => self::value_with(Value::new(node.map_subvalues(|v| {
+                self::value_recursive(v, definitions)
+            })), definitions).node,
+        };
+
+        Value { node: node, ..value }
+    }
+
+    /// Finds the value of every register in a function.
+    fn definitions(func: &ir::Function) -> Definitions {
+        let mut definitions = Definitions::new();
+        let mut worklist: Vec<&Value> = func.values().collect();
+
+        while let Some(value) = worklist.pop() {
+            if let Expression::Register(ref reg) = value.node {
+                definitions.insert(reg.get_id(), (*reg.value).clone());
+            }
+
+            worklist.extend(value.node.subvalues());
+        }
+
+        definitions
+    }
+
+    pub mod rules
+    {
+        use super::*;
+
+        pub fn canonicalize_commutative(inst: &Instruction, _: &Definitions) -> Option<Expression> {
+            match *inst {
+                Instruction::Add(ref i) => swap_constant(i).map(|(l,r)| Expression::add(l, r)),
+                Instruction::Mul(ref i) => swap_constant(i).map(|(l,r)| Expression::mul(l, r)),
+                _ => None,
+            }
+        }
+
+        pub fn add_zero(inst: &Instruction, _: &Definitions) -> Option<Expression> {
+            if let Instruction::Add(ref i) = *inst { identity(i, 0) } else { None }
+        }
+
+        pub fn sub_zero(inst: &Instruction, _: &Definitions) -> Option<Expression> {
+            if let Instruction::Sub(ref i) = *inst { identity(i, 0) } else { None }
+        }
+
+        pub fn sub_self(inst: &Instruction, _: &Definitions) -> Option<Expression> {
+            if let Instruction::Sub(ref i) = *inst {
+                let (lhs, rhs) = i.operands();
+
+                if lhs == rhs && is_pure(lhs) {
+                    return zero(&lhs.node.ty());
+                }
+            }
+            None
+        }
+
+        pub fn mul_one(inst: &Instruction, _: &Definitions) -> Option<Expression> {
+            if let Instruction::Mul(ref i) = *inst { identity(i, 1) } else { None }
+        }
+
+        pub fn mul_zero(inst: &Instruction, _: &Definitions) -> Option<Expression> {
+            if let Instruction::Mul(ref i) = *inst {
+                let (lhs, rhs) = i.operands();
+
+                if is_integer(rhs, 0) && is_pure(lhs) {
+                    return Some(rhs.node.clone());
+                }
+            }
+            None
+        }
+
+        pub fn div_one(inst: &Instruction, _: &Definitions) -> Option<Expression> {
+            if let Instruction::Div(ref i) = *inst { identity(i, 1) } else { None }
+        }
+
+        pub fn shl_zero(inst: &Instruction, _: &Definitions) -> Option<Expression> {
+            if let Instruction::Shl(ref i) = *inst { identity(i, 0) } else { None }
+        }
+
+        pub fn shr_zero(inst: &Instruction, _: &Definitions) -> Option<Expression> {
+            if let Instruction::Shr(ref i) = *inst { identity(i, 0) } else { None }
+        }
+
+        pub fn reassociate_add(inst: &Instruction, definitions: &Definitions) -> Option<Expression> {
+            if let Instruction::Add(ref outer) = *inst {
+                let (lhs, c2) = outer.operands();
+
+                if let Some(Instruction::Add(ref inner)) = operand(lhs, definitions) {
+                    let (x, c1) = inner.operands();
+
+                    if !may_reuse(lhs, x) {
+                        return None;
+                    }
+
+                    if let (Some(c1), Some(c2)) = (integer(c1), integer(c2)) {
+                        return Some(Expression::add(x.clone(), Value::new((c1.clone() + c2.clone()).into())));
+                    }
+                }
+            }
+            None
+        }
+
+        pub fn reassociate_mul(inst: &Instruction, definitions: &Definitions) -> Option<Expression> {
+            if let Instruction::Mul(ref outer) = *inst {
+                let (lhs, c2) = outer.operands();
+
+                if let Some(Instruction::Mul(ref inner)) = operand(lhs, definitions) {
+                    let (x, c1) = inner.operands();
+
+                    if !may_reuse(lhs, x) {
+                        return None;
+                    }
+
+                    if let (Some(c1), Some(c2)) = (integer(c1), integer(c2)) {
+                        return Some(Expression::mul(x.clone(), Value::new((c1.clone() * c2.clone()).into())));
+                    }
+                }
+            }
+            None
+        }
+
+        /// Gets the instruction which computes an operand, looking
+        /// through a register which names it.
+        fn operand<'a>(value: &'a Value, definitions: &'a Definitions) -> Option<&'a Instruction> {
+            let value = match value.node {
+                Expression::RegisterRef(ref r) => match definitions.get(&r.register_id) {
+                    Some(definition) => definition,
+                    None => return None,
+                },
+                _ => value,
+            };
+
+            match value.node {
+                Expression::Instruction(ref i) => Some(i),
+                _ => None,
+            }
+        }
+
+        /// Checks if a part of an operand's instruction can be used
+        /// in place of the operand.
+        ///
+        /// The instruction of a register has already been computed,
+        /// so only parts which are cheap to repeat may be reused.
+        fn may_reuse(operand: &Value, part: &Value) -> bool {
+            match (&operand.node, &part.node) {
+                (&Expression::RegisterRef(..), &Expression::Instruction(..)) |
+                (&Expression::RegisterRef(..), &Expression::Register(..)) => false,
+                _ => true,
+            }
+        }
+
+        /// Moves a constant left-hand operand to the right-hand side.
+        fn swap_constant<I: Binary>(inst: &I) -> Option<(Value,Value)> {
+            let (lhs, rhs) = inst.operands();
+
+            if lhs.node.is_literal() && !rhs.node.is_literal() {
+                Some((rhs.clone(), lhs.clone()))
+            } else {
+                None
+            }
+        }
+
+        /// Simplifies `x op c` to `x` if the right-hand side is `c`.
+        fn identity<I: Binary>(inst: &I, c: i64) -> Option<Expression> {
+            let (lhs, rhs) = inst.operands();
+
+            if is_integer(rhs, c) { Some(lhs.node.clone()) } else { None }
+        }
+
+        /// Checks if a value can be removed without losing side effects.
+        fn is_pure(value: &Value) -> bool {
+            match value.node {
+                Expression::Instruction(ref i) => {
+                    !i.is_single_critical() && i.subvalues().iter().all(|v| is_pure(v))
+                },
+                Expression::Register(..) => false,
+                _ => true,
+            }
+        }
+
+        fn integer(value: &Value) -> Option<&Integer> {
+            value.node.as_literal().and_then(|l| l.as_integer())
+        }
+
+        fn is_integer(value: &Value, c: i64) -> bool {
+            integer(value).map_or(false, |i| i.value() == c.into())
+        }
+
+        fn zero(ty: &Type) -> Option<Expression> {
+            match *ty {
+                Type::Integer(ty) => Expression::integer(ty, 0),
+                _ => None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+fn x() -> ir::Value {
+    thread_local!(static X: ir::Parameter = ir::Parameter::new("x".to_owned(), ir::Type::i8()));
+
+    X.with(|param| ir::Expression::argument_ref(param).into())
+}
+
+#[cfg(test)]
+fn c(value: i8) -> ir::Value {
+    ir::Expression::i8(value).into()
+}
+
+value_mapping_test!(test_canonicalize_commutative : combine::value {
+    Expression::add(c(3), x()) => Expression::add(x(), c(3)),
+    Expression::mul(c(3), x()) => Expression::mul(x(), c(3)),
+    Expression::sub(c(3), x()) => Expression::sub(c(3), x())
+});
+
+value_mapping_test!(test_add_zero : combine::value {
+    Expression::add(x(), c(0)) => x().node,
+    Expression::add(c(0), x()) => x().node
+});
+
+value_mapping_test!(test_sub_zero : combine::value {
+    Expression::sub(x(), c(0)) => x().node
+});
+
+value_mapping_test!(test_sub_self : combine::value {
+    Expression::sub(x(), x()) => Expression::i8(0)
+});
+
+value_mapping_test!(test_mul_one : combine::value {
+    Expression::mul(x(), c(1)) => x().node,
+    Expression::mul(c(1), x()) => x().node
+});
+
+value_mapping_test!(test_mul_zero : combine::value {
+    Expression::mul(x(), c(0)) => Expression::i8(0)
+});
+
+value_mapping_test!(test_div_one : combine::value {
+    Expression::div(x(), c(1)) => x().node
+});
+
+value_mapping_test!(test_shift_zero : combine::value {
+    Expression::shl(x(), c(0)) => x().node,
+    Expression::shr(x(), c(0)) => x().node
+});
+
+value_mapping_test!(test_reassociate_add : combine::value {
+    Expression::add(Value::new(Expression::add(x(), c(2))), c(3))
+        => Expression::add(x(), c(5)),
+    Expression::add(Value::new(Expression::add(x(), c(127))), c(1))
+        => Expression::add(x(), c(-128))
+});
+
+value_mapping_test!(test_reassociate_mul : combine::value {
+    Expression::mul(Value::new(Expression::mul(x(), c(2))), c(3))
+        => Expression::mul(x(), c(6))
+});
@@ -0,0 +1,386 @@
+use {Metadata,Id,Info,Transform};
+use ir;
+
+/// An interprocedural constant propagation pass.
+///
+/// This works on internal functions whose every use is a direct call.
+///   * arguments which are the same constant at every call site are
+///     propagated into the callee
+///   * parameters which are never used are removed from the signature
+///     and from every call site
+pub struct InterproceduralConstantPropagation;
+
+impl Metadata for InterproceduralConstantPropagation
+{
+    fn id(&self) -> Id { Id(0x5a93c6e1) }
+    fn name(&self) -> &'static str { "interprocedural constant propagation" }
+}
+
+impl Transform for InterproceduralConstantPropagation
+{
+    fn run_module(&mut self,
+                  module: ir::Module) -> ir::Module {
+        self::propagate::module(module)
+    }
+}
+
+// TODO: blamket impl for all passes
+impl Into<Info> for Box<InterproceduralConstantPropagation>
+{
+    fn into(self) -> Info {
+        Info::Transform(self)
+    }
+}
+
+pub mod propagate
+{
+    use ir::{self,Value,Expression,Instruction};
+    use ir::value::FunctionRef;
+    use util::{self,Identifiable};
+
+    /// Propagates constant arguments and removes unused parameters
+    /// until nothing changes.
+    pub fn module(mut module: ir::Module) -> ir::Module {
+        loop {
+            let (m, propagated) = self::constant_arguments(module);
+            let (m, removed) = self::dead_arguments(m);
+            module = m;
+
+            if !propagated && !removed {
+                return module;
+            }
+        }
+    }
+
+    /// Gets the arguments passed at every call to a function.
+    ///
+    /// Returns `None` if the function is exported, or if it is used
+    /// in any way other than being called directly, as there may be
+    /// calls which cannot be seen.
+    pub fn call_sites(func: &ir::Function, module: &ir::Module) -> Option<Vec<Vec<Value>>> {
+        if func.is_exported() {
+            return None;
+        }
+
+        let mut sites = Vec::new();
+        for value in module.values() {
+            self::find_calls(func.id(), value, &mut sites);
+        }
+
+        let param_count = func.signature().parameters().count();
+
+        if sites.len() == module.users_of(func).len() &&
+           sites.iter().all(|arguments| arguments.len() == param_count) {
+            Some(sites)
+        } else {
+            None
+        }
+    }
+
+    /// Replaces parameters with the constant that every call site
+    /// passes for them.
+    pub fn constant_arguments(module: ir::Module) -> (ir::Module, bool) {
+        let constants: Vec<(util::Id, Vec<(util::Id, Value)>)> = module.functions().filter_map(|func| {
+            let sites = match self::call_sites(func, &module) {
+                Some(sites) => sites,
+                None => return None,
+            };
+
+            if sites.is_empty() {
+                return None;
+            }
+
+            let params: Vec<_> = func.signature().parameters().enumerate().filter_map(|(index, param)| {
+                if !self::uses_parameter(func, param.get_id()) {
+                    return None;
+                }
+
+                self::constant_argument(param.get_id(), index, &sites).map(|c| (param.get_id(), c))
+            }).collect();
+
+            if params.is_empty() { None } else { Some((func.id(), params)) }
+        }).collect();
+
+        let changed = !constants.is_empty();
+
+        let module = module.map_functions(|func, _| {
+            match constants.iter().find(|&&(id, _)| id == func.id()) {
+                Some(&(_, ref params)) => func.map_values(|v| self::replace_arguments(v, params)),
+                None => func,
+            }
+        });
+
+        (module, changed)
+    }
+
+    /// Removes parameters which are never used from functions
+    /// and the calls to them.
+    pub fn dead_arguments(mut module: ir::Module) -> (ir::Module, bool) {
+        let dead: Vec<(util::Id, Vec<usize>)> = module.functions().filter_map(|func| {
+            let sites = match self::call_sites(func, &module) {
+                Some(sites) => sites,
+                None => return None,
+            };
+
+            // Arguments with side effects must still be evaluated, so
+            // their parameters are kept.
+            let positions: Vec<_> = func.signature().parameters().enumerate()
+                .filter(|&(_, param)| !self::uses_parameter(func, param.get_id()))
+                .filter(|&(index, _)| sites.iter().all(|arguments| !self::has_side_effects(&arguments[index])))
+                .map(|(index, _)| index)
+                .collect();
+
+            if positions.is_empty() { None } else { Some((func.id(), positions)) }
+        }).collect();
+
+        let changed = !dead.is_empty();
+
+        for (id, positions) in dead {
+            for func in module.functions_mut().filter(|f| f.id() == id) {
+                let mut index = 0;
+                func.signature = func.signature.clone().remove_parameters(|_| {
+                    index += 1;
+                    positions.contains(&(index - 1))
+                });
+            }
+
+            let reference = FunctionRef::reference(module.get_function(id));
+            module = module.map_values(|v| self::rewrite_calls(v, &reference, &positions));
+        }
+
+        (module, changed)
+    }
+
+    /// Gets the constant passed for a parameter at every call site.
+    ///
+    /// Recursive calls which pass the parameter straight through
+    /// do not change its value, so they are ignored.
+    fn constant_argument(param: util::Id,
+                         index: usize,
+                         sites: &[Vec<Value>]) -> Option<Value> {
+        let mut constant: Option<&Value> = None;
+
+        for arguments in sites {
+            let argument = &arguments[index];
+
+            match argument.node {
+                Expression::Literal(..) => match constant {
+                    Some(c) if c != argument => return None,
+                    _ => constant = Some(argument),
+                },
+                Expression::ArgumentRef(ref a) if a.parameter_id() == param => (),
+                _ => return None,
+            }
+        }
+
+        constant.cloned()
+    }
+
+    /// Finds the arguments of every direct call to a function.
+    fn find_calls(func: util::Id, value: &Value, sites: &mut Vec<Vec<Value>>) {
+        match value.node {
+            Expression::Register(ref reg) => return self::find_calls(func, &reg.value, sites),
+            Expression::Instruction(Instruction::Call(ref call)) => {
+                if let Expression::FunctionRef(ref f) = call.target().node {
+                    if f.function_id() == func {
+                        sites.push(call.arguments().cloned().collect());
+                    }
+                }
+            },
+            _ => (),
+        }
+
+        for subvalue in value.node.subvalues() {
+            self::find_calls(func, subvalue, sites);
+        }
+    }
+
+    /// Checks if evaluating a value does anything besides
+    /// computing it.
+    pub fn has_side_effects(value: &Value) -> bool {
+        match value.node {
+            Expression::Register(ref reg) => self::has_side_effects(&reg.value),
+            Expression::Instruction(ref i) => {
+                i.is_single_critical() || i.subvalues().into_iter().any(self::has_side_effects)
+            },
+            _ => false,
+        }
+    }
+
+    fn uses_parameter(func: &ir::Function, param: util::Id) -> bool {
+        fn uses(value: &Value, param: util::Id) -> bool {
+            match value.node {
+                Expression::ArgumentRef(ref a) => a.parameter_id() == param,
+                Expression::Register(ref reg) => uses(&reg.value, param),
+                ref node => node.subvalues().into_iter().any(|v| uses(v, param)),
+            }
+        }
+
+        func.values().any(|v| uses(v, param))
+    }
+
+    fn replace_arguments(value: Value, constants: &[(util::Id, Value)]) -> Value {
+        match value.node {
+            Expression::ArgumentRef(ref a) => {
+                match constants.iter().find(|&&(id, _)| id == a.parameter_id()) {
+                    Some(&(_, ref constant)) => constant.clone(),
+                    None => value.clone(),
+                }
+            },
+            Expression::Register(mut reg) => {
+                reg.value = Box::new(self::replace_arguments(*reg.value, constants));
+                Value::new(Expression::Register(reg))
+            },
+            node => Value::new(node.map_subvalues(|v| self::replace_arguments(v, constants))),
+        }
+    }
+
+    /// Removes arguments from the calls to a function, and updates
+    /// the references to it with its new signature.
+    fn rewrite_calls(value: Value, reference: &FunctionRef, positions: &[usize]) -> Value {
+        match value.node {
+            Expression::FunctionRef(ref f) if f.function_id() == reference.function_id() => {
+                Value::new(reference.clone().into())
+            },
+            Expression::Instruction(Instruction::Call(call)) => {
+                let is_target = match call.target().node {
+                    Expression::FunctionRef(ref f) => f.function_id() == reference.function_id(),
+                    _ => false,
+                };
+
+                let call = if is_target { call.remove_arguments(|i| positions.contains(&i)) } else { call };
+                Value::new(call.map_subvalues(|v| self::rewrite_calls(v, reference, positions)).into())
+            },
+            Expression::Register(mut reg) => {
+                reg.value = Box::new(self::rewrite_calls(*reg.value, reference, positions));
+                Value::new(Expression::Register(reg))
+            },
+            node => Value::new(node.map_subvalues(|v| self::rewrite_calls(v, reference, positions))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use ir::{self,Expression,Value,Type};
+
+    /// Builds an internal `fn @add(%a: i8, %b: i8, %unused: i8) -> i8`.
+    fn callee() -> (ir::Function, Vec<ir::Parameter>) {
+        let params: Vec<_> = ["a", "b", "unused"].iter().map(|n| {
+            ir::Parameter::new(n.to_string(), Type::i8())
+        }).collect();
+
+        let mut func = ir::Function::empty("add", ir::Signature::new(params.clone(), vec![Type::i8()]));
+        func.linkage = ir::Linkage::Internal;
+
+        let mut entry = ir::Block::empty("entry");
+        entry.append_value(Expression::ret(Value::new(Expression::add(
+            Expression::argument_ref(&params[0]), Expression::argument_ref(&params[1])))));
+        func.append_block(entry);
+
+        (func, params)
+    }
+
+    fn caller(callee: &ir::Function, param: ir::Parameter, arguments: Vec<Vec<Value>>) -> ir::Function {
+        let mut func = ir::Function::empty("main", ir::Signature::new(vec![param], vec![]));
+
+        let mut entry = ir::Block::empty("entry");
+        for arguments in arguments {
+            entry.append_value(Expression::call_with_arguments(Expression::function_ref(callee), arguments));
+        }
+        entry.append_value(Expression::ret_void());
+        func.append_block(entry);
+
+        func
+    }
+
+    fn constants(values: &[i8]) -> Vec<Value> {
+        values.iter().map(|&v| Value::new(Expression::i8(v))).collect()
+    }
+
+    fn calls(module: &ir::Module) -> Vec<Vec<Value>> {
+        module.functions().find(|f| f.name() == "main").unwrap().values().filter_map(|v| match v.node {
+            Expression::Instruction(ir::Instruction::Call(ref call)) => Some(call.arguments().cloned().collect()),
+            _ => None,
+        }).collect()
+    }
+
+    #[test]
+    fn propagates_constant_arguments() {
+        let (add, params) = callee();
+
+        let x = ir::Parameter::new("x".to_owned(), Type::i8());
+
+        // `%a` varies between calls, `%b` is always 5.
+        let mut first = constants(&[0, 5, 1]);
+        first[0] = Value::new(Expression::argument_ref(&x));
+        let main = caller(&add, x, vec![first, constants(&[3, 5, 2])]);
+
+        let module = ir::Module::empty().function(add).function(main);
+        let module = super::propagate::module(module);
+
+        let add = module.functions().find(|f| f.name() == "add").unwrap();
+        let names: Vec<_> = add.signature().parameters().map(|p| p.name()).collect();
+
+        assert_eq!(names, vec!["a"]);
+        assert_eq!(add.blocks[0].body[0], Expression::ret(Value::new(Expression::add(
+            Expression::argument_ref(&params[0]), Expression::i8(5)))).into());
+        assert!(calls(&module).iter().all(|arguments| arguments.len() == 1));
+    }
+
+    #[test]
+    fn keeps_arguments_with_side_effects() {
+        let (add, _) = callee();
+        let effect = ir::Function::empty("effect", ir::Signature::new(vec![], vec![Type::i8()]));
+
+        let x = ir::Parameter::new("x".to_owned(), Type::i8());
+
+        let mut arguments = constants(&[0, 5, 0]);
+        arguments[0] = Value::new(Expression::argument_ref(&x));
+        arguments[2] = Value::new(Expression::call(Expression::function_ref(&effect)));
+        let main = caller(&add, x, vec![arguments.clone()]);
+
+        let module = ir::Module::empty().function(add).function(main).function(effect);
+        let module = super::propagate::module(module);
+
+        let add = module.functions().find(|f| f.name() == "add").unwrap();
+        let names: Vec<_> = add.signature().parameters().map(|p| p.name()).collect();
+
+        assert_eq!(names, vec!["a", "unused"]);
+        assert_eq!(calls(&module), vec![vec![arguments[0].clone(), arguments[2].clone()]]);
+    }
+
+    #[test]
+    fn ignores_exported_functions() {
+        let (mut add, _) = callee();
+        add.linkage = ir::Linkage::External;
+
+        let main = caller(&add, ir::Parameter::new("x".to_owned(), Type::i8()),
+                              vec![constants(&[1, 5, 1])]);
+
+        let module = ir::Module::empty().function(add).function(main);
+        let module = super::propagate::module(module);
+
+        let add = module.functions().find(|f| f.name() == "add").unwrap();
+        assert_eq!(add.signature().parameters().count(), 3);
+        assert_eq!(calls(&module)[0].len(), 3);
+    }
+
+    #[test]
+    fn ignores_functions_whose_address_is_taken() {
+        let (add, _) = callee();
+
+        let mut main = caller(&add, ir::Parameter::new("x".to_owned(), Type::i8()),
+                              vec![constants(&[1, 5, 1])]);
+        let escape = ir::Function::empty("escape", ir::Signature::empty());
+        main.blocks[0].body.insert(0, Value::new(Expression::call_with_arguments(
+            Expression::function_ref(&escape), vec![Value::new(Expression::function_ref(&add))])));
+
+        let module = ir::Module::empty().function(add).function(main).function(escape);
+        let module = super::propagate::module(module);
+
+        let add = module.functions().find(|f| f.name() == "add").unwrap();
+        assert_eq!(add.signature().parameters().count(), 3);
+    }
+}
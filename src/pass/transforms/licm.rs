@@ -0,0 +1,351 @@
+use {Metadata,Id,Info,Transform};
+use ir;
+
+/// A loop-invariant code motion pass.
+///
+/// Registers inside a loop whose values do not change between
+/// iterations are hoisted into a preheader block, which is executed
+/// once before the loop is entered.
+pub struct LoopInvariantCodeMotion;
+
+impl Metadata for LoopInvariantCodeMotion
+{
+    fn id(&self) -> Id { Id(0x11c3e58d) }
+    fn name(&self) -> &'static str { "loop invariant code motion" }
+}
+
+impl Transform for LoopInvariantCodeMotion
+{
+    fn run_function(&mut self,
+                    function: ir::Function,
+                    _module: &ir::Module) -> ir::Function {
+        self::hoist::function(function)
+    }
+}
+
+// TODO: blamket impl for all passes
+impl Into<Info> for Box<LoopInvariantCodeMotion>
+{
+    fn into(self) -> Info {
+        Info::Transform(self)
+    }
+}
+
+pub mod hoist
+{
+    use ir::{self,Value,Expression,Instruction};
+    use ir::value::BlockRef;
+    use analysis::{ControlFlowGraph,DominatorTree,Loop,LoopInfo};
+    use analysis::cfg;
+    use transforms::simplify_cfg::simplify;
+    use util::{self,Identifiable};
+
+    use std::collections::HashSet;
+
+    /// Hoists loop-invariant registers out of all loops in a function.
+    ///
+    /// Inner loops are handled first, so that values can be hoisted
+    /// through several levels of nesting.
+    pub fn function(mut func: ir::Function) -> ir::Function {
+        loop {
+            let graph = ControlFlowGraph::new(&func);
+            let loops = LoopInfo::new(&graph, &DominatorTree::new(&graph));
+
+            let mut order: Vec<&Loop> = loops.loops().collect();
+            order.sort_by(|a, b| b.depth().cmp(&a.depth()));
+
+            let hoisted = order.into_iter().filter_map(|l| self::hoist_loop(&func, l, &graph)).next();
+
+            match hoisted {
+                Some(f) => func = f,
+                None => return func,
+            }
+        }
+    }
+
+    /// Hoists the invariant registers of a single loop.
+    ///
+    /// Returns `None` if there is nothing to hoist, or if the loop
+    /// cannot be given a preheader.
+    pub fn hoist_loop(func: &ir::Function,
+                      l: &Loop,
+                      graph: &ControlFlowGraph) -> Option<ir::Function> {
+        let invariant = self::invariant_registers(func, l);
+
+        if invariant.is_empty() {
+            return None;
+        }
+
+        let (func, preheader) = match self::preheader(func.clone(), l, graph) {
+            Some(p) => p,
+            None => return None,
+        };
+
+        let mut hoisted = Vec::new();
+        let func = func.map_blocks(|block| {
+            if !l.contains(block.get_id()) {
+                return block;
+            }
+
+            let body = block.body.into_iter().filter_map(|value| {
+                let is_invariant = match value.node {
+                    Expression::Register(ref reg) => invariant.contains(&reg.get_id()),
+                    _ => false,
+                };
+
+                if is_invariant {
+                    hoisted.push(value);
+                    None
+                } else {
+                    Some(value)
+                }
+            }).collect();
+
+            ir::Block { body: body, ..block }
+        });
+
+        // Keep the registers in an order where each comes after the
+        // registers it depends on.
+        hoisted.sort_by_key(|value| match value.node {
+            Expression::Register(ref reg) => invariant.iter().position(|&id| id == reg.get_id()),
+            _ => None,
+        });
+
+        let mut func = func;
+        let block = &mut func.blocks[preheader];
+        let position = match block.values().last().map(|v| &v.node) {
+            Some(&Expression::Instruction(Instruction::Break(..))) => block.body.len() - 1,
+            _ => block.body.len(),
+        };
+
+        for (offset, value) in hoisted.into_iter().enumerate() {
+            block.body.insert(position + offset, value);
+        }
+
+        Some(func)
+    }
+
+    /// Finds the registers in a loop whose values do not change
+    /// between iterations.
+    ///
+    /// A register is only included after the registers it depends on.
+    pub fn invariant_registers(func: &ir::Function, l: &Loop) -> Vec<util::Id> {
+        let blocks: Vec<_> = func.blocks().filter(|b| l.contains(b.get_id())).collect();
+
+        let defined: HashSet<_> = blocks.iter().flat_map(|b| b.values()).filter_map(|v| {
+            match v.node {
+                Expression::Register(ref reg) => Some(reg.get_id()),
+                _ => None,
+            }
+        }).collect();
+
+        let mut invariant = Vec::new();
+
+        loop {
+            let mut changed = false;
+
+            for value in blocks.iter().flat_map(|b| b.values()) {
+                if let Expression::Register(ref reg) = value.node {
+                    if invariant.contains(&reg.get_id()) || !reg.value.node.is_instruction() {
+                        continue;
+                    }
+
+                    if self::is_invariant(&reg.value, &defined, &invariant) {
+                        invariant.push(reg.get_id());
+                        changed = true;
+                    }
+                }
+            }
+
+            if !changed {
+                return invariant;
+            }
+        }
+    }
+
+    /// Checks if a value computes the same thing on every iteration,
+    /// and is safe to execute before the loop.
+    fn is_invariant(value: &Value,
+                    defined: &HashSet<util::Id>,
+                    invariant: &[util::Id]) -> bool {
+        match value.node {
+            Expression::Literal(..) |
+            Expression::ArgumentRef(..) |
+            Expression::GlobalRef(..) |
+            Expression::FunctionRef(..) |
+            Expression::String(..) => true,
+            Expression::RegisterRef(ref r) => {
+                !defined.contains(&r.register_id) || invariant.contains(&r.register_id)
+            },
+            Expression::Instruction(ref i) => {
+                let safe = match *i {
+                    Instruction::Copy(..) => false,
+                    // The memory may be written to inside the loop.
+                    Instruction::Load(..) => false,
+                    // Only hoist divisions which can never divide by zero.
+                    Instruction::Div(ref div) => self::has_nonzero_divisor(div),
+                    Instruction::Rem(ref rem) => self::has_nonzero_divisor(rem),
+                    _ => !i.is_single_critical(),
+                };
+
+                safe && i.subvalues().iter().all(|v| self::is_invariant(v, defined, invariant))
+            },
+            _ => false,
+        }
+    }
+
+    fn has_nonzero_divisor<I>(inst: &I) -> bool
+        where I: ir::instruction::Binary {
+        inst.operands().1.node.as_literal()
+                              .and_then(|l| l.as_integer())
+                              .map_or(false, |i| !i.is_zero())
+    }
+
+    /// Gets the index of the preheader of a loop, creating one if necessary.
+    ///
+    /// The preheader is the only block outside the loop which branches
+    /// to the header, and it only branches to the header.
+    fn preheader(mut func: ir::Function,
+                 l: &Loop,
+                 graph: &ControlFlowGraph) -> Option<(ir::Function, usize)> {
+        let entries = l.entries(graph);
+
+        if entries.len() == 1 && graph.successors(entries[0]) == [l.header()] {
+            let index = func.blocks().position(|b| b.get_id() == entries[0]).unwrap();
+            return Some((func, index));
+        }
+
+        let index = func.blocks().position(|b| b.get_id() == l.header()).unwrap();
+
+        // Phis in the header would need to merge the values coming
+        // from each entry in the preheader.
+        if entries.len() > 1 && simplify::has_phis(&func.blocks[index]) {
+            return None;
+        }
+
+        // The preheader is placed right before the header, so a loop
+        // block cannot fall through into the header.
+        if index > 0 {
+            let before = &func.blocks[index - 1];
+
+            if l.contains(before.get_id()) && cfg::falls_through(before) {
+                return None;
+            }
+        }
+
+        let preheader = ir::Block::empty(format!("{}_preheader", func.blocks[index].name()));
+        let (from, to) = (l.header(), preheader.get_id());
+
+        func.blocks.insert(index, preheader);
+
+        let func = func.map_blocks(|block| {
+            if block.get_id() == from {
+                // The header is now entered from the preheader.
+                match entries.first() {
+                    Some(&entry) => block.map_values(|v| {
+                        if self::is_phi(&v) { self::replace_block_refs(v, entry, to) } else { v }
+                    }),
+                    None => block,
+                }
+            } else if l.contains(block.get_id()) {
+                block
+            } else {
+                block.map_values(|v| self::replace_block_refs(v, from, to))
+            }
+        });
+
+        Some((func, index))
+    }
+
+    fn is_phi(value: &Value) -> bool {
+        match value.node {
+            Expression::Register(ref reg) => match reg.value.node {
+                Expression::Instruction(Instruction::Phi(..)) => true,
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
+    fn replace_block_refs(value: Value, from: util::Id, to: util::Id) -> Value {
+        match value.node {
+            Expression::BlockRef(ref r) if r.block_id() == from => {
+                Value::new(BlockRef::new(to).into())
+            },
+            Expression::Register(mut reg) => {
+                reg.value = Box::new(self::replace_block_refs(*reg.value, from, to));
+                Value::new(Expression::Register(reg))
+            },
+            node => Value::new(node.map_subvalues(|v| self::replace_block_refs(v, from, to))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use ir::{self,Expression,Condition};
+
+    fn condition(param: &ir::Parameter) -> Condition {
+        Condition::Equal(Box::new(Expression::argument_ref(param).into()),
+                         Box::new(Expression::i8(0).into()))
+    }
+
+    #[test]
+    fn hoists_into_existing_preheader() {
+        let param = ir::Parameter::new("x".to_owned(), ir::Type::i8());
+        let invariant = Expression::register_named("inv", Expression::add(
+            Expression::argument_ref(&param), Expression::i8(1)));
+
+        let mut entry = ir::Block::empty("entry");
+        let mut header = ir::Block::empty("header");
+        let mut exit = ir::Block::empty("exit");
+
+        header.append_value(invariant.clone());
+        header.append_value(Expression::br(condition(&param), Expression::block_ref(&header)));
+        exit.append_value(Expression::ret_void());
+        entry.append_value(Expression::br(Condition::True, Expression::block_ref(&header)));
+
+        let mut func = ir::Function::empty("f", ir::Signature::empty());
+        func.append_block(entry);
+        func.append_block(header);
+        func.append_block(exit);
+
+        let func = super::hoist::function(func);
+
+        assert_eq!(func.blocks.len(), 3);
+        assert_eq!(func.blocks[0].body[0], invariant.into());
+        assert_eq!(func.blocks[1].body.len(), 1);
+    }
+
+    #[test]
+    fn creates_preheader() {
+        let param = ir::Parameter::new("x".to_owned(), ir::Type::i8());
+        let invariant = Expression::register_named("inv", Expression::shl(
+            Expression::argument_ref(&param), Expression::i8(1)));
+        let call = Expression::register_named("c", Expression::call(
+            Expression::argument_ref(&param)));
+
+        let mut entry = ir::Block::empty("entry");
+        let mut header = ir::Block::empty("header");
+        let mut exit = ir::Block::empty("exit");
+
+        exit.append_value(Expression::ret_void());
+        entry.append_value(Expression::br(condition(&param), Expression::block_ref(&exit)));
+        header.append_value(call.clone());
+        header.append_value(invariant.clone());
+        header.append_value(Expression::br(condition(&param), Expression::block_ref(&header)));
+
+        let mut func = ir::Function::empty("f", ir::Signature::empty());
+        func.append_block(entry);
+        func.append_block(header);
+        func.append_block(exit);
+
+        let func = super::hoist::function(func);
+        let names: Vec<_> = func.blocks().map(|b| b.name()).collect();
+
+        assert_eq!(names, vec!["entry", "header_preheader", "header", "exit"]);
+        assert_eq!(func.blocks[1].body, vec![invariant.into()]);
+        assert_eq!(func.blocks[2].body[0], call.into());
+    }
+}
@@ -0,0 +1,670 @@
+use {Metadata,Id,Info,Transform};
+use ir;
+
+/// The default maximum number of values in an unrolled loop.
+pub const DEFAULT_THRESHOLD: usize = 32;
+
+/// A loop unrolling pass.
+///
+/// Single block loops which are controlled by an induction variable
+/// with constant bounds are unrolled. If the fully unrolled loop would
+/// contain at most `threshold` values, the loop is replaced by
+/// straight-line code. Otherwise, the loop body is repeated by the
+/// largest factor which stays under the threshold, and the iterations
+/// left over are peeled off into a block in front of the loop.
+pub struct LoopUnroll
+{
+    threshold: usize,
+}
+
+impl LoopUnroll
+{
+    /// Creates a new loop unroller with a size threshold.
+    pub fn new(threshold: usize) -> Self {
+        LoopUnroll { threshold: threshold }
+    }
+
+    /// Gets the maximum number of values in an unrolled loop.
+    pub fn threshold(&self) -> usize { self.threshold }
+}
+
+impl Default for LoopUnroll
+{
+    fn default() -> Self {
+        LoopUnroll::new(DEFAULT_THRESHOLD)
+    }
+}
+
+impl Metadata for LoopUnroll
+{
+    fn id(&self) -> Id { Id(0x4b8e17f2) }
+    fn name(&self) -> &'static str { "loop unrolling" }
+}
+
+impl Transform for LoopUnroll
+{
+    fn run_function(&mut self,
+                    function: ir::Function,
+                    _module: &ir::Module) -> ir::Function {
+        self::unroll::function(function, self.threshold)
+    }
+}
+
+// TODO: blamket impl for all passes
+impl Into<Info> for Box<LoopUnroll>
+{
+    fn into(self) -> Info {
+        Info::Transform(self)
+    }
+}
+
+pub mod unroll
+{
+    use ir::{self,Value,Expression,Instruction,Condition,Register};
+    use ir::value::{RegisterRef,BlockRef};
+    use ir::value::literal::Integer;
+    use analysis::{ControlFlowGraph,DominatorTree,Loop,LoopInfo};
+    use transforms::constant_folding::fold;
+    use util::{self,Identifiable};
+
+    use std::collections::{HashMap,HashSet};
+
+    /// The largest trip count that will be computed.
+    pub const MAX_TRIP_COUNT: usize = 1024;
+
+    /// A variable which is increased by a constant on every iteration.
+    #[derive(Clone,Debug,PartialEq,Eq)]
+    pub struct InductionVariable
+    {
+        /// The phi which holds the value for the current iteration.
+        pub phi: util::Id,
+        /// The register which holds the value for the next iteration.
+        pub next: util::Id,
+        /// The value on the first iteration.
+        pub initial: Integer,
+        /// The amount added on every iteration.
+        pub step: Integer,
+    }
+
+    /// A loop which may be unrolled.
+    #[derive(Clone,Debug)]
+    pub struct CountedLoop
+    {
+        /// The index of the block that forms the loop.
+        pub index: usize,
+        /// The block outside the loop which enters it.
+        pub entry: util::Id,
+        /// The variable which controls the loop.
+        pub induction_variable: InductionVariable,
+        /// The number of times the body is executed.
+        pub trip_count: usize,
+    }
+
+    /// Unrolls all of the loops in a function which may be unrolled.
+    pub fn function(mut func: ir::Function, threshold: usize) -> ir::Function {
+        let mut visited = HashSet::new();
+
+        loop {
+            let graph = ControlFlowGraph::new(&func);
+            let loops = LoopInfo::new(&graph, &DominatorTree::new(&graph));
+
+            let next = loops.loops().find(|l| !visited.contains(&l.header())).cloned();
+
+            let l = match next {
+                Some(l) => l,
+                None => return func,
+            };
+
+            visited.insert(l.header());
+
+            if let Some(counted) = self::analyze(&func, &l, &graph) {
+                if let Some(f) = self::unroll(&func, &counted, threshold) {
+                    func = f;
+                }
+            }
+        }
+    }
+
+    /// Checks if a loop has a constant trip count.
+    ///
+    /// Only loops consisting of a single block which falls through
+    /// to the exit are recognized.
+    pub fn analyze(func: &ir::Function,
+                   l: &Loop,
+                   graph: &ControlFlowGraph) -> Option<CountedLoop> {
+        let entries = l.entries(graph);
+
+        if l.blocks().len() != 1 || entries.len() != 1 {
+            return None;
+        }
+
+        let index = func.blocks().position(|b| b.get_id() == l.header()).unwrap();
+        let block = &func.blocks[index];
+
+        if index + 1 == func.blocks.len() {
+            return None;
+        }
+
+        let br = match block.values().last().map(|v| &v.node) {
+            Some(&Expression::Instruction(Instruction::Break(ref br))) => br,
+            _ => return None,
+        };
+
+        if br.condition().is_trivial() || !self::is_block(br.target(), l.header()) {
+            return None;
+        }
+
+        let (phis, body) = self::split(block);
+
+        // Control may only leave the loop through the latch.
+        if body.iter().any(|v| self::is_control_flow(v)) {
+            return None;
+        }
+
+        for phi in phis.iter() {
+            let phi = self::as_phi(phi).unwrap();
+
+            if phi.incoming().count() != 2 || phi.incoming_from(entries[0]).is_none() ||
+               phi.incoming_from(l.header()).is_none() {
+                return None;
+            }
+        }
+
+        let (compared, iv) = match self::induction_variable(br.condition(), &phis, &body, entries[0], l.header()) {
+            Some(iv) => iv,
+            None => return None,
+        };
+
+        let trip_count = match self::trip_count(br.condition(), compared, &iv) {
+            Some(count) => count,
+            None => return None,
+        };
+
+        Some(CountedLoop {
+            index: index,
+            entry: entries[0],
+            induction_variable: iv,
+            trip_count: trip_count,
+        })
+    }
+
+    /// Unrolls a loop, if it fits under the threshold.
+    ///
+    /// When the factor does not divide the trip count, the copies of
+    /// the remaining iterations count towards the threshold too.
+    pub fn unroll(func: &ir::Function,
+                  l: &CountedLoop,
+                  threshold: usize) -> Option<ir::Function> {
+        let block = &func.blocks[l.index];
+        let (phis, body) = self::split(block);
+        let size = body.len();
+
+        let full = l.trip_count * size <= threshold;
+        let factor = if full {
+            l.trip_count
+        } else {
+            match (2..l.trip_count).rev().find(|f| (f + l.trip_count % f) * size <= threshold) {
+                Some(f) => f,
+                None => return None,
+            }
+        };
+        let remainder = l.trip_count % factor;
+
+        let header = block.get_id();
+        let mut values: Vec<Value> = Vec::new();
+        let mut mapping: HashMap<util::Id, Value> = HashMap::new();
+
+        for copy in 0..factor {
+            let last = copy + 1 == factor;
+
+            // Each phi takes the value the previous copy passes back
+            // to the header.
+            let mut phi_values = HashMap::new();
+            for phi in phis.iter() {
+                let reg = self::as_register(phi).unwrap();
+                let incoming = self::as_phi(phi).unwrap();
+
+                let value = if copy == 0 {
+                    if !full { continue; }
+                    incoming.incoming_from(l.entry).unwrap().clone()
+                } else {
+                    self::remap(incoming.incoming_from(header).unwrap().clone(), &mapping)
+                };
+
+                phi_values.insert(reg.get_id(), value);
+            }
+
+            mapping = phi_values;
+
+            for original in body.iter() {
+                let value = self::remap((*original).clone(), &mapping);
+
+                match value.node {
+                    Expression::Register(reg) => {
+                        // The last copy keeps the original registers, so
+                        // references after the loop stay valid.
+                        let reg = if last {
+                            reg
+                        } else {
+                            Register::new(self::copy_name(&reg.name, copy), *reg.value)
+                        };
+
+                        if !last {
+                            let reg_ref = RegisterRef::new(reg.get_id(), reg.value.node.ty());
+                            mapping.insert(self::as_register(original).unwrap().get_id(),
+                                           Value::new(reg_ref.into()));
+                        }
+
+                        values.push(Value::new(reg.into()));
+                    },
+                    node => values.push(Value::new(node)),
+                }
+            }
+        }
+
+        // `mapping` now holds the phi values for the last copy.
+        let mut unrolled = block.clone();
+        unrolled.body = if full {
+            values
+        } else {
+            let terminator = self::remap(block.values().last().unwrap().clone(), &mapping);
+
+            phis.iter().map(|&v| v.clone()).chain(values).chain(Some(terminator)).collect()
+        };
+
+        let phi_ids: HashSet<_> = phis.iter().map(|p| self::as_register(p).unwrap().get_id()).collect();
+        let outside: HashMap<_,_> = mapping.into_iter().filter(|&(id, _)| phi_ids.contains(&id)).collect();
+
+        let mut func = func.clone();
+        func.blocks[l.index] = unrolled;
+
+        let func = func.map_blocks(|b| {
+            if b.get_id() == header {
+                b
+            } else {
+                b.map_values(|v| self::remap(v, &outside))
+            }
+        });
+
+        if remainder == 0 {
+            Some(func)
+        } else {
+            Some(self::peel(func, block, l, remainder))
+        }
+    }
+
+    /// Runs the first iterations of a loop in a new block in front of
+    /// it, so the loop starts where they left off.
+    fn peel(mut func: ir::Function,
+            block: &ir::Block,
+            l: &CountedLoop,
+            count: usize) -> ir::Function {
+        let (mut peeled, phi_values) = {
+            let (phis, body) = self::split(block);
+            let header = block.get_id();
+
+            let mut phi_values: HashMap<util::Id, Value> = phis.iter().map(|phi| {
+                let value = self::as_phi(phi).unwrap().incoming_from(l.entry).unwrap().clone();
+                (self::as_register(phi).unwrap().get_id(), value)
+            }).collect();
+
+            let mut peeled = ir::Block::empty(format!("{}_remainder", block.name()));
+
+            for copy in 0..count {
+                let mut mapping = phi_values.clone();
+
+                for original in body.iter() {
+                    let value = self::remap((*original).clone(), &mapping);
+
+                    match value.node {
+                        Expression::Register(reg) => {
+                            let reg = Register::new(self::remainder_name(&reg.name, copy), *reg.value);
+                            let reg_ref = RegisterRef::new(reg.get_id(), reg.value.node.ty());
+
+                            mapping.insert(self::as_register(original).unwrap().get_id(),
+                                           Value::new(reg_ref.into()));
+                            peeled.append_value(Value::new(reg.into()));
+                        },
+                        node => peeled.append_value(node),
+                    }
+                }
+
+                phi_values = phis.iter().map(|phi| {
+                    let value = self::as_phi(phi).unwrap().incoming_from(header).unwrap().clone();
+                    (self::as_register(phi).unwrap().get_id(), self::remap(value, &mapping))
+                }).collect();
+            }
+
+            (peeled, phi_values)
+        };
+
+        let header = block.get_id();
+        let peeled_id = peeled.get_id();
+        peeled.append_value(Expression::br(Condition::True, Expression::BlockRef(BlockRef::new(header))));
+
+        // The loop is now entered from the peeled block, with the
+        // values it computed.
+        let blocks = func.blocks.drain(..).map(|b| {
+            if b.get_id() == header {
+                b.map_values(|v| self::enter_from(v, l.entry, peeled_id, &phi_values))
+            } else if b.get_id() == l.entry {
+                b.map_values(|v| self::redirect(v, header, peeled_id))
+            } else {
+                b
+            }
+        }).collect::<Vec<_>>();
+
+        func.blocks = blocks;
+        func.blocks.insert(l.index, peeled);
+        func
+    }
+
+    /// Makes a phi take its value from a new entry block.
+    fn enter_from(value: Value,
+                  old: util::Id,
+                  new: util::Id,
+                  values: &HashMap<util::Id, Value>) -> Value {
+        let (id, phi) = match (self::as_register(&value), self::as_phi(&value)) {
+            (Some(reg), Some(phi)) => (reg.get_id(), phi.clone()),
+            _ => return value,
+        };
+
+        let incoming = phi.incoming().map(|&(ref v, ref b)| {
+            if self::is_block(b, old) {
+                (values[&id].clone(), Value::new(BlockRef::new(new).into()))
+            } else {
+                (v.clone(), b.clone())
+            }
+        }).collect();
+
+        match value.node {
+            Expression::Register(mut reg) => {
+                reg.value = Box::new(Value::new(ir::instruction::Phi::new(phi.ty(), incoming).into()));
+                Value::new(Expression::Register(reg))
+            },
+            _ => unreachable!(),
+        }
+    }
+
+    /// Replaces references to one block with another.
+    fn redirect(value: Value, from: util::Id, to: util::Id) -> Value {
+        match value.node {
+            Expression::BlockRef(ref r) if r.block_id() == from => Value::new(BlockRef::new(to).into()),
+            node => Value::new(node.map_subvalues(|v| self::redirect(v, from, to))),
+        }
+    }
+
+    /// Finds the induction variable which controls the exit condition.
+    ///
+    /// Returns the register the condition compares, along with the
+    /// variable itself.
+    fn induction_variable(cond: &Condition,
+                          phis: &[&Value],
+                          body: &[&Value],
+                          entry: util::Id,
+                          header: util::Id) -> Option<(util::Id, InductionVariable)> {
+        let compared = match cond.values().iter().filter_map(|v| self::register_id(v)).next() {
+            Some(id) => id,
+            None => return None,
+        };
+
+        // Either the phi itself, or its next value is compared.
+        let phi_id = match body.iter().filter_map(|v| self::as_register(v)).find(|r| r.get_id() == compared) {
+            Some(reg) => match self::step(&reg.value) {
+                Some((phi_id, _)) => phi_id,
+                None => return None,
+            },
+            None => compared,
+        };
+
+        let phi = match phis.iter().find(|p| self::as_register(p).unwrap().get_id() == phi_id) {
+            Some(phi) => self::as_phi(phi).unwrap(),
+            None => return None,
+        };
+
+        let initial = match phi.incoming_from(entry).and_then(|v| self::integer(v)) {
+            Some(i) => i.clone(),
+            None => return None,
+        };
+
+        let next = match phi.incoming_from(header).and_then(|v| self::register_id(v)) {
+            Some(id) => id,
+            None => return None,
+        };
+
+        let step = body.iter().filter_map(|v| self::as_register(v))
+                       .find(|r| r.get_id() == next)
+                       .and_then(|r| self::step(&r.value));
+
+        match step {
+            Some((id, step)) if id == phi_id => Some((compared, InductionVariable {
+                phi: phi_id,
+                next: next,
+                initial: initial,
+                step: step,
+            })),
+            _ => None,
+        }
+    }
+
+    /// Counts the iterations of a loop by stepping the induction
+    /// variable until the exit condition fails.
+    fn trip_count(cond: &Condition,
+                  compared: util::Id,
+                  iv: &InductionVariable) -> Option<usize> {
+        let mut current = iv.initial.clone();
+
+        for count in 1..MAX_TRIP_COUNT + 1 {
+            let next = current.clone() + iv.step.clone();
+            let value = if compared == iv.phi { current } else { next.clone() };
+
+            let cond = cond.clone().map_values(|v| match self::register_id(&v) {
+                Some(id) if id == compared => Value::new(value.clone().into()),
+                _ => v,
+            });
+
+            match fold::condition(cond) {
+                Condition::True => current = next,
+                Condition::False => return Some(count),
+                _ => return None,
+            }
+        }
+
+        None
+    }
+
+    /// Matches `add %phi, c`, giving the phi and the step.
+    fn step(value: &Value) -> Option<(util::Id, Integer)> {
+        use ir::instruction::Binary;
+
+        if let Expression::Instruction(Instruction::Add(ref add)) = value.node {
+            let (lhs, rhs) = add.operands();
+
+            let operands = self::register_id(lhs).and_then(|id| self::integer(rhs).map(|c| (id, c)))
+                .or_else(|| self::register_id(rhs).and_then(|id| self::integer(lhs).map(|c| (id, c))));
+
+            return operands.map(|(id, c)| (id, c.clone()));
+        }
+
+        None
+    }
+
+    /// Splits a loop block into its phis and its body, leaving
+    /// out the latch.
+    fn split(block: &ir::Block) -> (Vec<&Value>, Vec<&Value>) {
+        let values: Vec<_> = block.values().collect();
+        let values = &values[..values.len() - 1];
+
+        let phi_count = values.iter().take_while(|v| self::as_phi(v).is_some()).count();
+        (values[..phi_count].to_vec(), values[phi_count..].to_vec())
+    }
+
+    /// Replaces references to registers.
+    fn remap(value: Value, mapping: &HashMap<util::Id, Value>) -> Value {
+        match value.node {
+            Expression::RegisterRef(r) => match mapping.get(&r.register_id) {
+                Some(v) => v.clone(),
+                None => Value::new(Expression::RegisterRef(r)),
+            },
+            Expression::Register(mut reg) => {
+                reg.value = Box::new(self::remap(*reg.value, mapping));
+                Value::new(Expression::Register(reg))
+            },
+            node => Value::new(node.map_subvalues(|v| self::remap(v, mapping))),
+        }
+    }
+
+    fn copy_name(name: &ir::Name, copy: usize) -> ir::Name {
+        match *name {
+            ir::Name::Named(ref name) => ir::Name::named(format!("{}_{}", name, copy)),
+            ir::Name::Unnamed => ir::Name::Unnamed,
+        }
+    }
+
+    fn remainder_name(name: &ir::Name, copy: usize) -> ir::Name {
+        match *name {
+            ir::Name::Named(ref name) => ir::Name::named(format!("{}_r{}", name, copy)),
+            ir::Name::Unnamed => ir::Name::Unnamed,
+        }
+    }
+
+    fn as_register(value: &Value) -> Option<&Register> {
+        if let Expression::Register(ref reg) = value.node { Some(reg) } else { None }
+    }
+
+    fn as_phi(value: &Value) -> Option<&ir::instruction::Phi> {
+        match self::as_register(value).map(|r| &r.value.node) {
+            Some(&Expression::Instruction(Instruction::Phi(ref phi))) => Some(phi),
+            _ => None,
+        }
+    }
+
+    fn register_id(value: &Value) -> Option<util::Id> {
+        if let Expression::RegisterRef(ref r) = value.node { Some(r.register_id) } else { None }
+    }
+
+    fn integer(value: &Value) -> Option<&Integer> {
+        value.node.as_literal().and_then(|l| l.as_integer())
+    }
+
+    fn is_block(value: &Value, block: util::Id) -> bool {
+        if let Expression::BlockRef(ref r) = value.node { r.block_id() == block } else { false }
+    }
+
+    fn is_control_flow(value: &Value) -> bool {
+        match value.node {
+            Expression::Instruction(ref i) => i.is_terminator(),
+            Expression::Register(ref reg) => self::is_control_flow(&reg.value),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use ir::{self,Expression,Value,Condition,Type};
+    use ir::value::RegisterRef;
+    use analysis::cfg;
+    use util::Identifiable;
+
+    fn reference(reg: &Expression) -> Value {
+        match *reg {
+            Expression::Register(ref r) => Expression::RegisterRef(RegisterRef::new(r.get_id(), r.value.node.ty())).into(),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Builds `for (i = 0; i < count; i++) { acc += i }`, returning `acc`.
+    fn sum(count: i8) -> ir::Function {
+        let mut entry = ir::Block::empty("entry");
+        let mut body = ir::Block::empty("loop");
+        let mut exit = ir::Block::empty("exit");
+
+        let i = Expression::register_named("i", Expression::i8(0));
+        let acc = Expression::register_named("acc", Expression::i8(0));
+        let acc_next = Expression::register_named("acc_next", Expression::add(reference(&acc), reference(&i)));
+        let next = Expression::register_named("next", Expression::add(reference(&i), Expression::i8(1).into()));
+
+        let phi = |reg: Expression, next: &Expression| match reg {
+            Expression::Register(mut r) => {
+                r.value = Box::new(Expression::phi(Type::i8(), vec![
+                    (Expression::i8(0).into(), Expression::block_ref(&entry).into()),
+                    (reference(next), Expression::block_ref(&body).into()),
+                ]).into());
+                Expression::Register(r)
+            },
+            _ => unreachable!(),
+        };
+
+        let (i_phi, acc_phi) = (phi(i.clone(), &next), phi(acc.clone(), &acc_next));
+
+        entry.append_value(Expression::br(Condition::True, Expression::block_ref(&body)));
+        body.append_value(i_phi);
+        body.append_value(acc_phi);
+        body.append_value(acc_next.clone());
+        body.append_value(next.clone());
+        body.append_value(Expression::br(Condition::LessThan(Box::new(reference(&next)),
+                                                             Box::new(Expression::i8(count).into())),
+                                         Expression::block_ref(&body)));
+        exit.append_value(Expression::ret(reference(&acc_next)));
+
+        let mut func = ir::Function::empty("sum", ir::Signature::empty().ret(Type::i8()));
+        func.append_block(entry);
+        func.append_block(body);
+        func.append_block(exit);
+        func
+    }
+
+    fn names(block: &ir::Block) -> Vec<String> {
+        block.values().filter_map(|v| match v.node {
+            Expression::Register(ref r) => Some(r.name.clone().into()),
+            _ => None,
+        }).collect()
+    }
+
+    #[test]
+    fn computes_trip_count() {
+        use analysis::{ControlFlowGraph,DominatorTree,LoopInfo};
+
+        let func = sum(4);
+        let graph = ControlFlowGraph::new(&func);
+        let loops = LoopInfo::new(&graph, &DominatorTree::new(&graph));
+
+        let counted = super::unroll::analyze(&func, loops.get(0), &graph).unwrap();
+
+        assert_eq!(counted.trip_count, 4);
+        assert_eq!(counted.induction_variable.step, ir::value::literal::Integer::new(ir::types::Integer::i8(), 1).unwrap());
+    }
+
+    #[test]
+    fn fully_unrolls_small_loops() {
+        let func = super::unroll::function(sum(3), 8);
+
+        assert_eq!(names(&func.blocks[1]), vec!["acc_next_0", "next_0", "acc_next_1", "next_1", "acc_next", "next"]);
+        assert!(cfg::falls_through(&func.blocks[1]));
+    }
+
+    #[test]
+    fn partially_unrolls_large_loops() {
+        let func = super::unroll::function(sum(6), 6);
+
+        assert_eq!(names(&func.blocks[1]), vec!["i", "acc", "acc_next_0", "next_0", "acc_next_1", "next_1", "acc_next", "next"]);
+    }
+
+    #[test]
+    fn peels_the_remaining_iterations() {
+        let func = super::unroll::function(sum(7), 6);
+
+        assert_eq!(func.blocks[1].name(), "loop_remainder");
+        assert_eq!(names(&func.blocks[1]), vec!["acc_next_r0", "next_r0"]);
+        assert_eq!(names(&func.blocks[2]), vec!["i", "acc", "acc_next_0", "next_0", "acc_next", "next"]);
+    }
+
+    #[test]
+    fn leaves_loops_without_a_suitable_factor() {
+        let func = super::unroll::function(sum(7), 4);
+
+        assert_eq!(names(&func.blocks[1]), vec!["i", "acc", "acc_next", "next"]);
+    }
+}
@@ -1,15 +1,27 @@
 
 pub use self::dce::DeadCodeElimination;
+pub use self::global_dce::GlobalDeadCodeElimination;
 pub use self::constant_folding::ConstantFolding;
 pub use self::strength_reduction::StrengthReduction;
 pub use self::inliner::Inliner;
+pub use self::gvn::GlobalValueNumbering;
+pub use self::instcombine::InstCombine;
+pub use self::sccp::SparseConditionalConstantPropagation;
+pub use self::simplify_cfg::SimplifyCfg;
+pub use self::licm::LoopInvariantCodeMotion;
+pub use self::loop_unroll::LoopUnroll;
+pub use self::tce::TailCallElimination;
+pub use self::ipcp::InterproceduralConstantPropagation;
+pub use self::globalopt::GlobalOptimization;
+pub use self::scalarize::Scalarize;
+pub use self::soft_float::SoftFloat;
 
 /// Implements a test that checks that a set of values
 /// is mapped to another set of values.
 ///
 /// It is of the form
-/// ```
-/// value_mapping_test!(test_name : mapping_fn }
+/// ```ignore
+/// value_mapping_test!(test_name : mapping_fn {
 ///     input_value => output_value,
 /// });
 /// ```
@@ -39,9 +51,33 @@ macro_rules! value_mapping_test {
 
 /// The dead code elimination pass.
 pub mod dce;
+/// The whole-module dead code elimination pass.
+pub mod global_dce;
 /// The constant folder.
 pub mod constant_folding;
 /// The strength reduction pass.
 pub mod strength_reduction;
 /// The inliner.
 pub mod inliner;
+/// The global value numbering pass.
+pub mod gvn;
+/// The instruction combining pass.
+pub mod instcombine;
+/// The sparse conditional constant propagation pass.
+pub mod sccp;
+/// The control flow graph simplification pass.
+pub mod simplify_cfg;
+/// The loop-invariant code motion pass.
+pub mod licm;
+/// The loop unrolling pass.
+pub mod loop_unroll;
+/// The tail call elimination pass.
+pub mod tce;
+/// The interprocedural constant propagation pass.
+pub mod ipcp;
+/// The global variable optimization pass.
+pub mod globalopt;
+/// The aggregate scalarization pass.
+pub mod scalarize;
+/// The soft-float lowering pass.
+pub mod soft_float;
@@ -0,0 +1,342 @@
+use {Metadata,Id,Info,Transform};
+use ir;
+
+/// A pass which breaks aggregates up into their elements.
+///
+/// This
+///   * splits loads of aggregates which are only read element by
+///     element into a load of each element
+///   * splits stores of aggregates into a store of each element
+///   * replaces `extractvalue` of literals and `insertvalue` chains
+///     with the element itself
+///   * removes aggregate registers which are no longer used
+///
+/// Backends which have no registers wide enough to hold aggregates,
+/// such as AVR, run this before instruction selection.
+pub struct Scalarize;
+
+impl Metadata for Scalarize
+{
+    fn id(&self) -> Id { Id(0x5ca1a12e) }
+    fn name(&self) -> &'static str { "scalarize" }
+}
+
+impl Transform for Scalarize
+{
+    fn run_function(&mut self,
+                    function: ir::Function,
+                    _module: &ir::Module) -> ir::Function {
+        self::scalarize::function(function)
+    }
+}
+
+// TODO: blamket impl for all passes
+impl Into<Info> for Box<Scalarize>
+{
+    fn into(self) -> Info {
+        Info::Transform(self)
+    }
+}
+
+pub mod scalarize
+{
+    use ir::{self,Value,Expression,Instruction,Name,Users};
+    use ir::instruction::Binary;
+    use ir::value::Register;
+    use util::{self,Identifiable};
+
+    use std::collections::HashMap;
+
+    /// Scalarizes the aggregates in a function.
+    pub fn function(func: ir::Function) -> ir::Function {
+        let mut func = func;
+
+        // Splitting an aggregate can give aggregate elements, so
+        // keep going until there is nothing left to split.
+        loop {
+            let (split, changed) = self::split_memory(func);
+            func = self::fold_extracts(split);
+
+            if !changed {
+                return self::unused_aggregates(func);
+            }
+        }
+    }
+
+    /// Splits aggregate loads and stores into one per element.
+    ///
+    /// Returns whether anything was split.
+    pub fn split_memory(func: ir::Function) -> (ir::Function, bool) {
+        let splittable: Vec<util::Id> = func.values().filter_map(|value| match value.node {
+            Expression::Register(ref reg) if self::is_splittable_load(reg, &func) => Some(reg.get_id()),
+            _ => None,
+        }).collect();
+
+        let mut elements: HashMap<(util::Id, u64), Value> = HashMap::new();
+        let mut changed = false;
+
+        let func = func.map_blocks(|block| {
+            let mut body = Vec::new();
+
+            for value in block.body.into_iter() {
+                match value.node {
+                    Expression::Register(ref reg) if splittable.contains(&reg.get_id()) => {
+                        let pointer = match reg.value.node {
+                            Expression::Instruction(Instruction::Load(ref load)) => load.pointer().clone(),
+                            _ => unreachable!(),
+                        };
+
+                        for index in 0..reg.subvalue().node.ty().element_count().unwrap() {
+                            let load = Expression::load(self::element_pointer(&pointer, index));
+                            let element = Register::new(self::element_name(reg.name(), index), Value::new(load));
+
+                            elements.insert((reg.get_id(), index), Value::new(Expression::register_ref(&element)));
+                            body.push(Value::new(element.into()));
+                        }
+
+                        changed = true;
+                        continue;
+                    },
+                    Expression::Instruction(Instruction::Store(ref store)) if self::is_splittable_store(store) => {
+                        let count = store.value().node.ty().element_count().unwrap();
+
+                        for index in 0..count {
+                            let pointer = self::element_pointer(store.pointer(), index);
+                            let element = Expression::extractvalue(store.value().clone(), index);
+
+                            body.push(Value::new(Expression::store(pointer, Value::new(element))));
+                        }
+
+                        changed = true;
+                        continue;
+                    },
+                    _ => (),
+                }
+
+                body.push(value);
+            }
+
+            ir::Block { body: body, ..block }
+        });
+
+        if elements.is_empty() {
+            return (func, changed);
+        }
+
+        (func.map_values(|v| self::replace_extracts(v, &elements)), changed)
+    }
+
+    /// Replaces `extractvalue` instructions with the elements they read
+    /// when they are known.
+    pub fn fold_extracts(func: ir::Function) -> ir::Function {
+        let copied: Vec<util::Id> = func.values().filter_map(|value| match value.node {
+            Expression::Instruction(Instruction::Copy(ref copy)) => match copy.operands().0.node {
+                Expression::RegisterRef(ref r) => Some(r.register_id()),
+                _ => None,
+            },
+            _ => None,
+        }).collect();
+
+        // Registers which are copied to can hold more than one value.
+        let definitions: HashMap<util::Id, Value> = func.values().filter_map(|value| match value.node {
+            Expression::Register(ref reg) if !copied.contains(&reg.get_id()) => {
+                Some((reg.get_id(), reg.subvalue().clone()))
+            },
+            _ => None,
+        }).collect();
+
+        func.map_values(|v| self::fold_value(v, &definitions))
+    }
+
+    /// Removes aggregate registers which have no users.
+    pub fn unused_aggregates(mut func: ir::Function) -> ir::Function {
+        // Removing a register can leave the aggregates it was built
+        // from unused.
+        loop {
+            let unused: Vec<util::Id> = func.values().filter_map(|value| match value.node {
+                Expression::Register(ref reg) => {
+                    let dead = reg.subvalue().node.ty().is_aggregate() &&
+                               !self::has_side_effects(reg.subvalue()) &&
+                               Users::of_register(reg, &func).is_empty();
+
+                    if dead { Some(reg.get_id()) } else { None }
+                },
+                _ => None,
+            }).collect();
+
+            if unused.is_empty() {
+                return func;
+            }
+
+            func = func.map_blocks(|block| block.filter(|v| match v.node {
+                Expression::Register(ref reg) => !unused.contains(&reg.get_id()),
+                _ => true,
+            }));
+        }
+    }
+
+    /// Gets an element of an aggregate if it can be read without
+    /// changing what the program does.
+    pub fn element(aggregate: &Value,
+                   index: u64,
+                   definitions: &HashMap<util::Id, Value>) -> Option<Value> {
+        match aggregate.node {
+            Expression::Literal(ref literal) => {
+                literal.elements().and_then(|e| e.get(index as usize).map(|&v| v.clone()))
+            },
+            Expression::RegisterRef(ref r) => {
+                definitions.get(&r.register_id()).and_then(|v| self::element(v, index, definitions))
+            },
+            Expression::Instruction(Instruction::InsertValue(ref insert)) => {
+                if insert.index() != index {
+                    self::element(insert.aggregate(), index, definitions)
+                } else if insert.value().node.is_instruction() {
+                    // Evaluating the instruction where the element is
+                    // read could give a different value.
+                    None
+                } else {
+                    Some(insert.value().clone())
+                }
+            },
+            _ => None,
+        }
+    }
+
+    fn fold_value(value: Value, definitions: &HashMap<util::Id, Value>) -> Value {
+        let value = self::map_nested(value, |v| self::fold_value(v, definitions));
+
+        let folded = match value.node {
+            Expression::Instruction(Instruction::ExtractValue(ref extract)) => {
+                self::element(extract.aggregate(), extract.index(), definitions)
+            },
+            _ => None,
+        };
+
+        match folded {
+            // The element may itself read from an aggregate.
+            Some(element) => self::fold_value(element, definitions),
+            None => value,
+        }
+    }
+
+    fn replace_extracts(value: Value, elements: &HashMap<(util::Id, u64), Value>) -> Value {
+        let replaced = match value.node {
+            Expression::Instruction(Instruction::ExtractValue(ref extract)) => match extract.aggregate().node {
+                Expression::RegisterRef(ref r) => elements.get(&(r.register_id(), extract.index())).cloned(),
+                _ => None,
+            },
+            _ => None,
+        };
+
+        match replaced {
+            Some(element) => element,
+            None => self::map_nested(value, |v| self::replace_extracts(v, elements)),
+        }
+    }
+
+    /// Maps the subvalues of a value, including the value a register holds.
+    fn map_nested<F>(value: Value, mut f: F) -> Value
+        where F: FnMut(Value) -> Value {
+        match value.node {
+            Expression::Register(mut reg) => {
+                reg.value = Box::new(f(*reg.value));
+                Value { node: Expression::Register(reg), ..value }
+            },
+            node => Value { node: node.map_subvalues(f), ..value },
+        }
+    }
+
+    /// Checks if a register holds an aggregate load whose value is
+    /// only ever read an element at a time.
+    fn is_splittable_load(reg: &Register, func: &ir::Function) -> bool {
+        let is_load = match reg.value.node {
+            Expression::Instruction(Instruction::Load(..)) => true,
+            _ => false,
+        };
+
+        is_load && reg.subvalue().node.ty().is_aggregate() &&
+            func.values().all(|v| self::only_extracted(reg.get_id(), v))
+    }
+
+    fn has_side_effects(value: &Value) -> bool {
+        match value.node {
+            Expression::Instruction(ref i) => {
+                i.is_single_critical() || i.subvalues().into_iter().any(self::has_side_effects)
+            },
+            _ => false,
+        }
+    }
+
+    /// Checks if a store writes a simple aggregate value.
+    ///
+    /// Other values would have to be evaluated once per element.
+    fn is_splittable_store(store: &ir::instruction::Store) -> bool {
+        let value = &store.value().node;
+        value.ty().is_aggregate() && value.is_simple()
+    }
+
+    /// Checks that every reference to a register inside a value is
+    /// the aggregate of an `extractvalue`.
+    fn only_extracted(register: util::Id, value: &Value) -> bool {
+        match value.node {
+            Expression::Instruction(Instruction::ExtractValue(ref extract)) => match extract.aggregate().node {
+                Expression::RegisterRef(ref r) if r.register_id() == register => true,
+                _ => self::only_extracted(register, extract.aggregate()),
+            },
+            Expression::RegisterRef(ref r) => r.register_id() != register,
+            Expression::Register(ref reg) => self::only_extracted(register, reg.subvalue()),
+            ref node => node.subvalues().into_iter().all(|v| self::only_extracted(register, v)),
+        }
+    }
+
+    /// Computes the address of an element of the aggregate a pointer
+    /// points to.
+    fn element_pointer(pointer: &Value, index: u64) -> Value {
+        let indices = vec![Value::new(Expression::i32(0)), Value::new(Expression::i32(index as i32))];
+        Value::new(Expression::getelementptr(pointer.clone(), indices))
+    }
+
+    fn element_name(name: &Name, index: u64) -> Name {
+        match *name {
+            Name::Named(ref name) => Name::named(format!("{}_{}", name, index)),
+            Name::Unnamed => Name::unnamed(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use ir;
+
+    fn scalarize(text: &str) -> String {
+        let module = ir::read::textual(text.chars()).expect("parsing failed");
+        let module = module.map_functions(|f, _| super::scalarize::function(f));
+
+        assert!(ir::verifier::verify(&module).is_ok());
+        format!("{}", ir::printable(&module))
+    }
+
+    #[test]
+    fn folds_extracts_of_known_aggregates() {
+        let text = "fn @f(%a: i8) -> i8 {\n  %s = { i8 1, u16 2 }\n  %t = insertvalue %s, %a, 0\n  \
+                    %x = extractvalue %t, 0\n  %y = extractvalue %t, 1\n  ret %x\n}\n";
+
+        assert_eq!(scalarize(text), "\nfn @f(%a: i8) -> i8 {\nentry:\n  %x = %a\n  %y = u16 2\n  ret %x\n}\n\n");
+    }
+
+    #[test]
+    fn splits_loads_and_stores() {
+        let text = "global @pair = { i8 1, u16 2 }\n\
+                    fn @f() -> u16 {\n  store @pair, { i8 3, u16 4 }\n  %p = load @pair\n  \
+                    %y = extractvalue %p, 1\n  ret %y\n}\n";
+
+        assert_eq!(scalarize(text), "%pair = { i8 1, u16 2 }\n\n\
+                                     fn @f() -> u16 {\nentry:\n  \
+                                     store (getelementptr %pair, i32 0, i32 0), i8 3\n  \
+                                     store (getelementptr %pair, i32 0, i32 1), u16 4\n  \
+                                     %p_0 = (load (getelementptr %pair, i32 0, i32 0))\n  \
+                                     %p_1 = (load (getelementptr %pair, i32 0, i32 1))\n  \
+                                     %y = %p_1\n  ret %y\n}\n\n");
+    }
+}
@@ -0,0 +1,447 @@
+use {Metadata,Id,Info,Transform};
+use ir;
+
+/// A sparse conditional constant propagation pass.
+///
+/// This propagates constants through registers, and only follows the
+/// edges of branches which can actually be taken. Branches with a
+/// constant condition are folded, and blocks which can never be
+/// executed are removed.
+pub struct SparseConditionalConstantPropagation;
+
+impl Metadata for SparseConditionalConstantPropagation
+{
+    fn id(&self) -> Id { Id(0x5cc9a0e1) }
+    fn name(&self) -> &'static str { "sparse conditional constant propagation" }
+}
+
+impl Transform for SparseConditionalConstantPropagation
+{
+    fn run_function(&mut self,
+                    function: ir::Function,
+                    _module: &ir::Module) -> ir::Function {
+        self::propagate::function(function)
+    }
+}
+
+// TODO: blamket impl for all passes
+impl Into<Info> for Box<SparseConditionalConstantPropagation>
+{
+    fn into(self) -> Info {
+        Info::Transform(self)
+    }
+}
+
+pub mod propagate
+{
+    use ir::{self,Value,Expression,Instruction,Condition};
+    use ir::instruction::{Break,Phi};
+    use ir::value::literal::{Literal,Integer};
+    use analysis::cfg;
+    use transforms::constant_folding::fold;
+    use transforms::simplify_cfg::simplify;
+    use util::{self,Identifiable};
+
+    use std::collections::{HashMap,HashSet};
+
+    /// What is known about the value of a register.
+    #[derive(Clone,Debug,PartialEq,Eq)]
+    pub enum Lattice
+    {
+        /// No definition has been seen yet.
+        Undefined,
+        /// The register always holds a constant.
+        Constant(Integer),
+        /// The register may hold more than one value.
+        Overdefined,
+    }
+
+    impl Lattice
+    {
+        /// Combines two facts about the same value.
+        pub fn meet(self, other: Lattice) -> Lattice {
+            match (self, other) {
+                (Lattice::Undefined, l) | (l, Lattice::Undefined) => l,
+                (Lattice::Constant(a), Lattice::Constant(b)) => {
+                    if a == b { Lattice::Constant(a) } else { Lattice::Overdefined }
+                },
+                _ => Lattice::Overdefined,
+            }
+        }
+    }
+
+    /// The result of the analysis.
+    pub struct Solution
+    {
+        /// The values of the registers.
+        pub registers: HashMap<util::Id, Lattice>,
+        /// The blocks which may be executed.
+        pub executable: HashSet<util::Id>,
+        /// The edges between blocks which may be taken.
+        pub edges: HashSet<(util::Id, util::Id)>,
+    }
+
+    /// Propagates constants through a function and removes the
+    /// code which can never be executed.
+    pub fn function(func: ir::Function) -> ir::Function {
+        let solution = self::solve(&func);
+        self::rewrite(func, &solution)
+    }
+
+    /// Finds the constant registers and the executable blocks.
+    ///
+    /// A phi is the meet of the values flowing in over the edges
+    /// which may be taken, so its block is revisited whenever one of
+    /// its incoming edges becomes executable.
+    pub fn solve(func: &ir::Function) -> Solution {
+        let order: Vec<_> = func.blocks().map(|b| b.get_id()).collect();
+        let uses = self::register_uses(func);
+
+        let mut solution = Solution {
+            registers: HashMap::new(),
+            executable: HashSet::new(),
+            edges: HashSet::new(),
+        };
+
+        let mut worklist: Vec<_> = order.first().cloned().into_iter().collect();
+        if let Some(&entry) = order.first() {
+            solution.executable.insert(entry);
+        }
+
+        while let Some(block_id) = worklist.pop() {
+            let index = order.iter().position(|&id| id == block_id).unwrap();
+            let block = &func.blocks[index];
+            let next = order.get(index + 1).cloned();
+
+            for value in block.values() {
+                if let Expression::Register(ref reg) = value.node {
+                    let old = solution.registers.get(&reg.get_id()).cloned().unwrap_or(Lattice::Undefined);
+                    let value = match reg.value.node {
+                        Expression::Instruction(Instruction::Phi(ref phi)) => {
+                            self::evaluate_phi(phi, block_id, &solution)
+                        },
+                        _ => self::evaluate(&reg.value, &solution.registers),
+                    };
+                    let new = old.clone().meet(value);
+
+                    if new != old {
+                        solution.registers.insert(reg.get_id(), new);
+
+                        // Blocks which use this register need revisiting.
+                        for &user in uses.get(&reg.get_id()).map(|u| &u[..]).unwrap_or(&[]) {
+                            if solution.executable.contains(&user) && !worklist.contains(&user) {
+                                worklist.push(user);
+                            }
+                        }
+                    }
+                }
+            }
+
+            for succ in self::executable_successors(block, next, &solution.registers) {
+                // The phis of a block which was already executable
+                // may gain an incoming value over the new edge.
+                let new_edge = solution.edges.insert((block_id, succ));
+                let new_block = solution.executable.insert(succ);
+
+                if (new_edge || new_block) && !worklist.contains(&succ) {
+                    worklist.push(succ);
+                }
+            }
+        }
+
+        solution
+    }
+
+    /// Rewrites a function using the result of the analysis.
+    pub fn rewrite(func: ir::Function, solution: &Solution) -> ir::Function {
+        let blocks: Vec<_> = func.blocks.iter().cloned()
+            .filter(|b| solution.executable.contains(&b.get_id()))
+            .map(|block| {
+                let body = block.body.into_iter().filter_map(|value| {
+                    let value = self::substitute(value, &solution.registers);
+
+                    match value.node {
+                        Expression::Instruction(Instruction::Break(br)) => {
+                            let br = fold::br(br);
+
+                            // The target was never taken, so the
+                            // branch can be removed entirely.
+                            if *br.condition() == Condition::False {
+                                None
+                            } else {
+                                Some(Expression::Instruction(Instruction::Break(br)).into())
+                            }
+                        },
+                        node => Some(Value::new(node)),
+                    }
+                }).collect();
+
+                ir::Block { body: body, ..block }
+            }).collect();
+
+        simplify::remove_stale_incoming(func.with_blocks(blocks.into_iter()))
+    }
+
+    /// Evaluates a value given what is known about the registers.
+    pub fn evaluate(value: &Value,
+                    registers: &HashMap<util::Id, Lattice>) -> Lattice {
+        match value.node {
+            Expression::Literal(Literal::Integer(ref i)) => Lattice::Constant(i.clone()),
+            Expression::RegisterRef(ref r) => {
+                registers.get(&r.register_id).cloned().unwrap_or(Lattice::Undefined)
+            },
+            Expression::Instruction(ref inst) if self::is_foldable(inst) => {
+                let operands: Vec<_> = inst.subvalues().into_iter()
+                                           .map(|v| self::evaluate(v, registers))
+                                           .collect();
+
+                if operands.iter().any(|o| *o == Lattice::Overdefined) {
+                    return Lattice::Overdefined;
+                }
+
+                if operands.iter().any(|o| *o == Lattice::Undefined) {
+                    return Lattice::Undefined;
+                }
+
+                let inst = inst.clone().map_subvalues(|v| self::substitute(v, registers));
+                match fold::instruction(inst) {
+                    Ok(Expression::Literal(Literal::Integer(i))) => Lattice::Constant(i),
+                    _ => Lattice::Overdefined,
+                }
+            },
+            _ => Lattice::Overdefined,
+        }
+    }
+
+    /// Evaluates a phi as the meet of the values flowing in over
+    /// the executable edges into its block.
+    pub fn evaluate_phi(phi: &Phi,
+                        block: util::Id,
+                        solution: &Solution) -> Lattice {
+        phi.incoming().filter(|&&(_, ref b)| match b.node {
+            Expression::BlockRef(ref r) => solution.edges.contains(&(r.block_id(), block)),
+            _ => false,
+        }).fold(Lattice::Undefined, |lattice, &(ref v, _)| {
+            lattice.meet(self::evaluate(v, &solution.registers))
+        })
+    }
+
+    /// Replaces references to constant registers with the constants.
+    pub fn substitute(value: Value,
+                      registers: &HashMap<util::Id, Lattice>) -> Value {
+        match value.node {
+            Expression::RegisterRef(r) => match registers.get(&r.register_id) {
+                Some(&Lattice::Constant(ref i)) => Value::new(i.clone().into()),
+                _ => Value::new(Expression::RegisterRef(r)),
+            },
+            Expression::Register(mut reg) => {
+                reg.value = match registers.get(&reg.get_id()) {
+                    Some(&Lattice::Constant(ref i)) => Box::new(Value::new(i.clone().into())),
+                    _ => Box::new(self::substitute(*reg.value, registers)),
+                };
+                Value::new(Expression::Register(reg))
+            },
+            node => Value::new(node.map_subvalues(|v| self::substitute(v, registers))),
+        }
+    }
+
+    /// Gets the successors of a block which may be taken.
+    fn executable_successors(block: &ir::Block,
+                             next: Option<util::Id>,
+                             registers: &HashMap<util::Id, Lattice>) -> Vec<util::Id> {
+        let br = match block.values().last().map(|v| &v.node) {
+            Some(&Expression::Instruction(Instruction::Break(ref br))) => br,
+            _ => return cfg::successors(block, next),
+        };
+
+        let operands: Vec<_> = br.condition().values().into_iter()
+                                 .map(|v| self::evaluate(v, registers))
+                                 .collect();
+
+        if operands.iter().any(|o| *o == Lattice::Overdefined) {
+            return cfg::branch_successors(br, next);
+        }
+
+        if operands.iter().any(|o| *o == Lattice::Undefined) {
+            return Vec::new();
+        }
+
+        let condition = br.condition().clone().map_values(|v| self::substitute(v, registers));
+        let br = Break::new(fold::condition(condition), br.target().clone());
+        cfg::branch_successors(&br, next)
+    }
+
+    /// Finds the blocks which use each register.
+    fn register_uses(func: &ir::Function) -> HashMap<util::Id, Vec<util::Id>> {
+        let mut uses: HashMap<_, Vec<_>> = HashMap::new();
+
+        for block in func.blocks() {
+            let mut worklist: Vec<&Value> = block.values().collect();
+
+            while let Some(value) = worklist.pop() {
+                match value.node {
+                    Expression::RegisterRef(ref r) => {
+                        let users = uses.entry(r.register_id).or_insert_with(Vec::new);
+
+                        if !users.contains(&block.get_id()) {
+                            users.push(block.get_id());
+                        }
+                    },
+                    Expression::Register(ref reg) => worklist.push(&reg.value),
+                    _ => worklist.extend(value.node.subvalues()),
+                }
+            }
+        }
+
+        uses
+    }
+
+    fn is_foldable(inst: &Instruction) -> bool {
+        match *inst {
+            Instruction::Add(..) |
+            Instruction::Sub(..) |
+            Instruction::Mul(..) |
+            Instruction::Div(..) |
+            Instruction::Rem(..) |
+            Instruction::MulHigh(..) |
+            Instruction::And(..) |
+            Instruction::Shl(..) |
+            Instruction::Shr(..) |
+            Instruction::ICmp(..) => true,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use ir::{self,Expression,Value,Condition,Type};
+    use ir::value::RegisterRef;
+    use util::Identifiable;
+
+    fn register(value: Expression) -> (Expression, Value) {
+        let reg = Expression::register(value);
+        let reg_ref = match reg {
+            Expression::Register(ref r) => RegisterRef::new(r.get_id(), r.value.node.ty()),
+            _ => unreachable!(),
+        };
+
+        (reg, Expression::RegisterRef(reg_ref).into())
+    }
+
+    #[test]
+    fn removes_branches_on_constant_registers() {
+        let (a, a_ref) = register(Expression::add(Expression::i8(1), Expression::i8(2)));
+        let (b, b_ref) = register(Expression::mul(a_ref, Expression::i8(2).into()));
+
+        let exit = {
+            let mut block = ir::Block::empty("exit");
+            block.append_value(Expression::ret(b_ref.clone()));
+            block
+        };
+
+        // `b` is 6, so `dead` can never be reached.
+        let entry = {
+            let mut block = ir::Block::empty("entry");
+            block.append_value(a);
+            block.append_value(b);
+            block.append_value(Expression::br(
+                Condition::Equal(Box::new(b_ref), Box::new(Expression::i8(6).into())),
+                Expression::block_ref(&exit)));
+            block
+        };
+
+        let dead = {
+            let mut block = ir::Block::empty("dead");
+            block.append_value(Expression::ret(Expression::i8(0).into()));
+            block
+        };
+
+        let mut func = ir::Function::empty("f", ir::Signature::empty());
+        func.append_block(entry);
+        func.append_block(dead);
+        func.append_block(exit);
+
+        let func = super::propagate::function(func);
+        let names: Vec<_> = func.blocks().map(|b| b.name()).collect();
+
+        assert_eq!(names, vec!["entry", "exit"]);
+        assert_eq!(*func.blocks[0].terminator(),
+                   Expression::br(Condition::True, Expression::block_ref(&func.blocks[1])).into());
+        assert_eq!(*func.blocks[1].terminator(), Expression::ret(Expression::i8(6).into()).into());
+    }
+
+    #[test]
+    fn ignores_phi_values_from_dead_blocks() {
+        let join = ir::Block::empty("join");
+
+        let entry = {
+            let mut block = ir::Block::empty("entry");
+            block.append_value(Expression::br(
+                Condition::Equal(Box::new(Expression::i8(1).into()), Box::new(Expression::i8(1).into())),
+                Expression::block_ref(&join)));
+            block
+        };
+
+        // Never executed, so its value never flows into the phi.
+        let dead = ir::Block::empty("dead");
+
+        let (x, x_ref) = register(Expression::phi(Type::i8(), vec![
+            (Expression::i8(5).into(), Expression::block_ref(&entry).into()),
+            (Expression::i8(7).into(), Expression::block_ref(&dead).into()),
+        ]));
+
+        let join = {
+            let mut block = join;
+            block.append_value(x);
+            block.append_value(Expression::ret(x_ref));
+            block
+        };
+
+        let mut func = ir::Function::empty("f", ir::Signature::empty());
+        func.append_block(entry);
+        func.append_block(dead);
+        func.append_block(join);
+
+        let func = super::propagate::function(func);
+        let names: Vec<_> = func.blocks().map(|b| b.name()).collect();
+
+        assert_eq!(names, vec!["entry", "join"]);
+        assert_eq!(*func.blocks[1].terminator(), Expression::ret(Expression::i8(5).into()).into());
+    }
+
+    #[test]
+    fn removes_never_taken_branches() {
+        let skipped = {
+            let mut block = ir::Block::empty("skipped");
+            block.append_value(Expression::ret_void());
+            block
+        };
+
+        let entry = {
+            let mut block = ir::Block::empty("entry");
+            block.append_value(Expression::br(
+                Condition::LessThan(Box::new(Expression::i8(2).into()), Box::new(Expression::i8(1).into())),
+                Expression::block_ref(&skipped)));
+            block
+        };
+
+        let fallthrough = {
+            let mut block = ir::Block::empty("fallthrough");
+            block.append_value(Expression::ret_void());
+            block
+        };
+
+        let mut func = ir::Function::empty("f", ir::Signature::empty());
+        func.append_block(entry);
+        func.append_block(fallthrough);
+        func.append_block(skipped);
+
+        let func = super::propagate::function(func);
+        let names: Vec<_> = func.blocks().map(|b| b.name()).collect();
+
+        assert_eq!(names, vec!["entry", "fallthrough"]);
+        assert!(func.blocks[0].body.is_empty());
+    }
+}
@@ -0,0 +1,365 @@
+use {Metadata,Id,Info,Transform};
+use analysis::constant_time;
+use ir;
+
+/// A control flow graph simplification pass.
+///
+/// This
+///   * folds branches with constant conditions
+///   * removes blocks which are unreachable
+///   * forwards jumps through blocks which only jump elsewhere
+///   * merges blocks into their only predecessor
+///
+/// Constant-time functions are never given extra conditional branches.
+pub struct SimplifyCfg;
+
+impl Metadata for SimplifyCfg
+{
+    fn id(&self) -> Id { Id(0x7e5d2c40) }
+    fn name(&self) -> &'static str { "simplify cfg" }
+}
+
+impl Transform for SimplifyCfg
+{
+    fn run_function(&mut self,
+                    function: ir::Function,
+                    _module: &ir::Module) -> ir::Function {
+        constant_time::transform(function, self::simplify::function)
+    }
+}
+
+// TODO: blamket impl for all passes
+impl Into<Info> for Box<SimplifyCfg>
+{
+    fn into(self) -> Info {
+        Info::Transform(self)
+    }
+}
+
+pub mod simplify
+{
+    use ir::{self,Value,Expression,Instruction,Condition,Users};
+    use ir::value::BlockRef;
+    use analysis::ControlFlowGraph;
+    use analysis::cfg;
+    use transforms::constant_folding::fold;
+    use util::{self,Identifiable};
+
+    /// Simplifies the control flow of a function until nothing changes.
+    pub fn function(mut func: ir::Function) -> ir::Function {
+        func = self::branches(func);
+
+        loop {
+            let (f, removed) = self::unreachable_blocks(func);
+            func = f;
+
+            let (f, forwarded) = self::forward_blocks(func);
+            func = f;
+
+            let (f, merged) = self::merge_blocks(func);
+            func = f;
+
+            if !removed && !forwarded && !merged {
+                return self::remove_stale_incoming(func);
+            }
+        }
+    }
+
+    /// Folds constant branch conditions, and removes branches
+    /// which are never taken.
+    pub fn branches(func: ir::Function) -> ir::Function {
+        func.map_blocks(|block| {
+            let body = block.body.into_iter().filter_map(|value| {
+                match value.node {
+                    Expression::Instruction(Instruction::Break(br)) => {
+                        let br = fold::br(br);
+
+                        if *br.condition() == Condition::False {
+                            None
+                        } else {
+                            Some(Expression::Instruction(Instruction::Break(br)).into())
+                        }
+                    },
+                    node => Some(Value::new(node)),
+                }
+            }).collect();
+
+            ir::Block { body: body, ..block }
+        })
+    }
+
+    /// Removes blocks which cannot be reached from the entry block.
+    pub fn unreachable_blocks(mut func: ir::Function) -> (ir::Function, bool) {
+        let reachable = ControlFlowGraph::new(&func).reachable();
+        let count = func.blocks.len();
+
+        let blocks: Vec<_> = func.blocks.drain(..).filter(|b| reachable.contains(&b.get_id())).collect();
+        let changed = blocks.len() != count;
+
+        (func.with_blocks(blocks.into_iter()), changed)
+    }
+
+    /// Removes a block which only jumps to another block, redirecting
+    /// all references to it.
+    pub fn forward_blocks(mut func: ir::Function) -> (ir::Function, bool) {
+        for index in 1..func.blocks.len() {
+            // A block which is fallen into cannot be removed without
+            // changing where its predecessor goes.
+            if cfg::falls_through(&func.blocks[index - 1]) {
+                continue;
+            }
+
+            let from = func.blocks[index].get_id();
+            let to = match self::forwarding_target(&func, index) {
+                Some(to) if to != from => to,
+                _ => continue,
+            };
+
+            // Phis in the target would need to merge the incoming
+            // values of every predecessor.
+            if func.blocks().find(|b| b.get_id() == to).map_or(false, |b| self::has_phis(b)) {
+                continue;
+            }
+
+            func.blocks.remove(index);
+            let func = func.map_blocks(|block| {
+                block.map_values(|v| self::replace_block_refs(v, from, to))
+            });
+            return (func, true);
+        }
+
+        (func, false)
+    }
+
+    /// Merges a block into its predecessor if it is the only
+    /// successor of that predecessor.
+    pub fn merge_blocks(mut func: ir::Function) -> (ir::Function, bool) {
+        let cfg = ControlFlowGraph::new(&func);
+
+        for index in 1..func.blocks.len() {
+            let id = func.blocks[index].get_id();
+
+            let pred = match *cfg.predecessors(id) {
+                [pred] if pred != id => pred,
+                _ => continue,
+            };
+
+            if cfg.successors(pred) != [id] || self::has_phis(&func.blocks[index]) {
+                continue;
+            }
+
+            let pred_index = func.blocks.iter().position(|b| b.get_id() == pred).unwrap();
+
+            // If the block falls through, it must stay directly after
+            // its predecessor.
+            if cfg::falls_through(&func.blocks[index]) && pred_index + 1 != index {
+                continue;
+            }
+
+            // The block may only be referred to by the terminator
+            // of its predecessor.
+            let jumps_to_block = func.blocks[pred_index].values().last()
+                .map_or(false, |v| self::is_branch_to(v, id));
+            let users = Users::of_block(&func.blocks[index], &func).len();
+
+            if users != if jumps_to_block { 1 } else { 0 } {
+                continue;
+            }
+
+            let block = func.blocks.remove(index);
+            let pred_index = if pred_index > index { pred_index - 1 } else { pred_index };
+            let pred_block = &mut func.blocks[pred_index];
+
+            if pred_block.values().last().map_or(false, |v| self::is_branch(v)) {
+                pred_block.body.pop();
+            }
+
+            pred_block.body.extend(block.body);
+            return (func, true);
+        }
+
+        (func, false)
+    }
+
+    /// Removes the incoming values of phis which come from blocks
+    /// that no longer branch to the block containing the phi.
+    pub fn remove_stale_incoming(func: ir::Function) -> ir::Function {
+        let cfg = ControlFlowGraph::new(&func);
+
+        func.map_blocks(|block| {
+            let preds = cfg.predecessors(block.get_id()).to_vec();
+
+            block.map_values(|value| match value.node {
+                Expression::Register(mut reg) => {
+                    reg.value = Box::new(match reg.value.node {
+                        Expression::Instruction(Instruction::Phi(phi)) => {
+                            phi.remove_incoming(|b| !preds.contains(&b)).into()
+                        },
+                        node => node,
+                    }.into());
+
+                    Value::new(Expression::Register(reg))
+                },
+                node => Value::new(node),
+            })
+        })
+    }
+
+    /// Checks if a block begins with phis.
+    pub fn has_phis(block: &ir::Block) -> bool {
+        block.values().next().map_or(false, |value| match value.node {
+            Expression::Register(ref reg) => match reg.value.node {
+                Expression::Instruction(Instruction::Phi(..)) => true,
+                _ => false,
+            },
+            _ => false,
+        })
+    }
+
+    /// Gets the block that a block only passes control on to.
+    fn forwarding_target(func: &ir::Function, index: usize) -> Option<util::Id> {
+        let block = &func.blocks[index];
+
+        match block.body.len() {
+            0 => func.blocks.get(index + 1).map(|b| b.get_id()),
+            1 => match block.body[0].node {
+                Expression::Instruction(Instruction::Break(ref br)) if *br.condition() == Condition::True => {
+                    match br.target().node {
+                        Expression::BlockRef(ref r) => Some(r.block_id()),
+                        _ => None,
+                    }
+                },
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn is_branch(value: &Value) -> bool {
+        if let Expression::Instruction(Instruction::Break(..)) = value.node { true } else { false }
+    }
+
+    fn is_branch_to(value: &Value, block: util::Id) -> bool {
+        match value.node {
+            Expression::Instruction(Instruction::Break(ref br)) => match br.target().node {
+                Expression::BlockRef(ref r) => r.block_id() == block,
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
+    /// Replaces all references to a block with references to another.
+    fn replace_block_refs(value: Value, from: util::Id, to: util::Id) -> Value {
+        match value.node {
+            Expression::BlockRef(ref r) if r.block_id() == from => {
+                Value::new(BlockRef::new(to).into())
+            },
+            Expression::Register(mut reg) => {
+                reg.value = Box::new(self::replace_block_refs(*reg.value, from, to));
+                Value::new(Expression::Register(reg))
+            },
+            node => Value::new(node.map_subvalues(|v| self::replace_block_refs(v, from, to))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use ir::{self,Expression,Condition};
+    use util::Identifiable;
+
+    fn block(name: &str, values: Vec<Expression>) -> ir::Block {
+        let mut block = ir::Block::empty(name);
+        for value in values {
+            block.append_value(value);
+        }
+        block
+    }
+
+    #[test]
+    fn forwards_jumps_through_empty_blocks() {
+        let param = ir::Parameter::new("x".to_owned(), ir::Type::i8());
+        let condition = Condition::Equal(Box::new(Expression::argument_ref(&param).into()),
+                                         Box::new(Expression::i8(0).into()));
+
+        let exit = block("exit", vec![Expression::ret_void()]);
+        let forward = block("forward", vec![Expression::br(Condition::True, Expression::block_ref(&exit))]);
+        let other = block("other", vec![Expression::br(Condition::True, Expression::block_ref(&exit))]);
+        let entry = block("entry", vec![Expression::br(condition.clone(), Expression::block_ref(&forward))]);
+
+        let exit_id = exit.get_id();
+
+        let mut func = ir::Function::empty("f", ir::Signature::empty());
+        func.append_block(entry);
+        func.append_block(other);
+        func.append_block(forward);
+        func.append_block(exit);
+
+        let func = super::simplify::function(func);
+        let names: Vec<_> = func.blocks().map(|b| b.name()).collect();
+
+        // `other` is fallen into from `entry`, so it must stay.
+        assert_eq!(names, vec!["entry", "other", "exit"]);
+        assert_eq!(func.blocks[0].body,
+                   vec![Expression::br(condition, ir::Value::new(ir::value::BlockRef::new(exit_id).into())).into()]);
+    }
+
+    #[test]
+    fn merges_blocks_into_their_predecessor() {
+        let first = Expression::register_named("a", Expression::add(Expression::i8(1), Expression::i8(2)));
+        let second = Expression::register_named("b", Expression::add(Expression::i8(3), Expression::i8(4)));
+
+        let exit = block("exit", vec![Expression::ret_void()]);
+        let middle = block("middle", vec![second.clone(),
+                                          Expression::br(Condition::True, Expression::block_ref(&exit))]);
+        let entry = block("entry", vec![first.clone(),
+                                        Expression::br(Condition::True, Expression::block_ref(&middle))]);
+
+        let mut func = ir::Function::empty("f", ir::Signature::empty());
+        func.append_block(entry);
+        func.append_block(exit);
+        func.append_block(middle);
+
+        let func = super::simplify::function(func);
+        let names: Vec<_> = func.blocks().map(|b| b.name()).collect();
+
+        assert_eq!(names, vec!["entry"]);
+        assert_eq!(func.blocks[0].body, vec![first.into(), second.into(), Expression::ret_void().into()]);
+    }
+
+    #[test]
+    fn removes_constant_branches_and_unreachable_blocks() {
+        let unreachable = {
+            let mut block = ir::Block::empty("unreachable");
+            block.append_value(Expression::ret_void());
+            block
+        };
+
+        let entry = {
+            let mut block = ir::Block::empty("entry");
+            block.append_value(Expression::br(
+                Condition::GreaterThan(Box::new(Expression::i8(1).into()), Box::new(Expression::i8(2).into())),
+                Expression::block_ref(&unreachable)));
+            block
+        };
+
+        let next = {
+            let mut block = ir::Block::empty("next");
+            block.append_value(Expression::ret_void());
+            block
+        };
+
+        let mut func = ir::Function::empty("f", ir::Signature::empty());
+        func.append_block(entry);
+        func.append_block(next);
+        func.append_block(unreachable);
+
+        let func = super::simplify::function(func);
+        let names: Vec<_> = func.blocks().map(|b| b.name()).collect();
+
+        assert_eq!(names, vec!["entry"]);
+        assert_eq!(func.blocks[0].body, vec![Expression::ret_void().into()]);
+    }
+}
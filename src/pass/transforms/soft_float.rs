@@ -0,0 +1,362 @@
+use {Metadata,Id,Info,Transform};
+use ir;
+use util;
+
+/// A pass which replaces floating point instructions with calls to
+/// the soft-float routines of the runtime library.
+///
+/// The routines are the ones libgcc provides, which is what avr-gcc
+/// links against. Declarations are added for the routines which the
+/// module does not already have.
+///
+/// Comparisons become an `icmp` of the `int` returned by a
+/// comparison routine, so the pass needs to know how wide `int` is.
+///
+/// Only `f32` and `f64` are supported, and integers must be 32 or
+/// 64 bits wide to be converted. Anything else is left as-is and
+/// recorded as a diagnostic.
+pub struct SoftFloat
+{
+    int: ir::types::Integer,
+    diagnostics: Vec<String>,
+}
+
+impl SoftFloat
+{
+    /// Creates a new soft-float pass, given the type of a C `int`.
+    pub fn new(int: ir::types::Integer) -> Self {
+        SoftFloat {
+            int: int,
+            diagnostics: Vec::new(),
+        }
+    }
+
+    /// Gets the instructions which could not be lowered.
+    pub fn diagnostics(&self) -> ::std::slice::Iter<String> {
+        self.diagnostics.iter()
+    }
+}
+
+impl Metadata for SoftFloat
+{
+    fn id(&self) -> Id { Id(0x50f7f10a) }
+    fn name(&self) -> &'static str { "soft float" }
+
+    fn report(&self) -> Vec<util::Diagnostic> {
+        self.diagnostics.iter().map(|d| util::Diagnostic::warning(d.clone())).collect()
+    }
+}
+
+impl Transform for SoftFloat
+{
+    fn run_module(&mut self,
+                  module: ir::Module) -> ir::Module {
+        let (module, diagnostics) = self::lower::module(module, self.int);
+        self.diagnostics.extend(diagnostics);
+        module
+    }
+}
+
+// TODO: blamket impl for all passes
+impl Into<Info> for Box<SoftFloat>
+{
+    fn into(self) -> Info {
+        Info::Transform(self)
+    }
+}
+
+pub mod lower
+{
+    use ir::{self,types,Value,Expression,Instruction,Condition,Type};
+    use ir::instruction::Binary;
+    use ir::value::FunctionRef;
+
+    use std::collections::HashMap;
+
+    /// The runtime routines used by a module.
+    pub struct Runtime
+    {
+        /// The type of a C `int`.
+        int: types::Integer,
+        /// The functions which are already in the module, by name.
+        existing: HashMap<String, FunctionRef>,
+        /// The declarations which need to be added to the module.
+        declarations: Vec<ir::Function>,
+        /// The instructions which could not be lowered.
+        diagnostics: Vec<String>,
+    }
+
+    impl Runtime
+    {
+        /// Gets a reference to a routine, declaring it if the module
+        /// does not have it.
+        fn routine(&mut self, name: &str, params: &[Type], ret: Type) -> FunctionRef {
+            if let Some(f) = self.existing.get(name) {
+                return f.clone();
+            }
+
+            let names = ["a", "b"];
+            let params = params.iter().zip(names.iter()).map(|(ty, name)| {
+                ir::Parameter::new(name.to_string(), ty.clone())
+            });
+
+            let declaration = ir::Function::empty(name, ir::Signature::new(params, Some(ret)));
+            let reference = FunctionRef::reference(&declaration);
+
+            self.existing.insert(name.to_owned(), reference.clone());
+            self.declarations.push(declaration);
+            reference
+        }
+
+        fn unsupported(&mut self, what: String) {
+            self.diagnostics.push(format!("there is no soft-float routine for {}", what));
+        }
+    }
+
+    /// Lowers the floating point instructions in a module.
+    ///
+    /// Returns the instructions which could not be lowered.
+    pub fn module(module: ir::Module, int: types::Integer) -> (ir::Module, Vec<String>) {
+        let mut runtime = Runtime {
+            int: int,
+            existing: module.functions().map(|f| (f.name().to_owned(), FunctionRef::reference(f))).collect(),
+            declarations: Vec::new(),
+            diagnostics: Vec::new(),
+        };
+
+        let mut module = module.map_functions(|f, _| f.map_values(|v| self::value(v, &mut runtime)));
+
+        for declaration in runtime.declarations {
+            module.add_function(declaration);
+        }
+
+        (module, runtime.diagnostics)
+    }
+
+    /// Lowers the floating point instructions in a value and
+    /// everything inside it.
+    pub fn value(value: Value, runtime: &mut Runtime) -> Value {
+        let value = match value.node {
+            Expression::Register(mut reg) => {
+                reg.value = Box::new(self::value(*reg.value, runtime));
+                return Value { node: Expression::Register(reg), ..value };
+            },
+            node => Value { node: node.map_subvalues(|v| self::value(v, runtime)), ..value },
+        };
+
+        let lowered = match value.node {
+            Expression::Instruction(ref i) => self::instruction(i, runtime),
+            _ => None,
+        };
+
+        match lowered {
+            Some(node) => Value { node: node, ..value },
+            None => value,
+        }
+    }
+
+    /// Lowers an instruction whose operands have been lowered.
+    fn instruction(inst: &Instruction, runtime: &mut Runtime) -> Option<Expression> {
+        match *inst {
+            Instruction::FAdd(ref i) => self::arithmetic("add", i, runtime),
+            Instruction::FSub(ref i) => self::arithmetic("sub", i, runtime),
+            Instruction::FMul(ref i) => self::arithmetic("mul", i, runtime),
+            Instruction::FDiv(ref i) => self::arithmetic("div", i, runtime),
+            Instruction::FCmp(ref i) => match *i.condition() {
+                Condition::True => Some(Expression::u(1, 1)),
+                Condition::False => Some(Expression::u(1, 0)),
+                ref cond => self::condition(cond, runtime).map(Expression::icmp),
+            },
+            Instruction::Break(ref br) => {
+                let is_float = br.condition().values().iter().any(|v| v.node.ty().is_decimal());
+                if !is_float {
+                    return None;
+                }
+
+                self::condition(br.condition(), runtime).map(|cond| {
+                    Expression::br(cond, br.target().clone())
+                })
+            },
+            Instruction::IntToFloat(ref i) => {
+                let ty = i.value().node.ty();
+                let suffixes = (self::int_suffix(&ty), self::float_suffix(&i.ty()));
+
+                match suffixes {
+                    (Some((signed, int)), Some(float)) => {
+                        let unsigned = if signed { "" } else { "un" };
+                        let name = format!("__float{}{}{}", unsigned, int, float);
+                        let routine = runtime.routine(&name, &[ty], i.ty());
+
+                        Some(Expression::call_with_arguments(Value::new(routine.into()), vec![i.value().clone()]))
+                    },
+                    _ => {
+                        runtime.unsupported(format!("converting {} to {}", ty, i.ty()));
+                        None
+                    },
+                }
+            },
+            Instruction::FloatToInt(ref i) => {
+                let ty = i.value().node.ty();
+                let suffixes = (self::float_suffix(&ty), self::int_suffix(&i.ty()));
+
+                match suffixes {
+                    (Some(float), Some((signed, int))) => {
+                        let unsigned = if signed { "" } else { "uns" };
+                        let name = format!("__fix{}{}{}", unsigned, float, int);
+                        let routine = runtime.routine(&name, &[ty], i.ty());
+
+                        Some(Expression::call_with_arguments(Value::new(routine.into()), vec![i.value().clone()]))
+                    },
+                    _ => {
+                        runtime.unsupported(format!("converting {} to {}", ty, i.ty()));
+                        None
+                    },
+                }
+            },
+            _ => None,
+        }
+    }
+
+    /// Lowers `fadd`, `fsub`, `fmul` or `fdiv` to `__addsf3` and
+    /// friends.
+    fn arithmetic<I>(operation: &str, inst: &I, runtime: &mut Runtime) -> Option<Expression>
+        where I: Binary {
+        let (lhs, rhs) = inst.operands();
+        let ty = lhs.node.ty();
+
+        let suffix = match self::float_suffix(&ty) {
+            Some(suffix) => suffix,
+            None => {
+                runtime.unsupported(format!("f{} on {}", operation, ty));
+                return None;
+            },
+        };
+
+        let routine = runtime.routine(&format!("__{}{}3", operation, suffix), &[ty.clone(), ty.clone()], ty);
+        Some(Expression::call_with_arguments(Value::new(routine.into()), vec![lhs.clone(), rhs.clone()]))
+    }
+
+    /// Turns a comparison of floating point values into a comparison
+    /// of the `int` returned by a comparison routine with zero.
+    ///
+    /// Each routine gives a result which fails the comparison when
+    /// either value is NaN, except for `__nesf2`, which passes it.
+    fn condition(cond: &Condition, runtime: &mut Runtime) -> Option<Condition> {
+        let (lhs, rhs) = match (cond.values().get(0), cond.values().get(1)) {
+            (Some(&lhs), Some(&rhs)) => (lhs.clone(), rhs.clone()),
+            _ => return None,
+        };
+
+        let ty = lhs.node.ty();
+        let suffix = match self::float_suffix(&ty) {
+            Some(suffix) => suffix,
+            None => {
+                runtime.unsupported(format!("comparing {}", ty));
+                return None;
+            },
+        };
+
+        let name = format!("__{}{}2", self::comparison_name(cond), suffix);
+        let routine = runtime.routine(&name, &[ty.clone(), ty], runtime.int.into());
+
+        let result = Value::new(Expression::call_with_arguments(Value::new(routine.into()), vec![lhs, rhs]));
+        let zero = Value::new(Expression::integer(runtime.int, 0).unwrap());
+
+        Condition::from_abbreviation(cond.abbreviation(), result, zero)
+    }
+
+    /// Gets the part of a comparison routine's name which says what
+    /// it compares.
+    fn comparison_name(cond: &Condition) -> &'static str {
+        match *cond {
+            Condition::Equal(..) => "eq",
+            Condition::NotEqual(..) => "ne",
+            Condition::GreaterThan(..) => "gt",
+            Condition::GreaterThanOrEq(..) => "ge",
+            Condition::LessThan(..) => "lt",
+            Condition::LessThanOrEq(..) => "le",
+            Condition::True | Condition::False => unreachable!(),
+        }
+    }
+
+    /// Gets the machine mode libgcc names a floating point type by.
+    fn float_suffix(ty: &Type) -> Option<&'static str> {
+        match *ty {
+            Type::Decimal(ref d) if d.bit_width() == 32 => Some("sf"),
+            Type::Decimal(ref d) if d.bit_width() == 64 => Some("df"),
+            _ => None,
+        }
+    }
+
+    /// Gets the signedness of an integer type along with the machine
+    /// mode libgcc names it by.
+    fn int_suffix(ty: &Type) -> Option<(bool, &'static str)> {
+        let int = match *ty {
+            Type::Integer(ref i) => i,
+            _ => return None,
+        };
+
+        match int.width() {
+            32 => Some((int.is_signed(), "si")),
+            64 => Some((int.is_signed(), "di")),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use ir;
+
+    fn lower(text: &str) -> (String, Vec<String>) {
+        let module = ir::read::textual(text.chars()).expect("parsing failed");
+        let (module, diagnostics) = super::lower::module(module, ir::types::Integer::i16());
+
+        assert!(ir::verifier::verify(&module).is_ok());
+        (format!("{}", ir::printable(&module)), diagnostics)
+    }
+
+    #[test]
+    fn calls_arithmetic_routines() {
+        let text = "fn @f(%a: f32, %b: f64) -> f32 {\n  %c = fadd %a, f32 1.5\n  \
+                    %d = fmul %b, %b\n  %e = fsub %c, %a\n  ret %e\n}\n";
+
+        let (output, diagnostics) = lower(text);
+
+        assert_eq!(output, "\nfn @f(%a: f32, %b: f64) -> f32 {\nentry:\n  \
+                            %c = (call @__addsf3(%a, f32 1.5))\n  %d = (call @__muldf3(%b, %b))\n  \
+                            %e = (call @__subsf3(%c, %a))\n  ret %e\n}\n\n\
+                            fn @__addsf3(%a: f32, %b: f32) -> f32\n\n\
+                            fn @__muldf3(%a: f64, %b: f64) -> f64\n\n\
+                            fn @__subsf3(%a: f32, %b: f32) -> f32\n\n");
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn compares_routine_results_with_zero() {
+        let text = "fn @__ltsf2(%x: f32, %y: f32) -> i16\n\
+                    fn @f(%a: f32) {\n  %c = fcmp %a lt f32 0\n  br %a neq f32 1, %done\n:done\n  ret\n}\n";
+
+        let (output, _) = lower(text);
+
+        assert_eq!(output, "\nfn @__ltsf2(%x: f32, %y: f32) -> i16\n\n\
+                            fn @f(%a: f32) {\nentry:\n  \
+                            %c = (icmp (call @__ltsf2(%a, f32 0.0)) lt i16 0)\n  \
+                            break (call @__nesf2(%a, f32 1.0)) neq i16 0 done\ndone:\n  ret void\n}\n\n\
+                            fn @__nesf2(%a: f32, %b: f32) -> i16\n\n");
+    }
+
+    #[test]
+    fn converts_between_integers_and_floats() {
+        let text = "fn @f(%a: u32, %b: f64) -> i64 {\n  %c = itof %a, f32\n  %d = ftoi %b, i64\n  \
+                    %e = itof i8 1, f32\n  ret %d\n}\n";
+
+        let (output, diagnostics) = lower(text);
+
+        assert!(output.contains("%c = (call @__floatunsisf(%a))"));
+        assert!(output.contains("%d = (call @__fixdfdi(%b))"));
+        assert!(output.contains("%e = (itof i8 1, f32)"));
+        assert_eq!(diagnostics, vec!["there is no soft-float routine for converting i8 to f32"]);
+    }
+}
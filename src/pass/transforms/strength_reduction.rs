@@ -1,9 +1,10 @@
 use {Metadata,Id,Info,Transform};
 use ir;
 
-// FIXME: do not perform strength reduction if the value would overflow
-
 /// An IR strength reduction pass.
+///
+/// Every reduction is branch-free, so constant-time functions stay
+/// constant-time without needing `constant_time::transform`.
 pub struct StrengthReduction;
 
 impl Metadata for StrengthReduction
@@ -36,29 +37,47 @@ impl Into<Info> for Box<StrengthReduction>
 
 pub mod reduce
 {
-    use ir::{self,instruction,Instruction};
+    use ir::{self,types,instruction,Instruction,Value};
+    use ir::instruction::Binary;
+    use num::{BigInt,One,Zero};
 
     pub fn reduce(inst: Instruction) -> ir::Instruction {
 
         match inst {
             Instruction::Mul(i) => self::mul(i),
             Instruction::Div(i) => self::div(i),
+            Instruction::Rem(i) => self::rem(i),
             _ => inst,
         }
     }
 
     pub fn mul(inst: instruction::Mul) -> ir::Instruction {
-        self::mul_pow2_shl(inst)
+        match self::mul_pow2_shl(inst.clone()) {
+            Instruction::Mul(_) => self::mul_shift_add(inst),
+            reduced => reduced,
+        }
     }
 
     pub fn div(inst: instruction::Div) -> ir::Instruction {
-        self::div_pow2_shr(inst)
+        let (ty, divisor) = match self::constant_operand(inst.operands().1) {
+            Some(c) => c,
+            None => return inst.into(),
+        };
+
+        match (ty.is_signed(), util::log2(&divisor)) {
+            (_, Some(0)) | (false, Some(_)) => self::div_pow2_shr(inst),
+            (true, Some(k)) => self::sdiv_pow2(inst, ty, k),
+            (false, None) => self::udiv_magic(inst),
+            (true, None) => inst.into(),
+        }
+    }
+
+    pub fn rem(inst: instruction::Rem) -> ir::Instruction {
+        self::rem_pow2_and(inst)
     }
 
     /// Converts a multiplication instruction into a shift instruction if possible.
     pub fn mul_pow2_shl(inst: instruction::Mul) -> ir::Instruction {
-        use ir::instruction::Binary;
-
         let (lhs,rhs) = inst.operands();
         match maybe_shift_expressions(lhs.node.clone(), rhs.node.clone()) {
             Some((value,amount)) => Instruction::shl(value,amount).into(),
@@ -66,18 +85,178 @@ pub mod reduce
         }
     }
 
+    /// Converts a multiplication by a constant with two bits set, or
+    /// one less than a power of two, into shifts and an addition or
+    /// subtraction.
+    ///
+    /// `x * 10` becomes `(x << 3) + (x << 1)`, and `x * 7`
+    /// becomes `(x << 3) - x`.
+    pub fn mul_shift_add(inst: instruction::Mul) -> ir::Instruction {
+        let (value, (ty, factor)) = {
+            let (lhs, rhs) = inst.operands();
+
+            match (self::constant_operand(lhs), self::constant_operand(rhs)) {
+                (_, Some(c)) => (lhs.clone(), c),
+                (Some(c), None) => (rhs.clone(), c),
+                (None, None) => return inst.into(),
+            }
+        };
+
+        if !util::is_duplicable(&value) || factor <= BigInt::zero() {
+            return inst.into();
+        }
+
+        let shifted = |amount: u64| -> Value {
+            if amount == 0 {
+                value.clone()
+            } else {
+                Instruction::shl(value.clone(), util::constant(ty, amount)).into()
+            }
+        };
+
+        let bits = util::set_bits(&factor);
+        let width = ty.width() as u64;
+
+        match bits[..] {
+            [low, high] => Instruction::add(shifted(high), shifted(low)),
+            _ => match util::log2(&(&factor + BigInt::one())) {
+                Some(k) if k >= 2 && k < width => Instruction::sub(shifted(k), value.clone()),
+                _ => inst.into(),
+            },
+        }
+    }
+
     /// Converts a division instruction into a shift instruction if possible.
+    ///
+    /// This is only correct for unsigned division, or division by one.
     pub fn div_pow2_shr(inst: instruction::Div) -> ir::Instruction {
-        use ir::instruction::Binary;
-
         let (lhs,rhs) = inst.operands();
-        match maybe_shift_expressions(lhs.node.clone(), rhs.node.clone()) {
-            Some((value,amount)) => Instruction::shr(value,amount).into(),
+        match rhs.node.as_literal().and_then(|a| util::get_shift_amount(a)) {
+            Some(amount) => Instruction::shr(lhs.clone(),Value::new(amount)),
             None => inst.clone().into(),
         }
     }
 
-    /// Tries to convert the operands of a mul or div instruction into the operands
+    /// Converts a signed division by `2^k` into shifts.
+    ///
+    /// An arithmetic shift rounds towards negative infinity, but
+    /// division rounds towards zero, so negative dividends have
+    /// `2^k - 1` added to them first.
+    ///
+    /// `x / 2^k` becomes `(x + ((x >> (w-1)) & (2^k-1))) >> k`.
+    pub fn sdiv_pow2(inst: instruction::Div, ty: types::Integer, k: u64) -> ir::Instruction {
+        let value = inst.operands().0.clone();
+
+        if !util::is_duplicable(&value) {
+            return inst.into();
+        }
+
+        Instruction::shr(self::sdiv_pow2_biased(value, ty, k), util::constant(ty, k))
+    }
+
+    /// Converts an unsigned division by a constant into a multiplication
+    /// by a magic number.
+    ///
+    /// The quotient is found in the upper half of the product of the
+    /// dividend and an approximation of `2^(w+s) / d`. When that
+    /// approximation does not fit in `w` bits, the missing high bit
+    /// is added back in afterwards.
+    ///
+    /// See Granlund and Montgomery, "Division by Invariant Integers
+    /// using Multiplication".
+    pub fn udiv_magic(inst: instruction::Div) -> ir::Instruction {
+        let (value, (ty, divisor)) = {
+            let (lhs, rhs) = inst.operands();
+
+            match self::constant_operand(rhs) {
+                Some(c) => (lhs.clone(), c),
+                None => return inst.into(),
+            }
+        };
+
+        if ty.is_signed() || divisor <= BigInt::one() {
+            return inst.into();
+        }
+
+        let width = ty.width() as usize;
+        let l = divisor.bits() as usize; // ceil(log2(d)), as d is not a power of two.
+        let s = l - 1;
+
+        // m = ceil(2^(w+s) / d)
+        let numerator = BigInt::one() << (width + s);
+        let magic = (&numerator + &divisor - BigInt::one()) / &divisor;
+
+        if &magic * &divisor - &numerator <= BigInt::one() << s {
+            let high = Instruction::mulh(value, util::constant(ty, magic));
+            return Instruction::shr(high, util::constant(ty, s as u64));
+        }
+
+        if !util::is_duplicable(&value) {
+            return inst.into();
+        }
+
+        // m = floor(2^w * (2^l - d) / d) + 1
+        let magic = ((BigInt::one() << width) * ((BigInt::one() << l) - &divisor)) / &divisor + BigInt::one();
+
+        let high: Value = Instruction::mulh(value.clone(), util::constant(ty, magic)).into();
+        let difference = Instruction::sub(value, high.clone());
+        let halved = Instruction::shr(difference, util::constant(ty, 1));
+
+        Instruction::shr(Instruction::add(high, halved), util::constant(ty, s as u64))
+    }
+
+    /// Converts a remainder by `2^k` into a mask.
+    ///
+    /// For unsigned types, `x % 2^k` becomes `x & (2^k-1)`. For signed
+    /// types the remainder takes the sign of the dividend, so the mask
+    /// is applied to the rounded dividend, giving
+    /// `x - ((x + bias) & -2^k)`.
+    pub fn rem_pow2_and(inst: instruction::Rem) -> ir::Instruction {
+        let (value, (ty, divisor)) = {
+            let (lhs, rhs) = inst.operands();
+
+            match self::constant_operand(rhs) {
+                Some(c) => (lhs.clone(), c),
+                None => return inst.into(),
+            }
+        };
+
+        let k = match util::log2(&divisor) {
+            Some(k) => k,
+            None => return inst.into(),
+        };
+
+        if !ty.is_signed() {
+            return Instruction::and(value, util::constant(ty, divisor - BigInt::one()));
+        }
+
+        if !util::is_duplicable(&value) {
+            return inst.into();
+        }
+
+        let rounded = Instruction::and(self::sdiv_pow2_biased(value.clone(), ty, k),
+                                       util::constant(ty, -divisor));
+        Instruction::sub(value, rounded)
+    }
+
+    /// Builds `x + ((x >> (w-1)) & (2^k-1))`, which is the dividend
+    /// rounded so that an arithmetic shift by `k` truncates towards zero.
+    fn sdiv_pow2_biased(value: Value, ty: types::Integer, k: u64) -> Value {
+        let sign = Instruction::shr(value.clone(), util::constant(ty, ty.width() as u64 - 1));
+        let mask = (BigInt::one() << k as usize) - BigInt::one();
+        let bias = Instruction::and(sign, util::constant(ty, mask));
+
+        Instruction::add(value, bias).into()
+    }
+
+    /// Gets the type and value of an operand if it is a constant integer.
+    fn constant_operand(value: &Value) -> Option<(types::Integer, BigInt)> {
+        value.node.as_literal()
+                  .and_then(|l| l.as_integer())
+                  .map(|i| (i.integer_ty(), i.value()))
+    }
+
+    /// Tries to convert the operands of a mul instruction into the operands
     /// of a shift instruction.
     pub fn maybe_shift_expressions(lhs: ir::Expression, rhs: ir::Expression)
         -> Option<(ir::Expression,ir::Expression)> {
@@ -95,52 +274,65 @@ pub mod reduce
             (Some(_),Some(v)) => Some((lhs.clone(),v.into())),
 
             (None, Some(v)) => Some((lhs.clone(),v.into())),
-            (Some(v),None) => Some((v.into(),rhs.clone())),
+            (Some(v),None) => Some((rhs.clone(),v.into())),
         }
     }
 
     pub mod util {
-        use ir::{value,Expression};
+        use ir::{types,value,Expression,Value};
+        use num::{BigInt,One,Zero};
+        use num::bigint::ToBigInt;
 
         /// Checks if a value is an integer and a power of two.
         pub fn is_power_of_two(value: &value::Literal) -> bool {
-            use ::num::traits::ToPrimitive;
-
-            // FIXME: this will panic if the value >64bits
-            // N.B. this will give that `0` is a power of two.
-            //      we don't care because constant folding has already been done.
-            value.as_integer()
-                 .map(|i| i.value().to_i64().unwrap())
-                 .map_or(false, |x| {
-                     debug_assert!(x != 0);
-                     
-                     (x & (x - 1)) == 0
-                 })
+            value.as_integer().and_then(|i| log2(&i.value())).is_some()
         }
 
-        /// If `expr` is a power of two, this gets the
-        /// number of bits that would make an equivalent shift.
-        /// 
-        /// Returns `None` if the expr is not a power of two.
-        pub fn get_shift_amount(expr: &value::Literal) -> Option<Expression> {
-            use ::num::traits::ToPrimitive;
-
-            if !is_power_of_two(&expr) {
-                return None;
+        /// Gets `k` if a value is `2^k`.
+        pub fn log2(value: &BigInt) -> Option<u64> {
+            if *value > BigInt::zero() && (value & (value - BigInt::one())).is_zero() {
+                Some(value.bits() - 1)
+            } else {
+                None
             }
+        }
 
-            // FIXME: this will panic if expr >64bits
-            let const_val = expr.as_integer()
-                                 .expect("expr must be an integer");
-
-            let ty = const_val.integer_ty();
-            let val = const_val.value().to_f64().unwrap();
+        /// Gets the positions of the bits which are set in a
+        /// positive value, from least to most significant.
+        pub fn set_bits(value: &BigInt) -> Vec<u64> {
+            (0..value.bits()).filter(|&i| !((value >> i as usize) & BigInt::one()).is_zero())
+                             .collect()
+        }
 
-            let log2 = 2f64.log(2.);
+        /// Checks if a value can be used more than once without
+        /// repeating any work.
+        pub fn is_duplicable(value: &Value) -> bool {
+            match value.node {
+                Expression::Literal(..) |
+                Expression::RegisterRef(..) |
+                Expression::ArgumentRef(..) => true,
+                _ => false,
+            }
+        }
 
-            let n = (val.log(2.) / log2) as u64;
+        /// Creates a constant of an integer type.
+        pub fn constant<T: ToBigInt>(ty: types::Integer, value: T) -> Value {
+            Value::new(Expression::integer(ty, value).expect("constant does not fit in type"))
+        }
 
-            Some(Expression::integer(ty, n).unwrap())
+        /// If `expr` is a power of two, this gets the
+        /// number of bits that would make an equivalent shift.
+        ///
+        /// Returns `None` if the expr is not a power of two.
+        pub fn get_shift_amount(expr: &value::Literal) -> Option<Expression> {
+            let const_val = match expr.as_integer() {
+                Some(i) => i,
+                None => return None,
+            };
+
+            log2(&const_val.value()).map(|n| {
+                Expression::integer(const_val.integer_ty(), n).unwrap()
+            })
         }
     }
 }
@@ -151,7 +343,6 @@ value_mapping_test!(test_mul_div_shift : reduce::reduce {
     Instruction::mul(2 as i8,1 as i8) => Instruction::shl(2 as i8,0 as i8),
     Instruction::mul(2 as i8,2 as i8) => Instruction::shl(2 as i8,1 as i8),
     Instruction::div(2 as i8,1 as i8) => Instruction::shr(2 as i8,0 as i8),
-    Instruction::div(2 as i8,2 as i8) => Instruction::shr(2 as i8,1 as i8),
 
     // u32
     Instruction::mul(2 as u32,1 as u32) => Instruction::shl(2 as u32,0 as u32),
@@ -159,7 +350,107 @@ value_mapping_test!(test_mul_div_shift : reduce::reduce {
     Instruction::div(2 as u32,1 as u32) => Instruction::shr(2 as u32,0 as u32),
     Instruction::div(2 as u32,2 as u32) => Instruction::shr(2 as u32,1 as u32),
 
-    // Cases we shouldn't handle (non-powers of two).
-    Instruction::mul(5 as i16,3 as i16) => Instruction::mul(5 as i16,3 as i16),
-    Instruction::div(5 as i16,3 as i16) => Instruction::div(5 as i16,3 as i16)
+    // Cases we shouldn't handle.
+    Instruction::mul(5 as i16,11 as i16) => Instruction::mul(5 as i16,11 as i16),
+    Instruction::div(5 as i16,3 as i16) => Instruction::div(5 as i16,3 as i16),
+    Instruction::rem(5 as i16,3 as i16) => Instruction::rem(5 as i16,3 as i16)
+});
+
+value_mapping_test!(test_shift_add : reduce::reduce {
+    Instruction::mul(3 as u8,10 as u8) => Instruction::add(Instruction::shl(3 as u8,3 as u8),
+                                                           Instruction::shl(3 as u8,1 as u8)),
+    Instruction::mul(3 as i8,9 as i8) => Instruction::add(Instruction::shl(3 as i8,3 as i8),3 as i8),
+    Instruction::mul(3 as u8,7 as u8) => Instruction::sub(Instruction::shl(3 as u8,3 as u8),3 as u8)
+});
+
+value_mapping_test!(test_udiv_magic : reduce::reduce {
+    Instruction::div(4 as u16,5 as u16) => Instruction::shr(Instruction::mulh(4 as u16,52429 as u16),2 as u16)
+});
+
+value_mapping_test!(test_rem_pow2 : reduce::reduce {
+    Instruction::rem(13 as u8,8 as u8) => Instruction::and(13 as u8,7 as u8)
 });
+
+#[cfg(test)]
+mod test
+{
+    use super::reduce;
+    use ir::{self,Instruction,Expression,Value,Type};
+    use ir::value::RegisterRef;
+    use transforms::constant_folding::fold;
+    use util;
+
+    /// Folds every instruction in a value, from the leaves upwards.
+    fn evaluate(value: Value) -> Value {
+        match value.node {
+            Expression::Instruction(i) => {
+                let inst = i.map_subvalues(evaluate);
+                fold::try_value(Value::new(inst.into())).expect("could not fold")
+            },
+            node => Value::new(node),
+        }
+    }
+
+    /// Checks that reducing `x op c` gives the same result as
+    /// computing it directly, for every `x` and `c` of a type.
+    fn check_all<F>(ty: ir::types::Integer, build: F)
+        where F: Fn(Value, Value) -> Instruction {
+        let (min, max) = if ty.is_signed() { (-128, 127) } else { (0, 255) };
+
+        for c in min..max + 1 {
+            for x in min..max + 1 {
+                let (x, c) = (Value::new(Expression::integer(ty, x).unwrap()),
+                              Value::new(Expression::integer(ty, c).unwrap()));
+                let inst = build(x, c);
+
+                let expected = match fold::try_value(inst.clone().into()) {
+                    Ok(v) => v,
+                    Err(..) => continue, // division by zero
+                };
+
+                let reduced = reduce::reduce(inst.clone());
+                assert_eq!(evaluate(reduced.into()), expected, "{:?}", inst);
+            }
+        }
+    }
+
+    #[test]
+    fn reductions_match_direct_results_u8() {
+        let ty = ir::types::Integer::u8();
+
+        check_all(ty, |x, c| Instruction::mul(x, c));
+        check_all(ty, |x, c| Instruction::div(x, c));
+        check_all(ty, |x, c| Instruction::rem(x, c));
+    }
+
+    #[test]
+    fn reductions_match_direct_results_i8() {
+        let ty = ir::types::Integer::i8();
+
+        check_all(ty, |x, c| Instruction::mul(x, c));
+        check_all(ty, |x, c| Instruction::div(x, c));
+        check_all(ty, |x, c| Instruction::rem(x, c));
+    }
+
+    #[test]
+    fn magic_division_with_fixup() {
+        // Dividing a `u8` by 7 needs a magic number which is too
+        // large for eight bits.
+        let x = Value::new(RegisterRef::new(util::Id::next(), Type::u8()).into());
+        let reduced = reduce::reduce(Instruction::div(x.clone(), Value::new(Expression::u8(7))));
+
+        let high: Value = Instruction::mulh(x.clone(), Value::new(Expression::u8(37))).into();
+        let halved = Instruction::shr(Instruction::sub(x, high.clone()), Value::new(Expression::u8(1)));
+
+        assert_eq!(reduced, Instruction::shr(Instruction::add(high, halved), Value::new(Expression::u8(2))));
+    }
+
+    #[test]
+    fn only_duplicates_simple_operands() {
+        let x = Value::new(RegisterRef::new(util::Id::next(), Type::i8()).into());
+        let call: Value = Instruction::call(x).into();
+        let mul = Instruction::mul(call, Value::new(Expression::i8(10)));
+
+        assert_eq!(reduce::reduce(mul.clone()), mul);
+    }
+}
@@ -0,0 +1,301 @@
+use {Metadata,Id,Info,Transform};
+use ir;
+
+/// A tail call elimination pass.
+///
+/// Self-recursive calls in tail position are turned into branches
+/// back to the start of the function, with phis rebinding the
+/// parameters to the new arguments. All other calls in tail position
+/// are marked as tail calls, so that the backend may jump to the
+/// callee rather than calling it and returning.
+pub struct TailCallElimination;
+
+impl Metadata for TailCallElimination
+{
+    fn id(&self) -> Id { Id(0x2f61d0a4) }
+    fn name(&self) -> &'static str { "tail call elimination" }
+}
+
+impl Transform for TailCallElimination
+{
+    fn run_function(&mut self,
+                    function: ir::Function,
+                    _module: &ir::Module) -> ir::Function {
+        self::eliminate::function(function)
+    }
+}
+
+// TODO: blamket impl for all passes
+impl Into<Info> for Box<TailCallElimination>
+{
+    fn into(self) -> Info {
+        Info::Transform(self)
+    }
+}
+
+pub mod eliminate
+{
+    use ir::{self,Value,Expression,Instruction,Condition,Register,Name};
+    use ir::instruction::Call;
+    use ir::value::{ArgumentRef,RegisterRef,BlockRef};
+    use analysis::ControlFlowGraph;
+    use util::{self,Identifiable};
+
+    use std::collections::HashMap;
+
+    /// The name given to the old entry block once it becomes
+    /// the target of recursive calls.
+    pub const HEADER_NAME: &'static str = "tailrecurse";
+
+    /// Eliminates self-recursive tail calls and marks all others.
+    pub fn function(func: ir::Function) -> ir::Function {
+        let func = self::recursion(func);
+        self::mark_tail_calls(func)
+    }
+
+    /// Turns self-recursive calls in tail position into branches
+    /// back to the start of the function.
+    pub fn recursion(mut func: ir::Function) -> ir::Function {
+        let id = func.get_id();
+        let param_count = func.signature().parameters().count();
+
+        let sites: Vec<_> = func.blocks().enumerate().filter_map(|(index, block)| {
+            self::tail_call(block).and_then(|(_, call)| {
+                let is_recursive = match call.target().node {
+                    Expression::FunctionRef(ref f) => f.function_id() == id,
+                    _ => false,
+                };
+
+                if is_recursive && call.arguments().count() == param_count {
+                    Some(index)
+                } else {
+                    None
+                }
+            })
+        }).collect();
+
+        if sites.is_empty() {
+            return func;
+        }
+
+        let header_id = func.blocks[0].get_id();
+        let existing_preds = ControlFlowGraph::new(&func).predecessors(header_id).to_vec();
+
+        let mut entry = ir::Block::empty(func.blocks[0].name().to_owned());
+        entry.append_value(Expression::br(Condition::True, Value::new(BlockRef::new(header_id).into())));
+        func.blocks[0].name = HEADER_NAME.to_owned();
+
+        // Every use of a parameter now refers to the phi which
+        // rebinds it.
+        let params: Vec<_> = func.signature().parameters().cloned().collect();
+        let phi_ids: Vec<_> = params.iter().map(|_| util::Id::next()).collect();
+        let rebound: HashMap<_,_> = params.iter().zip(phi_ids.iter()).map(|(p, &phi_id)| {
+            (p.get_id(), Value::new(RegisterRef::new(phi_id, p.ty().clone()).into()))
+        }).collect();
+
+        func = func.map_values(|v| self::replace_arguments(v, &rebound));
+
+        let mut incoming: Vec<Vec<(Value,Value)>> = params.iter().map(|p| {
+            vec![(Value::new(ArgumentRef::new(p.get_id(), p.ty().clone()).into()),
+                  Value::new(BlockRef::new(entry.get_id()).into()))]
+        }).collect();
+
+        // Blocks which already branched to the entry pass the
+        // parameters through unchanged.
+        for &pred in existing_preds.iter() {
+            for (values, (p, &phi_id)) in incoming.iter_mut().zip(params.iter().zip(phi_ids.iter())) {
+                values.push((Value::new(RegisterRef::new(phi_id, p.ty().clone()).into()),
+                             Value::new(BlockRef::new(pred).into())));
+            }
+        }
+
+        for &index in sites.iter() {
+            let block = &mut func.blocks[index];
+            let (position, arguments) = {
+                let (position, call) = self::tail_call(block).unwrap();
+                (position, call.arguments().cloned().collect::<Vec<_>>())
+            };
+
+            for (values, argument) in incoming.iter_mut().zip(arguments) {
+                values.push((argument, Value::new(BlockRef::new(block.get_id()).into())));
+            }
+
+            block.body.truncate(position);
+            block.append_value(Expression::br(Condition::True, Value::new(BlockRef::new(header_id).into())));
+        }
+
+        let phis = params.iter().zip(phi_ids.iter()).zip(incoming).map(|((p, &phi_id), values)| {
+            let mut reg = Register::new(Name::named(format!("{}_tr", p.name())),
+                                        Value::new(Expression::phi(p.ty().clone(), values)));
+            reg.internal_set_id(phi_id);
+            Value::new(reg.into())
+        });
+
+        let header = &mut func.blocks[0];
+        header.body = phis.chain(header.body.drain(..)).collect();
+
+        func.blocks.insert(0, entry);
+        func
+    }
+
+    /// Marks the calls which are in tail position.
+    pub fn mark_tail_calls(func: ir::Function) -> ir::Function {
+        func.map_blocks(|mut block| {
+            let position = match self::tail_call(&block) {
+                Some((position, _)) => position,
+                None => return block,
+            };
+
+            let value = block.body[position].clone();
+            block.body[position] = self::mark(value);
+            block
+        })
+    }
+
+    /// Finds a call in tail position at the end of a block.
+    ///
+    /// Returns the index of the value containing the call, along
+    /// with the call itself. Everything from that index onwards
+    /// is only the call and the return.
+    pub fn tail_call(block: &ir::Block) -> Option<(usize, &Call)> {
+        let len = block.body.len();
+
+        let ret = match block.body.last().map(|v| &v.node) {
+            Some(&Expression::Instruction(Instruction::Return(ref ret))) => ret,
+            _ => return None,
+        };
+
+        let previous = if len > 1 { Some(&block.body[len - 2].node) } else { None };
+
+        match (ret.subvalue().map(|v| &v.node), previous) {
+            // ret (call @f)
+            (Some(&Expression::Instruction(Instruction::Call(ref call))), _) => Some((len - 1, call)),
+            // %r = call @f; ret %r
+            (Some(&Expression::RegisterRef(ref r)), Some(&Expression::Register(ref reg))) => {
+                match reg.value.node {
+                    Expression::Instruction(Instruction::Call(ref call)) if reg.get_id() == r.register_id => {
+                        Some((len - 2, call))
+                    },
+                    _ => None,
+                }
+            },
+            // call @f; ret
+            (None, Some(&Expression::Instruction(Instruction::Call(ref call)))) => Some((len - 2, call)),
+            _ => None,
+        }
+    }
+
+    /// Marks the call inside a value found by `tail_call`.
+    fn mark(value: Value) -> Value {
+        match value.node {
+            Expression::Instruction(Instruction::Call(call)) => Value::new(call.tail().into()),
+            Expression::Register(mut reg) => {
+                reg.value = Box::new(self::mark(*reg.value));
+                Value::new(Expression::Register(reg))
+            },
+            Expression::Instruction(Instruction::Return(ret)) => {
+                Value::new(ret.map_subvalues(self::mark).into())
+            },
+            node => Value::new(node),
+        }
+    }
+
+    fn replace_arguments(value: Value, rebound: &HashMap<util::Id, Value>) -> Value {
+        match value.node {
+            Expression::ArgumentRef(ref a) if rebound.contains_key(&a.parameter_id()) => {
+                rebound[&a.parameter_id()].clone()
+            },
+            Expression::Register(mut reg) => {
+                reg.value = Box::new(self::replace_arguments(*reg.value, rebound));
+                Value::new(Expression::Register(reg))
+            },
+            node => Value::new(node.map_subvalues(|v| self::replace_arguments(v, rebound))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use ir::{self,Expression,Value,Condition,Type};
+    use util::Identifiable;
+
+    /// Builds `fn @count(%n: i8) -> i8`, which calls itself until
+    /// `%n` reaches zero.
+    fn count() -> ir::Function {
+        let param = ir::Parameter::new("n".to_owned(), Type::i8());
+        let sig = ir::Signature::new(vec![param.clone()], vec![Type::i8()]);
+        let mut func = ir::Function::empty("count", sig);
+
+        let mut entry = ir::Block::empty("entry");
+        let mut recurse = ir::Block::empty("recurse");
+        let mut done = ir::Block::empty("done");
+
+        entry.append_value(Expression::br(Condition::Equal(Box::new(Expression::argument_ref(&param).into()),
+                                                           Box::new(Expression::i8(0).into())),
+                                          Expression::block_ref(&done)));
+        recurse.append_value(Expression::ret(Value::new(Expression::call_with_arguments(
+            Expression::function_ref(&func),
+            vec![Value::new(Expression::sub(Expression::argument_ref(&param), Expression::i8(1)))]))));
+        done.append_value(Expression::ret(Expression::argument_ref(&param).into()));
+
+        func.append_block(entry);
+        func.append_block(recurse);
+        func.append_block(done);
+        func
+    }
+
+    #[test]
+    fn eliminates_self_recursion() {
+        let func = super::eliminate::function(count());
+        let names: Vec<_> = func.blocks().map(|b| b.name()).collect();
+
+        assert_eq!(names, vec!["entry", "tailrecurse", "recurse", "done"]);
+        assert_eq!(*func.blocks[2].terminator(),
+                   Expression::br(Condition::True, Expression::block_ref(&func.blocks[1])).into());
+
+        match func.blocks[1].body[0].node {
+            Expression::Register(ref reg) => match reg.value.node {
+                Expression::Instruction(ir::Instruction::Phi(ref phi)) => {
+                    assert_eq!(phi.incoming().count(), 2);
+                    assert!(phi.incoming_from(func.blocks[0].get_id()).unwrap().node.is_argument_ref());
+                },
+                _ => panic!("expected a phi"),
+            },
+            _ => panic!("expected a register"),
+        }
+
+        // The parameter is only referred to by the phi.
+        let argument_refs = func.values().filter(|v| match v.node {
+            Expression::Instruction(ref i) => i.subvalues().iter().any(|s| s.node.is_argument_ref()),
+            _ => false,
+        }).count();
+        assert_eq!(argument_refs, 0);
+    }
+
+    #[test]
+    fn marks_other_tail_calls() {
+        let callee = ir::Function::empty("callee", ir::Signature::empty().ret(Type::i8()));
+        let call = Expression::register_named("r", Expression::call(Expression::function_ref(&callee)));
+
+        let mut block = ir::Block::empty("entry");
+        block.append_value(call.clone());
+        block.append_value(Expression::ret(match call {
+            Expression::Register(ref r) => Expression::register_ref(r).into(),
+            _ => unreachable!(),
+        }));
+
+        let mut func = ir::Function::empty("caller", ir::Signature::empty().ret(Type::i8()));
+        func.append_block(block);
+
+        let func = super::eliminate::function(func);
+
+        match func.blocks[0].body[0].node {
+            Expression::Register(ref reg) => match reg.value.node {
+                Expression::Instruction(ir::Instruction::Call(ref call)) => assert!(call.is_tail()),
+                _ => panic!("expected a call"),
+            },
+            _ => panic!("expected a register"),
+        }
+    }
+}
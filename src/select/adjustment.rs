@@ -38,6 +38,14 @@ pub enum Adjustment<V: PatternValue>
         from: mir::Value,
         to: mir::Value,
     },
+    /// Copies an input into the register of the output it is tied to.
+    ///
+    /// `(set %a, (add %b, %c))` becomes `(set %a, %b)` followed by
+    /// `(set %a, (add %a, %c))`.
+    TieToOutput {
+        input: mir::Value,
+        output: mir::Value,
+    },
     /// A target-specific constraint.
     Target(V::Adjustment),
 }
@@ -133,6 +141,16 @@ impl<V: PatternValue> Adjustment<V>
                     }],
                 }
             },
+            Adjustment::TieToOutput { ref input, ref output } => {
+                let output_node = mir::Node::leaf(output.clone());
+                let copy = mir::Node::set(output_node, mir::Node::leaf(input.clone()));
+
+                AdjustmentApplication {
+                    preceding_nodes: vec![copy],
+                    adjusted_node: self::coerce_value(root_node, input, output),
+                    function_adjustments: Vec::new(),
+                }
+            },
             Adjustment::Target(ref _adjustment) => {
                 unimplemented!();
             },
@@ -1,6 +1,8 @@
 use Legalizer;
+use legalize;
 
 use mir;
+use util::Diagnostic;
 
 #[derive(Copy,Clone,Debug,PartialEq,Eq)]
 pub enum Action
@@ -17,21 +19,22 @@ pub enum Action
 
 impl Action
 {
-    pub fn perform_on(&self, node: mir::Node, legalizer: &Legalizer) -> mir::Node {
+    pub fn perform_on(&self, node: mir::Node, legalizer: &Legalizer) -> Result<mir::Node, Diagnostic> {
         match *self {
-            Action::Legal => node,
+            Action::Legal => Ok(node),
             Action::Expand => self::expand(legalizer, node),
             Action::Promote => self::promote(legalizer, node),
         }
     }
 }
 
-pub fn expand(_context: &Legalizer, _node: mir::Node) -> mir::Node
+pub fn expand(_context: &Legalizer, node: mir::Node) -> Result<mir::Node, Diagnostic>
 {
-    unimplemented!();
+    // FIXME: split the node into word-sized parts
+    Err(legalize::unsupported(&node))
 }
 
-pub fn promote(context: &Legalizer, node: mir::Node) -> mir::Node
+pub fn promote(context: &Legalizer, node: mir::Node) -> Result<mir::Node, Diagnostic>
 {
     match node.ty() {
         mir::Type::Integer { bit_width } => {
@@ -43,11 +46,9 @@ pub fn promote(context: &Legalizer, node: mir::Node) -> mir::Node
             let bit_size = byte_count * context.byte_width;
 
             // Zero extend the value.
-            mir::Node::zext(bit_size, node)
+            Ok(mir::Node::zext(bit_size, node))
         },
-        _ => {
-            unimplemented!();
-        }
+        _ => Err(legalize::unsupported(&node)),
     }
 }
 
@@ -6,6 +6,7 @@ pub mod operation;
 pub mod default;
 
 use mir;
+use util::Diagnostic;
 
 /// A selection context.
 pub struct Legalizer
@@ -32,19 +33,22 @@ impl Legalizer
         });
     }
 
-    pub fn legalize(&self, dag: mir::Dag) -> mir::Dag {
-        mir::Dag {
-            nodes: dag.nodes.into_iter().map(|node| {
-                self.legalize_node(node)
-            }).collect()
-        }
+    /// Legalizes a DAG, failing if it has a node the target
+    /// cannot handle.
+    pub fn legalize(&self, dag: mir::Dag) -> Result<mir::Dag, Diagnostic> {
+        let nodes: Result<Vec<_>, _> = dag.nodes.into_iter().map(|node| {
+            self.legalize_node(node)
+        }).collect();
+
+        Ok(mir::Dag { nodes: try!(nodes) })
     }
 
-    fn legalize_node(&self, node: mir::Node) -> mir::Node {
+    fn legalize_node(&self, node: mir::Node) -> Result<mir::Node, Diagnostic> {
         match node.kind {
             mir::NodeKind::Branch(branch) => {
-                let operands = branch.operands.into_iter().
+                let operands: Result<Vec<_>, _> = branch.operands.into_iter().
                     map(|operand| self.legalize_node(operand)).collect();
+                let operands = try!(operands);
 
                 let kind = mir::NodeKind::Branch(mir::Branch {
                     opcode: branch.opcode,
@@ -56,32 +60,43 @@ impl Legalizer
                     ..node
                 };
 
-                self.legalization_action(&node).perform_on(node, self)
+                let action = try!(self.legalization_action(&node));
+                action.perform_on(node, self)
             },
-            _ => node,
+            _ => Ok(node),
         }
     }
 
-    fn legalization_action(&self, node: &mir::Node) -> Action {
+    fn legalization_action(&self, node: &mir::Node) -> Result<Action, Diagnostic> {
         match node.kind {
             mir::NodeKind::Branch(ref branch) => {
-                if branch.opcode == mir::OpCode::Set { return Action::Legal };
+                if branch.opcode == mir::OpCode::Set { return Ok(Action::Legal) };
 
                 let predefined_action = self.operations.iter().find(|op| {
                     op.opcode == branch.opcode &&
                         op.result_types.iter().cloned().eq(node.result_types())
                 });
 
-                if let Some(operation) = predefined_action {
-                    operation.action
-                } else {
-                    panic!("no action for {:#?}", branch);
+                match predefined_action {
+                    Some(operation) => Ok(operation.action),
+                    None => Err(self::unsupported(node)),
                 }
             },
             mir::NodeKind::Leaf(..) => {
                 // FIXME: not all values are legal
-                Action::Legal
+                Ok(Action::Legal)
             },
         }
     }
 }
+
+/// Describes a node the target has no way to handle.
+pub fn unsupported(node: &mir::Node) -> Diagnostic {
+    let opcode = match node.kind {
+        mir::NodeKind::Branch(ref branch) => branch.opcode.mnemonic(),
+        mir::NodeKind::Leaf(..) => "value".to_owned(),
+    };
+
+    Diagnostic::error(format!("the backend cannot lower '{}' nodes of type {} yet", opcode, node.ty()))
+        .with_note(format!("the node is {:?}", node))
+}
@@ -82,19 +82,25 @@ impl<V: PatternValue> MatchContext<V>
         let mut adjustments = Vec::new();
 
         for (_, values) in repeated_name_values {
-            // We 'normalize' all other values of the same name to one 'cannonical' value.
+            // Inputs which share a name with the output are copied into
+            // the output register first.
             //
             // This is used for situations like:
             // (set %foo, (add %bar, i8 5))
             //
             // Where %foo and %bar must be the same register due to the target.
-            let cannonical_value = values.iter().find(|value| value.direction == mir::ValueInfo::Input).unwrap();
+            // Renaming %bar to %foo instead would clobber %bar for its
+            // other users.
+            let output = match values.iter().find(|value| value.direction == mir::ValueInfo::Output) {
+                Some(output) => output,
+                None => continue,
+            };
 
             for value in values {
-                if value.value != cannonical_value.value {
-                    adjustments.push(Adjustment::CoerceValue {
-                        from: value.value.clone(),
-                        to: cannonical_value.value.clone(),
+                if value.value != output.value {
+                    adjustments.push(Adjustment::TieToOutput {
+                        input: value.value.clone(),
+                        output: output.value.clone(),
                     });
                 }
             }
@@ -157,7 +163,10 @@ impl<S: Selectable, V: PatternValue> Pattern<S, V>
         };
 
         if let mir::NodeKind::Branch(ref branch) = node.kind {
-            self.root.matches(branch, &mut context) + context.match_result()
+            // Ties are adjusted first, so that their inputs are
+            // copied before being demoted to other registers.
+            let result = self.root.matches(branch, &mut context);
+            context.match_result() + result
         } else {
             MatchResult::None
         }
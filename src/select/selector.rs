@@ -1,6 +1,7 @@
 use {Pattern, PatternValue, MatchResult, Adjustment, AdjustmentApplication};
 use mir;
 use adjustment;
+use util::Diagnostic;
 
 pub trait Selectable : ::std::fmt::Debug
 {
@@ -59,39 +60,44 @@ impl<S: Selectable, V> Selector<S, V>
         }
     }
 
-    pub fn select(&mut self, dag: mir::Dag) -> Vec<S> {
-        dag.expect_valid();
-
+    /// Selects the instructions for a DAG.
+    ///
+    /// The DAG must already have been verified along with the rest
+    /// of its function, as it may use registers set in other DAGs.
+    pub fn select(&mut self, dag: mir::Dag) -> Result<Vec<S>, Diagnostic> {
         let mut function_adjustments = Vec::new();
+        let mut nodes = Vec::new();
 
         let dag = dag.expand();
-        let mut nodes: Vec<_> = dag.nodes.iter().flat_map(|node| {
-            let permutation = self.select_node(node);
+        for node in dag.nodes.iter() {
+            let permutation = try!(self.select_node(node));
             function_adjustments.extend(permutation.function_adjustments);
-            permutation.nodes
-        }).collect();
+            nodes.extend(permutation.nodes);
+        }
 
         for adjustment in function_adjustments {
             match adjustment {
                 Adjustment::CoerceValue { ref from, ref to } => {
                     nodes = nodes.into_iter().map(|node| adjustment::coerce_value(node, from, to)).collect();
                 },
-                _ => panic!("invalid function-level adjustment"),
+                _ => return Err(Diagnostic::error("invalid function-level adjustment")
+                                    .with_note(format!("the adjustment is {:?}", adjustment))),
             }
         }
 
         nodes.iter().map(|node| self.select_legal_node(&node)).collect()
     }
 
-    pub fn select_node(&mut self, node: &mir::Node) -> SelectedPermutation<V> {
+    pub fn select_node(&mut self, node: &mir::Node) -> Result<SelectedPermutation<V>, Diagnostic> {
         let permutations = self.find_matching_permutations(node);
 
         match self::find_optimal_permutation(&permutations) {
-            Some(permutation) => SelectedPermutation {
+            Some(permutation) => Ok(SelectedPermutation {
                 nodes: permutation.nodes.clone(),
                 function_adjustments: permutation.function_adjustments.clone(),
-            },
-            None => panic!("no patterns matching for this node: {:#?}", node),
+            }),
+            None => Err(Diagnostic::error("no instruction patterns match this node")
+                            .with_note(format!("the node is {:?}", node))),
         }
     }
 
@@ -108,6 +114,10 @@ impl<S: Selectable, V> Selector<S, V>
                     })
                 },
                 MatchResult::Partial(mut adjustments) => {
+                    if self::demotes_set_value(node, &adjustments) {
+                        return None;
+                    }
+
                     let mut current_application = AdjustmentApplication::unadjusted(node.clone());
 
                     // Try to legalize the permutation.
@@ -119,12 +129,21 @@ impl<S: Selectable, V> Selector<S, V>
                         match pat_match.pattern.matches(&current_application.adjusted_node) {
                             MatchResult::Perfect => {
                                 let mut new_function_adjustments = Vec::new();
-
-                                current_application.preceding_nodes = current_application.preceding_nodes.into_iter().flat_map(|preceding_node| {
-                                    let permutation = self.select_node(&preceding_node);
-                                    new_function_adjustments.extend(permutation.function_adjustments);
-                                    permutation.nodes
-                                }).collect();
+                                let mut preceding_nodes = Vec::new();
+
+                                // The permutation is useless if the nodes it
+                                // needs first cannot be selected.
+                                for preceding_node in current_application.preceding_nodes.iter() {
+                                    match self.select_node(preceding_node) {
+                                        Ok(permutation) => {
+                                            new_function_adjustments.extend(permutation.function_adjustments);
+                                            preceding_nodes.extend(permutation.nodes);
+                                        },
+                                        Err(..) => return None,
+                                    }
+                                }
+
+                                current_application.preceding_nodes = preceding_nodes;
 
                                 current_application.function_adjustments.extend(new_function_adjustments);
 
@@ -160,18 +179,35 @@ impl<S: Selectable, V> Selector<S, V>
     }
 
     /// Selects a node, under the guarantee that the node is already legal.
-    fn select_legal_node(&mut self, node: &mir::Node) -> S {
+    fn select_legal_node(&mut self, node: &mir::Node) -> Result<S, Diagnostic> {
         let matches: Vec<_> = self.find_similar_matches(node).into_iter().filter(|pat_match| {
             pat_match.result.is_perfect()
         }).collect();
 
-        assert_eq!(matches.len(), 1);
-
-        let ref pat_match = matches[0];
-        (pat_match.pattern.factory)(node)
+        match matches.len() {
+            1 => Ok((matches[0].pattern.factory)(node)),
+            0 => Err(Diagnostic::error("no instruction patterns match this legalized node")
+                         .with_note(format!("the node is {:?}", node))),
+            _ => Err(Diagnostic::error("several instruction patterns match this legalized node")
+                         .with_note(format!("the node is {:?}", node))),
+        }
     }
 }
 
+/// Checks if an adjustment would demote the value a register is
+/// set to, which would only create another node just like it.
+fn demotes_set_value<V: PatternValue>(node: &mir::Node, adjustments: &[Adjustment<V>]) -> bool {
+    let branch = match node.kind {
+        mir::NodeKind::Branch(ref branch) if branch.opcode == mir::OpCode::Set => branch,
+        _ => return false,
+    };
+
+    adjustments.iter().any(|adjustment| match *adjustment {
+        Adjustment::DemoteToRegister { ref demotee } => *demotee == branch.operands[1].kind,
+        _ => false,
+    })
+}
+
 fn find_optimal_permutation<S, V>(permutations: &[Permutation<S, V>]) -> Option<&Permutation<S, V>>
     where S: Selectable, V: PatternValue {
     permutations.iter().min_by_key(|permutation| permutation.pattern.root.area())
@@ -0,0 +1,31 @@
+use util::Diagnostic;
+use std::io;
+
+#[derive(Debug)]
+pub enum Error
+{
+    IoError(io::Error),
+    /// The input IR could not be read or is not well-formed.
+    InvalidIR(Diagnostic),
+    /// Code could not be generated for the input.
+    Codegen(Diagnostic),
+}
+
+impl Error
+{
+    /// Describes the error as a diagnostic.
+    pub fn diagnostic(&self) -> Diagnostic {
+        match *self {
+            Error::IoError(ref e) => Diagnostic::error(format!("{}", e)),
+            Error::InvalidIR(ref d) |
+            Error::Codegen(ref d) => d.clone(),
+        }
+    }
+}
+
+impl From<io::Error> for Error
+{
+    fn from(e: io::Error) -> Error {
+        Error::IoError(e)
+    }
+}
@@ -0,0 +1,365 @@
+use Span;
+use term;
+
+use std::io::{self,Write,IsTerminal};
+use std;
+
+/// How serious a diagnostic is.
+#[derive(Copy,Clone,Debug,PartialEq,Eq)]
+pub enum Severity
+{
+    Error,
+    Warning,
+    Note,
+}
+
+/// A problem found somewhere between parsing and code generation.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct Diagnostic
+{
+    pub severity: Severity,
+    pub message: String,
+    /// The location the diagnostic is about, if it is known.
+    pub span: Option<Span>,
+    /// Extra information about the problem.
+    pub notes: Vec<String>,
+}
+
+/// The way diagnostics are written out.
+#[derive(Copy,Clone,Debug,PartialEq,Eq)]
+pub enum Format
+{
+    /// Text for people to read, coloured if writing to a terminal.
+    Human,
+    /// One JSON object per line.
+    Json,
+}
+
+/// Writes diagnostics about a file to standard error.
+pub struct Emitter
+{
+    format: Format,
+    file_name: String,
+    source: Option<String>,
+}
+
+/// A piece of rendered text and how it should look.
+struct Segment
+{
+    text: String,
+    color: Option<term::color::Color>,
+    bold: bool,
+}
+
+impl Severity
+{
+    /// Gets the name of the severity.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Note => "note",
+        }
+    }
+
+    fn color(&self) -> term::color::Color {
+        match *self {
+            Severity::Error => term::color::BRIGHT_RED,
+            Severity::Warning => term::color::BRIGHT_YELLOW,
+            Severity::Note => term::color::BRIGHT_CYAN,
+        }
+    }
+}
+
+impl Diagnostic
+{
+    /// Creates a new diagnostic.
+    pub fn new<S>(severity: Severity, message: S) -> Self
+        where S: Into<String> {
+        Diagnostic {
+            severity: severity,
+            message: message.into(),
+            span: None,
+            notes: Vec::new(),
+        }
+    }
+
+    /// Creates an error.
+    pub fn error<S>(message: S) -> Self
+        where S: Into<String> {
+        Diagnostic::new(Severity::Error, message)
+    }
+
+    /// Creates a warning.
+    pub fn warning<S>(message: S) -> Self
+        where S: Into<String> {
+        Diagnostic::new(Severity::Warning, message)
+    }
+
+    /// Creates a note.
+    pub fn note<S>(message: S) -> Self
+        where S: Into<String> {
+        Diagnostic::new(Severity::Note, message)
+    }
+
+    /// Sets the location of the diagnostic.
+    pub fn with_span(mut self, span: Span) -> Self {
+        self.span = Some(span);
+        self
+    }
+
+    /// Sets the location of the diagnostic if it does not have one.
+    pub fn or_span(mut self, span: Option<Span>) -> Self {
+        self.span = self.span.or(span);
+        self
+    }
+
+    /// Adds a note to the diagnostic.
+    pub fn with_note<S>(mut self, note: S) -> Self
+        where S: Into<String> {
+        self.notes.push(note.into());
+        self
+    }
+
+    /// Checks if the diagnostic is an error.
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    /// Renders the diagnostic as plain text.
+    ///
+    /// If the source is given, the line the diagnostic is about is
+    /// shown with the offending characters underlined.
+    pub fn render(&self, file_name: &str, source: Option<&str>) -> String {
+        self.segments(file_name, source).into_iter().map(|s| s.text).collect()
+    }
+
+    /// Writes the diagnostic to a terminal in colour.
+    pub fn render_to<W>(&self,
+                        file_name: &str,
+                        source: Option<&str>,
+                        terminal: &mut term::Terminal<Output=W>) -> io::Result<()>
+        where W: Write {
+        for segment in self.segments(file_name, source) {
+            if let Some(color) = segment.color {
+                try!(terminal.fg(color).map_err(self::term_error));
+            }
+            if segment.bold {
+                try!(terminal.attr(term::Attr::Bold).map_err(self::term_error));
+            }
+
+            try!(write!(terminal, "{}", segment.text));
+
+            if segment.color.is_some() || segment.bold {
+                try!(terminal.reset().map_err(self::term_error));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Renders the diagnostic as a single line of JSON.
+    pub fn json(&self, file_name: &str) -> String {
+        let span = match self.span {
+            Some(span) => format!("{{\"start\":{{\"line\":{},\"column\":{}}},\"end\":{{\"line\":{},\"column\":{}}}}}",
+                                  span.start.line, span.start.column,
+                                  span.end.line, span.end.column),
+            None => "null".to_owned(),
+        };
+
+        let notes: Vec<_> = self.notes.iter().map(|n| self::json_string(n)).collect();
+
+        format!("{{\"severity\":\"{}\",\"message\":{},\"file\":{},\"span\":{},\"notes\":[{}]}}",
+                self.severity.name(),
+                self::json_string(&self.message),
+                self::json_string(file_name),
+                span,
+                notes.join(","))
+    }
+
+    fn segments(&self, file_name: &str, source: Option<&str>) -> Vec<Segment> {
+        let color = self.severity.color();
+        let mut segments = vec![
+            Segment::new(format!("{}:", self.severity.name()), Some(color), true),
+            Segment::new(format!(" {}", self.message), None, true),
+        ];
+
+        let gutter: String = match self.span {
+            Some(span) => span.start.line.to_string().chars().map(|_| ' ').collect(),
+            None => " ".to_owned(),
+        };
+
+        if let Some(span) = self.span {
+            let (start, end) = (span.start, span.end);
+
+            segments.push(Segment::new(format!("\n{}--> ", gutter), Some(term::color::BRIGHT_BLUE), true));
+            segments.push(Segment::new(format!("{}:{}", file_name, start), None, false));
+
+            if let Some(source) = source {
+                let line = source.lines().nth(start.line - 1).unwrap_or("");
+
+                // Keep tabs so that the underline lines up with the source.
+                let indent: String = line.chars().take(start.column - 1).map(|c| {
+                    if c == '\t' { '\t' } else { ' ' }
+                }).collect();
+
+                let width = if end.line == start.line && end.column > start.column {
+                    end.column - start.column
+                } else {
+                    1
+                };
+                let underline: String = std::iter::repeat('^').take(width).collect();
+
+                segments.push(Segment::new(format!("\n{} |\n{} | ", gutter, start.line), Some(term::color::BRIGHT_BLUE), true));
+                segments.push(Segment::new(line, None, false));
+                segments.push(Segment::new(format!("\n{} | ", gutter), Some(term::color::BRIGHT_BLUE), true));
+                segments.push(Segment::new(format!("{}{}", indent, underline), Some(color), true));
+            }
+        }
+
+        for note in self.notes.iter() {
+            segments.push(Segment::new(format!("\n{} = ", gutter), Some(term::color::BRIGHT_BLUE), true));
+            segments.push(Segment::new(format!("note: {}", note), None, false));
+        }
+
+        segments
+    }
+}
+
+impl Emitter
+{
+    /// Creates an emitter for diagnostics about a file.
+    pub fn new<S>(format: Format, file_name: S) -> Self
+        where S: Into<String> {
+        Emitter {
+            format: format,
+            file_name: file_name.into(),
+            source: None,
+        }
+    }
+
+    /// Gives the emitter the contents of the file, so that
+    /// source lines can be shown.
+    pub fn source<S>(mut self, source: S) -> Self
+        where S: Into<String> {
+        self.source = Some(source.into());
+        self
+    }
+
+    /// Writes a diagnostic to standard error.
+    pub fn emit(&self, diagnostic: &Diagnostic) {
+        let source = self.source.as_ref().map(|s| &s[..]);
+
+        match self.format {
+            Format::Human => {
+                if io::stderr().is_terminal() {
+                    if let Some(mut terminal) = term::stderr() {
+                        if diagnostic.render_to(&self.file_name, source, &mut *terminal).is_ok() {
+                            writeln!(terminal, "").unwrap();
+                            return;
+                        }
+                    }
+                }
+
+                writeln!(io::stderr(), "{}", diagnostic.render(&self.file_name, source)).unwrap();
+            },
+            Format::Json => {
+                writeln!(io::stderr(), "{}", diagnostic.json(&self.file_name)).unwrap();
+            },
+        }
+    }
+}
+
+impl Segment
+{
+    fn new<S>(text: S, color: Option<term::color::Color>, bold: bool) -> Self
+        where S: Into<String> {
+        Segment {
+            text: text.into(),
+            color: color,
+            bold: bold,
+        }
+    }
+}
+
+impl std::fmt::Display for Diagnostic
+{
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if let Some(span) = self.span {
+            try!(write!(fmt, "{}: ", span));
+        }
+
+        write!(fmt, "{}", self.message)
+    }
+}
+
+impl<'a> From<&'a str> for Diagnostic
+{
+    fn from(message: &'a str) -> Self {
+        Diagnostic::error(message)
+    }
+}
+
+impl From<String> for Diagnostic
+{
+    fn from(message: String) -> Self {
+        Diagnostic::error(message)
+    }
+}
+
+fn term_error(e: term::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, e)
+}
+
+fn json_string(s: &str) -> String {
+    let mut result = String::from("\"");
+
+    for c in s.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\t' => result.push_str("\\t"),
+            '\r' => result.push_str("\\r"),
+            c if (c as u32) < 0x20 => result.push_str(&format!("\\u{:04x}", c as u32)),
+            c => result.push(c),
+        }
+    }
+
+    result.push('"');
+    result
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::Diagnostic;
+    use {Location,Span};
+
+    fn diagnostic() -> Diagnostic {
+        Diagnostic::error("unknown type: \"x\"")
+            .with_span(Span::new(Location::new(2, 5), Location::new(2, 8)))
+            .with_note("types look like i8")
+    }
+
+    #[test]
+    fn renders_source_line() {
+        let text = diagnostic().render("a.ir", Some("fn @f() {\n  ret x12\n}\n"));
+
+        assert_eq!(text, "error: unknown type: \"x\"\n --> a.ir:2:5\n  |\n2 |   ret x12\n  |     ^^^\n  = note: types look like i8");
+    }
+
+    #[test]
+    fn renders_without_location() {
+        let text = Diagnostic::warning("something odd").render("a.ir", None);
+        assert_eq!(text, "warning: something odd");
+    }
+
+    #[test]
+    fn renders_json() {
+        assert_eq!(diagnostic().json("a.ir"),
+                   "{\"severity\":\"error\",\"message\":\"unknown type: \\\"x\\\"\",\"file\":\"a.ir\",\
+                    \"span\":{\"start\":{\"line\":2,\"column\":5},\"end\":{\"line\":2,\"column\":8}},\
+                    \"notes\":[\"types look like i8\"]}");
+    }
+}
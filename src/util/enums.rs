@@ -6,7 +6,7 @@ pub enum ByteOrder
 }
 
 /// Specified the signedness of an integer.
-#[derive(Copy,Clone,Debug,Eq,PartialEq)]
+#[derive(Copy,Clone,Debug,Eq,PartialEq,Hash)]
 pub enum IntegerKind
 {
     Signed,
@@ -3,15 +3,23 @@ pub use self::architecture::Architecture;
 pub use self::id::{Id,Identifiable};
 pub use self::list::{List,Slot};
 pub use self::set::Set;
+pub use self::span::{Location,Span};
+pub use self::diagnostic::{Diagnostic,Severity};
 
 pub mod enums;
 pub mod architecture;
 pub mod id;
 pub mod list;
 pub mod set;
+/// Source locations.
+pub mod span;
+/// Error and warning reporting.
+pub mod diagnostic;
 
 pub mod os;
 
+extern crate term;
+
 use std::fmt;
 
 pub struct SeparatedValues<T,S>
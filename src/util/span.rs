@@ -0,0 +1,89 @@
+use std::cmp;
+use std::fmt;
+
+/// A position in a source file.
+///
+/// Lines and columns start from one.
+#[derive(Copy,Clone,Debug,PartialEq,Eq,PartialOrd,Ord,Hash)]
+pub struct Location
+{
+    pub line: usize,
+    pub column: usize,
+}
+
+/// A range of characters in a source file.
+///
+/// The end is the location just past the last character.
+#[derive(Copy,Clone,Debug,PartialEq,Eq,Hash)]
+pub struct Span
+{
+    pub start: Location,
+    pub end: Location,
+}
+
+impl Location
+{
+    /// Creates a new location.
+    pub fn new(line: usize, column: usize) -> Self {
+        Location {
+            line: line,
+            column: column,
+        }
+    }
+
+    /// Gets the location of the first character in a file.
+    pub fn start() -> Self {
+        Location::new(1, 1)
+    }
+
+    /// Gets the location after a character.
+    pub fn advance(self, c: char) -> Self {
+        if c == '\n' {
+            Location::new(self.line + 1, 1)
+        } else {
+            Location::new(self.line, self.column + 1)
+        }
+    }
+}
+
+impl Span
+{
+    /// Creates a new span.
+    pub fn new(start: Location, end: Location) -> Self {
+        Span {
+            start: start,
+            end: end,
+        }
+    }
+
+    /// Creates a span which covers both spans.
+    pub fn to(self, other: Span) -> Self {
+        Span::new(cmp::min(self.start, other.start), cmp::max(self.end, other.end))
+    }
+}
+
+impl fmt::Display for Location
+{
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{}:{}", self.line, self.column)
+    }
+}
+
+impl fmt::Display for Span
+{
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.start, fmt)
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::Location;
+
+    #[test]
+    fn advances_over_new_lines() {
+        let location = Location::start().advance('a').advance('\n').advance('b');
+        assert_eq!(location, Location::new(2, 2));
+    }
+}
@@ -1,11 +1,11 @@
 extern crate compiler;
 extern crate argparse;
 
-use compiler::{ir,machine,target};
+use compiler::{ir,machine,pass,target,util};
 use compiler::target::Target;
+use compiler::util::diagnostic::{Emitter,Format};
 use std::error::Error;
 use std::io::{Read,Write};
-use std::fs;
 
 use argparse::ArgumentParser;
 
@@ -13,6 +13,7 @@ use argparse::ArgumentParser;
 enum Task
 {
     Parse,
+    CallGraph,
     Assemble,
     ListTargets,
 }
@@ -22,6 +23,7 @@ impl Task
     fn requires_input_files(&self) -> bool {
         match *self {
             Task::Parse => true,
+            Task::CallGraph => true,
             Task::Assemble => true,
             Task::ListTargets => false,
         }
@@ -35,6 +37,11 @@ fn main() {
 
     let mut task = Task::Assemble;
     let mut target_name = "avr".to_owned();
+    let mut passes: Vec<String> = Vec::new();
+    let mut unroll_threshold = pass::transforms::loop_unroll::DEFAULT_THRESHOLD;
+    let mut error_format = "human".to_owned();
+    let mut emit_bitcode = false;
+    let mut exact = false;
 
     {
         let mut ap = ArgumentParser::new();
@@ -46,9 +53,26 @@ fn main() {
         ap.refer(&mut target_name)
             .add_option(&["--target"], argparse::Store,
                         "the target");
+        ap.refer(&mut passes)
+            .add_option(&["--pass"], argparse::Collect,
+                        "run a transform over the parsed module");
+        ap.refer(&mut unroll_threshold)
+            .add_option(&["--unroll-threshold"], argparse::Store,
+                        "the maximum number of values in an unrolled loop");
+        ap.refer(&mut error_format)
+            .add_option(&["--error-format"], argparse::Store,
+                        "how to print errors (human or json)");
+        ap.refer(&mut emit_bitcode)
+            .add_option(&["--emit-bitcode"], argparse::StoreTrue,
+                        "write the parsed module as bitcode rather than text");
+        ap.refer(&mut exact)
+            .add_option(&["--exact"], argparse::StoreTrue,
+                        "print the module as it was written, keeping names and comments");
         ap.refer(&mut task)
             .add_option(&["--parse"], argparse::StoreConst(Task::Parse),
                         "only parse the module")
+            .add_option(&["--call-graph"], argparse::StoreConst(Task::CallGraph),
+                        "print the call graph of the module in DOT format")
             .add_option(&["--list-targets"], argparse::StoreConst(Task::ListTargets),
                         "list all of the targets supported");
         ap.parse_args_or_exit();
@@ -58,9 +82,18 @@ fn main() {
         abort("no files given");
     }
 
+    let format = match &*error_format {
+        "human" => Format::Human,
+        "json" => Format::Json,
+        _ => abort(format!("unknown error format '{}'", error_format)),
+    };
+
     match task {
         Task::ListTargets => list_targets(),
-        Task::Parse => for file_name in files { parse(&file_name) },
+        Task::Parse => for file_name in files {
+            parse(&file_name, &passes, unroll_threshold, format, emit_bitcode, exact)
+        },
+        Task::CallGraph => for file_name in files { call_graph(&file_name, format) },
         Task::Assemble => {
             if target_name.len() == 0 {
                 abort("target not speficied on command line");
@@ -74,19 +107,19 @@ fn main() {
             };
 
             for file_name in files.iter() {
-                generate(target::OutputType::Assembly, target, &file_name);
+                generate(target::OutputType::Assembly, target, &file_name, format);
             }
         }
     }
 }
 
-fn generate(output_type: target::OutputType, target: &Target, file_name: &str) {
-    let mut file = fs::File::open(file_name).unwrap();
+fn generate(output_type: target::OutputType, target: &Target, file_name: &str, format: Format) {
+    let data = read_file(file_name);
     let mut output: Vec<u8> = Vec::new();
 
-    target.generate(output_type,
-                    &mut file,
-                    &mut output).unwrap();
+    if let Err(e) = target.generate(output_type, &mut &data[..], &mut output) {
+        fail(&emitter(format, file_name, &data), &[e.diagnostic()]);
+    }
 
     let asm = String::from_utf8(output).unwrap();
     print!("{}", asm);
@@ -98,10 +131,71 @@ fn list_targets() {
     }
 }
 
-fn parse(file_name: &str) {
-    let module = parse_module(&file_name);
+fn parse(file_name: &str,
+         passes: &[String],
+         unroll_threshold: usize,
+         format: Format,
+         emit_bitcode: bool,
+         exact: bool) {
+    let data = read_file(file_name);
+    let emitter = emitter(format, file_name, &data);
+    let mut module = parse_module(&data, &emitter);
+
+    if !passes.is_empty() {
+        let mut pm = passes.iter().fold(pass::Manager::empty(), |pm, name| {
+            add_pass(pm, name, unroll_threshold)
+        });
+
+        module = pm.run(module);
+
+        let diagnostics = pm.diagnostics();
+        if diagnostics.iter().any(|d| d.is_error()) {
+            fail(&emitter, &diagnostics);
+        }
+
+        for diagnostic in diagnostics.iter() {
+            emitter.emit(diagnostic);
+        }
+    }
+
+    if emit_bitcode {
+        ir::write::bitcode(&module, &mut std::io::stdout()).unwrap();
+    } else if exact {
+        print!("{}", ir::printable_exact(&module));
+    } else {
+        print_module(&module);
+    }
+}
+
+fn call_graph(file_name: &str, format: Format) {
+    let data = read_file(file_name);
+    let module = parse_module(&data, &emitter(format, file_name, &data));
+    print!("{}", pass::analysis::CallGraph::new(&module).dot());
+}
+
+fn add_pass(pm: pass::Manager, name: &str, unroll_threshold: usize) -> pass::Manager {
+    use compiler::pass::transforms::*;
 
-    print_module(&module);
+    match name {
+        "constant-folding" => pm.add_pass(ConstantFolding::new()),
+        "dce" => pm.add_pass(DeadCodeElimination),
+        "global-dce" => pm.add_pass(GlobalDeadCodeElimination),
+        "strength-reduction" => pm.add_pass(StrengthReduction),
+        "inliner" => pm.add_pass(Inliner),
+        "gvn" => pm.add_pass(GlobalValueNumbering),
+        "instcombine" => pm.add_pass(InstCombine),
+        "sccp" => pm.add_pass(SparseConditionalConstantPropagation),
+        "simplify-cfg" => pm.add_pass(SimplifyCfg),
+        "licm" => pm.add_pass(LoopInvariantCodeMotion),
+        "loop-unroll" => pm.add_pass(LoopUnroll::new(unroll_threshold)),
+        "tce" => pm.add_pass(TailCallElimination),
+        "ipcp" => pm.add_pass(InterproceduralConstantPropagation),
+        "globalopt" => pm.add_pass(GlobalOptimization),
+        "scalarize" => pm.add_pass(Scalarize),
+        "soft-float" => pm.add_pass(SoftFloat::new(ir::types::Integer::i16())),
+        "verify-constant-time" => pm.add_pass(pass::analysis::ConstantTimeVerifier::new()),
+        _ => abort(format!("pass '{}' does not exist", name)),
+    }
 }
 
 fn open_file(path: &str) -> std::fs::File {
@@ -112,20 +206,61 @@ fn open_file(path: &str) -> std::fs::File {
     }
 }
 
-fn parse_module(file_name: &str) -> ir::Module {
-    let mut text = String::new();
-    open_file(file_name).read_to_string(&mut text).unwrap();
+fn read_file(path: &str) -> Vec<u8> {
+    let mut data = Vec::new();
+    open_file(path).read_to_end(&mut data).unwrap();
+    data
+}
+
+/// Creates an emitter for diagnostics about a file, which shows
+/// source lines if the file is text.
+fn emitter(format: Format, file_name: &str, data: &[u8]) -> Emitter {
+    let emitter = Emitter::new(format, file_name);
 
-    match ir::read::textual(text.chars()) {
-        Ok(module) => module,
-        Err(e) => abort(format!("could not parse IR file: {}", e)),
+    match std::str::from_utf8(data) {
+        Ok(text) => emitter.source(text),
+        Err(..) => emitter,
     }
 }
 
+/// Reads a module from either text or bitcode.
+fn parse_module(data: &[u8], emitter: &Emitter) -> ir::Module {
+    if ir::read::bitcode::is_bitcode(data) {
+        return match ir::read::bitcode(&mut &data[..]) {
+            Ok(module) => module,
+            Err(e) => fail(emitter, &[e.into()]),
+        };
+    }
+
+    let text = match std::str::from_utf8(data) {
+        Ok(text) => text,
+        Err(..) => fail(emitter, &["the file is neither text nor bitcode".into()]),
+    };
+
+    let parser = ir::read::Parser::new(text.chars()).preserve_comments();
+    let (module, errors) = parser.parse_recovering();
+
+    if !errors.is_empty() {
+        let diagnostics: Vec<_> = errors.into_iter().map(Into::into).collect();
+        fail(emitter, &diagnostics);
+    }
+
+    module
+}
+
 fn print_module(module: &ir::Module) {
     println!("{}", ir::printable(module));
 }
 
+/// Emits diagnostics and exits with an error.
+fn fail(emitter: &Emitter, diagnostics: &[util::Diagnostic]) -> ! {
+    for diagnostic in diagnostics {
+        emitter.emit(diagnostic);
+    }
+
+    std::process::exit(1);
+}
+
 fn abort<S>(msg: S) -> !
     where S: Into<String> {
     write!(std::io::stderr(),
@@ -88,7 +88,7 @@ fn create_module() -> ir::Module {
 
 fn create_ir_pass_manager() -> pass::Manager {
     pass::Manager::empty()
-        //.add_pass(pass::transforms::ConstantFolding)
+        //.add_pass(pass::transforms::ConstantFolding::new())
         .add_pass(pass::transforms::StrengthReduction)
         .add_pass(pass::transforms::DeadCodeElimination)
         .add_pass(pass::transforms::Inliner)